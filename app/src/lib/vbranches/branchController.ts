@@ -153,10 +153,11 @@ export class BranchController {
 		}
 	}
 
-	async unapplyFiles(files: LocalFile[]) {
+	async unapplyFiles(branchId: string, files: LocalFile[]) {
 		try {
 			await invoke<void>('reset_files', {
 				projectId: this.projectId,
+				branchId,
 				files: files.flatMap((f) => f.path).join('\n')
 			});
 		} catch (err) {
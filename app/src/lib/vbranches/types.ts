@@ -25,6 +25,8 @@ export class Hunk {
 	@Type(() => HunkLock)
 	lockedTo!: HunkLock[];
 	changeType!: ChangeType;
+	// Lines of the hunk owned by the branch if it only owns some of them, empty if it owns all.
+	lines!: number[];
 	new_start!: number;
 	new_lines!: number;
 }
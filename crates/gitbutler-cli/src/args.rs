@@ -0,0 +1,134 @@
+use std::path::PathBuf;
+
+#[derive(Debug, clap::Parser)]
+#[clap(name = "but", about = "A headless GitButler client", version)]
+pub struct Args {
+    /// The directory in which GitButler keeps its application data, like the list of projects.
+    ///
+    /// Defaults to the one used by the GitButler app.
+    #[clap(long, env = "GITBUTLER_APP_DATA_DIR", global = true)]
+    pub app_data_dir: Option<PathBuf>,
+    /// The worktree of the project to operate on.
+    #[clap(short = 'C', long, default_value = ".", global = true)]
+    pub project: PathBuf,
+    /// Print JSON instead of human-readable output.
+    #[clap(long, global = true)]
+    pub json: bool,
+    #[clap(subcommand)]
+    pub cmd: Subcommands,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Subcommands {
+    /// Manage the projects known to GitButler.
+    #[clap(subcommand)]
+    Project(project::SubCommands),
+    /// List, create, commit to and push virtual branches.
+    #[clap(subcommand)]
+    Branch(vbranch::SubCommands),
+    /// Inspect and update the base branch all virtual branches build upon.
+    #[clap(subcommand)]
+    Base(base::SubCommands),
+}
+
+pub mod project {
+    use std::path::PathBuf;
+
+    #[derive(Debug, clap::Subcommand)]
+    pub enum SubCommands {
+        /// List all projects.
+        List,
+        /// Add the repository at `path` as project.
+        Add {
+            /// The worktree of the repository to add, defaults to `--project`.
+            path: Option<PathBuf>,
+        },
+    }
+}
+
+pub mod vbranch {
+    #[derive(Debug, clap::Subcommand)]
+    pub enum SubCommands {
+        /// List all virtual branches.
+        List,
+        /// List the uncommitted hunks of each applied virtual branch, along with their ownership claims.
+        Hunks {
+            /// The name or id of the branch to limit the output to.
+            branch: Option<String>,
+        },
+        /// Create a new virtual branch.
+        Create {
+            /// The name of the new branch.
+            name: String,
+            /// Make the new branch the default destination for new changes.
+            #[clap(long)]
+            set_default: bool,
+            /// The name or id of the applied branch to stack the new branch on.
+            #[clap(long)]
+            parent: Option<String>,
+        },
+        /// Commit the uncommitted changes owned by a branch.
+        Commit {
+            /// The name or id of the branch to commit to.
+            branch: String,
+            /// The commit message.
+            #[clap(short = 'm', long)]
+            message: String,
+            /// Only commit the given ownership claims, like `path/to/file:1-5`, instead of all changes.
+            #[clap(long = "only")]
+            ownership: Vec<String>,
+            /// Skip running the `pre-commit` and `commit-msg` hooks.
+            #[clap(long)]
+            no_verify: bool,
+        },
+        /// Move ownership claims, like `path/to/file:1-5-<hash>`, into a branch.
+        Move {
+            /// The name or id of the branch to move the claims to.
+            branch: String,
+            /// The claims to move, as printed by `but branch hunks`.
+            #[clap(required = true)]
+            ownership: Vec<String>,
+        },
+        /// Discard the changes of the given ownership claims from the worktree.
+        Discard {
+            /// The claims to discard, as printed by `but branch hunks`.
+            #[clap(required = true)]
+            ownership: Vec<String>,
+        },
+        /// Push a branch to its upstream.
+        Push {
+            /// The name or id of the branch to push.
+            branch: String,
+            /// Push even if the remote branch has commits that would be lost.
+            #[clap(long)]
+            force: bool,
+        },
+    }
+}
+
+pub mod base {
+    #[derive(Debug, clap::Subcommand)]
+    pub enum SubCommands {
+        /// Show the base branch and how far the workspace is behind it.
+        Show,
+        /// Set the base branch, like `origin/main`.
+        Set {
+            /// The remote branch to use as base.
+            branch: String,
+        },
+        /// Fetch the remote of the base branch.
+        Fetch,
+        /// Rebase or merge all applied virtual branches onto the latest base branch.
+        Update,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    #[test]
+    fn args_are_consistent() {
+        super::Args::command().debug_assert();
+    }
+}
@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use gitbutler_core::virtual_branches::BaseBranch;
+
+use super::{first_line, App};
+use crate::args::base::SubCommands;
+
+pub async fn run(app: &App, cmd: SubCommands) -> Result<()> {
+    let project = app.project()?;
+    match cmd {
+        SubCommands::Show => {
+            let base = app.vbranches.get_base_branch_data(&project.id).await?;
+            app.print(&base, |base| match base {
+                Some(base) => print_base(base),
+                None => {
+                    println!("no base branch set, set one with `but base set <remote>/<branch>`")
+                }
+            })
+        }
+        SubCommands::Set { branch } => {
+            let refname = format!("refs/remotes/{branch}")
+                .parse()
+                .context("invalid branch name")?;
+            let base = app.vbranches.set_base_branch(&project.id, &refname).await?;
+            app.print(&base, print_base)
+        }
+        SubCommands::Fetch => {
+            let base = app.vbranches.fetch_from_target(&project.id, None).await?;
+            app.print(&base, print_base)
+        }
+        SubCommands::Update => {
+            app.vbranches.update_base_branch(&project.id).await?;
+            let base = app.vbranches.get_base_branch_data(&project.id).await?;
+            app.print(&base, |base| {
+                if let Some(base) = base {
+                    print_base(base);
+                }
+            })
+        }
+    }
+}
+
+fn print_base(base: &BaseBranch) {
+    println!("{} at {}", base.branch_name, base.base_sha);
+    if base.behind > 0 {
+        println!("{} commit(s) behind {}", base.behind, base.current_sha);
+        for commit in &base.upstream_commits {
+            println!("  {} {}", commit.id, first_line(&commit.description));
+        }
+    } else {
+        println!("up to date");
+    }
+}
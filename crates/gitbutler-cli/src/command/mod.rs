@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use gitbutler_core::{
+    git, keys,
+    projects::{self, Project},
+    users,
+    virtual_branches::{self, VirtualBranch},
+};
+use serde::Serialize;
+
+pub mod base;
+pub mod project;
+pub mod vbranch;
+
+/// The identifier of the GitButler app, which determines where it keeps its data.
+const APP_IDENTIFIER: &str = "com.gitbutler.app";
+
+/// Everything a command needs to talk to GitButler.
+pub struct App {
+    projects: projects::Controller,
+    vbranches: virtual_branches::Controller,
+    project_path: PathBuf,
+    json: bool,
+}
+
+impl App {
+    pub fn new(app_data_dir: Option<PathBuf>, project_path: PathBuf, json: bool) -> Result<Self> {
+        let app_data_dir = match app_data_dir {
+            Some(dir) => dir,
+            None => dirs::data_dir()
+                .context("could not determine the application data directory, use --app-data-dir")?
+                .join(APP_IDENTIFIER),
+        };
+        let users = users::Controller::from_path(&app_data_dir);
+        let keys = keys::Controller::from_path(&app_data_dir);
+        let projects = projects::Controller::from_path(&app_data_dir);
+        let helper = git::credentials::Helper::from_path(&app_data_dir);
+        let vbranches = virtual_branches::Controller::new(projects.clone(), users, keys, helper);
+        Ok(Self {
+            projects,
+            vbranches,
+            project_path,
+            json,
+        })
+    }
+
+    /// The project whose worktree is the one passed with `--project`.
+    pub fn project(&self) -> Result<Project> {
+        let path = canonicalize(&self.project_path)?;
+        self.projects
+            .list()?
+            .into_iter()
+            .find(|project| canonicalize(&project.path).map_or(false, |p| p == path))
+            .with_context(|| {
+                format!(
+                    "{} is not a GitButler project, add it with `but project add`",
+                    path.display()
+                )
+            })
+    }
+
+    /// Find an applied or unapplied virtual branch by its `name` or id.
+    pub async fn find_branch(&self, project: &Project, name: &str) -> Result<VirtualBranch> {
+        let (branches, _) = self.vbranches.list_virtual_branches(&project.id).await?;
+        let Some(branch) = branches
+            .into_iter()
+            .find(|branch| branch.name == name || branch.id.to_string() == name)
+        else {
+            bail!("there is no virtual branch named '{name}'");
+        };
+        Ok(branch)
+    }
+
+    /// Print `value` as JSON if `--json` was given, or call `human` to print it otherwise.
+    pub fn print<T: Serialize + ?Sized>(&self, value: &T, human: impl FnOnce(&T)) -> Result<()> {
+        if self.json {
+            let out = std::io::stdout().lock();
+            serde_json::to_writer_pretty(out, value)?;
+            println!();
+        } else {
+            human(value);
+        }
+        Ok(())
+    }
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf> {
+    path.canonicalize()
+        .with_context(|| format!("failed to canonicalize {}", path.display()))
+}
+
+/// The first line of a commit `message`, its title.
+fn first_line(message: impl ToString) -> String {
+    message
+        .to_string()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_owned()
+}
@@ -0,0 +1,37 @@
+use anyhow::Result;
+
+use super::{canonicalize, App};
+use crate::args::project::SubCommands;
+
+pub fn run(app: &App, cmd: SubCommands) -> Result<()> {
+    match cmd {
+        SubCommands::List => {
+            let projects = app.projects.list()?;
+            app.print(&projects, |projects| {
+                for project in projects {
+                    println!(
+                        "{}\t{}\t{}",
+                        project.id,
+                        project.title,
+                        project.path.display()
+                    );
+                }
+            })
+        }
+        SubCommands::Add { path } => {
+            let path = path.unwrap_or_else(|| app.project_path.clone());
+            let path = canonicalize(&path)?;
+            let project = app
+                .projects
+                .add(path)
+                .map_err(gitbutler_core::error::into_anyhow)?;
+            app.print(&project, |project| {
+                println!(
+                    "added project {} at {}",
+                    project.title,
+                    project.path.display()
+                );
+            })
+        }
+    }
+}
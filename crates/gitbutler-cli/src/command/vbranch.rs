@@ -0,0 +1,179 @@
+use anyhow::{Context, Result};
+use gitbutler_core::virtual_branches::{
+    branch::{BranchCreateRequest, BranchOwnershipClaims, BranchUpdateRequest, OwnershipClaim},
+    VirtualBranch, VirtualBranchHunk,
+};
+
+use super::{first_line, App};
+use crate::args::vbranch::SubCommands;
+
+pub async fn run(app: &App, cmd: SubCommands) -> Result<()> {
+    let project = app.project()?;
+    match cmd {
+        SubCommands::List => {
+            let (branches, _) = app.vbranches.list_virtual_branches(&project.id).await?;
+            app.print(&branches, |branches| {
+                for branch in branches {
+                    let parent = branch
+                        .parent
+                        .and_then(|id| branches.iter().find(|b| b.id == id))
+                        .map(|parent| format!(", stacked on {}", parent.name))
+                        .unwrap_or_default();
+                    println!(
+                        "{default} {id} {name}\t{applied}, {commits} commit(s), {files} file(s) changed{parent}",
+                        default = if branch.selected_for_changes { "*" } else { " " },
+                        id = branch.id,
+                        name = branch.name,
+                        applied = if branch.active { "applied" } else { "unapplied" },
+                        commits = branch.commits.len(),
+                        files = branch.files.len(),
+                    );
+                }
+            })
+        }
+        SubCommands::Hunks { branch } => {
+            let branches = match branch {
+                Some(name) => vec![app.find_branch(&project, &name).await?],
+                None => {
+                    let (branches, _) = app.vbranches.list_virtual_branches(&project.id).await?;
+                    branches.into_iter().filter(|b| b.active).collect()
+                }
+            };
+            app.print(&branches, |branches| {
+                for branch in branches {
+                    println!("{}", branch.name);
+                    for hunk in branch.files.iter().flat_map(|file| &file.hunks) {
+                        println!(
+                            "  {}{}",
+                            ownership_claim(hunk),
+                            if hunk.locked { " (locked)" } else { "" }
+                        );
+                    }
+                }
+            })
+        }
+        SubCommands::Create {
+            name,
+            set_default,
+            parent,
+        } => {
+            let parent = match parent {
+                Some(parent) => Some(app.find_branch(&project, &parent).await?.id),
+                None => None,
+            };
+            let branch_id = app
+                .vbranches
+                .create_virtual_branch(
+                    &project.id,
+                    &BranchCreateRequest {
+                        name: Some(name),
+                        selected_for_changes: set_default.then_some(true),
+                        parent,
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            app.print(&branch_id, |branch_id| println!("{branch_id}"))
+        }
+        SubCommands::Commit {
+            branch,
+            message,
+            ownership,
+            no_verify,
+        } => {
+            let branch = app.find_branch(&project, &branch).await?;
+            let ownership = (!ownership.is_empty())
+                .then(|| parse_claims(&ownership))
+                .transpose()?;
+            let oid = app
+                .vbranches
+                .create_commit(
+                    &project.id,
+                    &branch.id,
+                    &message,
+                    ownership.as_ref(),
+                    !no_verify,
+                )
+                .await?;
+            app.print(&oid, |oid| {
+                println!("{} {}", oid, first_line(&message));
+            })
+        }
+        SubCommands::Move { branch, ownership } => {
+            let branch = app.find_branch(&project, &branch).await?;
+            let mut claims = branch.ownership.clone();
+            for claim in parse_claims(&ownership)?.claims {
+                claims.put(claim);
+            }
+            app.vbranches
+                .update_virtual_branch(
+                    &project.id,
+                    BranchUpdateRequest {
+                        id: branch.id,
+                        ownership: Some(claims),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            print_branch(app, &project, &branch).await
+        }
+        SubCommands::Discard { ownership } => {
+            let claims = parse_claims(&ownership)?;
+            app.vbranches
+                .unapply_ownership(&project.id, &claims)
+                .await?;
+            app.print(&claims, |claims| {
+                for claim in &claims.claims {
+                    println!("discarded {claim}");
+                }
+            })
+        }
+        SubCommands::Push { branch, force } => {
+            let branch = app.find_branch(&project, &branch).await?;
+            app.vbranches
+                .push_virtual_branch(&project.id, &branch.id, force, None)
+                .await?;
+            print_branch(app, &project, &branch).await
+        }
+    }
+}
+
+/// Print the current state of `branch` after it was changed.
+async fn print_branch(
+    app: &App,
+    project: &gitbutler_core::projects::Project,
+    branch: &VirtualBranch,
+) -> Result<()> {
+    let branch = app.find_branch(project, &branch.id.to_string()).await?;
+    app.print(&branch, |branch| {
+        println!("{} {}", branch.id, branch.name);
+        if let Some(upstream) = &branch.upstream {
+            println!("  upstream {}", upstream.name);
+        }
+        for hunk in branch.files.iter().flat_map(|file| &file.hunks) {
+            println!("  {}", ownership_claim(hunk));
+        }
+    })
+}
+
+fn parse_claims(claims: &[String]) -> Result<BranchOwnershipClaims> {
+    let mut ownership = BranchOwnershipClaims::default();
+    for claim in claims {
+        let claim: OwnershipClaim = claim
+            .parse()
+            .with_context(|| format!("invalid ownership claim '{claim}'"))?;
+        ownership.put(claim);
+    }
+    Ok(ownership)
+}
+
+/// The claim identifying `hunk` precisely, as accepted by [`parse_claims()`].
+fn ownership_claim(hunk: &VirtualBranchHunk) -> String {
+    format!(
+        "{}:{}-{}-{:x}",
+        hunk.file_path.display(),
+        hunk.start,
+        hunk.end,
+        hunk.hash
+    )
+}
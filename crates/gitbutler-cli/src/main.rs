@@ -0,0 +1,25 @@
+//! `but` is a headless frontend to GitButler's virtual branches, meant for terminals and scripts.
+//!
+//! It drives the very same [`virtual_branches::Controller`](gitbutler_core::virtual_branches::Controller)
+//! and [`projects::Controller`](gitbutler_core::projects::Controller) the desktop application uses,
+//! and shares its application data directory, so both can be used on the same project interchangeably.
+
+use anyhow::Result;
+use clap::Parser;
+
+mod args;
+use args::{Args, Subcommands};
+
+mod command;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let app = command::App::new(args.app_data_dir, args.project, args.json)?;
+
+    match args.cmd {
+        Subcommands::Project(cmd) => command::project::run(&app, cmd),
+        Subcommands::Branch(cmd) => command::vbranch::run(&app, cmd).await,
+        Subcommands::Base(cmd) => command::base::run(&app, cmd).await,
+    }
+}
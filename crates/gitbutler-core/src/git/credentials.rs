@@ -1,4 +1,7 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+mod agent;
+pub use agent::AgentIdentity;
 
 use crate::error::{AnyhowContextExt, Code, Context, ErrorWithContext};
 use crate::{error, keys, project_repository, projects, users};
@@ -10,6 +13,8 @@ pub enum SshCredential {
         passphrase: Option<String>,
     },
     GitButlerKey(Box<keys::PrivateKey>),
+    /// The keys of the ssh-agent, which are tried in the order it lists them.
+    Agent(Vec<AgentIdentity>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -51,6 +56,21 @@ impl From<Credential> for git2::RemoteCallbacks<'_> {
                     git2::Cred::ssh_key_from_memory("git", None, &key.to_string(), None)
                 });
             }
+            Credential::Ssh(SshCredential::Agent(identities)) => {
+                remote_callbacks.credentials(move |url, username_from_url, _allowed_types| {
+                    tracing::info!(
+                        "authenticating with {} using ssh-agent keys {}",
+                        url,
+                        identities
+                            .iter()
+                            .map(|identity| format!("{} {}", identity.key_type, identity.comment))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                    // libgit2 finds the agent through SSH_AUTH_SOCK, and tries its keys in order
+                    git2::Cred::ssh_key_from_agent(username_from_url.unwrap_or("git"))
+                });
+            }
             Credential::Https(HttpsCredential::CredentialHelper { username, password }) => {
                 remote_callbacks.credentials(move |url, _username_from_url, _allowed_types| {
                     tracing::info!("authenticating with {url} as '{username}' with password using credential helper");
@@ -351,6 +371,17 @@ impl Helper {
 
     fn ssh_flow(&self) -> Result<Vec<SshCredential>, HelpError> {
         let mut flow = vec![];
+
+        // like ssh itself, try the keys of the agent before the key files. It's looked up the
+        // way libgit2 does when it authenticates, so that the keys are the ones that are tried.
+        if let Some(ssh_auth_sock) = std::env::var_os("SSH_AUTH_SOCK") {
+            match agent::identities(Path::new(&ssh_auth_sock)) {
+                Ok(identities) if identities.is_empty() => {}
+                Ok(identities) => flow.push(SshCredential::Agent(identities)),
+                Err(error) => tracing::warn!(?error, "failed to list ssh-agent identities"),
+            }
+        }
+
         if let Some(home_path) = self.home_dir.as_ref() {
             let id_rsa_path = home_path.join(".ssh").join("id_rsa");
             if id_rsa_path.exists() {
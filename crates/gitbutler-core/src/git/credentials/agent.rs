@@ -0,0 +1,109 @@
+//! Just enough of the [ssh-agent protocol](https://datatracker.ietf.org/doc/html/draft-miller-ssh-agent)
+//! to learn which identities a running agent offers.
+
+use std::{
+    io::{Read, Write},
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH2_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH2_AGENT_IDENTITIES_ANSWER: u8 = 12;
+
+/// The longest answer that is accepted from the agent, as in OpenSSH.
+const MAX_AGENT_REPLY_LEN: usize = 256 * 1024;
+
+/// How long to wait for the agent before giving up on it, so that a stale socket can't block
+/// fetches and pushes.
+const AGENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A key held by an ssh-agent, which can be hardware-backed and may never leave the agent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentIdentity {
+    /// The key type, e.g. `ssh-ed25519` or `sk-ssh-ed25519@openssh.com`.
+    pub key_type: String,
+    /// The public key in the ssh wire format.
+    pub public_key: Vec<u8>,
+    /// Usually the path the key was loaded from.
+    pub comment: String,
+}
+
+/// The identities the agent listening on `socket` offers, in the order it offers them.
+#[cfg(unix)]
+pub fn identities(socket: &Path) -> Result<Vec<AgentIdentity>> {
+    let mut stream = std::os::unix::net::UnixStream::connect(socket)
+        .with_context(|| format!("failed to connect to ssh-agent at {}", socket.display()))?;
+    stream.set_read_timeout(Some(AGENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(AGENT_TIMEOUT))?;
+
+    stream.write_all(&1u32.to_be_bytes())?;
+    stream.write_all(&[SSH2_AGENTC_REQUEST_IDENTITIES])?;
+
+    let mut len = [0; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_AGENT_REPLY_LEN {
+        bail!("ssh-agent answer of {len} bytes is too long");
+    }
+    let mut message = vec![0; len];
+    stream.read_exact(&mut message)?;
+    parse_identities_answer(&message)
+}
+
+/// The agent can only be reached through named pipes on Windows, which isn't supported.
+#[cfg(not(unix))]
+pub fn identities(_socket: &Path) -> Result<Vec<AgentIdentity>> {
+    Ok(vec![])
+}
+
+fn parse_identities_answer(message: &[u8]) -> Result<Vec<AgentIdentity>> {
+    let mut reader = Reader(message);
+    match reader.byte()? {
+        SSH2_AGENT_IDENTITIES_ANSWER => {}
+        SSH_AGENT_FAILURE => bail!("ssh-agent refused to list its identities"),
+        other => bail!("unexpected ssh-agent message type {other}"),
+    }
+
+    let count = reader.u32()?;
+    (0..count)
+        .map(|_| {
+            let public_key = reader.string()?.to_vec();
+            let comment = String::from_utf8_lossy(reader.string()?).into_owned();
+            let key_type = String::from_utf8_lossy(Reader(&public_key).string()?).into_owned();
+            Ok(AgentIdentity {
+                key_type,
+                public_key,
+                comment,
+            })
+        })
+        .collect()
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            bail!("truncated ssh-agent message");
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn string(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+}
@@ -54,6 +54,18 @@ pub struct GitHunk {
     pub binary: bool,
     pub locked_to: Box<[HunkLock]>,
     pub change_type: ChangeType,
+    /// Set if this hunk only has some of the changes of the hunk produced by `git2`.
+    #[serde(skip)]
+    pub selection: Option<HunkSelection>,
+}
+
+/// The changes of a hunk that were selected, see [`GitHunk::select_lines()`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct HunkSelection {
+    /// The diff of the hunk with all of its changes, as produced by `git2`.
+    pub diff_lines: BString,
+    /// The selected lines, as indices into the lines of `diff_lines` that follow its header.
+    pub lines: Vec<u32>,
 }
 
 /// Lifecycle
@@ -70,6 +82,7 @@ impl GitHunk {
             binary: true,
             change_type,
             locked_to: Box::new([]),
+            selection: None,
         }
     }
 
@@ -84,7 +97,100 @@ impl GitHunk {
             binary: false,
             change_type: ChangeType::Modified,
             locked_to: Box::new([]),
+            selection: None,
+        }
+    }
+
+    /// Returns a hunk with only the changes of this hunk that are at `lines`, indices into the lines
+    /// following the header of [`Self::full_diff()`], or `None` if none of them are.
+    ///
+    /// Deletions that aren't selected become context and additions that aren't selected are left out,
+    /// so the hunk still applies onto the same base. Its ranges remain those of the full hunk,
+    /// only its diff is reduced.
+    pub fn select_lines(&self, lines: &[u32]) -> Option<GitHunk> {
+        let selected = self
+            .selected_lines()
+            .into_iter()
+            .filter(|line| lines.contains(line))
+            .collect::<Vec<_>>();
+        if selected.is_empty() {
+            return None;
+        }
+
+        let full_diff = self.full_diff().to_owned();
+        if selected == self.changed_lines() {
+            return Some(GitHunk {
+                diff_lines: full_diff,
+                selection: None,
+                ..self.clone()
+            });
+        }
+
+        let mut header_and_body = full_diff.lines_with_terminator();
+        let header = header_and_body.next().unwrap_or_default();
+        let mut body = BString::default();
+        let (mut old_lines, mut new_lines) = (0, 0);
+        let mut previous_kept = true;
+        for (idx, line) in header_and_body.enumerate() {
+            let is_selected = selected.contains(&(idx as u32));
+            match line.first() {
+                Some(b'+') if !is_selected => {
+                    previous_kept = false;
+                    continue;
+                }
+                Some(b'-') if !is_selected => {
+                    body.push(b' ');
+                    body.push_str(&line[1..]);
+                    old_lines += 1;
+                    new_lines += 1;
+                }
+                Some(b'+') => {
+                    body.push_str(line);
+                    new_lines += 1;
+                }
+                Some(b'-') => {
+                    body.push_str(line);
+                    old_lines += 1;
+                }
+                Some(b'\\') => {
+                    // the no newline marker belongs to the line before it
+                    if previous_kept {
+                        body.push_str(line);
+                    }
+                    continue;
+                }
+                _ => {
+                    body.push_str(line);
+                    old_lines += 1;
+                    new_lines += 1;
+                }
+            }
+            previous_kept = true;
         }
+
+        // keep whatever follows the ranges in the header, like the name of the enclosing function
+        let header = header.trim_end_with(|c| c == '\r' || c == '\n');
+        let header_suffix = header
+            .get(2..)
+            .and_then(|rest| rest.find(b"@@").map(|pos| &rest[pos + 2..]))
+            .unwrap_or_default();
+        let mut diff_lines: BString = format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, old_lines, self.new_start, new_lines
+        )
+        .into();
+        diff_lines.push_str(header_suffix);
+        diff_lines.push(b'\n');
+        diff_lines.push_str(body);
+
+        Some(GitHunk {
+            diff_lines,
+            selection: Some(HunkSelection {
+                diff_lines: full_diff,
+                lines: selected,
+            }),
+            ..self.clone()
+        })
     }
 }
 
@@ -98,6 +204,41 @@ impl GitHunk {
         self.locked_to = locks.to_owned().into();
         self
     }
+
+    /// The diff of the hunk with all of its changes, even if only some of them are selected.
+    pub fn full_diff(&self) -> &BStr {
+        self.selection
+            .as_ref()
+            .map_or(self.diff_lines.as_ref(), |selection| {
+                selection.diff_lines.as_ref()
+            })
+    }
+
+    /// The lines of [`Self::full_diff()`] that add or delete something, as indices into the lines
+    /// that follow its header.
+    pub fn changed_lines(&self) -> Vec<u32> {
+        self.full_diff()
+            .lines()
+            .skip(1)
+            .enumerate()
+            .filter(|(_, line)| line.starts_with(b"+") || line.starts_with(b"-"))
+            .map(|(idx, _)| idx as u32)
+            .collect()
+    }
+
+    /// The changed lines this hunk has, which are all of them unless it is a selection.
+    pub fn selected_lines(&self) -> Vec<u32> {
+        match &self.selection {
+            Some(selection) => selection.lines.clone(),
+            None => self.changed_lines(),
+        }
+    }
+
+    /// Only the changes of text hunks of modified files can be selected line by line, as added or deleted
+    /// files and binary files are always taken as a whole.
+    pub fn can_select_lines(&self) -> bool {
+        !self.binary && self.change_type == ChangeType::Modified
+    }
 }
 
 // A hunk is locked when it depends on changes in commits that are in your
@@ -316,6 +457,7 @@ fn hunks_by_filepath(repo: Option<&Repository>, diff: &git2::Diff) -> Result<Dif
                                         binary: false,
                                         change_type,
                                         locked_to: Box::new([]),
+                                        selection: None,
                                     }
                                 }
                                 LineOrHexHash::HexHashOfBinaryBlob(id) => {
@@ -423,6 +565,7 @@ pub fn reverse_hunk(hunk: &GitHunk) -> Option<GitHunk> {
             binary: hunk.binary,
             change_type: hunk.change_type,
             locked_to: Box::new([]),
+            selection: None,
         })
     }
 }
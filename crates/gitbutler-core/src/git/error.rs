@@ -9,7 +9,7 @@ pub enum Error {
     #[error("authentication failed")]
     Auth(git2::Error),
     #[error("sign error: {0}")]
-    Signing(keys::SignError),
+    Signing(super::SigningError),
     #[error("remote url error: {0}")]
     Url(super::url::ParseError),
     #[error("io error: {0}")]
@@ -51,6 +51,12 @@ impl From<git2::Error> for Error {
 
 impl From<keys::SignError> for Error {
     fn from(err: keys::SignError) -> Self {
+        Error::Signing(err.into())
+    }
+}
+
+impl From<super::SigningError> for Error {
+    fn from(err: super::SigningError) -> Self {
         Error::Signing(err)
     }
 }
@@ -0,0 +1,282 @@
+use std::{
+    fmt,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use git2_hooks::HookResult;
+
+use super::{Oid, Repository, Result, Tree};
+
+/// Where hooks are looked up if `core.hooksPath` isn't set, relative to the git directory.
+const HOOK_DIRS: &[&str] = &["hooks", "../.husky"];
+
+pub const HOOK_PRE_PUSH: &str = "pre-push";
+pub const HOOK_POST_PUSH: &str = "post-push";
+
+/// A reference that is pushed, as the push hooks receive it on stdin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushUpdate {
+    pub local_ref: String,
+    pub local_oid: Oid,
+    pub remote_ref: String,
+    /// Where the remote reference is now, or `None` if it doesn't exist yet.
+    pub remote_oid: Option<Oid>,
+}
+
+impl fmt::Display for PushUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} {} {} {}",
+            self.local_ref,
+            self.local_oid,
+            self.remote_ref,
+            self.remote_oid
+                .map_or_else(|| "0".repeat(40), |oid| oid.to_string())
+        )
+    }
+}
+
+/// A worktree, an index and a git directory outside of the repository that contain nothing but
+/// a tree on top of a commit, so that hooks only get to see what's about to be committed, and not
+/// the changes of other branches that share the real worktree, index and `HEAD`.
+///
+/// The git directory is set up like the one of a linked worktree: it has its own `HEAD` and
+/// index, and shares everything else with the repository.
+struct TreeWorktree {
+    dir: tempfile::TempDir,
+}
+
+impl TreeWorktree {
+    fn new(repo: &git2::Repository, head: Oid, tree: &Tree) -> Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let tree: &git2::Tree = tree.into();
+
+        let mut checkout_builder = git2::build::CheckoutBuilder::new();
+        checkout_builder
+            .target_dir(&dir.path().join("worktree"))
+            .force()
+            .recreate_missing(true)
+            .update_index(false);
+        repo.checkout_tree(tree.as_object(), Some(&mut checkout_builder))?;
+
+        let git_dir = dir.path().join("git");
+        std::fs::create_dir(&git_dir)?;
+        std::fs::write(git_dir.join("HEAD"), format!("{head}\n"))?;
+        std::fs::write(
+            git_dir.join("commondir"),
+            format!("{}\n", repo.path().display()),
+        )?;
+
+        let mut index = git2::Index::open(&git_dir.join("index"))?;
+        index.read_tree(tree)?;
+        index.write()?;
+
+        Ok(Self { dir })
+    }
+
+    fn worktree(&self) -> PathBuf {
+        self.dir.path().join("worktree")
+    }
+
+    fn git_dir(&self) -> PathBuf {
+        self.dir.path().join("git")
+    }
+
+    fn index(&self) -> PathBuf {
+        self.git_dir().join("index")
+    }
+}
+
+impl Repository {
+    /// Run the `pre-commit` hook as if `tree` was staged and checked out on top of `head`.
+    pub fn run_hook_pre_commit(&self, head: Oid, tree: &Tree) -> Result<HookResult> {
+        self.run_hook_on_tree(git2_hooks::HOOK_PRE_COMMIT, head, tree, &[])
+    }
+
+    /// Run the `commit-msg` hook as if `tree` was staged and checked out on top of `head`,
+    /// and pick up the changes it made to `msg`.
+    pub fn run_hook_commit_msg(
+        &self,
+        head: Oid,
+        tree: &Tree,
+        msg: &mut String,
+    ) -> Result<HookResult> {
+        let repo: &git2::Repository = self.into();
+        if self.find_hook(git2_hooks::HOOK_COMMIT_MSG)?.is_none() {
+            return Ok(HookResult::NoHookFound);
+        }
+
+        let msg_file = repo.path().join("COMMIT_EDITMSG");
+        std::fs::write(&msg_file, msg.as_bytes())?;
+        let result = self.run_hook_on_tree(
+            git2_hooks::HOOK_COMMIT_MSG,
+            head,
+            tree,
+            &[&msg_file.to_string_lossy()],
+        )?;
+        *msg = std::fs::read_to_string(&msg_file)?;
+        Ok(result)
+    }
+
+    /// Run the `pre-push` hook before `updates` are pushed to `remote_name` at `remote_url`.
+    pub fn run_hook_pre_push(
+        &self,
+        remote_name: &str,
+        remote_url: &str,
+        updates: &[PushUpdate],
+    ) -> Result<HookResult> {
+        let Some(hook) = self.find_hook(HOOK_PRE_PUSH)? else {
+            return Ok(HookResult::NoHookFound);
+        };
+        let stdin = updates.iter().map(ToString::to_string).collect::<String>();
+        self.run_hook(hook, &[remote_name, remote_url], Some(&stdin), None)
+    }
+
+    /// Run the `post-push` hook after `updates` were pushed to `remote_name` at `remote_url`.
+    ///
+    /// Git itself has no such hook, it's called with the same arguments as `pre-push`.
+    pub fn run_hook_post_push(
+        &self,
+        remote_name: &str,
+        remote_url: &str,
+        updates: &[PushUpdate],
+    ) -> Result<()> {
+        if let Some(hook) = self.find_hook(HOOK_POST_PUSH)? {
+            let stdin = updates.iter().map(ToString::to_string).collect::<String>();
+            self.run_hook(hook, &[remote_name, remote_url], Some(&stdin), None)?;
+        }
+        Ok(())
+    }
+
+    fn run_hook_on_tree(
+        &self,
+        name: &str,
+        head: Oid,
+        tree: &Tree,
+        args: &[&str],
+    ) -> Result<HookResult> {
+        let repo: &git2::Repository = self.into();
+        let Some(hook) = self.find_hook(name)? else {
+            return Ok(HookResult::NoHookFound);
+        };
+        let worktree = TreeWorktree::new(repo, head, tree)?;
+        self.run_hook(hook, args, None, Some(&worktree))
+    }
+
+    /// Run `hook` in the worktree, or in `tree_worktree` if there is one.
+    fn run_hook(
+        &self,
+        hook: PathBuf,
+        args: &[&str],
+        stdin: Option<&str>,
+        tree_worktree: Option<&TreeWorktree>,
+    ) -> Result<HookResult> {
+        let repo: &git2::Repository = self.into();
+
+        let spawn = |mut command: Command| {
+            command
+                .args(args)
+                .stdin(if stdin.is_some() {
+                    Stdio::piped()
+                } else {
+                    Stdio::null()
+                })
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            match tree_worktree {
+                Some(tree_worktree) => command
+                    .current_dir(tree_worktree.worktree())
+                    .env("GIT_DIR", tree_worktree.git_dir())
+                    .env("GIT_COMMON_DIR", repo.path())
+                    .env("GIT_WORK_TREE", tree_worktree.worktree())
+                    .env("GIT_INDEX_FILE", tree_worktree.index()),
+                None => command.current_dir(repo.workdir().unwrap_or_else(|| repo.path())),
+            };
+            command.spawn()
+        };
+        let with_shell = || {
+            let mut command = Command::new("sh");
+            command.arg(&hook);
+            command
+        };
+
+        // like git, hooks are executed directly, and with `sh` if that's not possible
+        let mut child = if cfg!(windows) {
+            spawn(with_shell())?
+        } else {
+            match spawn(Command::new(&hook)) {
+                Err(error) if is_not_executable_format(&error) => spawn(with_shell())?,
+                result => result?,
+            }
+        };
+        if let (Some(stdin), Some(mut child_stdin)) = (stdin, child.stdin.take()) {
+            match child_stdin.write_all(stdin.as_bytes()) {
+                // the hook doesn't have to read what it's given
+                Err(error) if error.kind() == std::io::ErrorKind::BrokenPipe => {}
+                result => result?,
+            }
+        }
+        let output = child.wait_with_output()?;
+
+        if output.status.success() {
+            Ok(HookResult::Ok { hook })
+        } else {
+            Ok(HookResult::RunNotSuccessful {
+                code: output.status.code(),
+                stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+                hook,
+            })
+        }
+    }
+
+    /// The executable hook called `name`, looked up in the same places git and `git2_hooks` do.
+    fn find_hook(&self, name: &str) -> Result<Option<PathBuf>> {
+        let repo: &git2::Repository = self.into();
+        let hooks_path = match repo.config()?.get_path("core.hooksPath") {
+            Ok(hooks_path) => Some(hooks_path),
+            Err(error) if error.code() == git2::ErrorCode::NotFound => None,
+            Err(error) => return Err(error.into()),
+        };
+        let hook = match hooks_path {
+            Some(hooks_path) => repo
+                .workdir()
+                .unwrap_or_else(|| repo.path())
+                .join(hooks_path)
+                .join(name),
+            None => HOOK_DIRS
+                .iter()
+                .map(|dir| repo.path().join(dir).join(name))
+                .find(|hook| hook.exists())
+                .unwrap_or_else(|| repo.path().join(HOOK_DIRS[0]).join(name)),
+        };
+        Ok(is_executable(&hook).then_some(hook))
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Whether `error` means that a file can't be executed as it isn't a binary and has no shebang.
+#[cfg(unix)]
+fn is_not_executable_format(error: &std::io::Error) -> bool {
+    error.raw_os_error() == Some(libc::ENOEXEC)
+}
+
+#[cfg(not(unix))]
+fn is_not_executable_format(_error: &std::io::Error) -> bool {
+    false
+}
@@ -7,6 +7,9 @@ pub use blob::*;
 mod error;
 pub use error::*;
 
+mod hooks;
+pub use hooks::*;
+
 mod reference;
 pub use reference::*;
 mod repository;
@@ -34,6 +37,9 @@ pub use oid::*;
 mod signature;
 pub use signature::*;
 
+mod signer;
+pub use signer::*;
+
 mod config;
 pub use config::*;
 
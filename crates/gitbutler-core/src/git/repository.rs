@@ -1,13 +1,12 @@
 use std::{io::Write, path::Path, str};
 
 use git2::{BlameOptions, Submodule};
-use git2_hooks::HookResult;
 
 use super::{
-    Blob, Branch, Commit, Config, Index, Oid, Reference, Refname, Remote, Result, Signature, Tree,
-    TreeBuilder, Url,
+    Blob, Branch, Commit, Config, Index, Oid, Reference, Refname, Remote, Result, Signature,
+    Signer, Tree, TreeBuilder, Url,
 };
-use crate::{keys, path::Normalize};
+use crate::path::Normalize;
 
 // wrapper around git2::Repository to get control over how it's used.
 pub struct Repository(git2::Repository);
@@ -271,10 +270,11 @@ impl Repository {
     pub fn commit_signed(
         &self,
         author: &Signature<'_>,
+        committer: &Signature<'_>,
         message: &str,
         tree: &Tree<'_>,
         parents: &[&Commit<'_>],
-        key: &keys::PrivateKey,
+        signer: &Signer,
     ) -> Result<Oid> {
         let parents: Vec<&git2::Commit> = parents
             .iter()
@@ -282,21 +282,35 @@ impl Repository {
             .collect::<Vec<_>>();
         let commit_buffer = self.0.commit_create_buffer(
             author.into(),
-            // author and committer must be the same
-            // for signed commits
-            author.into(),
+            committer.into(),
             message,
             tree.into(),
             &parents,
         )?;
-        let commit_buffer = str::from_utf8(&commit_buffer).unwrap();
-        let signature = key.sign(commit_buffer.as_bytes())?;
+        let commit_buffer = str::from_utf8(&commit_buffer)?;
+        let signature = signer.sign(commit_buffer.as_bytes())?;
         self.0
             .commit_signed(commit_buffer, &signature, None)
             .map(Into::into)
             .map_err(Into::into)
     }
 
+    /// Create a commit without updating any reference, signed by `signer` if there is one.
+    pub fn commit_with_signer(
+        &self,
+        author: &Signature<'_>,
+        committer: &Signature<'_>,
+        message: &str,
+        tree: &Tree<'_>,
+        parents: &[&Commit<'_>],
+        signer: Option<&Signer>,
+    ) -> Result<Oid> {
+        match signer {
+            Some(signer) => self.commit_signed(author, committer, message, tree, parents, signer),
+            None => self.commit(None, author, committer, message, tree, parents),
+        }
+    }
+
     pub fn config(&self) -> Result<Config> {
         self.0.config().map(Into::into).map_err(Into::into)
     }
@@ -464,16 +478,6 @@ impl Repository {
             .map_err(Into::into)
     }
 
-    pub fn run_hook_pre_commit(&self) -> Result<HookResult> {
-        let res = git2_hooks::hooks_pre_commit(&self.0, Some(&["../.husky"]))?;
-        Ok(res)
-    }
-
-    pub fn run_hook_commit_msg(&self, msg: &mut String) -> Result<HookResult> {
-        let res = git2_hooks::hooks_commit_msg(&self.0, Some(&["../.husky"]), msg)?;
-        Ok(res)
-    }
-
     pub fn run_hook_post_commit(&self) -> Result<()> {
         git2_hooks::hooks_post_commit(&self.0, Some(&["../.husky"]))?;
         Ok(())
@@ -0,0 +1,190 @@
+use std::{
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+    str::FromStr,
+};
+
+use crate::keys;
+
+/// The kind of signature to create, as configured with `gpg.format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigningFormat {
+    OpenPgp,
+    X509,
+    Ssh,
+}
+
+impl SigningFormat {
+    /// The program git uses for this format if none is configured.
+    pub fn default_program(&self) -> &'static str {
+        match self {
+            SigningFormat::OpenPgp => "gpg",
+            SigningFormat::X509 => "gpgsm",
+            SigningFormat::Ssh => "ssh-keygen",
+        }
+    }
+}
+
+impl FromStr for SigningFormat {
+    type Err = SigningError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "openpgp" => Ok(SigningFormat::OpenPgp),
+            "x509" => Ok(SigningFormat::X509),
+            "ssh" => Ok(SigningFormat::Ssh),
+            _ => Err(SigningError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// An external program that signs commits the way git would, with the user's own key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningProgram {
+    pub format: SigningFormat,
+    pub program: String,
+    /// The key as configured with `user.signingkey`, which is a key id for gpg and gpgsm,
+    /// and a path to a key or a literal public key for ssh.
+    pub key: String,
+}
+
+/// Whatever signs the commits GitButler creates.
+#[derive(Debug, Clone)]
+pub enum Signer {
+    /// GitButler's own key.
+    Key(Box<keys::PrivateKey>),
+    Program(SigningProgram),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SigningError {
+    #[error(transparent)]
+    Key(#[from] keys::SignError),
+    #[error("unsupported gpg.format `{0}`, expected openpgp, x509 or ssh")]
+    UnknownFormat(String),
+    #[error("commits are to be signed, but there is no signing key configured in user.signingkey")]
+    MissingKey,
+    #[error("failed to run `{program}` to sign the commit: {error}")]
+    Spawn {
+        program: String,
+        error: std::io::Error,
+    },
+    #[error("`{program}` failed to sign the commit: {message}")]
+    Failed { program: String, message: String },
+}
+
+impl Signer {
+    /// Produce an armored signature of the commit `buffer`.
+    pub fn sign(&self, buffer: &[u8]) -> Result<String, SigningError> {
+        match self {
+            Signer::Key(key) => Ok(key.sign(buffer)?),
+            Signer::Program(program) => program.sign(buffer),
+        }
+    }
+}
+
+impl SigningProgram {
+    pub fn sign(&self, buffer: &[u8]) -> Result<String, SigningError> {
+        match self.format {
+            SigningFormat::OpenPgp | SigningFormat::X509 => self.sign_gpg(buffer),
+            SigningFormat::Ssh => self.sign_ssh(buffer),
+        }
+    }
+
+    fn spawn_error(&self, error: std::io::Error) -> SigningError {
+        SigningError::Spawn {
+            program: self.program.clone(),
+            error,
+        }
+    }
+
+    fn failed(&self, output: &std::process::Output) -> SigningError {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr
+            .lines()
+            .filter(|line| !line.starts_with("[GNUPG:]"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        SigningError::Failed {
+            program: self.program.clone(),
+            message: if message.trim().is_empty() {
+                output.status.to_string()
+            } else {
+                message.trim().to_string()
+            },
+        }
+    }
+
+    fn sign_gpg(&self, buffer: &[u8]) -> Result<String, SigningError> {
+        let mut child = Command::new(&self.program)
+            .args(["--status-fd=2", "-bsau", &self.key])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| self.spawn_error(error))?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(buffer)
+            .map_err(|error| self.spawn_error(error))?;
+        let output = child
+            .wait_with_output()
+            .map_err(|error| self.spawn_error(error))?;
+
+        // like git, only trust the status line, as the exit code isn't always reliable
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let created = stderr
+            .lines()
+            .any(|line| line.starts_with("[GNUPG:] SIG_CREATED "));
+        if !output.status.success() || !created || output.stdout.is_empty() {
+            return Err(self.failed(&output));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn sign_ssh(&self, buffer: &[u8]) -> Result<String, SigningError> {
+        let dir = tempfile::tempdir().map_err(|error| self.spawn_error(error))?;
+        let mut command = Command::new(&self.program);
+        command.args(["-Y", "sign", "-n", "git", "-f"]);
+        match literal_ssh_key(&self.key) {
+            Some(literal_key) => {
+                let key_path = dir.path().join("key.pub");
+                std::fs::write(&key_path, literal_key).map_err(|error| self.spawn_error(error))?;
+                // the private half of a literal key can only be in the agent
+                command.arg(key_path).arg("-U");
+            }
+            None => {
+                command.arg(expand_home(&self.key));
+            }
+        }
+        let buffer_path = dir.path().join("commit");
+        std::fs::write(&buffer_path, buffer).map_err(|error| self.spawn_error(error))?;
+
+        let output = command
+            .arg(&buffer_path)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|error| self.spawn_error(error))?;
+        if !output.status.success() {
+            return Err(self.failed(&output));
+        }
+        std::fs::read_to_string(buffer_path.with_extension("sig"))
+            .map_err(|error| self.spawn_error(error))
+    }
+}
+
+/// Keys may be configured inline as `key::<public key>`, or as a bare `ssh-…` public key.
+fn literal_ssh_key(key: &str) -> Option<&str> {
+    key.strip_prefix("key::")
+        .or_else(|| key.starts_with("ssh-").then_some(key))
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
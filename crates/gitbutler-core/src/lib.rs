@@ -52,6 +52,13 @@ pub mod serde {
         v.to_str_lossy().serialize(s)
     }
 
+    pub fn as_opt_string_lossy<S>(v: &Option<BString>, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        v.as_ref().map(|v| v.to_str_lossy()).serialize(s)
+    }
+
     pub fn hash_to_hex<S>(v: &HunkHash, s: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
@@ -1,5 +1,10 @@
+use std::time::Duration;
+
 use crate::git;
 
+/// How long a fetch or push may take unless `gitbutler.remoteTimeout` says otherwise.
+const DEFAULT_REMOTE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
 pub struct Config<'a> {
     git_repository: &'a git::Repository,
 }
@@ -13,14 +18,84 @@ impl<'a> From<&'a git::Repository> for Config<'a> {
 }
 
 impl Config<'_> {
+    /// Whether commits should be signed, either because GitButler was asked to or because git is
+    /// configured to always sign with `commit.gpgSign`.
     pub fn sign_commits(&self) -> Result<bool, git::Error> {
-        let sign_commits = self
+        let config = self.git_repository.config()?;
+        let sign_commits = ["gitbutler.signCommits", "commit.gpgSign"]
+            .iter()
+            .any(|key| config.get_bool(key).unwrap_or(None).unwrap_or(false));
+        Ok(sign_commits)
+    }
+
+    /// The program to sign commits with the user's own key, as configured for git with
+    /// `user.signingkey`, `gpg.format`, `gpg.program` and `gpg.ssh.program`.
+    ///
+    /// Without `user.signingkey`, `commit.gpgSign` makes gpg pick the key by the committer's identity
+    /// like git does, while `gitbutler.signCommits` alone returns `None` as GitButler's own key should
+    /// be used instead.
+    pub fn signing_program(&self) -> Result<Option<git::SigningProgram>, git::Error> {
+        let config = self.git_repository.config()?;
+        let key = config
+            .get_string("user.signingkey")?
+            .filter(|key| !key.trim().is_empty());
+        let gpg_sign = config
+            .get_bool("commit.gpgSign")
+            .unwrap_or(None)
+            .unwrap_or(false);
+        if key.is_none() && !gpg_sign {
+            return Ok(None);
+        }
+        let format = match config.get_string("gpg.format")? {
+            Some(format) => format.parse::<git::SigningFormat>()?,
+            None => git::SigningFormat::OpenPgp,
+        };
+        let program_keys: &[&str] = match format {
+            git::SigningFormat::OpenPgp => &["gpg.openpgp.program", "gpg.program"],
+            git::SigningFormat::X509 => &["gpg.x509.program"],
+            git::SigningFormat::Ssh => &["gpg.ssh.program"],
+        };
+        let mut program = None;
+        for program_key in program_keys {
+            program = config.get_string(program_key)?;
+            if program.is_some() {
+                break;
+            }
+        }
+        let key = match (key, format) {
+            (Some(key), _) => key,
+            (None, git::SigningFormat::Ssh) => return Err(git::SigningError::MissingKey.into()),
+            (None, _) => match (
+                config.get_string("user.name")?,
+                config.get_string("user.email")?,
+            ) {
+                (Some(name), Some(email)) => format!("{name} <{email}>"),
+                _ => return Err(git::SigningError::MissingKey.into()),
+            },
+        };
+        Ok(Some(git::SigningProgram {
+            format,
+            program: program.unwrap_or_else(|| format.default_program().to_string()),
+            key,
+        }))
+    }
+
+    /// How long a fetch or push with the git executable may take before it's aborted, as configured
+    /// in seconds with `gitbutler.remoteTimeout`, or `None` if it's set to `0` to wait forever.
+    pub fn remote_timeout(&self) -> Result<Option<Duration>, git::Error> {
+        let timeout = self
             .git_repository
             .config()?
-            .get_bool("gitbutler.signCommits")
-            .unwrap_or(Some(false))
-            .unwrap_or(false);
-        Ok(sign_commits)
+            .get_string("gitbutler.remoteTimeout")?;
+        Ok(match timeout.map(|timeout| timeout.trim().parse::<u64>()) {
+            None => Some(DEFAULT_REMOTE_TIMEOUT),
+            Some(Ok(0)) => None,
+            Some(Ok(seconds)) => Some(Duration::from_secs(seconds)),
+            Some(Err(error)) => {
+                tracing::warn!(?error, "ignoring invalid gitbutler.remoteTimeout");
+                Some(DEFAULT_REMOTE_TIMEOUT)
+            }
+        })
     }
 
     pub fn user_real_comitter(&self) -> Result<bool, git::Error> {
@@ -1,43 +1,109 @@
 // stuff to manage merge conflict state
-// this is the dumbest possible way to do this, but it is a placeholder
-// conflicts are stored one path per line in .git/conflicts
+// conflicts are stored in .git/conflicts.toml, one entry per file with the blobs of the
+// merge base, our and their side of the merge, and the virtual branch the conflict belongs to.
 // merge parent is stored in .git/base_merge_parent
 // conflicts are removed as they are resolved, the conflicts file is removed when there are no more conflicts
 // the merge parent file is removed when the merge is complete
 
 use std::{
-    io::{BufRead, Write},
+    io::BufRead,
     path::{Path, PathBuf},
 };
 
-use anyhow::Result;
-use itertools::Itertools;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 
 use super::Repository;
-use crate::git;
+use crate::{git, virtual_branches::BranchId};
+
+/// A file that couldn't be merged, along with the three versions that went into the merge.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conflict {
+    /// The path of the conflicting file, relative to the worktree.
+    pub path: PathBuf,
+    /// The virtual branch whose changes conflict, if known.
+    #[serde(default)]
+    pub branch_id: Option<BranchId>,
+    /// The blob of the file in the merge base, or `None` if it didn't exist there.
+    #[serde(default)]
+    pub ancestor: Option<git::Oid>,
+    /// The blob of the file on our side of the merge, or `None` if we deleted it.
+    #[serde(default)]
+    pub ours: Option<git::Oid>,
+    /// The blob of the file on their side of the merge, or `None` if they deleted it.
+    #[serde(default)]
+    pub theirs: Option<git::Oid>,
+}
+
+/// One of the three versions of a conflicting file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictSide {
+    Ancestor,
+    Ours,
+    Theirs,
+}
+
+impl Conflict {
+    /// The blob of the file on `side`, or `None` if the file doesn't exist there.
+    pub fn side(&self, side: ConflictSide) -> Option<git::Oid> {
+        match side {
+            ConflictSide::Ancestor => self.ancestor,
+            ConflictSide::Ours => self.ours,
+            ConflictSide::Theirs => self.theirs,
+        }
+    }
+}
+
+/// The conflicts file, as persisted in TOML.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Conflicts {
+    #[serde(default)]
+    conflicts: Vec<Conflict>,
+}
+
+/// Collect the conflicts of a merge `index`, attributing them to `branch_id`.
+pub fn from_index(index: &git::Index, branch_id: Option<BranchId>) -> Result<Vec<Conflict>> {
+    let mut conflicts = Vec::new();
+    for conflict in index.conflicts().context("failed to get conflicts")? {
+        let conflict = conflict.context("failed to read conflict")?;
+        let Some(entry) = conflict
+            .our
+            .as_ref()
+            .or(conflict.their.as_ref())
+            .or(conflict.ancestor.as_ref())
+        else {
+            continue;
+        };
+        let path = std::str::from_utf8(&entry.path)
+            .context("failed to convert path to utf8")?
+            .into();
+        conflicts.push(Conflict {
+            path,
+            branch_id,
+            ancestor: conflict.ancestor.map(|entry| entry.id.into()),
+            ours: conflict.our.map(|entry| entry.id.into()),
+            theirs: conflict.their.map(|entry| entry.id.into()),
+        });
+    }
+    Ok(conflicts)
+}
 
-pub fn mark<P: AsRef<Path>, A: AsRef<[P]>>(
+pub fn mark(
     repository: &Repository,
-    paths: A,
+    conflicts: impl IntoIterator<Item = Conflict>,
     parent: Option<git::Oid>,
 ) -> Result<()> {
-    let paths = paths.as_ref();
-    if paths.is_empty() {
+    let conflicts = conflicts.into_iter().collect::<Vec<_>>();
+    if conflicts.is_empty() {
         return Ok(());
     }
-    let conflicts_path = repository.git_repository.path().join("conflicts");
-    // write all the file paths to a file on disk
-    let mut file = std::fs::File::create(conflicts_path)?;
-    for path in paths {
-        file.write_all(path.as_ref().as_os_str().as_encoded_bytes())?;
-        file.write_all(b"\n")?;
-    }
+    write(repository, &Conflicts { conflicts })?;
 
     if let Some(parent) = parent {
         let merge_path = repository.git_repository.path().join("base_merge_parent");
-        // write all the file paths to a file on disk
-        let mut file = std::fs::File::create(merge_path)?;
-        file.write_all(parent.to_string().as_bytes())?;
+        crate::fs::write(merge_path, parent.to_string())?;
     }
 
     Ok(())
@@ -63,65 +129,49 @@ pub fn merge_parent(repository: &Repository) -> Result<Option<git::Oid>> {
 
 pub fn resolve<P: AsRef<Path>>(repository: &Repository, path: P) -> Result<()> {
     let path = path.as_ref();
-    let conflicts_path = repository.git_repository.path().join("conflicts");
-    let file = std::fs::File::open(conflicts_path.clone())?;
-    let reader = std::io::BufReader::new(file);
-    let mut remaining = Vec::new();
-    for line in reader.lines().map_ok(PathBuf::from) {
-        let line = line?;
-        if line != path {
-            remaining.push(line);
-        }
-    }
+    let mut conflicts = read(repository)?;
+    conflicts.conflicts.retain(|conflict| conflict.path != path);
 
     // remove file
-    std::fs::remove_file(conflicts_path)?;
+    remove(repository)?;
 
     // re-write file if needed
-    if !remaining.is_empty() {
-        mark(repository, &remaining, None)?;
+    if !conflicts.conflicts.is_empty() {
+        write(repository, &conflicts)?;
     }
     Ok(())
 }
 
-pub fn conflicting_files(repository: &Repository) -> Result<Vec<String>> {
-    let conflicts_path = repository.git_repository.path().join("conflicts");
-    if !conflicts_path.exists() {
-        return Ok(vec![]);
-    }
+/// All files that are still conflicting.
+pub fn list(repository: &Repository) -> Result<Vec<Conflict>> {
+    Ok(read(repository)?.conflicts)
+}
 
-    let file = std::fs::File::open(conflicts_path)?;
-    let reader = std::io::BufReader::new(file);
-    Ok(reader.lines().map_while(Result::ok).collect())
+/// The conflict of the file at `path`, if it is still conflicting.
+pub fn get<P: AsRef<Path>>(repository: &Repository, path: P) -> Result<Option<Conflict>> {
+    let path = path.as_ref();
+    Ok(list(repository)?
+        .into_iter()
+        .find(|conflict| conflict.path == path))
+}
+
+pub fn conflicting_files(repository: &Repository) -> Result<Vec<String>> {
+    Ok(list(repository)?
+        .into_iter()
+        .map(|conflict| conflict.path.display().to_string())
+        .collect())
 }
 
 /// Check if `path` is conflicting in `repository`, or if `None`, check if there is any conflict.
 // TODO(ST): Should this not rather check the conflicting state in the index?
 pub fn is_conflicting<P: AsRef<Path>>(repository: &Repository, path: Option<P>) -> Result<bool> {
-    let conflicts_path = repository.git_repository.path().join("conflicts");
-    if !conflicts_path.exists() {
-        return Ok(false);
-    }
-
-    let file = std::fs::File::open(conflicts_path)?;
-    let reader = std::io::BufReader::new(file);
-    let mut files = reader.lines().map_ok(PathBuf::from);
-    if let Some(pathname) = path {
-        // TODO(ST): This shouldn't work on UTF8 strings.
-        let pathname = pathname.as_ref();
-
-        // check if pathname is one of the lines in conflicts_path file
-        for line in files {
-            let line = line?;
-
-            if line == pathname {
-                return Ok(true);
-            }
-        }
-        Ok(false)
-    } else {
-        Ok(files.next().transpose().map(|x| x.is_some())?)
-    }
+    let conflicts = list(repository)?;
+    Ok(match path {
+        Some(path) => conflicts
+            .iter()
+            .any(|conflict| conflict.path == path.as_ref()),
+        None => !conflicts.is_empty(),
+    })
 }
 
 // is this project still in a resolving conflict state?
@@ -138,9 +188,64 @@ pub fn clear(repository: &Repository) -> Result<()> {
     let merge_path = repository.git_repository.path().join("base_merge_parent");
     std::fs::remove_file(merge_path)?;
 
-    for file in conflicting_files(repository)? {
-        resolve(repository, &file)?;
+    remove(repository)
+}
+
+fn conflicts_path(repository: &Repository) -> PathBuf {
+    repository.git_repository.path().join("conflicts.toml")
+}
+
+/// The path of the flat list of conflicting paths written by older versions.
+fn legacy_conflicts_path(repository: &Repository) -> PathBuf {
+    repository.git_repository.path().join("conflicts")
+}
+
+fn read(repository: &Repository) -> Result<Conflicts> {
+    let conflicts_path = conflicts_path(repository);
+    if conflicts_path.exists() {
+        let contents = std::fs::read_to_string(&conflicts_path)?;
+        return toml::from_str(&contents)
+            .with_context(|| format!("failed to parse {}", conflicts_path.display()));
+    }
+
+    // conflicts marked by older versions only know about the paths
+    let legacy_conflicts_path = legacy_conflicts_path(repository);
+    if !legacy_conflicts_path.exists() {
+        return Ok(Conflicts::default());
     }
+    let file = std::fs::File::open(legacy_conflicts_path)?;
+    let reader = std::io::BufReader::new(file);
+    Ok(Conflicts {
+        conflicts: reader
+            .lines()
+            .map_while(Result::ok)
+            .map(|path| Conflict {
+                path: path.into(),
+                branch_id: None,
+                ancestor: None,
+                ours: None,
+                theirs: None,
+            })
+            .collect(),
+    })
+}
 
+fn write(repository: &Repository, conflicts: &Conflicts) -> Result<()> {
+    let legacy_conflicts_path = legacy_conflicts_path(repository);
+    if legacy_conflicts_path.exists() {
+        std::fs::remove_file(legacy_conflicts_path)?;
+    }
+    crate::fs::write(conflicts_path(repository), toml::to_string(conflicts)?)
+}
+
+fn remove(repository: &Repository) -> Result<()> {
+    for path in [
+        conflicts_path(repository),
+        legacy_conflicts_path(repository),
+    ] {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+    }
     Ok(())
 }
@@ -1,8 +1,10 @@
 mod config;
 pub mod conflicts;
+mod progress;
 mod repository;
 
 pub use config::Config;
+pub use progress::{Progress, ProgressSink, RemoteOperation, RemoteProgress, Stage};
 pub use repository::{LogUntil, OpenError, RemoteError, Repository};
 
 pub mod signatures;
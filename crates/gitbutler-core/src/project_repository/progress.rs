@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+
+pub use gitbutler_git::{Progress, Stage};
+
+/// Whether a [`RemoteProgress`] is about a fetch or a push.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RemoteOperation {
+    Fetch,
+    Push,
+}
+
+/// How far along a fetch from or push to `remote` is, no matter if it's done
+/// by the git executable or by libgit2.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteProgress {
+    pub operation: RemoteOperation,
+    pub remote: String,
+    #[serde(flatten)]
+    pub progress: Progress,
+}
+
+/// Receives the progress of fetches and pushes while they run.
+pub type ProgressSink = Arc<dyn Fn(RemoteProgress) + Send + Sync>;
+
+/// The progress libgit2 reports while fetching, which is only about resolving
+/// deltas once all objects are received.
+pub(super) fn fetch_progress(stats: &git2::Progress<'_>) -> Progress {
+    if stats.received_objects() < stats.total_objects() || stats.total_deltas() == 0 {
+        Progress {
+            stage: Stage::Receiving,
+            current: stats.received_objects() as u64,
+            total: Some(stats.total_objects() as u64),
+            bytes: Some(stats.received_bytes() as u64),
+        }
+    } else {
+        Progress {
+            stage: Stage::ResolvingDeltas,
+            current: stats.indexed_deltas() as u64,
+            total: Some(stats.total_deltas() as u64),
+            bytes: None,
+        }
+    }
+}
+
+/// The progress libgit2 reports while pushing.
+pub(super) fn push_progress(current: usize, total: usize, bytes: usize) -> Progress {
+    Progress {
+        stage: Stage::Writing,
+        current: current as u64,
+        total: Some(total as u64),
+        bytes: Some(bytes as u64),
+    }
+}
+
+/// A callback that passes the progress of `operation` on `remote` to `sink`, if there is one.
+pub(super) fn reporter(
+    sink: Option<ProgressSink>,
+    operation: RemoteOperation,
+    remote: &str,
+) -> impl FnMut(Progress) + Clone + Send + 'static {
+    let remote = remote.to_owned();
+    move |progress| {
+        if let Some(sink) = &sink {
+            sink(RemoteProgress {
+                operation,
+                remote: remote.clone(),
+                progress,
+            });
+        }
+    }
+}
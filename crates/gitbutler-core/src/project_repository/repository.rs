@@ -5,15 +5,18 @@ use std::{
 };
 
 use anyhow::{Context, Result};
+use gitbutler_git::{CancellationToken, RepositoryError};
 
-use super::conflicts;
+use super::{
+    conflicts,
+    progress::{self, ProgressSink, RemoteOperation},
+};
 use crate::error::{AnyhowContextExt, Code, ErrorWithContext};
 use crate::{
     askpass,
     askpass::AskpassBroker,
     error,
     git::{self, credentials::HelpError, Url},
-    keys,
     projects::{self, AuthKey},
     ssh, users,
     virtual_branches::{Branch, BranchId},
@@ -194,9 +197,12 @@ impl Repository {
             &commit_id,
             &refname,
             false,
+            None,
             credentials,
             None,
             askpass.clone(),
+            None,
+            None,
         ) {
             Ok(()) => Ok(()),
             Err(e) => Err(anyhow::anyhow!(e.to_string())),
@@ -207,9 +213,12 @@ impl Repository {
             &commit_id,
             &refname,
             false,
+            None,
             credentials,
             empty_refspec,
             askpass,
+            None,
+            None,
         ) {
             Ok(()) => Ok(()),
             Err(e) => Err(anyhow::anyhow!(e.to_string())),
@@ -350,12 +359,13 @@ impl Repository {
         message: &str,
         tree: &git::Tree,
         parents: &[&git::Commit],
-        signing_key: Option<&keys::PrivateKey>,
+        signer: Option<&git::Signer>,
     ) -> Result<git::Oid> {
         let (author, committer) = self.git_signatures(user)?;
-        if let Some(key) = signing_key {
+        if let Some(signer) = signer {
+            // author and committer must be the same for signed commits
             self.git_repository
-                .commit_signed(&author, message, tree, parents, key)
+                .commit_signed(&author, &author, message, tree, parents, signer)
                 .context("failed to commit signed")
         } else {
             self.git_repository
@@ -445,15 +455,28 @@ impl Repository {
         Ok(total_objects_pushed > 0)
     }
 
+    /// Pushes `head` to `branch`. When forcing, a `lease` makes sure that the remote branch is only
+    /// overwritten if it is still at that commit, failing with [`RemoteError::LeaseViolated`] otherwise.
+    ///
+    /// The progress of the push is passed to `progress`, if set.
+    ///
+    /// With the git executable, the push can be aborted with `cancellation`, and times out
+    /// after [`Config::remote_timeout()`](super::Config::remote_timeout()).
+    #[allow(clippy::too_many_arguments)]
     pub fn push(
         &self,
         head: &git::Oid,
         branch: &git::RemoteRefname,
         with_force: bool,
+        lease: Option<git::Oid>,
         credentials: &git::credentials::Helper,
         refspec: Option<String>,
         askpass_broker: Option<(AskpassBroker, Option<BranchId>)>,
+        progress: Option<ProgressSink>,
+        cancellation: Option<CancellationToken>,
     ) -> Result<(), RemoteError> {
+        let report_progress = progress::reporter(progress, RemoteOperation::Push, branch.remote());
+
         let refspec = refspec.unwrap_or_else(|| {
             if with_force {
                 format!("+{}:refs/heads/{}", head, branch.branch())
@@ -470,6 +493,9 @@ impl Repository {
         if self.project.preferred_key == AuthKey::SystemExecutable {
             let path = self.path().to_path_buf();
             let remote = branch.remote().to_string();
+            let lease_str = lease.map(|lease| lease.to_string());
+            let timeout = self.config().remote_timeout()?;
+            let cancellation = cancellation.unwrap_or_default();
             return std::thread::spawn(move || {
                 tokio::runtime::Runtime::new()
                     .unwrap()
@@ -477,30 +503,53 @@ impl Repository {
                         path,
                         gitbutler_git::tokio::TokioExecutor,
                         &remote,
-                        gitbutler_git::RefSpec::parse(refspec).unwrap(),
+                        &[(
+                            gitbutler_git::RefSpec::parse(refspec).unwrap(),
+                            lease_str.as_deref(),
+                        )],
                         with_force,
+                        report_progress,
                         handle_git_prompt_push,
                         askpass_broker,
+                        &cancellation,
+                        timeout,
                     ))
             })
             .join()
             .unwrap()
-            .map_err(|e| RemoteError::Other(e.into()));
+            .map_err(|e| match e {
+                gitbutler_git::Error::StaleLease(_) => RemoteError::LeaseViolated(branch.clone()),
+                gitbutler_git::Error::Backend(RepositoryError::Cancelled) => RemoteError::Cancelled,
+                gitbutler_git::Error::Backend(RepositoryError::TimedOut(timeout)) => {
+                    RemoteError::TimedOut(timeout)
+                }
+                e => RemoteError::Other(e.into()),
+            });
         }
 
+        let leased_ref = format!("refs/heads/{}", branch.branch());
+
         let auth_flows = credentials.help(self, branch.remote())?;
         for (mut remote, callbacks) in auth_flows {
             if let Some(url) = remote.url().context("failed to get remote url")? {
                 if !self.project.omit_certificate_check.unwrap_or(false) {
-                    ssh::check_known_host(&url).context("failed to check known host")?;
+                    let askpass = askpass_broker.clone().map(|(askpass_broker, branch_id)| {
+                        (askpass_broker, askpass::Context::Push { branch_id })
+                    });
+                    ssh::check_known_host(&url, askpass).context("failed to check known host")?;
                 }
             }
             let mut update_refs_error: Option<git2::Error> = None;
+            let mut lease_violated = false;
             for callback in callbacks {
                 let mut cbs: git2::RemoteCallbacks = callback.into();
                 if self.project.omit_certificate_check.unwrap_or(false) {
                     cbs.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
                 }
+                let mut report_progress = report_progress.clone();
+                cbs.push_transfer_progress(move |current, total, bytes| {
+                    report_progress(progress::push_progress(current, total, bytes));
+                });
                 cbs.push_update_reference(|_reference: &str, status: Option<&str>| {
                     if let Some(status) = status {
                         update_refs_error = Some(git2::Error::from_str(status));
@@ -508,6 +557,20 @@ impl Repository {
                     };
                     Ok(())
                 });
+                if with_force && lease.is_some() {
+                    // the remote tells which commit it has the branch at before anything is sent
+                    cbs.push_negotiation(|updates| {
+                        let stale = updates.iter().any(|update| {
+                            update.dst_refname() == Some(leased_ref.as_str())
+                                && Some(git::Oid::from(update.src())) != lease
+                        });
+                        if stale {
+                            lease_violated = true;
+                            return Err(git2::Error::from_str("remote branch changed"));
+                        }
+                        Ok(())
+                    });
+                }
 
                 let push_result = remote.push(
                     &[refspec.as_str()],
@@ -532,6 +595,9 @@ impl Repository {
                         tracing::warn!(project_id = %self.project.id, ?error, "git push failed");
                         return Err(RemoteError::Network);
                     }
+                    Err(_) if lease_violated => {
+                        return Err(RemoteError::LeaseViolated(branch.clone()));
+                    }
                     Err(err) => {
                         if let Some(err) = update_refs_error.as_ref() {
                             return Err(RemoteError::Other(
@@ -547,13 +613,20 @@ impl Repository {
         Err(RemoteError::Auth)
     }
 
+    /// Fetches all branches of `remote_name`, passing the progress of the fetch to `progress`, if set.
+    ///
+    /// With the git executable, the fetch can be aborted with `cancellation`, and times out
+    /// after [`Config::remote_timeout()`](super::Config::remote_timeout()).
     pub fn fetch(
         &self,
         remote_name: &str,
         credentials: &git::credentials::Helper,
         askpass: Option<(AskpassBroker, String)>,
+        progress: Option<ProgressSink>,
+        cancellation: Option<CancellationToken>,
     ) -> Result<(), RemoteError> {
         let refspec = format!("+refs/heads/*:refs/remotes/{}/*", remote_name);
+        let report_progress = progress::reporter(progress, RemoteOperation::Fetch, remote_name);
 
         // NOTE(qix-): This is a nasty hack, however the codebase isn't structured
         // NOTE(qix-): in a way that allows us to really incorporate new backends
@@ -563,6 +636,8 @@ impl Repository {
         if self.project.preferred_key == AuthKey::SystemExecutable {
             let path = self.path().to_path_buf();
             let remote = remote_name.to_string();
+            let timeout = self.config().remote_timeout()?;
+            let cancellation = cancellation.unwrap_or_default();
             return std::thread::spawn(move || {
                 tokio::runtime::Runtime::new()
                     .unwrap()
@@ -571,20 +646,32 @@ impl Repository {
                         gitbutler_git::tokio::TokioExecutor,
                         &remote,
                         gitbutler_git::RefSpec::parse(refspec).unwrap(),
+                        report_progress,
                         handle_git_prompt_fetch,
                         askpass,
+                        &cancellation,
+                        timeout,
                     ))
             })
             .join()
             .unwrap()
-            .map_err(|e| RemoteError::Other(e.into()));
+            .map_err(|e| match e {
+                gitbutler_git::Error::Backend(RepositoryError::Cancelled) => RemoteError::Cancelled,
+                gitbutler_git::Error::Backend(RepositoryError::TimedOut(timeout)) => {
+                    RemoteError::TimedOut(timeout)
+                }
+                e => RemoteError::Other(e.into()),
+            });
         }
 
         let auth_flows = credentials.help(self, remote_name)?;
         for (mut remote, callbacks) in auth_flows {
             if let Some(url) = remote.url().context("failed to get remote url")? {
                 if !self.project.omit_certificate_check.unwrap_or(false) {
-                    ssh::check_known_host(&url).context("failed to check known host")?;
+                    let askpass = askpass.clone().map(|(askpass_broker, action)| {
+                        (askpass_broker, askpass::Context::Fetch { action })
+                    });
+                    ssh::check_known_host(&url, askpass).context("failed to check known host")?;
                 }
             }
             for callback in callbacks {
@@ -593,6 +680,11 @@ impl Repository {
                 if self.project.omit_certificate_check.unwrap_or(false) {
                     cbs.certificate_check(|_, _| Ok(git2::CertificateCheckStatus::CertificateOk));
                 }
+                let mut report_progress = report_progress.clone();
+                cbs.transfer_progress(move |stats| {
+                    report_progress(progress::fetch_progress(&stats));
+                    true
+                });
                 fetch_opts.remote_callbacks(cbs);
                 fetch_opts.prune(git2::FetchPrune::On);
 
@@ -626,6 +718,12 @@ pub enum RemoteError {
     Network,
     #[error("authentication failed")]
     Auth,
+    #[error("{0} changed on the remote since it was last pushed")]
+    LeaseViolated(git::RemoteRefname),
+    #[error("cancelled")]
+    Cancelled,
+    #[error("timed out after {0:?}")]
+    TimedOut(std::time::Duration),
     #[error("Git failed")]
     Git(#[from] git::Error),
     #[error(transparent)]
@@ -643,6 +741,20 @@ impl ErrorWithContext for RemoteError {
                 Code::ProjectGitAuth,
                 "Project remote authentication error",
             ),
+            RemoteError::LeaseViolated(branch) => error::Context::new(
+                Code::ProjectGitPush,
+                format!("{branch} was changed on the remote since it was last pushed, please fetch it first"),
+            ),
+            RemoteError::Cancelled => {
+                error::Context::new_static(Code::ProjectGitRemote, "The operation was cancelled")
+            }
+            RemoteError::TimedOut(timeout) => error::Context::new(
+                Code::ProjectGitRemote,
+                format!(
+                    "Git didn't finish talking to the remote within {} seconds. The limit can be changed with `git config gitbutler.remoteTimeout`",
+                    timeout.as_secs()
+                ),
+            ),
             RemoteError::Git(_) => {
                 error::Context::new_static(Code::ProjectGitRemote, "Git command failed")
             }
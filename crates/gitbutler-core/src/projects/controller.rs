@@ -21,6 +21,8 @@ pub trait Watchers {
     async fn stop(&self, id: ProjectId);
     async fn fetch_gb_data(&self, id: ProjectId) -> anyhow::Result<()>;
     async fn push_gb_data(&self, id: ProjectId) -> anyhow::Result<()>;
+    /// Tell whoever watches the project how far along a fetch or push is.
+    fn report_progress(&self, id: ProjectId, progress: project_repository::RemoteProgress);
 }
 
 #[derive(Clone)]
@@ -270,6 +272,14 @@ impl Controller {
         Ok(())
     }
 
+    /// Where the progress of fetches and pushes of project `id` is reported, if it's watched.
+    pub fn progress_sink(&self, id: ProjectId) -> Option<project_repository::ProgressSink> {
+        let watchers = self.watchers.clone()?;
+        Some(Arc::new(move |progress| {
+            watchers.report_progress(id, progress)
+        }))
+    }
+
     pub fn get_local_config(
         &self,
         id: &ProjectId,
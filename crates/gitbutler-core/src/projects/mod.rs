@@ -3,5 +3,7 @@ mod project;
 pub mod storage;
 
 pub use controller::*;
-pub use project::{ApiProject, AuthKey, CodePushState, FetchResult, Project, ProjectId};
+pub use project::{
+    ApiProject, AuthKey, CodePushState, FetchResult, Project, ProjectId, SnapshotRetention,
+};
 pub use storage::UpdateRequest;
@@ -58,6 +58,21 @@ pub struct CodePushState {
     pub timestamp: time::SystemTime,
 }
 
+/// How many snapshots of the oplog are kept, see [`crate::snapshots::snapshot::compact()`].
+///
+/// Each limit is optional, and snapshots are only ever dropped by a limit that is set.
+/// The most recent snapshot is always kept.
+#[derive(Debug, Deserialize, Serialize, Copy, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotRetention {
+    /// Keep at most this many snapshots.
+    pub max_count: Option<usize>,
+    /// Drop snapshots that are older than this many seconds.
+    pub max_age_seconds: Option<u64>,
+    /// Of the snapshots that are older than a day, only keep every nth one.
+    pub keep_every_nth_after_day: Option<usize>,
+}
+
 pub type ProjectId = Id<Project>;
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
@@ -84,6 +99,9 @@ pub struct Project {
     pub omit_certificate_check: Option<bool>,
     #[serde(default)]
     pub enable_snapshots: Option<bool>,
+    /// The policy the oplog is compacted with when snapshots are compacted.
+    #[serde(default)]
+    pub snapshot_retention: Option<SnapshotRetention>,
 }
 
 impl AsRef<Project> for Project {
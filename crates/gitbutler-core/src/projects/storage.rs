@@ -26,6 +26,7 @@ pub struct UpdateRequest {
     pub project_data_last_fetched: Option<project::FetchResult>,
     pub omit_certificate_check: Option<bool>,
     pub use_diff_context: Option<bool>,
+    pub snapshot_retention: Option<project::SnapshotRetention>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -122,6 +123,10 @@ impl Storage {
             project.omit_certificate_check = Some(omit_certificate_check);
         }
 
+        if let Some(snapshot_retention) = update_request.snapshot_retention {
+            project.snapshot_retention = Some(snapshot_retention);
+        }
+
         self.inner
             .write(PROJECTS_FILE, &serde_json::to_string_pretty(&projects)?)?;
 
@@ -10,6 +10,12 @@ use strum::EnumString;
 
 use serde::Serialize;
 
+use crate::git::{
+    self,
+    diff::{ChangeType, DiffByPathMap},
+};
+use crate::virtual_branches::{branch::BranchOwnershipClaims, BranchId};
+
 /// A snapshot of the repository and virtual branches state that GitButler can restore to.
 /// It captures the state of the working directory, virtual branches and commits.
 #[derive(Debug, PartialEq, Clone, Serialize)]
@@ -23,6 +29,32 @@ pub struct Snapshot {
     pub details: Option<SnapshotDetails>,
 }
 
+/// The changes between two snapshots.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotDiff {
+    /// The virtual branches that were created, deleted or changed, sorted by name
+    pub branches: Vec<BranchDiff>,
+    /// The changes of the working directory, by path
+    pub files: DiffByPathMap,
+}
+
+/// How a virtual branch changed between two snapshots.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchDiff {
+    pub id: BranchId,
+    /// The name of the branch in the newer snapshot, or in the older one if it was deleted
+    pub name: String,
+    pub change_type: ChangeType,
+    /// The head of the branch in the older snapshot, if it existed
+    pub old_head: Option<git::Oid>,
+    /// The head of the branch in the newer snapshot, if it exists
+    pub new_head: Option<git::Oid>,
+    pub old_ownership: Option<BranchOwnershipClaims>,
+    pub new_ownership: Option<BranchOwnershipClaims>,
+}
+
 /// The payload of a snapshot commit
 ///
 /// This is persisted as a commit message in the title, body and trailers format (https://git-scm.com/docs/git-interpret-trailers)
@@ -150,9 +182,19 @@ pub enum OperationType {
     UpdateCommitMessage,
     MoveCommit,
     RestoreFromSnapshot,
+    RestorePathsFromSnapshot,
+    RestoreBranchFromSnapshot,
     ReorderCommit,
     InsertBlankCommit,
     MoveCommitFile,
+    ResolveConflict,
+    SetRoutingRules,
+    EditHistory,
+    SplitCommit,
+    Absorb,
+    MergeBranches,
+    SplitBranch,
+    MoveHunksWithDependencies,
     #[default]
     Unknown,
 }
@@ -1,27 +1,41 @@
 use anyhow::anyhow;
 use itertools::Itertools;
+use std::collections::HashMap;
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
-use crate::{projects::Project, virtual_branches::VirtualBranchesHandle};
+use crate::{
+    git::{self, diff::ChangeType},
+    project_repository,
+    projects::{Project, SnapshotRetention},
+    virtual_branches::{
+        integration::update_gitbutler_integration, state::VirtualBranches, Branch, BranchId,
+        VirtualBranchesHandle,
+    },
+};
 
 use super::{
-    entry::{OperationType, Snapshot, SnapshotDetails, Trailer},
+    entry::{BranchDiff, OperationType, Snapshot, SnapshotDetails, SnapshotDiff, Trailer},
     reflog::set_reference_to_oplog,
     state::OplogHandle,
 };
 
 const SNAPSHOT_FILE_LIMIT_BYTES: u64 = 32 * 1024 * 1024;
 
+/// The name of the copy of the virtual branches state that is stored at the root of each snapshot.
+const VIRTUAL_BRANCHES_FILE: &str = "virtual_branches.toml";
+
 /// Creates a snapshot of the current state of the repository and virtual branches using the given label.
 ///
 /// If this is the first shapshot created, supporting structures are initialized:
 ///  - The current oplog head is persisted in `.git/gitbutler/oplog.toml`.
 ///  - A fake branch `gitbutler/target` is created and maintained in order to keep the oplog head reachable.
 ///
-/// The state of virtual branches `.git/gitbutler/virtual_branches.toml` is copied to the project root so that it is snapshotted.
+/// The state of virtual branches `.git/gitbutler/virtual_branches.toml` is added to the root of the snapshot tree.
+/// The tree is built without touching the index or the working directory, so snapshots can be taken at any time.
 pub fn create(project: &Project, details: SnapshotDetails) -> Result<()> {
     if project.enable_snapshots.is_none() || project.enable_snapshots == Some(false) {
         return Ok(());
@@ -43,24 +57,29 @@ pub fn create(project: &Project, details: SnapshotDetails) -> Result<()> {
         None => repo.find_commit(default_target_sha.into())?,
     };
 
-    // Copy virtual_branches.rs to the project root so that we snapshot it
-    std::fs::copy(
-        repo_path.join(".git/gitbutler/virtual_branches.toml"),
-        repo_path.join("virtual_branches.toml"),
-    )?;
-
     // Exclude files that are larger than the limit (eg. database.sql which may never be intended to be committed)
     let files_to_exclude = get_exclude_list(&repo)?;
     // In-memory, libgit2 internal ignore rule
     repo.add_ignore_rule(&files_to_exclude)?;
 
-    // Add everything in the workdir to the index
+    // Add everything in the workdir to an in-memory copy of the index, which is never written back
+    // so the user's staged changes are left alone
     let mut index = repo.index()?;
     index.add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)?;
-    index.write()?;
+    index.update_all(["*"].iter(), None)?;
+    let workdir_tree_id = index.write_tree()?;
 
-    // Create a tree out of the index
-    let tree_id = index.write_tree()?;
+    // Add the state of virtual branches at the root of the tree so that we snapshot it
+    let vb_blob_id = repo.blob(&std::fs::read(
+        repo_path.join(".git/gitbutler/virtual_branches.toml"),
+    )?)?;
+    let mut tree_builder = repo.treebuilder(Some(&repo.find_tree(workdir_tree_id)?))?;
+    tree_builder.insert(
+        VIRTUAL_BRANCHES_FILE,
+        vb_blob_id,
+        git2::FileMode::Blob.into(),
+    )?;
+    let tree_id = tree_builder.write()?;
     let tree = repo.find_tree(tree_id)?;
 
     // Construct a new commit
@@ -76,22 +95,11 @@ pub fn create(project: &Project, details: SnapshotDetails) -> Result<()> {
         &[&oplog_head_commit],
     )?;
 
-    // Remove the copied virtual_branches.rs
-    std::fs::remove_file(project.path.join("virtual_branches.toml"))?;
-
-    // Reset the workdir to how it was
-    let integration_branch = repo
-        .find_branch("gitbutler/integration", git2::BranchType::Local)?
-        .get()
-        .peel_to_commit()?;
-
-    repo.reset(
-        &integration_branch.into_object(),
-        git2::ResetType::Mixed,
-        None,
-    )?;
-
     oplog_state.set_oplog_head(new_commit_oid.to_string())?;
+    // A new operation can't be followed by the redo of an operation that was undone before it
+    if details.operation != OperationType::RestoreFromSnapshot {
+        oplog_state.clear_redo()?;
+    }
 
     set_reference_to_oplog(
         project,
@@ -153,10 +161,187 @@ pub fn list(project: &Project, limit: usize) -> Result<Vec<Snapshot>> {
 
 /// Reverts to a previous state of the working directory, virtual branches and commits.
 /// The provided sha must refer to a valid snapshot commit.
-/// Upon success, a new snapshot is created.
+/// Upon success, a new snapshot is created, and operations that were undone can't be redone anymore.
 ///
 /// The state of virtual branches `.git/gitbutler/virtual_branches.toml` is restored from the snapshot.
 pub fn restore(project: &Project, sha: String) -> Result<()> {
+    restore_snapshot(project, sha)?;
+    OplogHandle::new(&project.gb_dir()).clear_redo()
+}
+
+/// Reverts the files at `paths` to their state in the snapshot `sha`, leaving the rest of the working directory,
+/// the index and the virtual branches alone. Paths that didn't exist in the snapshot are removed.
+/// Upon success, a new snapshot is created with a `restored_path` trailer for each of the paths.
+pub fn restore_paths(project: &Project, sha: String, paths: &[PathBuf]) -> Result<()> {
+    let repo = git2::Repository::init(project.path.as_path())?;
+
+    let commit = repo.find_commit(git2::Oid::from_str(&sha)?)?;
+    let tree = commit.tree()?;
+
+    // Exclude files that are larger than the limit (eg. database.sql which may never be intended to be committed)
+    let files_to_exclude = get_exclude_list(&repo)?;
+    // In-memory, libgit2 internal ignore rule
+    repo.add_ignore_rule(&files_to_exclude)?;
+
+    // The virtual branches state is not part of the working directory
+    let paths = paths
+        .iter()
+        .filter(|path| path.as_path() != Path::new(VIRTUAL_BRANCHES_FILE))
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    let mut checkout_builder = git2::build::CheckoutBuilder::new();
+    checkout_builder
+        .force()
+        .remove_untracked(true)
+        .update_index(false);
+    for path in &paths {
+        checkout_builder.path(path.as_path());
+    }
+    repo.checkout_tree(tree.as_object(), Some(&mut checkout_builder))?;
+
+    let mut trailers = vec![Trailer {
+        key: "restored_from".to_string(),
+        value: sha,
+    }];
+    trailers.extend(paths.iter().map(|path| Trailer {
+        key: "restored_path".to_string(),
+        value: path.display().to_string(),
+    }));
+    let details = SnapshotDetails {
+        version: Default::default(),
+        operation: OperationType::RestorePathsFromSnapshot,
+        title: "Restored files from snapshot".to_string(),
+        body: None,
+        trailers,
+    };
+    create(project, details)
+}
+
+/// Reverts the virtual branch `branch_id` to its state in the snapshot `sha`, which includes its name,
+/// ownership and head. The working directory and other virtual branches are left alone, so only unapplied
+/// branches can be restored, and a branch that was deleted since is brought back unapplied.
+/// Upon success, a new snapshot is created with a `restored_branch` trailer.
+pub fn restore_branch(project: &Project, sha: String, branch_id: BranchId) -> Result<()> {
+    let repo = git::Repository::open(&project.path)?;
+
+    let tree = repo.find_commit(git::Oid::from_str(&sha)?)?.tree()?;
+    let mut branch = snapshot_branches(&repo, &tree)?
+        .remove(&branch_id)
+        .ok_or(anyhow!(
+            "branch {} is not part of snapshot {}",
+            branch_id,
+            sha
+        ))?;
+
+    let vb_state = VirtualBranchesHandle::new(&project.gb_dir());
+    match vb_state.get_branch(&branch_id) {
+        Ok(current) if current.applied => {
+            return Err(anyhow!(
+                "branch {} is applied, it has to be unapplied to be restored",
+                branch_id
+            ))
+        }
+        Ok(_) | Err(crate::reader::Error::NotFound) => {}
+        Err(error) => return Err(error.into()),
+    };
+    branch.applied = false;
+    vb_state.set_branch(branch)?;
+
+    let project_repository = project_repository::Repository::open(project)?;
+    update_gitbutler_integration(&vb_state, &project_repository)?;
+
+    let details = SnapshotDetails {
+        version: Default::default(),
+        operation: OperationType::RestoreBranchFromSnapshot,
+        title: "Restored branch from snapshot".to_string(),
+        body: None,
+        trailers: vec![
+            Trailer {
+                key: "restored_from".to_string(),
+                value: sha,
+            },
+            Trailer {
+                key: "restored_branch".to_string(),
+                value: branch_id.to_string(),
+            },
+        ],
+    };
+    create(project, details)
+}
+
+/// Undoes the last operation by restoring the snapshot preceding it, returning the sha of the restored snapshot.
+/// Undoing again goes further back in the oplog, while a restore created by an undo or redo is never undone itself.
+///
+/// Returns `None` if there is no operation to undo.
+pub fn undo(project: &Project) -> Result<Option<String>> {
+    let repo = git2::Repository::init(project.path.as_path())?;
+
+    let oplog_state = OplogHandle::new(&project.gb_dir());
+    let Some(head_sha) = oplog_state.get_oplog_head()? else {
+        return Ok(None);
+    };
+    let Some(current) = restored_state(&repo, git2::Oid::from_str(&head_sha)?)? else {
+        return Ok(None);
+    };
+    let Some(previous) = repo.find_commit(current)?.parent(0).ok() else {
+        return Ok(None);
+    };
+    let Some(previous) = restored_state(&repo, previous.id())? else {
+        return Ok(None);
+    };
+
+    restore_snapshot(project, previous.to_string())?;
+    oplog_state.push_redo(current.to_string())?;
+    Ok(Some(previous.to_string()))
+}
+
+/// Redoes the operation that was undone last by restoring the snapshot that was taken after it,
+/// returning the sha of the restored snapshot.
+///
+/// Returns `None` if there is no undone operation, or if a new operation was performed since.
+pub fn redo(project: &Project) -> Result<Option<String>> {
+    let oplog_state = OplogHandle::new(&project.gb_dir());
+    let Some(sha) = oplog_state.last_redo()? else {
+        return Ok(None);
+    };
+
+    restore_snapshot(project, sha.clone())?;
+    oplog_state.pop_redo()?;
+    Ok(Some(sha))
+}
+
+/// The snapshot whose state the snapshot `id` captures, which is the snapshot that was restored if `id`
+/// was created by a restore, or `id` itself otherwise.
+///
+/// Returns `None` if `id` isn't a snapshot.
+fn restored_state(repo: &git2::Repository, mut id: git2::Oid) -> Result<Option<git2::Oid>> {
+    loop {
+        let commit = repo.find_commit(id)?;
+        let Some(details) = commit
+            .message()
+            .and_then(|msg| SnapshotDetails::from_str(msg).ok())
+        else {
+            return Ok(None);
+        };
+        if details.operation != OperationType::RestoreFromSnapshot {
+            return Ok(Some(id));
+        }
+        let restored_from = details
+            .trailers
+            .iter()
+            .find(|trailer| trailer.key == "restored_from")
+            .ok_or(anyhow!(
+                "restore snapshot {} has no restored_from trailer",
+                id
+            ))?;
+        id = git2::Oid::from_str(&restored_from.value)?;
+    }
+}
+
+fn restore_snapshot(project: &Project, sha: String) -> Result<()> {
     let repo_path = project.path.as_path();
     let repo = git2::Repository::init(repo_path)?;
 
@@ -198,6 +383,280 @@ pub fn restore(project: &Project, sha: String) -> Result<()> {
     Ok(())
 }
 
+/// Drops the snapshots that are not retained by `retention`, returning how many were dropped.
+/// Snapshots that a retained restore was made from, and snapshots that were undone and can still be redone,
+/// are retained as well, so that undo and redo keep working across a compaction.
+///
+/// As snapshots form a chain of commits, the chain is rewritten from the oldest dropped snapshot onwards,
+/// with each surviving snapshot keeping its tree, message and creation time. The `restored_from` trailers
+/// of rewritten snapshots and the undone snapshots remembered for redo are updated to the new ids.
+/// The oplog head in `.git/gitbutler/oplog.toml` and the `gitbutler/target` reflog entry are moved to the
+/// new head of the chain, so the dropped snapshots become unreachable and will be garbage collected by git.
+///
+/// This changes the ids of the rewritten snapshots, so it is never done implicitly when snapshots are created.
+pub fn compact(project: &Project, retention: &SnapshotRetention) -> Result<usize> {
+    let repo_path = project.path.as_path();
+    let repo = git2::Repository::init(repo_path)?;
+
+    let oplog_state = OplogHandle::new(&project.gb_dir());
+    let Some(head_sha) = oplog_state.get_oplog_head()? else {
+        return Ok(0);
+    };
+
+    // Walk the chain from the newest snapshot to the oldest, the commit it started from is not a snapshot
+    let mut snapshots = Vec::new();
+    let mut commit = repo.find_commit(git2::Oid::from_str(&head_sha)?)?;
+    let base = loop {
+        let is_snapshot = commit
+            .message()
+            .map_or(false, |msg| SnapshotDetails::from_str(msg).is_ok());
+        if !is_snapshot || commit.parent_count() != 1 {
+            break Some(commit);
+        }
+        let parent = commit.parent(0).ok();
+        snapshots.push(commit);
+        match parent {
+            Some(parent) => commit = parent,
+            None => break None,
+        }
+    };
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs() as i64;
+    let created_at = snapshots
+        .iter()
+        .map(|commit| commit.time().seconds())
+        .collect::<Vec<_>>();
+    let mut retained = retained_snapshots(&created_at, now, retention);
+
+    // Undone snapshots have to survive to be redone
+    let redo_shas = oplog_state.redo_shas()?;
+    for (idx, snapshot) in snapshots.iter().enumerate() {
+        if redo_shas.contains(&snapshot.id().to_string()) {
+            retained[idx] = true;
+        }
+    }
+
+    // Restores always refer to older snapshots, so following them from the newest snapshot on
+    // also retains the snapshots that retained restores refer to in turn
+    for idx in 0..snapshots.len() {
+        if !retained[idx] {
+            continue;
+        }
+        for restored_from in restored_from(&snapshots[idx]) {
+            if let Some(restored_idx) = snapshots
+                .iter()
+                .position(|snapshot| snapshot.id() == restored_from)
+            {
+                retained[restored_idx] = true;
+            }
+        }
+    }
+
+    let dropped = retained.iter().filter(|retained| !**retained).count();
+    if dropped == 0 {
+        return Ok(0);
+    }
+
+    // Snapshots older than the oldest dropped one are unaffected and keep their ids
+    let oldest_dropped = retained.iter().rposition(|retained| !retained).unwrap_or(0);
+    let mut parent = match snapshots.get(oldest_dropped + 1) {
+        Some(commit) => Some(commit.clone()),
+        None => base,
+    };
+    let mut new_ids: HashMap<String, String> = HashMap::new();
+    for (commit, _) in snapshots[..oldest_dropped]
+        .iter()
+        .zip(&retained[..oldest_dropped])
+        .rev()
+        .filter(|(_, retained)| **retained)
+    {
+        let message = commit.message().unwrap_or_default();
+        let message = match SnapshotDetails::from_str(message) {
+            Ok(mut details)
+                if details.trailers.iter().any(|trailer| {
+                    trailer.key == "restored_from" && new_ids.contains_key(&trailer.value)
+                }) =>
+            {
+                for trailer in &mut details.trailers {
+                    if trailer.key == "restored_from" {
+                        if let Some(new_id) = new_ids.get(&trailer.value) {
+                            trailer.value.clone_from(new_id);
+                        }
+                    }
+                }
+                details.to_string()
+            }
+            _ => message.to_string(),
+        };
+        let new_commit_oid = repo.commit(
+            None,
+            &commit.author(),
+            &commit.committer(),
+            &message,
+            &commit.tree()?,
+            &parent.iter().collect::<Vec<_>>(),
+        )?;
+        new_ids.insert(commit.id().to_string(), new_commit_oid.to_string());
+        parent = Some(repo.find_commit(new_commit_oid)?);
+    }
+    let new_head = parent.ok_or(anyhow!("no snapshot was retained"))?;
+
+    oplog_state.set_oplog_head(new_head.id().to_string())?;
+    oplog_state.set_redo_shas(
+        redo_shas
+            .into_iter()
+            .map(|sha| new_ids.get(&sha).cloned().unwrap_or(sha))
+            .collect(),
+    )?;
+
+    let vb_state = VirtualBranchesHandle::new(&project.gb_dir());
+    let default_target_sha = vb_state.get_default_target()?.sha;
+    set_reference_to_oplog(
+        project,
+        &default_target_sha.to_string(),
+        &new_head.id().to_string(),
+    )?;
+
+    Ok(dropped)
+}
+
+/// The ids of the snapshots that the snapshot `commit` was restored from, if it was created by a restore.
+fn restored_from(commit: &git2::Commit) -> Vec<git2::Oid> {
+    commit
+        .message()
+        .and_then(|msg| SnapshotDetails::from_str(msg).ok())
+        .map(|details| {
+            details
+                .trailers
+                .iter()
+                .filter(|trailer| trailer.key == "restored_from")
+                .filter_map(|trailer| git2::Oid::from_str(&trailer.value).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Decides which of the snapshots created at `created_at` (in seconds, newest first) are kept by `retention`
+/// at the time `now`.
+fn retained_snapshots(created_at: &[i64], now: i64, retention: &SnapshotRetention) -> Vec<bool> {
+    const DAY_SECONDS: i64 = 24 * 60 * 60;
+
+    let mut older_than_a_day = 0;
+    created_at
+        .iter()
+        .enumerate()
+        .map(|(idx, created_at)| {
+            let age = now - created_at;
+            if idx == 0 {
+                return true;
+            }
+            if retention
+                .max_count
+                .map_or(false, |max_count| idx >= max_count)
+            {
+                return false;
+            }
+            if retention
+                .max_age_seconds
+                .map_or(false, |max_age| age > max_age as i64)
+            {
+                return false;
+            }
+            if let Some(nth) = retention.keep_every_nth_after_day {
+                if age > DAY_SECONDS {
+                    older_than_a_day += 1;
+                    return (older_than_a_day - 1) % nth.max(1) == 0;
+                }
+            }
+            true
+        })
+        .collect()
+}
+
+/// Computes the changes from the snapshot `from_sha` to the snapshot `to_sha`, without restoring either.
+/// Both shas must refer to valid snapshot commits.
+///
+/// Changes to virtual branches are decoded from the `virtual_branches.toml` stored in each snapshot,
+/// while file changes are the diff between the snapshotted working directories.
+pub fn diff(project: &Project, from_sha: String, to_sha: String) -> Result<SnapshotDiff> {
+    let repo = git::Repository::open(&project.path)?;
+
+    let from_tree = repo.find_commit(git::Oid::from_str(&from_sha)?)?.tree()?;
+    let to_tree = repo.find_commit(git::Oid::from_str(&to_sha)?)?.tree()?;
+
+    let branches = diff_branches(
+        snapshot_branches(&repo, &from_tree)?,
+        snapshot_branches(&repo, &to_tree)?,
+    );
+
+    let mut files = git::diff::trees(&repo, &from_tree, &to_tree)?;
+    // the virtual branches state is part of the snapshot, but not of the working directory
+    files.remove(Path::new(VIRTUAL_BRANCHES_FILE));
+
+    Ok(SnapshotDiff { branches, files })
+}
+
+/// The virtual branches as persisted in the snapshot `tree`.
+fn snapshot_branches(
+    repo: &git::Repository,
+    tree: &git::Tree,
+) -> Result<HashMap<BranchId, Branch>> {
+    let Some(entry) = tree.get_name(VIRTUAL_BRANCHES_FILE) else {
+        return Ok(HashMap::new());
+    };
+    let blob = repo.find_blob(entry.id())?;
+    let virtual_branches: VirtualBranches = toml::from_str(std::str::from_utf8(blob.content())?)
+        .context("failed to parse snapshotted virtual branches")?;
+    Ok(virtual_branches.branches)
+}
+
+fn diff_branches(
+    old: HashMap<BranchId, Branch>,
+    mut new: HashMap<BranchId, Branch>,
+) -> Vec<BranchDiff> {
+    let mut diffs = Vec::new();
+    for (id, old_branch) in old {
+        let (change_type, name, new_head, new_ownership) = match new.remove(&id) {
+            Some(new_branch) => {
+                if new_branch.head == old_branch.head
+                    && new_branch.ownership == old_branch.ownership
+                {
+                    continue;
+                }
+                (
+                    ChangeType::Modified,
+                    new_branch.name,
+                    Some(new_branch.head),
+                    Some(new_branch.ownership),
+                )
+            }
+            None => (ChangeType::Deleted, old_branch.name, None, None),
+        };
+        diffs.push(BranchDiff {
+            id,
+            name,
+            change_type,
+            old_head: Some(old_branch.head),
+            new_head,
+            old_ownership: Some(old_branch.ownership),
+            new_ownership,
+        });
+    }
+    diffs.extend(new.into_values().map(|branch| BranchDiff {
+        id: branch.id,
+        name: branch.name,
+        change_type: ChangeType::Added,
+        old_head: None,
+        new_head: Some(branch.head),
+        old_ownership: None,
+        new_ownership: Some(branch.ownership),
+    }));
+    diffs.sort_by(|a, b| a.name.cmp(&b.name));
+    diffs
+}
+
 fn get_exclude_list(repo: &git2::Repository) -> Result<String> {
     let repo_path = repo
         .path()
@@ -227,20 +686,17 @@ fn get_exclude_list(repo: &git2::Repository) -> Result<String> {
 
 #[cfg(test)]
 mod tests {
-    use std::{io::Write, path::PathBuf};
-
-    use crate::virtual_branches::Branch;
+    use std::io::Write;
 
     use super::*;
     use tempfile::tempdir;
 
-    #[test]
-    fn test_create_and_restore() {
-        let dir = tempdir().unwrap();
-        let repo = git2::Repository::init(dir.path()).unwrap();
-        let file_path = dir.path().join("1.txt");
+    /// A project in `dir` with two committed files, whose target is the initial commit.
+    fn test_project(dir: &Path) -> Project {
+        let repo = git2::Repository::init(dir).unwrap();
+        let file_path = dir.join("1.txt");
         std::fs::write(file_path, "test").unwrap();
-        let file_path = dir.path().join("2.txt");
+        let file_path = dir.join("2.txt");
         std::fs::write(file_path, "test").unwrap();
         let mut index = repo.index().unwrap();
         index.add_path(&PathBuf::from("1.txt")).unwrap();
@@ -269,22 +725,32 @@ mod tests {
         .unwrap();
 
         let project = Project {
-            path: dir.path().to_path_buf(),
+            path: dir.to_path_buf(),
             enable_snapshots: Some(true),
             ..Default::default()
         };
         // create gb_dir folder
         std::fs::create_dir_all(project.gb_dir()).unwrap();
 
-        let vb_state = VirtualBranchesHandle::new(&project.gb_dir());
-
         let target_sha = initial_commit.to_string();
         let default_target = crate::virtual_branches::target::Target {
             branch: crate::git::RemoteRefname::new("origin", "main"),
             remote_url: Default::default(),
             sha: crate::git::Oid::from_str(&target_sha).unwrap(),
         };
-        vb_state.set_default_target(default_target.clone()).unwrap();
+        VirtualBranchesHandle::new(&project.gb_dir())
+            .set_default_target(default_target)
+            .unwrap();
+
+        project
+    }
+
+    #[test]
+    fn test_create_and_restore() {
+        let dir = tempdir().unwrap();
+        let project = test_project(dir.path());
+        let vb_state = VirtualBranchesHandle::new(&project.gb_dir());
+
         let file_path = dir.path().join("uncommitted.txt");
         std::fs::write(file_path, "test").unwrap();
 
@@ -347,4 +813,504 @@ mod tests {
         // The fake branch is gone
         assert!(vb_state.get_branch(&id).is_err());
     }
+
+    #[test]
+    fn test_diff() {
+        let dir = tempdir().unwrap();
+        let project = test_project(dir.path());
+        let vb_state = VirtualBranchesHandle::new(&project.gb_dir());
+
+        let removed_id = crate::id::Id::from_str("9acb2a3b-cddf-47d7-b531-a7798978c237").unwrap();
+        vb_state
+            .set_branch(Branch {
+                id: removed_id,
+                name: "removed".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        let moved_id = crate::id::Id::from_str("1c4f0ed6-e04b-4b34-8ba5-1a4a3f1c1f05").unwrap();
+        let moved = Branch {
+            id: moved_id,
+            name: "moved".to_string(),
+            ..Default::default()
+        };
+        vb_state.set_branch(moved.clone()).unwrap();
+        create(&project, SnapshotDetails::new(OperationType::CreateCommit)).unwrap();
+
+        // Remove a branch, move the head of another, create a third one and modify a file
+        vb_state.remove_branch(removed_id).unwrap();
+        let new_head = vb_state.get_default_target().unwrap().sha;
+        vb_state
+            .set_branch(Branch {
+                head: new_head,
+                ownership: "1.txt:1-1".parse().unwrap(),
+                ..moved
+            })
+            .unwrap();
+        let added_id = crate::id::Id::from_str("b2e5f8a4-94a6-4c8b-9a65-7d3d8d3e5f2e").unwrap();
+        vb_state
+            .set_branch(Branch {
+                id: added_id,
+                name: "added".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        std::fs::write(dir.path().join("1.txt"), "TEST").unwrap();
+        create(
+            &project,
+            SnapshotDetails::new(OperationType::UpdateWorkspaceBase),
+        )
+        .unwrap();
+
+        let snapshots = list(&project, 100).unwrap();
+        let diff = diff(&project, snapshots[1].id.clone(), snapshots[0].id.clone()).unwrap();
+
+        assert_eq!(diff.branches.len(), 3);
+        assert_eq!(diff.branches[0].id, added_id);
+        assert_eq!(diff.branches[0].change_type, ChangeType::Added);
+        assert_eq!(diff.branches[1].id, moved_id);
+        assert_eq!(diff.branches[1].change_type, ChangeType::Modified);
+        assert_eq!(diff.branches[1].old_head, Some(moved.head));
+        assert_eq!(diff.branches[1].new_head, Some(new_head));
+        assert_eq!(
+            diff.branches[1].new_ownership,
+            Some("1.txt:1-1".parse().unwrap())
+        );
+        assert_eq!(diff.branches[2].id, removed_id);
+        assert_eq!(diff.branches[2].change_type, ChangeType::Deleted);
+        assert_eq!(diff.branches[2].new_head, None);
+
+        // the snapshotted virtual branches state is not a change of the working directory
+        assert_eq!(diff.files.len(), 1);
+        assert!(diff.files.contains_key(Path::new("1.txt")));
+    }
+
+    #[test]
+    fn test_compact_and_restore() {
+        let dir = tempdir().unwrap();
+        let project = test_project(dir.path());
+
+        for content in ["one", "two", "three", "four"] {
+            std::fs::write(dir.path().join("1.txt"), content).unwrap();
+            create(&project, SnapshotDetails::new(OperationType::CreateCommit)).unwrap();
+        }
+
+        let retention = SnapshotRetention {
+            max_count: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(compact(&project, &retention).unwrap(), 2);
+        assert_eq!(compact(&project, &retention).unwrap(), 0);
+
+        let snapshots = list(&project, 100)
+            .unwrap()
+            .into_iter()
+            .filter(|snapshot| snapshot.details.is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(snapshots.len(), 2);
+
+        // The oplog head and the reflog entry keeping it reachable point to the new chain
+        let oplog_head = OplogHandle::new(&project.gb_dir())
+            .get_oplog_head()
+            .unwrap()
+            .unwrap();
+        assert_eq!(oplog_head, snapshots[0].id);
+        let reflog =
+            std::fs::read_to_string(dir.path().join(".git/logs/refs/heads/gitbutler/target"))
+                .unwrap();
+        assert!(reflog.lines().nth(1).unwrap().contains(&oplog_head));
+
+        // The survivors can still be restored
+        restore(&project, snapshots[1].id.clone()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("1.txt")).unwrap(),
+            "three"
+        );
+    }
+
+    #[test]
+    fn test_create_does_not_compact() {
+        let dir = tempdir().unwrap();
+        let project = Project {
+            snapshot_retention: Some(SnapshotRetention {
+                max_count: Some(2),
+                ..Default::default()
+            }),
+            ..test_project(dir.path())
+        };
+
+        let mut shas = Vec::new();
+        for content in ["one", "two", "three"] {
+            std::fs::write(dir.path().join("1.txt"), content).unwrap();
+            create(&project, SnapshotDetails::new(OperationType::CreateCommit)).unwrap();
+            shas.push(list(&project, 1).unwrap()[0].id.clone());
+        }
+
+        let snapshots = list(&project, 100)
+            .unwrap()
+            .into_iter()
+            .filter(|snapshot| snapshot.details.is_some())
+            .map(|snapshot| snapshot.id)
+            .collect::<Vec<_>>();
+        assert_eq!(snapshots, shas.into_iter().rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_compact_keeps_restored_snapshots() {
+        let dir = tempdir().unwrap();
+        let project = test_project(dir.path());
+
+        let mut shas = Vec::new();
+        for content in ["one", "two", "three", "four"] {
+            std::fs::write(dir.path().join("1.txt"), content).unwrap();
+            create(&project, SnapshotDetails::new(OperationType::CreateCommit)).unwrap();
+            shas.push(list(&project, 1).unwrap()[0].id.clone());
+        }
+        restore(&project, shas[1].clone()).unwrap();
+
+        let retention = SnapshotRetention {
+            max_count: Some(2),
+            ..Default::default()
+        };
+        // "one" and "three" are dropped, "two" is kept as the restore refers to it
+        assert_eq!(compact(&project, &retention).unwrap(), 2);
+
+        let snapshots = list(&project, 100)
+            .unwrap()
+            .into_iter()
+            .filter(|snapshot| snapshot.details.is_some())
+            .collect::<Vec<_>>();
+        assert_eq!(snapshots.len(), 3);
+        assert_ne!(snapshots[2].id, shas[1]);
+        let restored_from = snapshots[0]
+            .details
+            .as_ref()
+            .unwrap()
+            .trailers
+            .iter()
+            .find(|trailer| trailer.key == "restored_from")
+            .unwrap()
+            .value
+            .clone();
+        assert_eq!(restored_from, snapshots[2].id);
+
+        std::fs::write(dir.path().join("1.txt"), "five").unwrap();
+        restore(&project, snapshots[2].id.clone()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("1.txt")).unwrap(),
+            "two"
+        );
+    }
+
+    #[test]
+    fn test_retained_snapshots() {
+        const HOUR: i64 = 60 * 60;
+        let now = 100 * 24 * HOUR;
+        let created_at = [
+            now,
+            now - HOUR,
+            now - 25 * HOUR,
+            now - 26 * HOUR,
+            now - 27 * HOUR,
+            now - 28 * HOUR,
+        ];
+
+        assert_eq!(
+            retained_snapshots(&created_at, now, &SnapshotRetention::default()),
+            vec![true; 6]
+        );
+        assert_eq!(
+            retained_snapshots(
+                &created_at,
+                now,
+                &SnapshotRetention {
+                    max_count: Some(3),
+                    ..Default::default()
+                }
+            ),
+            vec![true, true, true, false, false, false]
+        );
+        assert_eq!(
+            retained_snapshots(
+                &created_at,
+                now,
+                &SnapshotRetention {
+                    max_age_seconds: Some(2 * HOUR as u64),
+                    ..Default::default()
+                }
+            ),
+            vec![true, true, false, false, false, false]
+        );
+        assert_eq!(
+            retained_snapshots(
+                &created_at,
+                now,
+                &SnapshotRetention {
+                    keep_every_nth_after_day: Some(2),
+                    ..Default::default()
+                }
+            ),
+            vec![true, true, true, false, true, false]
+        );
+
+        // The most recent snapshot is always kept
+        assert_eq!(
+            retained_snapshots(
+                &[now - 2 * HOUR],
+                now,
+                &SnapshotRetention {
+                    max_count: Some(0),
+                    max_age_seconds: Some(HOUR as u64),
+                    ..Default::default()
+                }
+            ),
+            vec![true]
+        );
+    }
+
+    #[test]
+    fn test_create_leaves_index_and_worktree_alone() {
+        let dir = tempdir().unwrap();
+        let project = test_project(dir.path());
+        let repo = git2::Repository::open(dir.path()).unwrap();
+
+        // Stage a change and leave another one unstaged
+        std::fs::write(dir.path().join("1.txt"), "staged").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("1.txt")).unwrap();
+        index.write().unwrap();
+        std::fs::write(dir.path().join("2.txt"), "unstaged").unwrap();
+        let index_before = std::fs::read(dir.path().join(".git/index")).unwrap();
+
+        create(&project, SnapshotDetails::new(OperationType::CreateCommit)).unwrap();
+
+        assert_eq!(
+            std::fs::read(dir.path().join(".git/index")).unwrap(),
+            index_before
+        );
+        assert!(!dir.path().join(VIRTUAL_BRANCHES_FILE).exists());
+
+        // The snapshot has the worktree content and the virtual branches state
+        let snapshots = list(&project, 1).unwrap();
+        let tree = repo
+            .find_commit(git2::Oid::from_str(&snapshots[0].id).unwrap())
+            .unwrap()
+            .tree()
+            .unwrap();
+        let content = |path: &str| {
+            let entry = tree.get_path(Path::new(path)).unwrap();
+            repo.find_blob(entry.id()).unwrap().content().to_vec()
+        };
+        assert_eq!(content("1.txt"), b"staged");
+        assert_eq!(content("2.txt"), b"unstaged");
+        assert_eq!(
+            content(VIRTUAL_BRANCHES_FILE),
+            std::fs::read(project.gb_dir().join("virtual_branches.toml")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_undo_and_redo() {
+        let dir = tempdir().unwrap();
+        let project = test_project(dir.path());
+        let read = || std::fs::read_to_string(dir.path().join("1.txt")).unwrap();
+
+        let mut shas = Vec::new();
+        for content in ["one", "two", "three"] {
+            std::fs::write(dir.path().join("1.txt"), content).unwrap();
+            create(&project, SnapshotDetails::new(OperationType::CreateCommit)).unwrap();
+            shas.push(list(&project, 1).unwrap()[0].id.clone());
+        }
+
+        // Undo walks back past the restores it creates itself
+        assert_eq!(undo(&project).unwrap(), Some(shas[1].clone()));
+        assert_eq!(read(), "two");
+        assert_eq!(undo(&project).unwrap(), Some(shas[0].clone()));
+        assert_eq!(read(), "one");
+        // There is no snapshot of the state before the first operation
+        assert_eq!(undo(&project).unwrap(), None);
+
+        assert_eq!(redo(&project).unwrap(), Some(shas[1].clone()));
+        assert_eq!(read(), "two");
+        assert_eq!(redo(&project).unwrap(), Some(shas[2].clone()));
+        assert_eq!(read(), "three");
+        assert_eq!(redo(&project).unwrap(), None);
+
+        // A new operation after an undo can't be followed by a redo
+        assert_eq!(undo(&project).unwrap(), Some(shas[1].clone()));
+        std::fs::write(dir.path().join("1.txt"), "four").unwrap();
+        create(&project, SnapshotDetails::new(OperationType::CreateCommit)).unwrap();
+        assert_eq!(redo(&project).unwrap(), None);
+
+        // Undoing the new operation goes back to the state it was performed on
+        assert_eq!(undo(&project).unwrap(), Some(shas[1].clone()));
+        assert_eq!(read(), "two");
+    }
+
+    #[test]
+    fn test_undo_and_redo_across_compaction() {
+        let dir = tempdir().unwrap();
+        let project = test_project(dir.path());
+        let read = || std::fs::read_to_string(dir.path().join("1.txt")).unwrap();
+
+        let mut shas = Vec::new();
+        for content in ["one", "two", "three", "four"] {
+            std::fs::write(dir.path().join("1.txt"), content).unwrap();
+            create(&project, SnapshotDetails::new(OperationType::CreateCommit)).unwrap();
+            shas.push(list(&project, 1).unwrap()[0].id.clone());
+        }
+        assert_eq!(undo(&project).unwrap(), Some(shas[2].clone()));
+        assert_eq!(undo(&project).unwrap(), Some(shas[1].clone()));
+
+        // "one" and the restore of "three" are dropped, the undone and restored snapshots stay
+        let retention = SnapshotRetention {
+            max_count: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(compact(&project, &retention).unwrap(), 2);
+
+        let three = redo(&project).unwrap().unwrap();
+        assert_ne!(three, shas[2]);
+        assert_eq!(read(), "three");
+        redo(&project).unwrap().unwrap();
+        assert_eq!(read(), "four");
+        assert_eq!(redo(&project).unwrap(), None);
+
+        assert_eq!(undo(&project).unwrap(), Some(three));
+        assert_eq!(read(), "three");
+        undo(&project).unwrap().unwrap();
+        assert_eq!(read(), "two");
+        // The state before "two" was dropped
+        assert_eq!(undo(&project).unwrap(), None);
+    }
+
+    #[test]
+    fn test_restore_paths() {
+        let dir = tempdir().unwrap();
+        let project = test_project(dir.path());
+        let vb_state = VirtualBranchesHandle::new(&project.gb_dir());
+
+        create(&project, SnapshotDetails::new(OperationType::CreateCommit)).unwrap();
+        let sha = list(&project, 1).unwrap()[0].id.clone();
+
+        // Modify file 1, remove file 2, create file 3 and a branch
+        std::fs::write(dir.path().join("1.txt"), "TEST").unwrap();
+        std::fs::remove_file(dir.path().join("2.txt")).unwrap();
+        std::fs::write(dir.path().join("3.txt"), "something_new").unwrap();
+        let id = crate::id::Id::from_str("9acb2a3b-cddf-47d7-b531-a7798978c237").unwrap();
+        vb_state
+            .set_branch(Branch {
+                id,
+                ..Default::default()
+            })
+            .unwrap();
+
+        restore_paths(
+            &project,
+            sha.clone(),
+            &[PathBuf::from("1.txt"), PathBuf::from("3.txt")],
+        )
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("1.txt")).unwrap(),
+            "test"
+        );
+        assert!(!dir.path().join("3.txt").exists());
+        // Everything else is left alone
+        assert!(!dir.path().join("2.txt").exists());
+        assert!(vb_state.get_branch(&id).is_ok());
+
+        let details = list(&project, 1).unwrap()[0].details.clone().unwrap();
+        assert_eq!(details.operation, OperationType::RestorePathsFromSnapshot);
+        assert_eq!(
+            details
+                .trailers
+                .iter()
+                .filter(|trailer| trailer.key == "restored_path")
+                .map(|trailer| trailer.value.as_str())
+                .collect::<Vec<_>>(),
+            vec!["1.txt", "3.txt"]
+        );
+    }
+
+    #[test]
+    fn test_restore_branch() {
+        let dir = tempdir().unwrap();
+        let project = test_project(dir.path());
+        let vb_state = VirtualBranchesHandle::new(&project.gb_dir());
+        let target_sha = vb_state.get_default_target().unwrap().sha;
+        let target_tree = git2::Repository::open(dir.path())
+            .unwrap()
+            .find_commit(target_sha.into())
+            .unwrap()
+            .tree_id();
+
+        let id = crate::id::Id::from_str("9acb2a3b-cddf-47d7-b531-a7798978c237").unwrap();
+        let branch = Branch {
+            id,
+            name: "name".to_string(),
+            ownership: "1.txt:1-1".parse().unwrap(),
+            applied: true,
+            head: target_sha,
+            tree: target_tree.into(),
+            ..Default::default()
+        };
+        vb_state.set_branch(branch.clone()).unwrap();
+        let other_id = crate::id::Id::from_str("1c4f0ed6-e04b-4b34-8ba5-1a4a3f1c1f05").unwrap();
+        vb_state
+            .set_branch(Branch {
+                id: other_id,
+                name: "other".to_string(),
+                ownership: Default::default(),
+                applied: false,
+                ..branch.clone()
+            })
+            .unwrap();
+        create(&project, SnapshotDetails::new(OperationType::CreateCommit)).unwrap();
+        let sha = list(&project, 1).unwrap()[0].id.clone();
+
+        vb_state
+            .set_branch(Branch {
+                name: "renamed".to_string(),
+                ownership: Default::default(),
+                applied: false,
+                ..branch.clone()
+            })
+            .unwrap();
+        vb_state.remove_branch(other_id).unwrap();
+        std::fs::write(dir.path().join("1.txt"), "TEST").unwrap();
+
+        restore_branch(&project, sha.clone(), id).unwrap();
+
+        let restored = vb_state.get_branch(&id).unwrap();
+        assert_eq!(restored.name, "name");
+        assert_eq!(restored.ownership, branch.ownership);
+        // Whether the branch is applied is not restored, neither is the working directory
+        assert!(!restored.applied);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("1.txt")).unwrap(),
+            "TEST"
+        );
+        assert!(vb_state.get_branch(&other_id).is_err());
+
+        // Deleted branches come back unapplied
+        restore_branch(&project, sha.clone(), other_id).unwrap();
+        let restored = vb_state.get_branch(&other_id).unwrap();
+        assert_eq!(restored.name, "other");
+        assert!(!restored.applied);
+
+        assert!(restore_branch(&project, sha.clone(), crate::id::Id::generate()).is_err());
+
+        // Applied branches would get out of sync with the working directory
+        vb_state
+            .set_branch(Branch {
+                name: "applied".to_string(),
+                applied: true,
+                ..branch
+            })
+            .unwrap();
+        assert!(restore_branch(&project, sha, id).is_err());
+        assert_eq!(vb_state.get_branch(&id).unwrap().name, "applied");
+    }
 }
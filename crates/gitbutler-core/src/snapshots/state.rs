@@ -12,6 +12,9 @@ use serde::{Deserialize, Serialize};
 pub struct Oplog {
     /// This is the sha of the last oplog commit
     pub head_sha: Option<String>,
+    /// The shas of the snapshots that were undone, the most recently undone one last
+    #[serde(default)]
+    pub redo_shas: Vec<String>,
 }
 
 pub struct OplogHandle {
@@ -44,6 +47,62 @@ impl OplogHandle {
         Ok(oplog.head_sha)
     }
 
+    /// Remembers the snapshot `sha` as undone, so it can be restored by a redo.
+    ///
+    /// Errors if the file cannot be read or written.
+    pub fn push_redo(&self, sha: String) -> Result<()> {
+        let mut oplog = self.read_file()?;
+        oplog.redo_shas.push(sha);
+        self.write_file(&oplog)
+    }
+
+    /// Gets the sha of the most recently undone snapshot, if any.
+    ///
+    /// Errors if the file cannot be read or written.
+    pub fn last_redo(&self) -> Result<Option<String>> {
+        let oplog = self.read_file()?;
+        Ok(oplog.redo_shas.last().cloned())
+    }
+
+    /// Forgets the most recently undone snapshot, returning its sha.
+    ///
+    /// Errors if the file cannot be read or written.
+    pub fn pop_redo(&self) -> Result<Option<String>> {
+        let mut oplog = self.read_file()?;
+        let sha = oplog.redo_shas.pop();
+        self.write_file(&oplog)?;
+        Ok(sha)
+    }
+
+    /// Gets the shas of all undone snapshots, the most recently undone one last.
+    ///
+    /// Errors if the file cannot be read or written.
+    pub fn redo_shas(&self) -> Result<Vec<String>> {
+        let oplog = self.read_file()?;
+        Ok(oplog.redo_shas)
+    }
+
+    /// Replaces the shas of all undone snapshots, for when the snapshots were rewritten.
+    ///
+    /// Errors if the file cannot be read or written.
+    pub fn set_redo_shas(&self, shas: Vec<String>) -> Result<()> {
+        let mut oplog = self.read_file()?;
+        oplog.redo_shas = shas;
+        self.write_file(&oplog)
+    }
+
+    /// Forgets all undone snapshots, as they can't be redone anymore.
+    ///
+    /// Errors if the file cannot be read or written.
+    pub fn clear_redo(&self) -> Result<()> {
+        let mut oplog = self.read_file()?;
+        if oplog.redo_shas.is_empty() {
+            return Ok(());
+        }
+        oplog.redo_shas.clear();
+        self.write_file(&oplog)
+    }
+
     /// Reads and parses the state file.
     ///
     /// If the file does not exist, it will be created.
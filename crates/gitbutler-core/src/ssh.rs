@@ -1,8 +1,15 @@
-use std::{env, fs, path::Path};
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
 
-use ssh2::{CheckResult, KnownHostFileKind};
+use crate::{askpass, askpass::AskpassBroker, git};
 
-use crate::git;
+mod known_hosts;
+pub use known_hosts::{
+    fingerprint, host_name, key_type, Check, Entry, HostPattern, KnownHosts, Marker,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -10,13 +17,32 @@ pub enum Error {
     Ssh(ssh2::Error),
     #[error(transparent)]
     Io(std::io::Error),
-    #[error("mismatched host key")]
-    MismatchedHostKey,
+    #[error("the host key of {host} doesn't match the one in {known_hosts}", known_hosts = .known_hosts.display())]
+    MismatchedHostKey { host: String, known_hosts: PathBuf },
+    #[error("the host key of {host} was revoked")]
+    RevokedHostKey { host: String },
+    #[error("the host key of {host} with fingerprint {fingerprint} wasn't accepted, to trust it run `{keyscan} >> {known_hosts}`", known_hosts = .known_hosts.display())]
+    UnknownHostKey {
+        host: String,
+        fingerprint: String,
+        /// The command that prints the key in the `known_hosts` format.
+        keyscan: String,
+        known_hosts: PathBuf,
+    },
     #[error("failed to check the known hosts")]
     Failure,
 }
 
-pub fn check_known_host(remote_url: &git::Url) -> Result<(), Error> {
+/// Makes sure that the host of `remote_url` is known, asking the user whether to trust its key
+/// through `askpass` the first time, like ssh does. Accepted keys are added to `~/.ssh/known_hosts`,
+/// and without `askpass` unknown keys are rejected.
+///
+/// This blocks until the prompt is answered, so it must run on a thread that may block, like one
+/// of [`tokio::task::spawn_blocking`], and never on an async runtime's worker.
+pub fn check_known_host(
+    remote_url: &git::Url,
+    askpass: Option<(AskpassBroker, askpass::Context)>,
+) -> Result<(), Error> {
     if remote_url.scheme != git::Scheme::Ssh {
         return Ok(());
     }
@@ -34,37 +60,101 @@ pub fn check_known_host(remote_url: &git::Url) -> Result<(), Error> {
         std::net::TcpStream::connect(format!("{}:{}", host, port)).map_err(Error::Io)?,
     );
     session.handshake().map_err(Error::Ssh)?;
+    let (key, _) = session.host_key().ok_or(Error::Failure)?;
 
-    let mut known_hosts = session.known_hosts().map_err(Error::Ssh)?;
+    let known_hosts = Path::new(&env::var("HOME").unwrap())
+        .join(".ssh")
+        .join("known_hosts");
+    verify_host_key(&known_hosts, host, port, key, |prompt| {
+        let Some((askpass_broker, context)) = askpass else {
+            tracing::warn!("received host key prompt but no broker was supplied; rejecting");
+            return false;
+        };
+        let response = futures::executor::block_on(askpass_broker.submit_prompt(prompt, context));
+        response.is_some_and(|response| response.trim().eq_ignore_ascii_case("yes"))
+    })
+}
+
+/// Looks up `key` of `host` on `port` in the `known_hosts` file. Unknown keys are added to it
+/// if `confirm` accepts them, given a prompt that shows their fingerprint.
+pub fn verify_host_key(
+    known_hosts: &Path,
+    host: &str,
+    port: u16,
+    key: &[u8],
+    confirm: impl FnOnce(String) -> bool,
+) -> Result<(), Error> {
+    let name = host_name(host, port);
+    match KnownHosts::read(known_hosts)
+        .map_err(Error::Io)?
+        .check(host, port, key)
+    {
+        Check::Match => Ok(()),
+        Check::Mismatch => Err(Error::MismatchedHostKey {
+            host: name,
+            known_hosts: known_hosts.to_path_buf(),
+        }),
+        Check::Revoked => Err(Error::RevokedHostKey { host: name }),
+        Check::NotFound => {
+            let key_type = key_type(key).ok_or(Error::Failure)?;
+            let fingerprint = fingerprint(key);
+            let prompt = format!(
+                "The authenticity of host '{name}' can't be established.\n\
+                 {} key fingerprint is {fingerprint}.\n\
+                 Are you sure you want to continue connecting (yes/no)?",
+                display_key_type(&key_type),
+            );
+            if !confirm(prompt) {
+                return Err(Error::UnknownHostKey {
+                    host: name,
+                    fingerprint,
+                    keyscan: match port {
+                        22 => format!("ssh-keyscan {host}"),
+                        port => format!("ssh-keyscan -p {port} {host}"),
+                    },
+                    known_hosts: known_hosts.to_path_buf(),
+                });
+            }
 
-    // Initialize the known hosts with a global known hosts file
-    let dotssh = Path::new(&env::var("HOME").unwrap()).join(".ssh");
-    let file = dotssh.join("known_hosts");
-    if !file.exists() {
-        fs::create_dir_all(&dotssh).map_err(Error::Io)?;
-        fs::File::create(&file).map_err(Error::Io)?;
+            tracing::info!(host = name, fingerprint, "adding host key");
+            let entry = Entry {
+                marker: None,
+                hosts: vec![HostPattern::Plain {
+                    pattern: name,
+                    negated: false,
+                }],
+                key_type,
+                key: key.to_vec(),
+            };
+            append_entry(known_hosts, &entry).map_err(Error::Io)
+        }
     }
+}
 
-    known_hosts
-        .read_file(&file, KnownHostFileKind::OpenSSH)
-        .map_err(Error::Ssh)?;
+fn append_entry(known_hosts: &Path, entry: &Entry) -> std::io::Result<()> {
+    if let Some(dotssh) = known_hosts.parent() {
+        fs::create_dir_all(dotssh)?;
+    }
+    let needs_newline = fs::read(known_hosts)
+        .map(|content| content.last().is_some_and(|last| *last != b'\n'))
+        .unwrap_or(false);
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(known_hosts)?;
+    if needs_newline {
+        writeln!(file)?;
+    }
+    writeln!(file, "{entry}")
+}
 
-    // Now check to see if the seesion's host key is anywhere in the known
-    // hosts file
-    let (key, key_type) = session.host_key().unwrap();
-    match known_hosts.check(host, key) {
-        CheckResult::Match => Ok(()),
-        CheckResult::Mismatch => Err(Error::MismatchedHostKey),
-        CheckResult::Failure => Err(Error::Failure),
-        CheckResult::NotFound => {
-            tracing::info!("adding host key for {}", host);
-            known_hosts
-                .add(host, key, "added by gitbutler client", key_type.into())
-                .map_err(Error::Ssh)?;
-            known_hosts
-                .write_file(&file, KnownHostFileKind::OpenSSH)
-                .map_err(Error::Ssh)?;
-            Ok(())
-        }
+/// The key type as ssh shows it in prompts, e.g. `ED25519` for `ssh-ed25519`.
+fn display_key_type(key_type: &str) -> &str {
+    match key_type {
+        "ssh-ed25519" => "ED25519",
+        "ssh-rsa" => "RSA",
+        "ssh-dss" => "DSA",
+        key_type if key_type.starts_with("ecdsa-") => "ECDSA",
+        key_type => key_type,
     }
 }
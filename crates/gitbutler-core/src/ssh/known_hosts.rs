@@ -0,0 +1,269 @@
+use std::{fmt, fs, io, path::Path};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+/// The marker some known_hosts entries start with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    /// `@cert-authority`: the key is a CA that signs host certificates.
+    CertAuthority,
+    /// `@revoked`: the key must never be accepted, for any host.
+    Revoked,
+}
+
+/// One of the comma-separated host patterns of an entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostPattern {
+    /// A hostname, or `[hostname]:port`, with `*` and `?` wildcards. Negated patterns
+    /// exclude matching hosts from the entry.
+    Plain { pattern: String, negated: bool },
+    /// `|1|<salt>|<hash>`, the HMAC-SHA1 of the hostname, as written with `HashKnownHosts`.
+    Hashed { salt: Vec<u8>, hash: Vec<u8> },
+}
+
+/// A line of a known_hosts file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub marker: Option<Marker>,
+    pub hosts: Vec<HostPattern>,
+    pub key_type: String,
+    /// The public key in the ssh wire format.
+    pub key: Vec<u8>,
+}
+
+/// The result of looking a host key up in [`KnownHosts`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    Match,
+    /// The host is known with another key of the same type.
+    Mismatch,
+    /// The key was revoked.
+    Revoked,
+    NotFound,
+}
+
+/// The entries of a known_hosts file, in the format described in `sshd(8)`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KnownHosts {
+    pub entries: Vec<Entry>,
+}
+
+impl KnownHosts {
+    /// Parses `input`, skipping comments and the lines that aren't valid entries.
+    pub fn parse(input: &str) -> Self {
+        let entries = input
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let entry = parse_entry(line);
+                if entry.is_none() {
+                    tracing::warn!(line, "skipping invalid known_hosts entry");
+                }
+                entry
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Reads the known_hosts file at `path`, which is empty if it doesn't exist.
+    pub fn read(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(input) => Ok(Self::parse(&input)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Looks up `key` for `host` on `port`.
+    ///
+    /// Host certificates aren't supported, so `@cert-authority` entries are never a match.
+    pub fn check(&self, host: &str, port: u16, key: &[u8]) -> Check {
+        if self
+            .entries
+            .iter()
+            .any(|entry| entry.marker == Some(Marker::Revoked) && entry.key == key)
+        {
+            return Check::Revoked;
+        }
+
+        let name = host_name(host, port);
+        let key_type = key_type(key);
+        let mut known_with_other_key = false;
+        for entry in self
+            .entries
+            .iter()
+            .filter(|entry| entry.marker.is_none() && entry.matches(&name))
+        {
+            if entry.key == key {
+                return Check::Match;
+            }
+            known_with_other_key |= Some(entry.key_type.as_str()) == key_type.as_deref();
+        }
+
+        if known_with_other_key {
+            Check::Mismatch
+        } else {
+            Check::NotFound
+        }
+    }
+}
+
+impl Entry {
+    /// Whether the entry applies to `name`, as returned by [`host_name`].
+    fn matches(&self, name: &str) -> bool {
+        let mut matched = false;
+        for pattern in &self.hosts {
+            match pattern {
+                HostPattern::Plain { pattern, negated } => {
+                    if wildcard_match(&pattern.to_lowercase(), name) {
+                        if *negated {
+                            return false;
+                        }
+                        matched = true;
+                    }
+                }
+                HostPattern::Hashed { salt, hash } => {
+                    let mut mac = Hmac::<Sha1>::new_from_slice(salt).expect("any key length");
+                    mac.update(name.as_bytes());
+                    matched |= mac.verify_slice(hash).is_ok();
+                }
+            }
+        }
+        matched
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.marker {
+            Some(Marker::CertAuthority) => write!(f, "@cert-authority ")?,
+            Some(Marker::Revoked) => write!(f, "@revoked ")?,
+            None => {}
+        }
+        let hosts = self
+            .hosts
+            .iter()
+            .map(|host| match host {
+                HostPattern::Plain { pattern, negated } => {
+                    format!("{}{}", if *negated { "!" } else { "" }, pattern)
+                }
+                HostPattern::Hashed { salt, hash } => {
+                    format!("|1|{}|{}", BASE64.encode(salt), BASE64.encode(hash))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        write!(
+            f,
+            "{} {} {}",
+            hosts,
+            self.key_type,
+            BASE64.encode(&self.key)
+        )
+    }
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+    let mut fields = line.split_whitespace();
+    let mut hosts = fields.next()?;
+    let marker = match hosts {
+        "@cert-authority" => Some(Marker::CertAuthority),
+        "@revoked" => Some(Marker::Revoked),
+        _ if hosts.starts_with('@') => return None,
+        _ => None,
+    };
+    if marker.is_some() {
+        hosts = fields.next()?;
+    }
+    let key_type = fields.next()?.to_string();
+    let key = BASE64.decode(fields.next()?).ok()?;
+    // the rest is a comment
+
+    let hosts = hosts
+        .split(',')
+        .map(|pattern| match pattern.strip_prefix("|1|") {
+            Some(hashed) => {
+                let (salt, hash) = hashed.split_once('|')?;
+                Some(HostPattern::Hashed {
+                    salt: BASE64.decode(salt).ok()?,
+                    hash: BASE64.decode(hash).ok()?,
+                })
+            }
+            None => Some(match pattern.strip_prefix('!') {
+                Some(pattern) => HostPattern::Plain {
+                    pattern: pattern.to_string(),
+                    negated: true,
+                },
+                None => HostPattern::Plain {
+                    pattern: pattern.to_string(),
+                    negated: false,
+                },
+            }),
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(Entry {
+        marker,
+        hosts,
+        key_type,
+        key,
+    })
+}
+
+/// The name `host` is known as on `port`, which only includes non-standard ports.
+pub fn host_name(host: &str, port: u16) -> String {
+    let host = host.to_lowercase();
+    if port == 22 {
+        host
+    } else {
+        format!("[{host}]:{port}")
+    }
+}
+
+/// The type of `key`, which is the first field of the ssh wire format.
+pub fn key_type(key: &[u8]) -> Option<String> {
+    let len = u32::from_be_bytes(key.get(..4)?.try_into().ok()?) as usize;
+    let key_type = key.get(4..4 + len)?;
+    Some(String::from_utf8_lossy(key_type).into_owned())
+}
+
+/// The fingerprint of `key` as OpenSSH shows it, e.g. `SHA256:+DiY3wvvV6TuJJhbpZisF/zLDA0zPMSvHdkr4UvCOqU`.
+pub fn fingerprint(key: &[u8]) -> String {
+    let digest = Sha256::digest(key);
+    format!(
+        "SHA256:{}",
+        base64::engine::general_purpose::STANDARD_NO_PAD.encode(digest)
+    )
+}
+
+/// Matches `name` against a `pattern` with `*` and `?` wildcards.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
@@ -10,7 +10,6 @@ use super::{
 };
 use crate::{
     git::{self, diff},
-    keys,
     project_repository::{self, LogUntil},
     projects::FetchResult,
     users,
@@ -260,6 +259,8 @@ pub fn set_base_branch(
                 ownership,
                 order: 0,
                 selected_for_changes: None,
+                parent: None,
+                parent_head: None,
             };
 
             vb_state.set_branch(branch)?;
@@ -312,7 +313,7 @@ fn _print_tree(repo: &git2::Repository, tree: &git2::Tree) -> Result<()> {
 pub fn update_base_branch(
     project_repository: &project_repository::Repository,
     user: Option<&users::User>,
-    signing_key: Option<&keys::PrivateKey>,
+    signer: Option<&git::Signer>,
 ) -> Result<(), errors::UpdateBaseBranchError> {
     if project_repository.is_resolving() {
         return Err(errors::UpdateBaseBranchError::Conflict(
@@ -362,11 +363,41 @@ pub fn update_base_branch(
 
     let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
 
+    let is_stacked = vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?
+        .iter()
+        .any(|branch| branch.parent.is_some());
+    let vbranches = if is_stacked {
+        // restacking merges the tree of a stacked branch onto the new head of its parent, so the
+        // tree of every applied branch has to be its head along with the changes it owns. The
+        // stored trees are based on the target and include the commits of the stacks.
+        let integration_commit =
+            super::integration::get_workspace_head(&vb_state, project_repository)?;
+        super::get_status_by_branch(project_repository, Some(&integration_commit))?
+            .0
+            .into_iter()
+            .map(|(mut branch, files)| {
+                if branch.applied {
+                    branch.tree = super::write_tree(project_repository, &branch.head, files)?;
+                }
+                Ok(branch)
+            })
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        super::get_status_by_branch(project_repository, None)?
+            .0
+            .into_iter()
+            .map(|(branch, _)| branch)
+            .collect()
+    };
+    let (mut stacked_vbranches, vbranches): (Vec<_>, Vec<_>) = vbranches
+        .into_iter()
+        .partition(|branch| branch.parent.is_some());
+
     // try to update every branch
-    let updated_vbranches = super::get_status_by_branch(project_repository, None)?
-        .0
+    let mut updated_vbranches = vbranches
         .into_iter()
-        .map(|(branch, _)| branch)
         .map(
             |mut branch: branch::Branch| -> Result<Option<branch::Branch>> {
                 let branch_tree = repo.find_tree(branch.tree)?;
@@ -483,7 +514,7 @@ pub fn update_base_branch(
                             .as_str(),
                             &branch_head_merge_tree,
                             &[&branch_head_commit, &new_target_commit],
-                            signing_key,
+                            signer,
                         )
                         .context("failed to commit merge")?;
 
@@ -551,6 +582,37 @@ pub fn update_base_branch(
         .flatten()
         .collect::<Vec<_>>();
 
+    // stacked branches are updated after their parent, by rebasing them onto its new head
+    let stack_order = super::stack_order(&stacked_vbranches);
+    stacked_vbranches.sort_by_key(|branch| stack_order.iter().position(|id| *id == branch.id));
+    for mut branch in stacked_vbranches {
+        // the parent is gone if it was integrated, in which case the branch sits on the target
+        let parent = updated_vbranches
+            .iter()
+            .find(|parent| Some(parent.id) == branch.parent);
+        match parent {
+            Some(parent) if !parent.applied => {
+                // the stack can't be applied partially
+                branch.applied = false;
+            }
+            _ => {
+                let new_parent_head = parent.map_or(new_target_commit.id(), |parent| parent.head);
+                branch.parent = parent.map(|parent| parent.id);
+                if !super::restack_branch(project_repository, &mut branch, new_parent_head, signer)?
+                {
+                    // the commits conflict with the new parent, they will be dealt with when
+                    // the branch is applied again.
+                    branch.applied = false;
+                }
+                if branch.parent.is_none() {
+                    branch.parent_head = None;
+                }
+            }
+        }
+        vb_state.set_branch(branch.clone())?;
+        updated_vbranches.push(branch);
+    }
+
     // ok, now all the problematic branches have been unapplied
     // now we calculate and checkout new tree for the working directory
 
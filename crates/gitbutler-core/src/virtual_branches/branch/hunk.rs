@@ -2,6 +2,7 @@ use std::{fmt::Display, ops::RangeInclusive, str::FromStr};
 
 use anyhow::{anyhow, Context, Result};
 use bstr::{BStr, ByteSlice};
+use itertools::Itertools;
 
 use crate::git::diff;
 
@@ -14,6 +15,9 @@ pub struct Hunk {
     pub start: u32,
     pub end: u32,
     pub locked_to: Vec<diff::HunkLock>,
+    /// The changed lines of the hunk that are claimed, as indices into the lines of its diff
+    /// that follow the header, or empty if the whole hunk is claimed.
+    pub lines: Vec<u32>,
 }
 
 impl From<&diff::GitHunk> for Hunk {
@@ -21,20 +25,21 @@ impl From<&diff::GitHunk> for Hunk {
         Hunk {
             start: hunk.new_start,
             end: hunk.new_start + hunk.new_lines,
-            hash: Some(Hunk::hash_diff(hunk.diff_lines.as_ref())),
+            hash: Some(Hunk::hash_diff(hunk.full_diff())),
             timestamp_ms: None,
             locked_to: hunk.locked_to.to_vec(),
+            lines: hunk
+                .selection
+                .as_ref()
+                .map(|selection| selection.lines.clone())
+                .unwrap_or_default(),
         }
     }
 }
 
 impl PartialEq for Hunk {
     fn eq(&self, other: &Self) -> bool {
-        if self.hash.is_some() && other.hash.is_some() {
-            self.hash == other.hash && self.start == other.start && self.end == other.end
-        } else {
-            self.start == other.start && self.end == other.end
-        }
+        self.same_hunk(other) && self.lines == other.lines
     }
 }
 
@@ -46,6 +51,7 @@ impl From<RangeInclusive<u32>> for Hunk {
             hash: None,
             timestamp_ms: None,
             locked_to: vec![],
+            lines: vec![],
         }
     }
 }
@@ -83,17 +89,27 @@ impl FromStr for Hunk {
             None
         };
 
-        let timestamp_ms = if let Some(raw_timestamp_ms) = range.next() {
-            Some(
+        let timestamp_ms = match range.next() {
+            Some(raw_timestamp_ms) if !raw_timestamp_ms.is_empty() => Some(
                 raw_timestamp_ms
                     .parse::<u128>()
                     .context(format!("failed to parse timestamp_ms of range: {}", s))?,
-            )
-        } else {
-            None
+            ),
+            _ => None,
+        };
+
+        let lines = match range.next() {
+            Some(raw_lines) => raw_lines
+                .split('.')
+                .map(|line| {
+                    line.parse::<u32>()
+                        .context(format!("failed to parse lines of range: {}", s))
+                })
+                .collect::<Result<Vec<_>>>()?,
+            None => vec![],
         };
 
-        Hunk::new(start, end, hash, timestamp_ms)
+        Ok(Hunk::new(start, end, hash, timestamp_ms)?.with_lines(lines))
     }
 }
 
@@ -101,11 +117,19 @@ impl Display for Hunk {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}-{}", self.start, self.end)?;
         match (self.hash.as_ref(), self.timestamp_ms.as_ref()) {
-            (Some(hash), Some(timestamp_ms)) => write!(f, "-{:x}-{}", hash, timestamp_ms),
-            (Some(hash), None) => write!(f, "-{:x}", hash),
-            (None, Some(timestamp_ms)) => write!(f, "--{}", timestamp_ms),
-            (None, None) => Ok(()),
+            (Some(hash), Some(timestamp_ms)) => write!(f, "-{:x}-{}", hash, timestamp_ms)?,
+            (Some(hash), None) => write!(f, "-{:x}", hash)?,
+            (None, Some(timestamp_ms)) => write!(f, "--{}", timestamp_ms)?,
+            (None, None) if self.lines.is_empty() => {}
+            (None, None) => write!(f, "-")?,
+        }
+        if !self.lines.is_empty() {
+            if self.timestamp_ms.is_none() {
+                write!(f, "-")?;
+            }
+            write!(f, "-{}", self.lines.iter().join("."))?;
         }
+        Ok(())
     }
 }
 
@@ -125,10 +149,28 @@ impl Hunk {
                 start,
                 end,
                 locked_to: vec![],
+                lines: vec![],
             })
         }
     }
 
+    /// Claim only the changed `lines` of the hunk, see [`Self::lines`].
+    pub fn with_lines(mut self, mut lines: Vec<u32>) -> Self {
+        lines.sort_unstable();
+        lines.dedup();
+        self.lines = lines;
+        self
+    }
+
+    /// Whether `other` claims (some of the lines of) the same hunk, regardless of which lines.
+    pub fn same_hunk(&self, other: &Hunk) -> bool {
+        if self.hash.is_some() && other.hash.is_some() {
+            self.hash == other.hash && self.start == other.start && self.end == other.end
+        } else {
+            self.start == other.start && self.end == other.end
+        }
+    }
+
     pub fn with_hash(mut self, hash: HunkHash) -> Self {
         self.hash = Some(hash);
         self
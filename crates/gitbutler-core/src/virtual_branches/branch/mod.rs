@@ -45,6 +45,14 @@ pub struct Branch {
     // is Some(timestamp), the branch is considered a default destination for new changes.
     // if more than one branch is selected, the branch with the highest timestamp wins.
     pub selected_for_changes: Option<i64>,
+    /// The branch this branch is stacked on, if any. Its commits build on top of the parent's
+    /// commits instead of directly on top of the default target.
+    #[serde(default)]
+    pub parent: Option<BranchId>,
+    /// The head of `parent` the commits of this branch were last rebased onto, which is where
+    /// the commits owned by this branch start.
+    #[serde(default)]
+    pub parent_head: Option<git::Oid>,
 }
 
 fn serialize_u128<S>(x: &u128, s: S) -> Result<S::Ok, S::Error>
@@ -67,6 +75,12 @@ impl Branch {
     pub fn refname(&self) -> git::VirtualRefname {
         self.into()
     }
+
+    /// The commit the commits owned by this branch are based on, which is the head of its parent
+    /// if it is stacked, or `target_sha` otherwise.
+    pub fn base(&self, target_sha: git::Oid) -> git::Oid {
+        self.parent_head.unwrap_or(target_sha)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -86,6 +100,8 @@ pub struct BranchCreateRequest {
     pub ownership: Option<BranchOwnershipClaims>,
     pub order: Option<usize>,
     pub selected_for_changes: Option<bool>,
+    /// The branch to stack the new branch on.
+    pub parent: Option<BranchId>,
 }
 
 impl Branch {
@@ -225,6 +241,8 @@ impl Branch {
             ownership,
             order,
             selected_for_changes,
+            parent: None,
+            parent_head: None,
         })
     }
 }
@@ -162,16 +162,18 @@ pub fn reconcile_claims(
         removed_claims: Vec::new(),
     });
 
-    // Check the outcomes consistency and error out if they would result in a hunk being claimed by multiple branches
+    // Check the outcomes consistency and error out if they would result in a hunk being claimed by multiple branches.
+    // Claims of some lines of a hunk may coexist with other claims of it, they are settled when computing the status.
     let mut seen = HashSet::new();
     for outcome in claim_outcomes.clone() {
         for claim in outcome.updated_branch.ownership.claims {
             for hunk in claim.hunks {
                 if !seen.insert(format!(
-                    "{}-{}-{}",
+                    "{}-{}-{}-{}",
                     claim.file_path.to_str().unwrap_or_default(),
                     hunk.start,
-                    hunk.end
+                    hunk.end,
+                    hunk.lines.iter().join(".")
                 )) {
                     return Err(anyhow::anyhow!("inconsistent ownership claims"));
                 }
@@ -8,12 +8,14 @@ use crate::{
 use std::{collections::HashMap, path::Path, sync::Arc};
 
 use anyhow::Context;
+use gitbutler_git::CancellationToken;
 use tokio::{sync::Semaphore, task::JoinHandle};
 
 use super::{
     branch::{BranchId, BranchOwnershipClaims},
     errors::{self, FetchFromTargetError},
-    target, target_to_base_branch, BaseBranch, RemoteBranchFile, VirtualBranchesHandle,
+    target, target_to_base_branch, AbsorbedHunk, BaseBranch, CommitPart, HistoryStep,
+    HunkLockExplanation, RemoteBranchFile, VirtualBranchesHandle,
 };
 use crate::{
     askpass::AskpassBroker,
@@ -219,11 +221,12 @@ impl Controller {
     pub async fn reset_files(
         &self,
         project_id: &ProjectId,
-        files: &Vec<String>,
+        branch_id: &BranchId,
+        files: &[String],
     ) -> Result<(), Error> {
         self.inner(project_id)
             .await
-            .reset_files(project_id, files)
+            .reset_files(project_id, branch_id, files)
             .await
     }
 
@@ -390,6 +393,91 @@ impl Controller {
             .await
     }
 
+    pub async fn merge_branches(
+        &self,
+        project_id: &ProjectId,
+        source_branch_id: &BranchId,
+        target_branch_id: &BranchId,
+    ) -> Result<(), Error> {
+        self.inner(project_id)
+            .await
+            .merge_branches(project_id, source_branch_id, target_branch_id)
+            .await
+    }
+
+    pub async fn split_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        name: Option<String>,
+        ownership: &BranchOwnershipClaims,
+        commit_oids: &[git::Oid],
+    ) -> Result<BranchId, Error> {
+        self.inner(project_id)
+            .await
+            .split_branch(project_id, branch_id, name, ownership, commit_oids)
+            .await
+    }
+
+    pub async fn explain_hunk_locks(
+        &self,
+        project_id: &ProjectId,
+        ownership: &BranchOwnershipClaims,
+    ) -> Result<Vec<HunkLockExplanation>, Error> {
+        self.inner(project_id)
+            .await
+            .explain_hunk_locks(project_id, ownership)
+            .await
+    }
+
+    pub async fn move_hunks_with_dependencies(
+        &self,
+        project_id: &ProjectId,
+        target_branch_id: &BranchId,
+        ownership: &BranchOwnershipClaims,
+    ) -> Result<(), Error> {
+        self.inner(project_id)
+            .await
+            .move_hunks_with_dependencies(project_id, target_branch_id, ownership)
+            .await
+    }
+
+    pub async fn absorb(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<Vec<AbsorbedHunk>, Error> {
+        self.inner(project_id)
+            .await
+            .absorb(project_id, branch_id)
+            .await
+    }
+
+    pub async fn split_commit(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        commit_oid: git::Oid,
+        parts: &[CommitPart],
+    ) -> Result<Vec<git::Oid>, Error> {
+        self.inner(project_id)
+            .await
+            .split_commit(project_id, branch_id, commit_oid, parts)
+            .await
+    }
+
+    pub async fn edit_history(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        plan: &[HistoryStep],
+    ) -> Result<git::Oid, Error> {
+        self.inner(project_id)
+            .await
+            .edit_history(project_id, branch_id, plan)
+            .await
+    }
+
     pub async fn fetch_from_target(
         &self,
         project_id: &ProjectId,
@@ -401,6 +489,11 @@ impl Controller {
             .await
     }
 
+    /// Abort the fetches and pushes of the project that are currently running.
+    pub async fn cancel_remote_operations(&self, project_id: &ProjectId) {
+        self.inner(project_id).await.cancel_remote_operations();
+    }
+
     pub async fn move_commit(
         &self,
         project_id: &ProjectId,
@@ -412,11 +505,70 @@ impl Controller {
             .move_commit(project_id, target_branch_id, commit_oid)
             .await
     }
+
+    pub async fn list_conflicts(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<project_repository::conflicts::Conflict>, Error> {
+        self.inner(project_id).await.list_conflicts(project_id)
+    }
+
+    pub async fn get_conflict(
+        &self,
+        project_id: &ProjectId,
+        path: &Path,
+    ) -> Result<super::ConflictVersions, Error> {
+        self.inner(project_id).await.get_conflict(project_id, path)
+    }
+
+    pub async fn resolve_conflict(
+        &self,
+        project_id: &ProjectId,
+        path: &Path,
+        content: &[u8],
+    ) -> Result<(), Error> {
+        self.inner(project_id)
+            .await
+            .resolve_conflict(project_id, path, content)
+            .await
+    }
+
+    pub async fn resolve_conflict_with_side(
+        &self,
+        project_id: &ProjectId,
+        path: &Path,
+        side: project_repository::conflicts::ConflictSide,
+    ) -> Result<(), Error> {
+        self.inner(project_id)
+            .await
+            .resolve_conflict_with_side(project_id, path, side)
+            .await
+    }
+
+    pub async fn list_routing_rules(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::RoutingRule>, Error> {
+        self.inner(project_id).await.list_routing_rules(project_id)
+    }
+
+    pub async fn set_routing_rules(
+        &self,
+        project_id: &ProjectId,
+        rules: Vec<super::RoutingRule>,
+    ) -> Result<(), Error> {
+        self.inner(project_id)
+            .await
+            .set_routing_rules(project_id, rules)
+            .await
+    }
 }
 
 #[derive(Clone)]
 struct ControllerInner {
     semaphore: Arc<Semaphore>,
+    /// Cancelled to abort the fetches and pushes that are running, and replaced for the next ones.
+    remote_cancellation: Arc<std::sync::Mutex<CancellationToken>>,
 
     projects: projects::Controller,
     users: users::Controller,
@@ -433,6 +585,7 @@ impl ControllerInner {
     ) -> Self {
         Self {
             semaphore: Arc::new(Semaphore::new(1)),
+            remote_cancellation: Default::default(),
             projects: projects.clone(),
             users: users.clone(),
             keys: keys.clone(),
@@ -451,23 +604,14 @@ impl ControllerInner {
         let _permit = self.semaphore.acquire().await;
 
         self.with_verify_branch(project_id, |project_repository, user| {
-            let signing_key = project_repository
-                .config()
-                .sign_commits()
-                .context("failed to get sign commits option")?
-                .then(|| {
-                    self.keys
-                        .get_or_create()
-                        .context("failed to get private key")
-                })
-                .transpose()?;
+            let signer = self.signer(project_repository)?;
 
             let result = super::commit(
                 project_repository,
                 branch_id,
                 message,
                 ownership,
-                signing_key.as_ref(),
+                signer.as_ref(),
                 user,
                 run_hooks,
             )
@@ -540,20 +684,11 @@ impl ControllerInner {
         let _permit = self.semaphore.acquire().await;
 
         self.with_verify_branch(project_id, |project_repository, user| {
-            let signing_key = project_repository
-                .config()
-                .sign_commits()
-                .context("failed to get sign commits option")?
-                .then(|| {
-                    self.keys
-                        .get_or_create()
-                        .context("failed to get private key")
-                })
-                .transpose()?;
+            let signer = self.signer(project_repository)?;
             let result = super::create_virtual_branch_from_branch(
                 project_repository,
                 branch,
-                signing_key.as_ref(),
+                signer.as_ref(),
                 user,
             )?;
             snapshot::create(
@@ -607,21 +742,12 @@ impl ControllerInner {
         let _permit = self.semaphore.acquire().await;
 
         self.with_verify_branch(project_id, |project_repository, user| {
-            let signing_key = project_repository
-                .config()
-                .sign_commits()
-                .context("failed to get sign commits option")?
-                .then(|| {
-                    self.keys
-                        .get_or_create()
-                        .context("failed to get private key")
-                })
-                .transpose()?;
+            let signer = self.signer(project_repository)?;
 
             let result = super::merge_virtual_branch_upstream(
                 project_repository,
                 branch_id,
-                signing_key.as_ref(),
+                signer.as_ref(),
                 user,
             )
             .map_err(Into::into);
@@ -637,18 +763,9 @@ impl ControllerInner {
         let _permit = self.semaphore.acquire().await;
 
         self.with_verify_branch(project_id, |project_repository, user| {
-            let signing_key = project_repository
-                .config()
-                .sign_commits()
-                .context("failed to get sign commits option")?
-                .then(|| {
-                    self.keys
-                        .get_or_create()
-                        .context("failed to get private key")
-                })
-                .transpose()?;
-
-            let result = super::update_base_branch(project_repository, user, signing_key.as_ref())
+            let signer = self.signer(project_repository)?;
+
+            let result = super::update_base_branch(project_repository, user, signer.as_ref())
                 .map_err(Into::into);
             snapshot::create(
                 project_repository.project(),
@@ -712,20 +829,10 @@ impl ControllerInner {
         let _permit = self.semaphore.acquire().await;
 
         self.with_verify_branch(project_id, |project_repository, user| {
-            let signing_key = project_repository
-                .config()
-                .sign_commits()
-                .context("failed to get sign commits option")?
-                .then(|| {
-                    self.keys
-                        .get_or_create()
-                        .context("failed to get private key")
-                })
-                .transpose()?;
+            let signer = self.signer(project_repository)?;
 
-            let result =
-                super::apply_branch(project_repository, branch_id, signing_key.as_ref(), user)
-                    .map_err(Into::into);
+            let result = super::apply_branch(project_repository, branch_id, signer.as_ref(), user)
+                .map_err(Into::into);
             snapshot::create(
                 project_repository.project(),
                 SnapshotDetails::new(OperationType::ApplyBranch),
@@ -755,12 +862,14 @@ impl ControllerInner {
     pub async fn reset_files(
         &self,
         project_id: &ProjectId,
-        ownership: &Vec<String>,
+        branch_id: &BranchId,
+        files: &[String],
     ) -> Result<(), Error> {
         let _permit = self.semaphore.acquire().await;
 
         self.with_verify_branch(project_id, |project_repository, _| {
-            let result = super::reset_files(project_repository, ownership).map_err(Into::into);
+            let result =
+                super::reset_files(project_repository, branch_id, files).map_err(Into::into);
             snapshot::create(
                 project_repository.project(),
                 SnapshotDetails::new(OperationType::DiscardFile),
@@ -779,8 +888,15 @@ impl ControllerInner {
         let _permit = self.semaphore.acquire().await;
 
         self.with_verify_branch(project_id, |project_repository, _| {
-            let result = super::amend(project_repository, branch_id, commit_oid, ownership)
-                .map_err(Into::into);
+            let signer = self.signer(project_repository)?;
+            let result = super::amend(
+                project_repository,
+                branch_id,
+                commit_oid,
+                ownership,
+                signer.as_ref(),
+            )
+            .map_err(Into::into);
             snapshot::create(
                 project_repository.project(),
                 SnapshotDetails::new(OperationType::AmendCommit),
@@ -800,12 +916,14 @@ impl ControllerInner {
         let _permit = self.semaphore.acquire().await;
 
         self.with_verify_branch(project_id, |project_repository, _| {
+            let signer = self.signer(project_repository)?;
             let result = super::move_commit_file(
                 project_repository,
                 branch_id,
                 from_commit_oid,
                 to_commit_oid,
                 ownership,
+                signer.as_ref(),
             )
             .map_err(Into::into);
             snapshot::create(
@@ -825,8 +943,10 @@ impl ControllerInner {
         let _permit = self.semaphore.acquire().await;
 
         self.with_verify_branch(project_id, |project_repository, _| {
+            let signer = self.signer(project_repository)?;
             let result =
-                super::undo_commit(project_repository, branch_id, commit_oid).map_err(Into::into);
+                super::undo_commit(project_repository, branch_id, commit_oid, signer.as_ref())
+                    .map_err(Into::into);
             snapshot::create(
                 project_repository.project(),
                 SnapshotDetails::new(OperationType::UndoCommit),
@@ -845,9 +965,16 @@ impl ControllerInner {
         let _permit = self.semaphore.acquire().await;
 
         self.with_verify_branch(project_id, |project_repository, user| {
-            let result =
-                super::insert_blank_commit(project_repository, branch_id, commit_oid, user, offset)
-                    .map_err(Into::into);
+            let signer = self.signer(project_repository)?;
+            let result = super::insert_blank_commit(
+                project_repository,
+                branch_id,
+                commit_oid,
+                user,
+                offset,
+                signer.as_ref(),
+            )
+            .map_err(Into::into);
             snapshot::create(
                 project_repository.project(),
                 SnapshotDetails::new(OperationType::InsertBlankCommit),
@@ -866,8 +993,15 @@ impl ControllerInner {
         let _permit = self.semaphore.acquire().await;
 
         self.with_verify_branch(project_id, |project_repository, _| {
-            let result = super::reorder_commit(project_repository, branch_id, commit_oid, offset)
-                .map_err(Into::into);
+            let signer = self.signer(project_repository)?;
+            let result = super::reorder_commit(
+                project_repository,
+                branch_id,
+                commit_oid,
+                offset,
+                signer.as_ref(),
+            )
+            .map_err(Into::into);
             snapshot::create(
                 project_repository.project(),
                 SnapshotDetails::new(OperationType::ReorderCommit),
@@ -923,6 +1057,8 @@ impl ControllerInner {
     ) -> Result<(), Error> {
         let _permit = self.semaphore.acquire().await;
         let helper = self.helper.clone();
+        let progress = self.projects.progress_sink(*project_id);
+        let cancellation = self.remote_cancellation();
         let project_id = *project_id;
         let branch_id = *branch_id;
         self.with_verify_branch_async(&project_id, move |project_repository, _| {
@@ -932,6 +1068,8 @@ impl ControllerInner {
                 with_force,
                 &helper,
                 askpass,
+                progress,
+                Some(cancellation),
             )?)
         })?
         .await
@@ -947,8 +1085,10 @@ impl ControllerInner {
         let _permit = self.semaphore.acquire().await;
 
         self.with_verify_branch(project_id, |project_repository, _| {
+            let signer = self.signer(project_repository)?;
             let result =
-                super::cherry_pick(project_repository, branch_id, commit_oid).map_err(Into::into);
+                super::cherry_pick(project_repository, branch_id, commit_oid, signer.as_ref())
+                    .map_err(Into::into);
             snapshot::create(
                 project_repository.project(),
                 SnapshotDetails::new(OperationType::CherryPick),
@@ -985,8 +1125,9 @@ impl ControllerInner {
         let _permit = self.semaphore.acquire().await;
 
         self.with_verify_branch(project_id, |project_repository, _| {
-            let result =
-                super::squash(project_repository, branch_id, commit_oid).map_err(Into::into);
+            let signer = self.signer(project_repository)?;
+            let result = super::squash(project_repository, branch_id, commit_oid, signer.as_ref())
+                .map_err(Into::into);
             snapshot::create(
                 project_repository.project(),
                 SnapshotDetails::new(OperationType::SquashCommit),
@@ -1004,9 +1145,15 @@ impl ControllerInner {
     ) -> Result<(), Error> {
         let _permit = self.semaphore.acquire().await;
         self.with_verify_branch(project_id, |project_repository, _| {
-            let result =
-                super::update_commit_message(project_repository, branch_id, commit_oid, message)
-                    .map_err(Into::into);
+            let signer = self.signer(project_repository)?;
+            let result = super::update_commit_message(
+                project_repository,
+                branch_id,
+                commit_oid,
+                message,
+                signer.as_ref(),
+            )
+            .map_err(Into::into);
             snapshot::create(
                 project_repository.project(),
                 SnapshotDetails::new(OperationType::UpdateCommitMessage),
@@ -1015,13 +1162,171 @@ impl ControllerInner {
         })
     }
 
+    pub async fn merge_branches(
+        &self,
+        project_id: &ProjectId,
+        source_branch_id: &BranchId,
+        target_branch_id: &BranchId,
+    ) -> Result<(), Error> {
+        let _permit = self.semaphore.acquire().await;
+        self.with_verify_branch(project_id, |project_repository, _| {
+            let signer = self.signer(project_repository)?;
+            let result = super::merge_branches(
+                project_repository,
+                source_branch_id,
+                target_branch_id,
+                signer.as_ref(),
+            )
+            .map_err(Into::into);
+            snapshot::create(
+                project_repository.project(),
+                SnapshotDetails::new(OperationType::MergeBranches),
+            )?;
+            result
+        })
+    }
+
+    pub async fn split_branch(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        name: Option<String>,
+        ownership: &BranchOwnershipClaims,
+        commit_oids: &[git::Oid],
+    ) -> Result<BranchId, Error> {
+        let _permit = self.semaphore.acquire().await;
+        self.with_verify_branch(project_id, |project_repository, _| {
+            let signer = self.signer(project_repository)?;
+            let result = super::split_branch(
+                project_repository,
+                branch_id,
+                name,
+                ownership,
+                commit_oids,
+                signer.as_ref(),
+            )
+            .map_err(Into::into);
+            snapshot::create(
+                project_repository.project(),
+                SnapshotDetails::new(OperationType::SplitBranch),
+            )?;
+            result
+        })
+    }
+
+    pub async fn explain_hunk_locks(
+        &self,
+        project_id: &ProjectId,
+        ownership: &BranchOwnershipClaims,
+    ) -> Result<Vec<HunkLockExplanation>, Error> {
+        let _permit = self.semaphore.acquire().await;
+        self.with_verify_branch(project_id, |project_repository, _| {
+            super::explain_hunk_locks(project_repository, ownership).map_err(Into::into)
+        })
+    }
+
+    pub async fn move_hunks_with_dependencies(
+        &self,
+        project_id: &ProjectId,
+        target_branch_id: &BranchId,
+        ownership: &BranchOwnershipClaims,
+    ) -> Result<(), Error> {
+        let _permit = self.semaphore.acquire().await;
+        self.with_verify_branch(project_id, |project_repository, _| {
+            let signer = self.signer(project_repository)?;
+            let result = super::move_hunks_with_dependencies(
+                project_repository,
+                target_branch_id,
+                ownership,
+                signer.as_ref(),
+            )
+            .map_err(Into::into);
+            snapshot::create(
+                project_repository.project(),
+                SnapshotDetails::new(OperationType::MoveHunksWithDependencies),
+            )?;
+            result
+        })
+    }
+
+    pub async fn absorb(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+    ) -> Result<Vec<AbsorbedHunk>, Error> {
+        let _permit = self.semaphore.acquire().await;
+        self.with_verify_branch(project_id, |project_repository, _| {
+            let signer = self.signer(project_repository)?;
+            let result =
+                super::absorb(project_repository, branch_id, signer.as_ref()).map_err(Into::into);
+            snapshot::create(
+                project_repository.project(),
+                SnapshotDetails::new(OperationType::Absorb),
+            )?;
+            result
+        })
+    }
+
+    pub async fn split_commit(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        commit_oid: git::Oid,
+        parts: &[CommitPart],
+    ) -> Result<Vec<git::Oid>, Error> {
+        let _permit = self.semaphore.acquire().await;
+        self.with_verify_branch(project_id, |project_repository, _| {
+            let signer = self.signer(project_repository)?;
+            let result = super::split_commit(
+                project_repository,
+                branch_id,
+                commit_oid,
+                parts,
+                signer.as_ref(),
+            )
+            .map_err(Into::into);
+            snapshot::create(
+                project_repository.project(),
+                SnapshotDetails::new(OperationType::SplitCommit),
+            )?;
+            result
+        })
+    }
+
+    pub async fn edit_history(
+        &self,
+        project_id: &ProjectId,
+        branch_id: &BranchId,
+        plan: &[HistoryStep],
+    ) -> Result<git::Oid, Error> {
+        let _permit = self.semaphore.acquire().await;
+        self.with_verify_branch(project_id, |project_repository, _| {
+            let signer = self.signer(project_repository)?;
+            let result = super::edit_history(project_repository, branch_id, plan, signer.as_ref())
+                .map_err(Into::into);
+            snapshot::create(
+                project_repository.project(),
+                SnapshotDetails::new(OperationType::EditHistory),
+            )?;
+            result
+        })
+    }
+
+    pub fn cancel_remote_operations(&self) {
+        std::mem::take(&mut *self.remote_cancellation.lock().unwrap()).cancel();
+    }
+
+    fn remote_cancellation(&self) -> CancellationToken {
+        self.remote_cancellation.lock().unwrap().clone()
+    }
+
     pub async fn fetch_from_target(
         &self,
         project_id: &ProjectId,
         askpass: Option<(AskpassBroker, String)>,
     ) -> Result<BaseBranch, Error> {
         let project = self.projects.get(project_id)?;
-        let mut project_repository = project_repository::Repository::open(&project)?;
+        let project_repository = project_repository::Repository::open(&project)?;
 
         let default_target = default_target(&project_repository.project().gb_dir())
             .context("failed to get default target")?
@@ -1031,18 +1336,29 @@ impl ControllerInner {
                 },
             ))?;
 
-        let project_data_last_fetched = match project_repository
-            .fetch(default_target.branch.remote(), &self.helper, askpass)
-            .map_err(errors::FetchFromTargetError::Remote)
-        {
-            Ok(()) => projects::FetchResult::Fetched {
-                timestamp: std::time::SystemTime::now(),
-            },
-            Err(error) => projects::FetchResult::Error {
-                timestamp: std::time::SystemTime::now(),
-                error: error.to_string(),
-            },
-        };
+        // fetching blocks, also while waiting for the user to answer prompts
+        let remote = default_target.branch.remote().to_string();
+        let helper = self.helper.clone();
+        let progress = self.projects.progress_sink(*project_id);
+        let cancellation = self.remote_cancellation();
+        let (fetch_result, mut project_repository) = tokio::task::spawn_blocking(move || {
+            let result =
+                project_repository.fetch(&remote, &helper, askpass, progress, Some(cancellation));
+            (result, project_repository)
+        })
+        .await
+        .context("failed to fetch")?;
+
+        let project_data_last_fetched =
+            match fetch_result.map_err(errors::FetchFromTargetError::Remote) {
+                Ok(()) => projects::FetchResult::Fetched {
+                    timestamp: std::time::SystemTime::now(),
+                },
+                Err(error) => projects::FetchResult::Error {
+                    timestamp: std::time::SystemTime::now(),
+                    error: error.to_string(),
+                },
+            };
 
         let updated_project = self
             .projects
@@ -1071,22 +1387,13 @@ impl ControllerInner {
         let _permit = self.semaphore.acquire().await;
 
         self.with_verify_branch(project_id, |project_repository, user| {
-            let signing_key = project_repository
-                .config()
-                .sign_commits()
-                .context("failed to get sign commits option")?
-                .then(|| {
-                    self.keys
-                        .get_or_create()
-                        .context("failed to get private key")
-                })
-                .transpose()?;
+            let signer = self.signer(project_repository)?;
             let result = super::move_commit(
                 project_repository,
                 target_branch_id,
                 commit_oid,
                 user,
-                signing_key.as_ref(),
+                signer.as_ref(),
             )
             .map_err(Into::into);
             snapshot::create(
@@ -1096,9 +1403,117 @@ impl ControllerInner {
             result
         })
     }
+
+    pub fn list_conflicts(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<project_repository::conflicts::Conflict>, Error> {
+        let project = self.projects.get(project_id)?;
+        let project_repository = project_repository::Repository::open(&project)?;
+        Ok(super::list_conflicts(&project_repository)?)
+    }
+
+    pub fn get_conflict(
+        &self,
+        project_id: &ProjectId,
+        path: &Path,
+    ) -> Result<super::ConflictVersions, Error> {
+        let project = self.projects.get(project_id)?;
+        let project_repository = project_repository::Repository::open(&project)?;
+        Ok(super::get_conflict(&project_repository, path)?)
+    }
+
+    pub async fn resolve_conflict(
+        &self,
+        project_id: &ProjectId,
+        path: &Path,
+        content: &[u8],
+    ) -> Result<(), Error> {
+        let _permit = self.semaphore.acquire().await;
+
+        self.with_verify_branch(project_id, |project_repository, _| {
+            super::resolve_conflict(project_repository, path, content)?;
+            snapshot::create(
+                project_repository.project(),
+                SnapshotDetails::new(OperationType::ResolveConflict),
+            )?;
+            Ok(())
+        })
+    }
+
+    pub async fn resolve_conflict_with_side(
+        &self,
+        project_id: &ProjectId,
+        path: &Path,
+        side: project_repository::conflicts::ConflictSide,
+    ) -> Result<(), Error> {
+        let _permit = self.semaphore.acquire().await;
+
+        self.with_verify_branch(project_id, |project_repository, _| {
+            super::resolve_conflict_with_side(project_repository, path, side)?;
+            snapshot::create(
+                project_repository.project(),
+                SnapshotDetails::new(OperationType::ResolveConflict),
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn list_routing_rules(
+        &self,
+        project_id: &ProjectId,
+    ) -> Result<Vec<super::RoutingRule>, Error> {
+        let project = self.projects.get(project_id)?;
+        let project_repository = project_repository::Repository::open(&project)?;
+        Ok(super::list_routing_rules(&project_repository)?)
+    }
+
+    pub async fn set_routing_rules(
+        &self,
+        project_id: &ProjectId,
+        rules: Vec<super::RoutingRule>,
+    ) -> Result<(), Error> {
+        let _permit = self.semaphore.acquire().await;
+
+        let project = self.projects.get(project_id)?;
+        let project_repository = project_repository::Repository::open(&project)?;
+        super::set_routing_rules(&project_repository, rules)?;
+        snapshot::create(
+            project_repository.project(),
+            SnapshotDetails::new(OperationType::SetRoutingRules),
+        )?;
+        Ok(())
+    }
 }
 
 impl ControllerInner {
+    /// The signer for new commits, or `None` if commits shouldn't be signed.
+    /// GitButler's own key is only used when signing is enabled with `gitbutler.signCommits`
+    /// and the user has no signing key of their own.
+    fn signer(
+        &self,
+        project_repository: &project_repository::Repository,
+    ) -> anyhow::Result<Option<git::Signer>> {
+        let config = project_repository.config();
+        if !config
+            .sign_commits()
+            .context("failed to get sign commits option")?
+        {
+            return Ok(None);
+        }
+        if let Some(program) = config
+            .signing_program()
+            .context("failed to read signing configuration")?
+        {
+            return Ok(Some(git::Signer::Program(program)));
+        }
+        let key = self
+            .keys
+            .get_or_create()
+            .context("failed to get private key")?;
+        Ok(Some(git::Signer::Key(Box::new(key))))
+    }
+
     fn with_verify_branch<T>(
         &self,
         project_id: &ProjectId,
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use super::{branch::BranchOwnershipClaims, BranchId, GITBUTLER_INTEGRATION_REFERENCE};
 use crate::error::{AnyhowContextExt, Code, Context, ErrorWithContext};
 use crate::{
@@ -183,6 +185,8 @@ pub enum UnapplyBranchError {
     DefaultTargetNotSet(DefaultTargetNotSet),
     #[error("branch not found")]
     BranchNotFound(BranchNotFound),
+    #[error("branches stacked on this one are applied")]
+    Stacked,
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -192,6 +196,10 @@ impl ErrorWithContext for UnapplyBranchError {
         Some(match self {
             UnapplyBranchError::DefaultTargetNotSet(ctx) => ctx.to_context(),
             UnapplyBranchError::BranchNotFound(ctx) => ctx.to_context(),
+            UnapplyBranchError::Stacked => error::Context::new_static(
+                Code::Branches,
+                "Unapply the branches stacked on this one first",
+            ),
             UnapplyBranchError::Other(error) => return error.custom_context_or_root_cause().into(),
         })
     }
@@ -218,16 +226,27 @@ impl ErrorWithContext for ListVirtualBranchesError {
 pub enum CreateVirtualBranchError {
     #[error("project")]
     DefaultTargetNotSet(DefaultTargetNotSet),
+    #[error("parent branch not found")]
+    ParentNotFound(BranchNotFound),
+    #[error("parent branch {0} is not applied")]
+    ParentNotApplied(BranchId),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
 impl ErrorWithContext for CreateVirtualBranchError {
     fn context(&self) -> Option<Context> {
-        match self {
-            CreateVirtualBranchError::DefaultTargetNotSet(ctx) => ctx.to_context().into(),
-            CreateVirtualBranchError::Other(error) => error.custom_context_or_root_cause().into(),
-        }
+        Some(match self {
+            CreateVirtualBranchError::DefaultTargetNotSet(ctx) => ctx.to_context(),
+            CreateVirtualBranchError::ParentNotFound(ctx) => ctx.to_context(),
+            CreateVirtualBranchError::ParentNotApplied(id) => error::Context::new(
+                Code::Branches,
+                format!("Branch {} must be applied to stack a branch on it", id),
+            ),
+            CreateVirtualBranchError::Other(error) => {
+                return error.custom_context_or_root_cause().into()
+            }
+        })
     }
 }
 
@@ -292,6 +311,10 @@ pub enum PushError {
     DefaultTargetNotSet(DefaultTargetNotSet),
     #[error("branch not found")]
     BranchNotFound(BranchNotFound),
+    #[error("{0} changed on the remote since it was last pushed")]
+    LeaseViolated(git::RemoteRefname),
+    #[error("pre-push hook rejected")]
+    PrePushHookRejected(String),
     #[error(transparent)]
     Remote(#[from] project_repository::RemoteError),
     #[error(transparent)]
@@ -303,6 +326,16 @@ impl ErrorWithContext for PushError {
         Some(match self {
             PushError::DefaultTargetNotSet(ctx) => ctx.to_context(),
             PushError::BranchNotFound(ctx) => ctx.to_context(),
+            PushError::LeaseViolated(remote_branch) => error::Context::new(
+                Code::ProjectGitPush,
+                format!(
+                    "{remote_branch} was changed on the remote since it was last pushed, \
+                    force pushing would discard those changes"
+                ),
+            ),
+            PushError::PrePushHookRejected(output) => {
+                error::Context::new(Code::PrePushHook, output)
+            }
             PushError::Remote(error) => return error.context(),
             PushError::Other(error) => return error.custom_context_or_root_cause().into(),
         })
@@ -572,6 +605,262 @@ impl ErrorWithContext for MoveCommitError {
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum MergeBranchesError {
+    #[error("a branch can't be merged into itself")]
+    SameBranch,
+    #[error("branches stacked on each other can't be merged")]
+    Stacked,
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSet),
+    #[error("rebase failed")]
+    RebaseFailed,
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFound),
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflict),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl ErrorWithContext for MergeBranchesError {
+    fn context(&self) -> Option<Context> {
+        Some(match self {
+            MergeBranchesError::SameBranch => {
+                error::Context::new_static(Code::Branches, "A branch can't be merged into itself")
+            }
+            MergeBranchesError::Stacked => error::Context::new_static(
+                Code::Branches,
+                "Branches that are stacked on each other can't be merged",
+            ),
+            MergeBranchesError::DefaultTargetNotSet(ctx) => ctx.to_context(),
+            MergeBranchesError::RebaseFailed => error::Context::new_static(
+                Code::Branches,
+                "The commits of the branch conflict with the branch it is merged into",
+            ),
+            MergeBranchesError::BranchNotFound(ctx) => ctx.to_context(),
+            MergeBranchesError::Conflict(ctx) => ctx.to_context(),
+            MergeBranchesError::Other(error) => return error.custom_context_or_root_cause().into(),
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SplitBranchError {
+    #[error("nothing to split off")]
+    NothingToSplit,
+    #[error("force push not allowed")]
+    ForcePushNotAllowed(ForcePushNotAllowed),
+    #[error("commit {0} not in the branch")]
+    CommitNotFound(git::Oid),
+    #[error("target ownership not found")]
+    TargetOwnershipNotFound(BranchOwnershipClaims),
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSet),
+    #[error("rebase failed")]
+    RebaseFailed,
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFound),
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflict),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl ErrorWithContext for SplitBranchError {
+    fn context(&self) -> Option<Context> {
+        Some(match self {
+            SplitBranchError::NothingToSplit => error::Context::new_static(
+                Code::Branches,
+                "Choose the changes or commits to move to the new branch",
+            ),
+            SplitBranchError::ForcePushNotAllowed(ctx) => ctx.to_context(),
+            SplitBranchError::CommitNotFound(oid) => {
+                error::Context::new(Code::Branches, format!("Commit {} not found", oid))
+            }
+            SplitBranchError::TargetOwnershipNotFound(ownership) => error::Context::new(
+                Code::Branches,
+                format!("The branch has no changes for {}", ownership),
+            ),
+            SplitBranchError::DefaultTargetNotSet(ctx) => ctx.to_context(),
+            SplitBranchError::RebaseFailed => error::Context::new_static(
+                Code::Branches,
+                "The commits can't be split off without conflicts",
+            ),
+            SplitBranchError::BranchNotFound(ctx) => ctx.to_context(),
+            SplitBranchError::Conflict(ctx) => ctx.to_context(),
+            SplitBranchError::Other(error) => return error.custom_context_or_root_cause().into(),
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AbsorbError {
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSet),
+    #[error("rebase failed")]
+    RebaseFailed,
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFound),
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflict),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl ErrorWithContext for AbsorbError {
+    fn context(&self) -> Option<Context> {
+        Some(match self {
+            AbsorbError::DefaultTargetNotSet(ctx) => ctx.to_context(),
+            AbsorbError::RebaseFailed => error::Context::new_static(
+                Code::Branches,
+                "The changes could not be absorbed without conflicts, no changes were made",
+            ),
+            AbsorbError::BranchNotFound(ctx) => ctx.to_context(),
+            AbsorbError::Conflict(ctx) => ctx.to_context(),
+            AbsorbError::Other(error) => return error.custom_context_or_root_cause().into(),
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SplitCommitError {
+    #[error("force push not allowed")]
+    ForcePushNotAllowed(ForcePushNotAllowed),
+    #[error("empty message")]
+    EmptyMessage,
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSet),
+    #[error("commit {0} not in the branch")]
+    CommitNotFound(git::Oid),
+    #[error("a commit can only be split into two or more parts")]
+    TooFewParts,
+    #[error("target ownership not found")]
+    TargetOwnershipNotFound(BranchOwnershipClaims),
+    #[error("hunk of {} is in more than one part", .0.display())]
+    HunkClaimedTwice(PathBuf),
+    #[error("hunk of {} is in no part", .0.display())]
+    HunkNotClaimed(PathBuf),
+    #[error("rebase failed")]
+    RebaseFailed,
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFound),
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflict),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl ErrorWithContext for SplitCommitError {
+    fn context(&self) -> Option<Context> {
+        Some(match self {
+            SplitCommitError::ForcePushNotAllowed(ctx) => ctx.to_context(),
+            SplitCommitError::EmptyMessage => {
+                error::Context::new_static(Code::Branches, "Commit message can not be empty")
+            }
+            SplitCommitError::DefaultTargetNotSet(ctx) => ctx.to_context(),
+            SplitCommitError::CommitNotFound(oid) => {
+                error::Context::new(Code::Branches, format!("Commit {} not found", oid))
+            }
+            SplitCommitError::TooFewParts => error::Context::new_static(
+                Code::Branches,
+                "A commit can only be split into two or more parts",
+            ),
+            SplitCommitError::TargetOwnershipNotFound(ownership) => error::Context::new(
+                Code::Branches,
+                format!("The commit has no changes for {}", ownership),
+            ),
+            SplitCommitError::HunkClaimedTwice(path) => error::Context::new(
+                Code::Branches,
+                format!("A hunk of {} is in more than one part", path.display()),
+            ),
+            SplitCommitError::HunkNotClaimed(path) => error::Context::new(
+                Code::Branches,
+                format!("A hunk of {} is in none of the parts", path.display()),
+            ),
+            SplitCommitError::RebaseFailed => error::Context::new_static(
+                Code::Branches,
+                "The commits above the split commit could not be rebased",
+            ),
+            SplitCommitError::BranchNotFound(ctx) => ctx.to_context(),
+            SplitCommitError::Conflict(ctx) => ctx.to_context(),
+            SplitCommitError::Other(error) => return error.custom_context_or_root_cause().into(),
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EditHistoryError {
+    #[error("force push not allowed")]
+    ForcePushNotAllowed(ForcePushNotAllowed),
+    #[error("empty message")]
+    EmptyMessage,
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSet),
+    #[error("commit {0} not in the branch")]
+    CommitNotFound(git::Oid),
+    #[error("commit {0} is missing from the plan")]
+    MissingCommit(git::Oid),
+    #[error("commit {0} is in the plan more than once")]
+    DuplicateCommit(git::Oid),
+    #[error("no commit to squash {0} into")]
+    NothingToSquashInto(git::Oid),
+    #[error("target ownership not found")]
+    TargetOwnershipNotFound(BranchOwnershipClaims),
+    #[error("commit {0} conflicts with the commits before it")]
+    StepConflict(git::Oid),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFound),
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflict),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl ErrorWithContext for EditHistoryError {
+    fn context(&self) -> Option<Context> {
+        Some(match self {
+            EditHistoryError::ForcePushNotAllowed(ctx) => ctx.to_context(),
+            EditHistoryError::EmptyMessage => {
+                error::Context::new_static(Code::Branches, "Commit message can not be empty")
+            }
+            EditHistoryError::DefaultTargetNotSet(ctx) => ctx.to_context(),
+            EditHistoryError::CommitNotFound(oid) => {
+                error::Context::new(Code::Branches, format!("Commit {} not found", oid))
+            }
+            EditHistoryError::MissingCommit(oid) => error::Context::new(
+                Code::Branches,
+                format!(
+                    "Commit {} is missing from the plan, drop it explicitly",
+                    oid
+                ),
+            ),
+            EditHistoryError::DuplicateCommit(oid) => error::Context::new(
+                Code::Branches,
+                format!("Commit {} can only be in the plan once", oid),
+            ),
+            EditHistoryError::NothingToSquashInto(oid) => error::Context::new(
+                Code::Branches,
+                format!("There is no commit before {} to squash it into", oid),
+            ),
+            EditHistoryError::TargetOwnershipNotFound(ownership) => error::Context::new(
+                Code::Branches,
+                format!("There are no uncommitted changes for {}", ownership),
+            ),
+            EditHistoryError::StepConflict(oid) => error::Context::new(
+                Code::Branches,
+                format!(
+                    "Commit {} conflicts with the commits before it, no changes were made",
+                    oid
+                ),
+            ),
+            EditHistoryError::BranchNotFound(ctx) => ctx.to_context(),
+            EditHistoryError::Conflict(ctx) => ctx.to_context(),
+            EditHistoryError::Other(error) => return error.custom_context_or_root_cause().into(),
+        })
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum CreateVirtualBranchFromBranchError {
     #[error("failed to apply")]
@@ -725,3 +1014,137 @@ impl ErrorWithContext for GetRemoteBranchDataError {
         }
     }
 }
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConflictError {
+    #[error("{0} is not conflicting")]
+    NotConflicting(PathBuf),
+    #[error("the conflicting versions of {0} are unknown")]
+    VersionsUnknown(PathBuf),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl ErrorWithContext for ConflictError {
+    fn context(&self) -> Option<Context> {
+        Some(match self {
+            ConflictError::NotConflicting(path) => error::Context::new(
+                Code::ProjectConflict,
+                format!("{} is not conflicting", path.display()),
+            ),
+            ConflictError::VersionsUnknown(path) => error::Context::new(
+                Code::ProjectConflict,
+                format!(
+                    "The conflicting versions of {} are unknown, please resolve it in the file",
+                    path.display()
+                ),
+            ),
+            ConflictError::Other(error) => return error.custom_context_or_root_cause().into(),
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RoutingRuleError {
+    #[error("invalid pattern {0}")]
+    InvalidPattern(String),
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFound),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl ErrorWithContext for RoutingRuleError {
+    fn context(&self) -> Option<Context> {
+        Some(match self {
+            RoutingRuleError::InvalidPattern(pattern) => error::Context::new(
+                Code::Validation,
+                format!("\"{pattern}\" is not a valid pattern for a path in the project"),
+            ),
+            RoutingRuleError::BranchNotFound(ctx) => ctx.to_context(),
+            RoutingRuleError::Other(error) => return error.custom_context_or_root_cause().into(),
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExplainHunkLocksError {
+    #[error("target ownership not found")]
+    TargetOwnershipNotFound(BranchOwnershipClaims),
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSet),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl ErrorWithContext for ExplainHunkLocksError {
+    fn context(&self) -> Option<Context> {
+        Some(match self {
+            ExplainHunkLocksError::TargetOwnershipNotFound(ownership) => error::Context::new(
+                Code::Branches,
+                format!("There are no changes for {}", ownership),
+            ),
+            ExplainHunkLocksError::DefaultTargetNotSet(ctx) => ctx.to_context(),
+            ExplainHunkLocksError::Other(error) => {
+                return error.custom_context_or_root_cause().into()
+            }
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MoveHunksWithDependenciesError {
+    #[error("target ownership not found")]
+    TargetOwnershipNotFound(BranchOwnershipClaims),
+    #[error("the changes depend on pushed commit {0}")]
+    DependsOnPushedCommit(git::Oid),
+    #[error("commit {0} depends on the commits to move")]
+    DependentCommit(git::Oid),
+    #[error("default target not set")]
+    DefaultTargetNotSet(DefaultTargetNotSet),
+    #[error("rebase failed")]
+    RebaseFailed,
+    #[error("branch not found")]
+    BranchNotFound(BranchNotFound),
+    #[error("project is in conflict state")]
+    Conflict(ProjectConflict),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl ErrorWithContext for MoveHunksWithDependenciesError {
+    fn context(&self) -> Option<Context> {
+        Some(match self {
+            MoveHunksWithDependenciesError::TargetOwnershipNotFound(ownership) => {
+                error::Context::new(
+                    Code::Branches,
+                    format!("There are no changes for {}", ownership),
+                )
+            }
+            MoveHunksWithDependenciesError::DependsOnPushedCommit(oid) => error::Context::new(
+                Code::Branches,
+                format!(
+                    "The changes depend on commit {}, which was pushed already and can't be moved",
+                    oid
+                ),
+            ),
+            MoveHunksWithDependenciesError::DependentCommit(oid) => error::Context::new(
+                Code::Branches,
+                format!(
+                    "Commit {} changes lines of the commits the changes depend on, so they can't be moved without it",
+                    oid
+                ),
+            ),
+            MoveHunksWithDependenciesError::DefaultTargetNotSet(ctx) => ctx.to_context(),
+            MoveHunksWithDependenciesError::RebaseFailed => error::Context::new_static(
+                Code::Branches,
+                "The commits the changes depend on can't be moved without conflicts",
+            ),
+            MoveHunksWithDependenciesError::BranchNotFound(ctx) => ctx.to_context(),
+            MoveHunksWithDependenciesError::Conflict(ctx) => ctx.to_context(),
+            MoveHunksWithDependenciesError::Other(error) => {
+                return error.custom_context_or_root_cause().into()
+            }
+        })
+    }
+}
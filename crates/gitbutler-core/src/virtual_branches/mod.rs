@@ -22,5 +22,8 @@ pub use r#virtual::*;
 mod remote;
 pub use remote::*;
 
-mod state;
+mod routing;
+pub use routing::*;
+
+pub(crate) mod state;
 pub use state::VirtualBranchesHandle;
@@ -0,0 +1,87 @@
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use super::{errors, BranchId, VirtualBranchesHandle};
+use crate::project_repository;
+
+/// A rule that sends new hunks of files matching `pattern` to the branch `branch_id`,
+/// instead of the branch that is selected for changes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoutingRule {
+    /// A pathspec relative to the root of the repository, like `docs/` or `*.lock`,
+    /// with the same meaning as it has to `git add`.
+    pub pattern: String,
+    pub branch_id: BranchId,
+}
+
+impl RoutingRule {
+    pub fn matches(&self, path: &Path) -> bool {
+        git2::Pathspec::new([self.pattern.as_str()])
+            .map(|pathspec| pathspec.matches_path(path, git2::PathspecFlags::DEFAULT))
+            .unwrap_or(false)
+    }
+}
+
+/// Returns the branch that new hunks of `path` go to according to the first matching rule,
+/// considering only the rules that route to one of `branch_ids`.
+pub(crate) fn route(
+    rules: &[RoutingRule],
+    branch_ids: &[BranchId],
+    path: &Path,
+) -> Option<BranchId> {
+    rules
+        .iter()
+        .filter(|rule| branch_ids.contains(&rule.branch_id))
+        .find(|rule| rule.matches(path))
+        .map(|rule| rule.branch_id)
+}
+
+pub fn list_routing_rules(
+    project_repository: &project_repository::Repository,
+) -> Result<Vec<RoutingRule>, errors::RoutingRuleError> {
+    let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
+    Ok(vb_state
+        .list_routing_rules()
+        .context("failed to read routing rules")?)
+}
+
+/// Replaces the routing rules of the project with `rules`, which are evaluated in order.
+pub fn set_routing_rules(
+    project_repository: &project_repository::Repository,
+    rules: Vec<RoutingRule>,
+) -> Result<(), errors::RoutingRuleError> {
+    let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
+    for rule in &rules {
+        let path = Path::new(&rule.pattern);
+        if rule.pattern.trim().is_empty()
+            || path.is_absolute()
+            || path
+                .components()
+                .any(|component| component == std::path::Component::ParentDir)
+            || git2::Pathspec::new([rule.pattern.as_str()]).is_err()
+        {
+            return Err(errors::RoutingRuleError::InvalidPattern(
+                rule.pattern.clone(),
+            ));
+        }
+        match vb_state.get_branch(&rule.branch_id) {
+            Ok(_) => {}
+            Err(crate::reader::Error::NotFound) => {
+                return Err(errors::RoutingRuleError::BranchNotFound(
+                    errors::BranchNotFound {
+                        project_id: project_repository.project().id,
+                        branch_id: rule.branch_id,
+                    },
+                ))
+            }
+            Err(error) => return Err(errors::RoutingRuleError::Other(error.into())),
+        }
+    }
+    vb_state
+        .set_routing_rules(rules)
+        .context("failed to write routing rules")?;
+    Ok(())
+}
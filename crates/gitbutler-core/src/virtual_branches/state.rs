@@ -7,7 +7,7 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 
-use super::{target::Target, Branch};
+use super::{target::Target, Branch, RoutingRule};
 use crate::virtual_branches::BranchId;
 
 /// The state of virtual branches data, as persisted in a TOML file.
@@ -19,6 +19,9 @@ pub struct VirtualBranches {
     pub branch_targets: HashMap<BranchId, Target>,
     /// The current state of the virtual branches
     pub branches: HashMap<BranchId, Branch>,
+    /// The rules deciding which branch new hunks go to, in the order they are evaluated
+    #[serde(default)]
+    pub routing_rules: Vec<RoutingRule>,
 }
 /// A handle to the state of virtual branches.
 ///
@@ -87,12 +90,15 @@ impl VirtualBranchesHandle {
         Ok(())
     }
 
-    /// Removes the given virtual branch.
+    /// Removes the given virtual branch, along with the routing rules for it.
     ///
     /// Errors if the file cannot be read or written.
     pub fn remove_branch(&self, id: BranchId) -> anyhow::Result<()> {
         let mut virtual_branches = self.read_file()?;
         virtual_branches.branches.remove(&id);
+        virtual_branches
+            .routing_rules
+            .retain(|rule| rule.branch_id != id);
         self.write_file(&virtual_branches)?;
         Ok(())
     }
@@ -118,6 +124,23 @@ impl VirtualBranchesHandle {
         Ok(branches)
     }
 
+    /// Persists the routing rules, replacing the existing ones.
+    ///
+    /// Errors if the file cannot be read or written.
+    pub fn set_routing_rules(&self, rules: Vec<RoutingRule>) -> anyhow::Result<()> {
+        let mut virtual_branches = self.read_file()?;
+        virtual_branches.routing_rules = rules;
+        self.write_file(&virtual_branches)?;
+        Ok(())
+    }
+
+    /// Lists the routing rules in the order they are evaluated.
+    ///
+    /// Errors if the file cannot be read.
+    pub fn list_routing_rules(&self) -> anyhow::Result<Vec<RoutingRule>> {
+        Ok(self.read_file()?.routing_rules)
+    }
+
     /// Checks if the state file exists.
     ///
     /// This would only be false if the application just updated from a very old verion.
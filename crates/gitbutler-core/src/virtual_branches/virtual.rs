@@ -3,7 +3,7 @@ use std::borrow::{Borrow, Cow};
 use std::os::unix::prelude::PermissionsExt;
 use std::time::SystemTime;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     hash::Hash,
     path::{Path, PathBuf},
     time, vec,
@@ -13,8 +13,9 @@ use anyhow::{anyhow, bail, Context, Result};
 use bstr::{BStr, BString, ByteSlice, ByteVec};
 use diffy::{apply_bytes as diffy_apply, Line, Patch};
 use git2_hooks::HookResult;
+use gitbutler_git::CancellationToken;
 use regex::Regex;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use super::integration::get_workspace_head;
 use super::{
@@ -34,13 +35,14 @@ use crate::{
         diff::{self},
         Commit, Refname, RemoteRefname,
     },
-    keys,
     project_repository::{self, conflicts, LogUntil},
     reader, users,
 };
 use crate::{error::Error, git::diff::GitHunk};
 
 type AppliedStatuses = Vec<(branch::Branch, BranchStatus)>;
+/// The lines each branch claims of the hunks that are split between branches, by file, start and hash of the hunk.
+type ClaimedLines = HashMap<(PathBuf, u32, HunkHash), Vec<(BranchId, Vec<u32>, bool)>>;
 
 // this struct is a mapping to the view `Branch` type in Typescript
 // found in src-tauri/src/routes/repo/[project_id]/types.ts
@@ -69,6 +71,7 @@ pub struct VirtualBranch {
     pub updated_at: u128,
     pub selected_for_changes: bool,
     pub head: git::Oid,
+    pub parent: Option<BranchId>, // the branch this branch is stacked on, if any
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
@@ -147,6 +150,8 @@ pub struct VirtualBranchHunk {
     pub locked: bool,
     pub locked_to: Option<Box<[diff::HunkLock]>>,
     pub change_type: diff::ChangeType,
+    /// The lines of the hunk the branch owns if it only owns some of them, or empty if it owns all.
+    pub lines: Vec<u32>,
 }
 
 /// Lifecycle
@@ -160,7 +165,12 @@ impl VirtualBranchHunk {
         hunk: GitHunk,
         mtimes: &mut MTimeCache,
     ) -> Self {
-        let hash = Hunk::hash_diff(hunk.diff_lines.as_ref());
+        let hash = Hunk::hash_diff(hunk.full_diff());
+        let lines = hunk
+            .selection
+            .as_ref()
+            .map(|selection| selection.lines.clone())
+            .unwrap_or_default();
         Self {
             id: Self::gen_id(hunk.new_start, hunk.new_lines),
             modified_at: mtimes.mtime_by_path(project_path.join(&file_path)),
@@ -174,6 +184,7 @@ impl VirtualBranchHunk {
             locked: hunk.locked_to.len() > 0,
             locked_to: Some(hunk.locked_to),
             change_type: hunk.change_type,
+            lines,
         }
     }
 }
@@ -223,7 +234,7 @@ fn get_default_target(
 pub fn apply_branch(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
-    signing_key: Option<&keys::PrivateKey>,
+    signer: Option<&git::Signer>,
     user: Option<&users::User>,
 ) -> Result<(), errors::ApplyBranchError> {
     if project_repository.is_resolving() {
@@ -315,21 +326,10 @@ pub fn apply_branch(
                 .context("failed to checkout index")?;
 
             // mark conflicts
-            let conflicts = merge_index
-                .conflicts()
-                .context("failed to get merge index conflicts")?;
-            let mut merge_conflicts = Vec::new();
-            for path in conflicts.flatten() {
-                if let Some(ours) = path.our {
-                    let path = std::str::from_utf8(&ours.path)
-                        .context("failed to convert path to utf8")?
-                        .to_string();
-                    merge_conflicts.push(path);
-                }
-            }
+            let merge_conflicts = conflicts::from_index(&merge_index, Some(*branch_id))?;
             conflicts::mark(
                 project_repository,
-                &merge_conflicts,
+                merge_conflicts,
                 Some(default_target.sha),
             )?;
 
@@ -364,7 +364,7 @@ pub fn apply_branch(
                 .as_str(),
                 &merged_branch_tree,
                 &[&head_commit, &target_commit],
-                signing_key,
+                signer,
             )?;
 
             // ok, update the virtual branch
@@ -433,7 +433,7 @@ pub fn apply_branch(
                         .as_str(),
                         &merge_tree,
                         &[&head_commit, &target_commit],
-                        signing_key,
+                        signer,
                     )
                     .context("failed to commit merge")?;
 
@@ -544,9 +544,14 @@ pub fn unapply_ownership(
         .flatten()
         .collect::<Vec<_>>();
 
+    // hunks that are only some lines of a change can't be reversed on their own, so they are
+    // left out of their branch instead
+    let mut discarded_selections = Vec::new();
     let mut diff = HashMap::new();
     for h in hunks_to_unapply {
-        if let Some(reversed_hunk) = diff::reverse_hunk(h.1) {
+        if h.1.selection.is_some() {
+            discarded_selections.push((h.0, h.1.clone()));
+        } else if let Some(reversed_hunk) = diff::reverse_hunk(h.1) {
             diff.entry(h.0).or_insert_with(Vec::new).push(reversed_hunk);
         } else {
             return Err(errors::UnapplyOwnershipError::Other(anyhow::anyhow!(
@@ -566,7 +571,20 @@ pub fn unapply_ownership(
         target_commit.tree().context("failed to get target tree"),
         |final_tree, status| {
             let final_tree = final_tree?;
-            let tree_oid = write_tree(project_repository, &integration_commit_id, status.1)?;
+            let files = status.1.into_iter().filter_map(|(path, hunks)| {
+                let hunks = hunks
+                    .into_iter()
+                    .filter(|hunk| {
+                        !discarded_selections
+                            .iter()
+                            .any(|(discarded_path, discarded)| {
+                                *discarded_path == path && discarded == hunk
+                            })
+                    })
+                    .collect::<Vec<_>>();
+                (!hunks.is_empty()).then_some((path, hunks))
+            });
+            let tree_oid = write_tree(project_repository, &integration_commit_id, files)?;
             let branch_tree = repo.find_tree(tree_oid)?;
             let mut result = repo.merge_trees(&base_tree, &final_tree, &branch_tree)?;
             let final_tree_oid = result.write_tree_to(repo)?;
@@ -591,10 +609,11 @@ pub fn unapply_ownership(
     Ok(())
 }
 
-// reset a file in the project to the index state
+// reset the files of a branch in the project to the index state
 pub fn reset_files(
     project_repository: &project_repository::Repository,
-    files: &Vec<String>,
+    branch_id: &BranchId,
+    files: &[String],
 ) -> Result<(), errors::UnapplyOwnershipError> {
     if conflicts::is_resolving(project_repository) {
         return Err(errors::UnapplyOwnershipError::Conflict(
@@ -604,25 +623,105 @@ pub fn reset_files(
         ));
     }
 
+    let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
+
+    let default_target = get_default_target(&vb_state)
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::UnapplyOwnershipError::DefaultTargetNotSet(errors::DefaultTargetNotSet {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let applied_branches = vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .filter(|b| b.applied)
+        .collect::<Vec<_>>();
+
+    let integration_commit_id =
+        super::integration::get_workspace_head(&vb_state, project_repository)?;
+
+    let (applied_statuses, _) = get_applied_status(
+        project_repository,
+        &integration_commit_id,
+        &default_target.sha,
+        applied_branches,
+    )
+    .context("failed to get status by branch")?;
+
+    // files whose hunks are split with other branches only lose the lines of this branch
+    let mut shared_files = BranchOwnershipClaims::default();
+    let mut whole_files = vec![];
+    for file in files {
+        let path = Path::new(file);
+        let is_shared = applied_statuses
+            .iter()
+            .any(|(branch, status)| branch.id != *branch_id && status.contains_key(path));
+        let branch_hunks = applied_statuses
+            .iter()
+            .find(|(branch, _)| branch.id == *branch_id)
+            .and_then(|(_, status)| status.get(path));
+        match branch_hunks {
+            Some(hunks) if is_shared => shared_files.put(OwnershipClaim {
+                file_path: path.to_path_buf(),
+                hunks: hunks.iter().map(Hunk::from).collect(),
+            }),
+            Some(_) => whole_files.push(path),
+            None => {}
+        }
+    }
+
     // for each tree, we need to checkout the entry from the index at that path
     // or if it doesn't exist, remove the file from the working directory
     let repo = &project_repository.git_repository;
     let index = repo.index().context("failed to get index")?;
-    for file in files {
-        let entry = index.get_path(Path::new(file), 0);
+    for path in whole_files {
+        let entry = index.get_path(path, 0);
         if entry.is_some() {
-            repo.checkout_index_path(Path::new(file))
+            repo.checkout_index_path(path)
                 .context("failed to checkout index")?;
         } else {
             // find the project root
             let project_root = &project_repository.project().path;
-            let path = Path::new(file);
             //combine the project root with the file path
             let path = &project_root.join(path);
             std::fs::remove_file(path).context("failed to remove file")?;
         }
     }
 
+    if !shared_files.claims.is_empty() {
+        unapply_ownership(project_repository, &shared_files)?;
+
+        // the lines of the other branches make up the whole hunks now, and claims of whole hunks
+        // carry over to the hunks that changed since
+        for (mut branch, _) in applied_statuses {
+            for claim in &mut branch.ownership.claims {
+                if shared_files
+                    .claims
+                    .iter()
+                    .any(|shared| shared.file_path == claim.file_path)
+                {
+                    for hunk in &mut claim.hunks {
+                        hunk.lines.clear();
+                    }
+                }
+            }
+            if branch.id == *branch_id {
+                branch.ownership.claims.retain(|claim| {
+                    !shared_files
+                        .claims
+                        .iter()
+                        .any(|shared| shared.file_path == claim.file_path)
+                });
+            }
+            vb_state
+                .set_branch(branch)
+                .context("failed to write branch")?;
+        }
+    }
+
     Ok(())
 }
 
@@ -649,6 +748,16 @@ pub fn unapply_branch(
         return Ok(Some(target_branch));
     }
 
+    // the heads of stacked branches contain the commits of this one, so they'd keep them applied
+    if vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?
+        .iter()
+        .any(|branch| branch.applied && branch.parent == Some(target_branch.id))
+    {
+        return Err(errors::UnapplyBranchError::Stacked);
+    }
+
     let default_target = get_default_target(&vb_state)
         .context("failed to get default target")?
         .ok_or_else(|| {
@@ -855,9 +964,12 @@ pub fn list_virtual_branches(
         let mut is_integrated = false;
         let mut is_remote = false;
 
-        // find all commits on head that are not on target.sha
+        // find all commits on head that are not on target.sha, or on the parent for stacked branches
         let commits = project_repository
-            .log(branch.head, LogUntil::Commit(default_target.sha))
+            .log(
+                branch.head,
+                LogUntil::Commit(branch.base(default_target.sha)),
+            )
             .context(format!("failed to get log for branch {}", branch.name))?
             .iter()
             .map(|commit| {
@@ -939,6 +1051,7 @@ pub fn list_virtual_branches(
             updated_at: branch.updated_timestamp_ms,
             selected_for_changes: branch.selected_for_changes == Some(max_selected_for_changes),
             head: branch.head,
+            parent: branch.parent,
         };
         branches.push(branch);
     }
@@ -1067,9 +1180,32 @@ pub fn create_virtual_branch(
             })
         })?;
 
+    // a stacked branch starts out at the head of its parent, otherwise at the default target
+    let parent = create
+        .parent
+        .map(|parent_id| match vb_state.get_branch(&parent_id) {
+            Ok(parent) if parent.applied => Ok(parent),
+            Ok(_) => Err(errors::CreateVirtualBranchError::ParentNotApplied(
+                parent_id,
+            )),
+            Err(reader::Error::NotFound) => Err(errors::CreateVirtualBranchError::ParentNotFound(
+                errors::BranchNotFound {
+                    project_id: project_repository.project().id,
+                    branch_id: parent_id,
+                },
+            )),
+            Err(error) => Err(errors::CreateVirtualBranchError::Other(
+                anyhow::Error::from(error).context("failed to read parent branch"),
+            )),
+        })
+        .transpose()?;
+    let head = parent
+        .as_ref()
+        .map_or(default_target.sha, |parent| parent.head);
+
     let commit = project_repository
         .git_repository
-        .find_commit(default_target.sha)
+        .find_commit(head)
         .context("failed to find default target commit")?;
 
     let tree = commit
@@ -1142,12 +1278,14 @@ pub fn create_virtual_branch(
         upstream: None,
         upstream_head: None,
         tree: tree.id(),
-        head: default_target.sha,
+        head,
         created_timestamp_ms: now,
         updated_timestamp_ms: now,
         ownership: BranchOwnershipClaims::default(),
         order,
         selected_for_changes,
+        parent: parent.as_ref().map(|parent| parent.id),
+        parent_head: parent.map(|parent| parent.head),
     };
 
     if let Some(ownership) = &create.ownership {
@@ -1166,7 +1304,7 @@ pub fn create_virtual_branch(
 pub fn merge_virtual_branch_upstream(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
-    signing_key: Option<&keys::PrivateKey>,
+    signer: Option<&git::Signer>,
     user: Option<&users::User>,
 ) -> Result<(), errors::MergeVirtualBranchUpstreamError> {
     if conflicts::is_conflicting::<&Path>(project_repository, None)? {
@@ -1229,9 +1367,9 @@ pub fn merge_virtual_branch_upstream(
         .filter(|b| b.id != *branch_id)
         .collect::<Vec<_>>();
 
-    // unapply all other branches
-    for other_branch in applied_branches {
-        unapply_branch(project_repository, &other_branch.id).context("failed to unapply branch")?;
+    // unapply all other branches, stacked ones before their parents
+    for other_branch_id in stack_order(&applied_branches).into_iter().rev() {
+        unapply_branch(project_repository, &other_branch_id).context("failed to unapply branch")?;
     }
 
     // get merge base from remote branch commit and target commit
@@ -1264,19 +1402,10 @@ pub fn merge_virtual_branch_upstream(
             .context("failed to checkout index")?;
 
         // mark conflicts
-        let conflicts = merge_index.conflicts().context("failed to get conflicts")?;
-        let mut merge_conflicts = Vec::new();
-        for path in conflicts.flatten() {
-            if let Some(ours) = path.our {
-                let path = std::str::from_utf8(&ours.path)
-                    .context("failed to convert path to utf8")?
-                    .to_string();
-                merge_conflicts.push(path);
-            }
-        }
+        let merge_conflicts = conflicts::from_index(&merge_index, Some(branch.id))?;
         conflicts::mark(
             project_repository,
-            &merge_conflicts,
+            merge_conflicts,
             Some(upstream_commit.id()),
         )?;
     } else {
@@ -1336,6 +1465,7 @@ pub fn merge_virtual_branch_upstream(
                 branch.head = last_rebase_head;
                 branch.tree = merge_tree_oid;
                 vb_state.set_branch(branch.clone())?;
+                restack_children(project_repository, &vb_state, &branch.id, signer)?;
                 super::integration::update_gitbutler_integration(&vb_state, project_repository)?;
 
                 return Ok(());
@@ -1359,7 +1489,7 @@ pub fn merge_virtual_branch_upstream(
             .as_str(),
             &merge_tree,
             &[&head_commit, &upstream_commit],
-            signing_key,
+            signer,
         )?;
 
         // checkout the merge tree
@@ -1374,6 +1504,7 @@ pub fn merge_virtual_branch_upstream(
         vb_state.set_branch(branch.clone())?;
     }
 
+    restack_children(project_repository, &vb_state, &branch.id, signer)?;
     super::integration::update_gitbutler_integration(&vb_state, project_repository)?;
 
     Ok(())
@@ -1480,6 +1611,20 @@ pub fn delete_branch(
     }
     .context("failed to read branch")?;
 
+    // branches stacked on this one now stack on its parent, and own its commits
+    for mut child in vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .filter(|b| b.parent == Some(branch.id))
+    {
+        child.parent = branch.parent;
+        child.parent_head = branch.parent_head;
+        vb_state
+            .set_branch(child)
+            .context("failed to write branch")?;
+    }
+
     if branch.applied && unapply_branch(project_repository, branch_id)?.is_none() {
         return Ok(());
     }
@@ -1726,9 +1871,16 @@ fn get_applied_status(
             ];
     }
 
+    // stacked branches only own the commits on top of their parent, unless the parent is gone
     let mut commit_to_branch = HashMap::new();
-    for branch in &mut virtual_branches {
-        for commit in project_repository.log(branch.head, LogUntil::Commit(*target_sha))? {
+    for branch in &virtual_branches {
+        let base = match branch.parent {
+            Some(parent_id) if virtual_branches.iter().any(|b| b.id == parent_id) => {
+                branch.base(*target_sha)
+            }
+            _ => *target_sha,
+        };
+        for commit in project_repository.log(branch.head, LogUntil::Commit(base))? {
             commit_to_branch.insert(commit.id(), branch.id);
         }
     }
@@ -1805,6 +1957,49 @@ fn get_applied_status(
         }
     }
 
+    // Claims of some lines of a hunk are settled first, so they take precedence over claims of whole hunks.
+    // The lines claimed by each branch are collected by the hunk they belong to, along with whether the
+    // branch claimed the whole hunk and got what was left of it.
+    let mut claimed_lines = ClaimedLines::new();
+    for branch in &virtual_branches {
+        for claim in &branch.ownership.claims {
+            let Some(git_diff_hunks) = base_diffs.get(&claim.file_path) else {
+                continue;
+            };
+            for claimed_hunk in claim.hunks.iter().filter(|hunk| !hunk.lines.is_empty()) {
+                let Some(git_diff_hunk) = git_diff_hunks
+                    .iter()
+                    .find(|git_diff_hunk| claimed_hunk.same_hunk(&Hunk::from(*git_diff_hunk)))
+                else {
+                    continue;
+                };
+                let hash = Hunk::hash_diff(git_diff_hunk.diff_lines.as_ref());
+                if !git_diff_hunk.can_select_lines() || locked_hunk_map.contains_key(&hash) {
+                    continue;
+                }
+                let claims = claimed_lines
+                    .entry((claim.file_path.clone(), git_diff_hunk.new_start, hash))
+                    .or_default();
+                let lines = claimed_hunk
+                    .lines
+                    .iter()
+                    .filter(|line| !claims.iter().any(|(_, taken, _)| taken.contains(line)))
+                    .copied()
+                    .collect::<Vec<_>>();
+                if lines.is_empty() {
+                    continue;
+                }
+                match claims
+                    .iter_mut()
+                    .find(|(branch_id, _, _)| *branch_id == branch.id)
+                {
+                    Some((_, branch_lines, _)) => branch_lines.extend(lines),
+                    None => claims.push((branch.id, lines, false)),
+                }
+            }
+        }
+    }
+
     for branch in &mut virtual_branches {
         if !branch.applied {
             bail!("branch {} is not applied", branch.name);
@@ -1825,13 +2020,53 @@ fn get_applied_status(
                     .hunks
                     .iter()
                     .filter_map(|claimed_hunk| {
+                        // hunks whose lines are claimed separately are split up once all claims are known
+                        let split_hunk = git_diff_hunks
+                            .iter()
+                            .find(|git_diff_hunk| {
+                                claimed_hunk.same_hunk(&Hunk::from(*git_diff_hunk))
+                            })
+                            .and_then(|git_diff_hunk| {
+                                let key = (
+                                    claim.file_path.clone(),
+                                    git_diff_hunk.new_start,
+                                    Hunk::hash_diff(git_diff_hunk.diff_lines.as_ref()),
+                                );
+                                claimed_lines
+                                    .get_mut(&key)
+                                    .map(|claims| (git_diff_hunk, claims))
+                            });
+                        if let Some((git_diff_hunk, claims)) = split_hunk {
+                            if claimed_hunk.lines.is_empty() {
+                                // a claim of the whole hunk gets the lines nobody else claimed
+                                let lines = git_diff_hunk
+                                    .changed_lines()
+                                    .into_iter()
+                                    .filter(|line| {
+                                        !claims.iter().any(|(_, taken, _)| taken.contains(line))
+                                    })
+                                    .collect::<Vec<_>>();
+                                if !lines.is_empty() {
+                                    claims.push((branch.id, lines, true));
+                                }
+                            }
+                            return None;
+                        }
+
                         // if any of the current hunks intersects with the owned hunk, we want to keep it
                         for (i, git_diff_hunk) in git_diff_hunks.iter().enumerate() {
                             let hash = Hunk::hash_diff(git_diff_hunk.diff_lines.as_ref());
                             if locked_hunk_map.contains_key(&hash) {
                                 return None; // Defer allocation to unclaimed hunks processing
                             }
-                            if claimed_hunk.eq(&Hunk::from(git_diff_hunk)) {
+                            if claimed_lines.contains_key(&(
+                                claim.file_path.clone(),
+                                git_diff_hunk.new_start,
+                                hash,
+                            )) {
+                                continue;
+                            }
+                            if claimed_hunk.same_hunk(&Hunk::from(git_diff_hunk)) {
                                 let timestamp = claimed_hunk.timestam_ms().unwrap_or(mtime);
                                 diffs_by_branch
                                     .entry(branch.id)
@@ -1845,9 +2080,13 @@ fn get_applied_status(
                                     claimed_hunk
                                         .clone()
                                         .with_timestamp(timestamp)
-                                        .with_hash(hash),
+                                        .with_hash(hash)
+                                        .with_lines(vec![]),
                                 );
-                            } else if claimed_hunk.intersects(git_diff_hunk) {
+                            } else if claimed_hunk.lines.is_empty()
+                                && claimed_hunk.intersects(git_diff_hunk)
+                            {
+                                // claimed lines don't carry over to a hunk that changed since
                                 diffs_by_branch
                                     .entry(branch.id)
                                     .or_default()
@@ -1860,6 +2099,7 @@ fn get_applied_status(
                                     timestamp_ms: Some(mtime),
                                     hash: Some(hash),
                                     locked_to: git_diff_hunk.locked_to.to_vec(),
+                                    lines: vec![],
                                 };
                                 git_diff_hunks.remove(i);
                                 return Some(updated_hunk);
@@ -1884,6 +2124,40 @@ fn get_applied_status(
         branch.ownership = BranchOwnershipClaims { claims: new_claims };
     }
 
+    // Now that all claims are known, each branch gets the lines it claimed of the hunks that are split up
+    for ((filepath, new_start, hash), claims) in &claimed_lines {
+        let Some(git_diff_hunk) = base_diffs.get(filepath).and_then(|hunks| {
+            hunks.iter().find(|hunk| {
+                hunk.new_start == *new_start && Hunk::hash_diff(hunk.diff_lines.as_ref()) == *hash
+            })
+        }) else {
+            continue;
+        };
+        for (branch_id, lines, whole) in claims {
+            let Some(selected_hunk) = git_diff_hunk.select_lines(lines) else {
+                continue;
+            };
+            let Some(branch) = virtual_branches.iter_mut().find(|b| b.id == *branch_id) else {
+                continue;
+            };
+            let mut claimed_hunk =
+                Hunk::from(&selected_hunk).with_timestamp(mtimes.mtime_by_path(filepath.as_path()));
+            if *whole {
+                claimed_hunk = claimed_hunk.with_lines(vec![]);
+            }
+            branch.ownership.put(OwnershipClaim {
+                file_path: filepath.clone(),
+                hunks: vec![claimed_hunk],
+            });
+            diffs_by_branch
+                .entry(*branch_id)
+                .or_default()
+                .entry(filepath.clone())
+                .or_default()
+                .push(selected_hunk);
+        }
+    }
+
     let max_selected_for_changes = virtual_branches
         .iter()
         .filter_map(|b| b.selected_for_changes)
@@ -1894,6 +2168,12 @@ fn get_applied_status(
         .position(|b| b.selected_for_changes == Some(max_selected_for_changes))
         .unwrap_or(0);
 
+    // Routing rules decide where new hunks go, before falling back to the default branch.
+    let routing_rules = VirtualBranchesHandle::new(&project_repository.project().gb_dir())
+        .list_routing_rules()
+        .context("failed to read routing rules")?;
+    let branch_ids = virtual_branches.iter().map(|b| b.id).collect::<Vec<_>>();
+
     // Everything claimed has been removed from `base_diffs`, here we just
     // process the remaining ones.
     for (filepath, hunks) in base_diffs {
@@ -1901,6 +2181,22 @@ fn get_applied_status(
             let hash = Hunk::hash_diff(hunk.diff_lines.as_ref());
             let locked_to = locked_hunk_map.get(&hash);
 
+            // of a hunk that is split up, only the lines nobody claimed are left
+            let hunk = match claimed_lines.get(&(filepath.clone(), hunk.new_start, hash)) {
+                Some(claims) => {
+                    let lines = hunk
+                        .changed_lines()
+                        .into_iter()
+                        .filter(|line| !claims.iter().any(|(_, taken, _)| taken.contains(line)))
+                        .collect::<Vec<_>>();
+                    match hunk.select_lines(&lines) {
+                        Some(hunk) => hunk,
+                        None => continue,
+                    }
+                }
+                None => hunk,
+            };
+
             let vbranch_pos = if let Some(locks) = locked_to {
                 let first_lock = &locks[0];
                 let p = virtual_branches
@@ -1911,10 +2207,12 @@ fn get_applied_status(
                     _ => default_vbranch_pos,
                 }
             } else {
-                default_vbranch_pos
+                super::route(&routing_rules, &branch_ids, &filepath)
+                    .and_then(|branch_id| virtual_branches.iter().position(|b| b.id == branch_id))
+                    .unwrap_or(default_vbranch_pos)
             };
 
-            let hash = Hunk::hash_diff(hunk.diff_lines.as_ref());
+            let hash = Hunk::hash_diff(hunk.full_diff());
             let mut new_hunk = Hunk::from(&hunk)
                 .with_timestamp(mtimes.mtime_by_path(filepath.as_path()))
                 .with_hash(hash);
@@ -1927,7 +2225,7 @@ fn get_applied_status(
                 file_path: filepath.clone(),
                 hunks: vec![Hunk::from(&hunk)
                     .with_timestamp(mtimes.mtime_by_path(filepath.as_path()))
-                    .with_hash(Hunk::hash_diff(hunk.diff_lines.as_ref()))],
+                    .with_hash(Hunk::hash_diff(hunk.full_diff()))],
             });
 
             let hunk = match locked_to {
@@ -2030,9 +2328,10 @@ pub fn reset_branch(
         return Ok(());
     }
 
-    if default_target.sha != target_commit_oid
+    let base = branch.base(default_target.sha);
+    if base != target_commit_oid
         && !project_repository
-            .l(branch.head, LogUntil::Commit(default_target.sha))?
+            .l(branch.head, LogUntil::Commit(base))?
             .contains(&target_commit_oid)
     {
         return Err(errors::ResetBranchError::CommitNotFoundInBranch(
@@ -2077,6 +2376,7 @@ pub fn reset_branch(
         .set_branch(branch)
         .context("failed to write branch")?;
 
+    restack_children(project_repository, &vb_state, branch_id, None)?;
     super::integration::update_gitbutler_integration(&vb_state, project_repository)
         .context("failed to update gitbutler integration")?;
 
@@ -2283,41 +2583,43 @@ fn _print_tree(repo: &git2::Repository, tree: &git2::Tree) -> Result<()> {
     Ok(())
 }
 
+/// Returns the part of `hunk` that is claimed by `owned_hunks`, which is all of it unless only
+/// some of its lines are claimed.
+fn owned_part_of_hunk(hunk: &diff::GitHunk, owned_hunks: &[Hunk]) -> Option<diff::GitHunk> {
+    let owned_hunks = owned_hunks
+        .iter()
+        .filter(|owned_hunk| {
+            owned_hunk.start == hunk.new_start && owned_hunk.end == hunk.new_start + hunk.new_lines
+        })
+        .collect::<Vec<_>>();
+    if owned_hunks.is_empty() {
+        None
+    } else if owned_hunks
+        .iter()
+        .any(|owned_hunk| owned_hunk.lines.is_empty())
+    {
+        Some(hunk.clone())
+    } else {
+        let lines = owned_hunks
+            .iter()
+            .flat_map(|owned_hunk| owned_hunk.lines.iter().copied())
+            .collect::<Vec<_>>();
+        hunk.select_lines(&lines)
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn commit(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
     message: &str,
     ownership: Option<&branch::BranchOwnershipClaims>,
-    signing_key: Option<&keys::PrivateKey>,
+    signer: Option<&git::Signer>,
     user: Option<&users::User>,
     run_hooks: bool,
 ) -> Result<git::Oid, errors::CommitError> {
-    let mut message_buffer = message.to_owned();
     let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
 
-    if run_hooks {
-        let hook_result = project_repository
-            .git_repository
-            .run_hook_commit_msg(&mut message_buffer)
-            .context("failed to run hook")?;
-
-        if let HookResult::RunNotSuccessful { stdout, .. } = hook_result {
-            return Err(errors::CommitError::CommitMsgHookRejected(stdout));
-        }
-
-        let hook_result = project_repository
-            .git_repository
-            .run_hook_pre_commit()
-            .context("failed to run hook")?;
-
-        if let HookResult::RunNotSuccessful { stdout, .. } = hook_result {
-            return Err(errors::CommitError::CommitHookRejected(stdout));
-        }
-    }
-
-    let message = &message_buffer;
-
     let integration_commit_id =
         super::integration::get_workspace_head(&vb_state, project_repository)?;
     // get the files to commit
@@ -2346,17 +2648,12 @@ pub fn commit(
         let files = files.into_iter().filter_map(|(filepath, hunks)| {
             let hunks = hunks
                 .into_iter()
-                .filter(|hunk| {
+                .filter_map(|hunk| {
                     ownership
                         .claims
                         .iter()
                         .find(|f| f.file_path.eq(&filepath))
-                        .map_or(false, |f| {
-                            f.hunks.iter().any(|h| {
-                                h.start == hunk.new_start
-                                    && h.end == hunk.new_start + hunk.new_lines
-                            })
-                        })
+                        .and_then(|f| owned_part_of_hunk(&hunk, &f.hunks))
                 })
                 .collect::<Vec<_>>();
             if hunks.is_empty() {
@@ -2378,6 +2675,27 @@ pub fn commit(
         .find_tree(tree_oid)
         .context(format!("failed to find tree {:?}", tree_oid))?;
 
+    // hooks get to see only the tree that is committed, not the changes of other branches
+    let mut message_buffer = message.to_owned();
+    if run_hooks {
+        let hook_result = git_repository
+            .run_hook_commit_msg(branch.head, &tree, &mut message_buffer)
+            .context("failed to run hook")?;
+
+        if let HookResult::RunNotSuccessful { stdout, stderr, .. } = hook_result {
+            return Err(errors::CommitError::CommitMsgHookRejected(stdout + &stderr));
+        }
+
+        let hook_result = git_repository
+            .run_hook_pre_commit(branch.head, &tree)
+            .context("failed to run hook")?;
+
+        if let HookResult::RunNotSuccessful { stdout, stderr, .. } = hook_result {
+            return Err(errors::CommitError::CommitHookRejected(stdout + &stderr));
+        }
+    }
+    let message = &message_buffer;
+
     // now write a commit, using a merge parent if it exists
     let extra_merge_parent =
         conflicts::merge_parent(project_repository).context("failed to get merge parent")?;
@@ -2392,12 +2710,12 @@ pub fn commit(
                 message,
                 &tree,
                 &[&parent_commit, &merge_parent],
-                signing_key,
+                signer,
             )?;
             conflicts::clear(project_repository).context("failed to clear conflicts")?;
             commit_oid
         }
-        None => project_repository.commit(user, message, &tree, &[&parent_commit], signing_key)?,
+        None => project_repository.commit(user, message, &tree, &[&parent_commit], signer)?,
     };
 
     if run_hooks {
@@ -2414,6 +2732,7 @@ pub fn commit(
         .set_branch(branch.clone())
         .context("failed to write branch")?;
 
+    restack_children(project_repository, &vb_state, &branch.id, signer)?;
     super::integration::update_gitbutler_integration(&vb_state, project_repository)
         .context("failed to update gitbutler integration")?;
 
@@ -2426,10 +2745,12 @@ pub fn push(
     with_force: bool,
     credentials: &git::credentials::Helper,
     askpass: Option<(AskpassBroker, Option<BranchId>)>,
+    progress: Option<project_repository::ProgressSink>,
+    cancellation: Option<CancellationToken>,
 ) -> Result<(), errors::PushError> {
     let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
 
-    let mut vbranch = vb_state
+    let vbranch = vb_state
         .get_branch(branch_id)
         .map_err(|error| match error {
             reader::Error::NotFound => errors::PushError::BranchNotFound(errors::BranchNotFound {
@@ -2439,10 +2760,80 @@ pub fn push(
             error => errors::PushError::Other(error.into()),
         })?;
 
+    // the commits of a stacked branch can only be reviewed if the branches below it are
+    // pushed as well, so push every layer with unpushed commits, starting at the bottom.
+    let mut layers = vec![vbranch];
+    while let Some(parent_id) = layers.last().and_then(|branch| branch.parent) {
+        match vb_state.get_branch(&parent_id) {
+            Ok(parent) => layers.push(parent),
+            Err(reader::Error::NotFound) => break,
+            Err(error) => return Err(errors::PushError::Other(error.into())),
+        }
+    }
+    let (vbranch, ancestors) = layers.split_first_mut().expect("branch is always present");
+
+    let mut remotes = Vec::new();
+    for ancestor in ancestors.iter_mut().rev() {
+        if ancestor.upstream_head == Some(ancestor.head) {
+            continue;
+        }
+        let remote_branch = push_branch(
+            project_repository,
+            &vb_state,
+            ancestor,
+            with_force,
+            credentials,
+            askpass.clone(),
+            progress.clone(),
+            cancellation.clone(),
+        )?;
+        remotes.push(remote_branch.remote().to_owned());
+    }
+    let remote_branch = push_branch(
+        project_repository,
+        &vb_state,
+        vbranch,
+        with_force,
+        credentials,
+        askpass.clone(),
+        progress.clone(),
+        cancellation.clone(),
+    )?;
+    remotes.push(remote_branch.remote().to_owned());
+
+    remotes.dedup();
+    for remote in remotes {
+        project_repository.fetch(
+            &remote,
+            credentials,
+            askpass
+                .clone()
+                .map(|(broker, _)| (broker, "modal".to_string())),
+            progress.clone(),
+            cancellation.clone(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Push the head of `vbranch` to its upstream, or to a new remote branch named after it,
+/// and remember the pushed head.
+#[allow(clippy::too_many_arguments)]
+fn push_branch(
+    project_repository: &project_repository::Repository,
+    vb_state: &VirtualBranchesHandle,
+    vbranch: &mut Branch,
+    with_force: bool,
+    credentials: &git::credentials::Helper,
+    askpass: Option<(AskpassBroker, Option<BranchId>)>,
+    progress: Option<project_repository::ProgressSink>,
+    cancellation: Option<CancellationToken>,
+) -> Result<git::RemoteRefname, errors::PushError> {
     let remote_branch = if let Some(upstream_branch) = vbranch.upstream.as_ref() {
         upstream_branch.clone()
     } else {
-        let default_target = get_default_target(&vb_state)
+        let default_target = get_default_target(vb_state)
             .context("failed to get default target")?
             .ok_or_else(|| {
                 errors::PushError::DefaultTargetNotSet(errors::DefaultTargetNotSet {
@@ -2475,27 +2866,60 @@ pub fn push(
         ))
     };
 
-    project_repository.push(
-        &vbranch.head,
-        &remote_branch,
-        with_force,
-        credentials,
-        None,
-        askpass.clone(),
-    )?;
+    let git_repository = &project_repository.git_repository;
+    let remote_url = git_repository
+        .find_remote(remote_branch.remote())
+        .and_then(|remote| remote.url())
+        .context("failed to get remote url")?
+        .map_or_else(|| remote_branch.remote().to_owned(), |url| url.to_string());
+    let updates = [git::PushUpdate {
+        local_ref: git::VirtualRefname::from(&*vbranch).to_string(),
+        local_oid: vbranch.head,
+        remote_ref: format!("refs/heads/{}", remote_branch.branch()),
+        remote_oid: git_repository
+            .find_reference(&remote_branch.clone().into())
+            .ok()
+            .and_then(|reference| reference.target()),
+    }];
+
+    let hook_result = git_repository
+        .run_hook_pre_push(remote_branch.remote(), &remote_url, &updates)
+        .context("failed to run hook")?;
+    if let HookResult::RunNotSuccessful { stdout, stderr, .. } = hook_result {
+        return Err(errors::PushError::PrePushHookRejected(stdout + &stderr));
+    }
+
+    // a force push must not discard commits someone else pushed since we last did
+    project_repository
+        .push(
+            &vbranch.head,
+            &remote_branch,
+            with_force,
+            vbranch.upstream_head,
+            credentials,
+            None,
+            askpass,
+            progress,
+            cancellation,
+        )
+        .map_err(|error| match error {
+            project_repository::RemoteError::LeaseViolated(remote_branch) => {
+                errors::PushError::LeaseViolated(remote_branch)
+            }
+            error => errors::PushError::Remote(error),
+        })?;
 
     vbranch.upstream = Some(remote_branch.clone());
     vbranch.upstream_head = Some(vbranch.head);
     vb_state
         .set_branch(vbranch.clone())
         .context("failed to write target branch after push")?;
-    project_repository.fetch(
-        remote_branch.remote(),
-        credentials,
-        askpass.map(|(broker, _)| (broker, "modal".to_string())),
-    )?;
 
-    Ok(())
+    git_repository
+        .run_hook_post_push(remote_branch.remote(), &remote_url, &updates)
+        .context("failed to run hook")?;
+
+    Ok(remote_branch)
 }
 
 fn is_commit_integrated(
@@ -2705,6 +3129,7 @@ pub fn move_commit_file(
     from_commit_oid: git::Oid,
     to_commit_oid: git::Oid,
     target_ownership: &BranchOwnershipClaims,
+    signer: Option<&git::Signer>,
 ) -> Result<git::Oid, errors::VirtualBranchError> {
     let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
 
@@ -2839,13 +3264,13 @@ pub fn move_commit_file(
             .find_tree(new_from_tree_oid)
             .map_err(|_error| errors::VirtualBranchError::GitObjectNotFound(new_from_tree_oid))?;
         let new_from_commit_oid = repo
-            .commit(
-                None,
+            .commit_with_signer(
                 &from_commit.author(),
                 &from_commit.committer(),
                 &from_commit.message().to_str_lossy(),
                 new_from_tree,
                 &[&from_parent],
+                signer,
             )
             .map_err(|_error| errors::VirtualBranchError::CommitFailed)?;
 
@@ -2855,6 +3280,7 @@ pub fn move_commit_file(
             new_from_commit_oid,
             from_commit_oid,
             target_branch.head,
+            signer,
         ) {
             Ok(Some(new_head)) => new_head,
             _ => {
@@ -2918,13 +3344,13 @@ pub fn move_commit_file(
         .context("failed to find head commit parents")?;
     let commit_oid = project_repository
         .git_repository
-        .commit(
-            None,
+        .commit_with_signer(
             &amend_commit.author(),
             &amend_commit.committer(),
             &amend_commit.message().to_str_lossy(),
             &new_tree,
             &parents.iter().collect::<Vec<_>>(),
+            signer,
         )
         .context("failed to create commit")?;
 
@@ -2934,6 +3360,7 @@ pub fn move_commit_file(
     if upstream_commits.is_empty() {
         target_branch.head = commit_oid;
         vb_state.set_branch(target_branch.clone())?;
+        restack_children(project_repository, &vb_state, &target_branch.id, signer)?;
         super::integration::update_gitbutler_integration(&vb_state, project_repository)?;
         return Ok(commit_oid);
     }
@@ -2945,12 +3372,14 @@ pub fn move_commit_file(
         commit_oid,
         amend_commit.id(),
         last_commit,
+        signer,
     )?;
 
     // if that rebase worked, update the branch head and the gitbutler integration
     if let Some(new_head) = new_head {
         target_branch.head = new_head;
         vb_state.set_branch(target_branch.clone())?;
+        restack_children(project_repository, &vb_state, &target_branch.id, signer)?;
         super::integration::update_gitbutler_integration(&vb_state, project_repository)?;
         Ok(commit_oid)
     } else {
@@ -2958,65 +3387,273 @@ pub fn move_commit_file(
     }
 }
 
-// takes a list of file ownership and a commit oid and rewrites that commit to
-// add the file changes. The branch is then rebased onto the new commit
-// and the respective branch head is updated
-pub fn amend(
+/// One of the commits a commit is split into.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitPart {
+    /// The hunks of the split commit that go into this part.
+    pub ownership: BranchOwnershipClaims,
+    pub message: String,
+}
+
+/// Replaces `commit_oid` with one consecutive commit per part, oldest first, and rebases the
+/// commits above it onto the last one. Every hunk of the commit has to be in exactly one part.
+///
+/// Returns the new commits, oldest first.
+pub fn split_commit(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
     commit_oid: git::Oid,
-    target_ownership: &BranchOwnershipClaims,
-) -> Result<git::Oid, errors::VirtualBranchError> {
+    parts: &[CommitPart],
+    signer: Option<&git::Signer>,
+) -> Result<Vec<git::Oid>, errors::SplitCommitError> {
     if conflicts::is_conflicting::<&Path>(project_repository, None)? {
-        return Err(errors::VirtualBranchError::Conflict(
+        return Err(errors::SplitCommitError::Conflict(
             errors::ProjectConflict {
                 project_id: project_repository.project().id,
             },
         ));
     }
 
-    let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
-
-    let all_branches = vb_state
-        .list_branches()
-        .context("failed to read virtual branches")?;
-
-    if !all_branches.iter().any(|b| b.id == *branch_id) {
-        return Err(errors::VirtualBranchError::BranchNotFound(
-            errors::BranchNotFound {
-                project_id: project_repository.project().id,
-                branch_id: *branch_id,
-            },
-        ));
+    if parts.len() < 2 {
+        return Err(errors::SplitCommitError::TooFewParts);
     }
-
-    let applied_branches = all_branches
-        .into_iter()
-        .filter(|b| b.applied)
-        .collect::<Vec<_>>();
-
-    if !applied_branches.iter().any(|b| b.id == *branch_id) {
-        return Err(errors::VirtualBranchError::BranchNotFound(
-            errors::BranchNotFound {
-                project_id: project_repository.project().id,
-                branch_id: *branch_id,
-            },
-        ));
+    if parts.iter().any(|part| part.message.is_empty()) {
+        return Err(errors::SplitCommitError::EmptyMessage);
     }
 
+    let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
+
     let default_target = get_default_target(&vb_state)
         .context("failed to read default target")?
         .ok_or_else(|| {
-            errors::VirtualBranchError::DefaultTargetNotSet(errors::DefaultTargetNotSet {
+            errors::SplitCommitError::DefaultTargetNotSet(errors::DefaultTargetNotSet {
                 project_id: project_repository.project().id,
             })
         })?;
 
-    let integration_commit_id =
-        super::integration::get_workspace_head(&vb_state, project_repository)?;
-
-    let (mut applied_statuses, _) = get_applied_status(
-        project_repository,
+    let mut branch = vb_state
+        .get_branch(branch_id)
+        .map_err(|error| match error {
+            reader::Error::NotFound => {
+                errors::SplitCommitError::BranchNotFound(errors::BranchNotFound {
+                    project_id: project_repository.project().id,
+                    branch_id: *branch_id,
+                })
+            }
+            error => errors::SplitCommitError::Other(error.into()),
+        })?;
+
+    let branch_commit_oids = project_repository.l(
+        branch.head,
+        project_repository::LogUntil::Commit(branch.base(default_target.sha)),
+    )?;
+
+    if !branch_commit_oids.contains(&commit_oid) {
+        return Err(errors::SplitCommitError::CommitNotFound(commit_oid));
+    }
+
+    let pushed_commit_oids = branch.upstream_head.map_or_else(
+        || Ok(vec![]),
+        |upstream_head| {
+            project_repository.l(
+                upstream_head,
+                project_repository::LogUntil::Commit(default_target.sha),
+            )
+        },
+    )?;
+
+    if pushed_commit_oids.contains(&commit_oid) && !project_repository.project().ok_with_force_push
+    {
+        // splitting a pushed commit will cause a force push that is not allowed
+        return Err(errors::SplitCommitError::ForcePushNotAllowed(
+            errors::ForcePushNotAllowed {
+                project_id: project_repository.project().id,
+            },
+        ));
+    }
+
+    let repo = &project_repository.git_repository;
+    let commit_to_split = repo
+        .find_commit(commit_oid)
+        .context("failed to find commit")?;
+    let parent_commit = commit_to_split
+        .parent(0)
+        .context("failed to find parent commit")?;
+    let parent_tree = parent_commit.tree().context("failed to find parent tree")?;
+
+    let commit_diffs = diff::trees(
+        repo,
+        &parent_tree,
+        &commit_to_split.tree().context("failed to find tree")?,
+    )
+    .context("failed to diff trees")?;
+
+    let claimed = |path: &PathBuf, hunk: &diff::GitHunk, ownership: &BranchOwnershipClaims| {
+        ownership.claims.iter().any(|file_ownership| {
+            file_ownership.file_path == *path
+                && file_ownership.hunks.iter().any(|owned_hunk| {
+                    owned_hunk.start == hunk.new_start
+                        && owned_hunk.end == hunk.new_start + hunk.new_lines
+                })
+        })
+    };
+    // the index of the part each hunk of the commit goes into
+    let mut hunk_parts = HashMap::new();
+    for (path, file_diff) in &commit_diffs {
+        for (hunk_index, hunk) in file_diff.hunks.iter().enumerate() {
+            let mut part_indices = parts
+                .iter()
+                .enumerate()
+                .filter(|(_, part)| claimed(path, hunk, &part.ownership))
+                .map(|(part_index, _)| part_index);
+            let part_index = part_indices
+                .next()
+                .ok_or_else(|| errors::SplitCommitError::HunkNotClaimed(path.clone()))?;
+            if part_indices.next().is_some() {
+                return Err(errors::SplitCommitError::HunkClaimedTwice(path.clone()));
+            }
+            hunk_parts.insert((path.clone(), hunk_index), part_index);
+        }
+    }
+    if let Some(part) = (0..parts.len())
+        .find(|part_index| !hunk_parts.values().any(|index| index == part_index))
+        .map(|part_index| &parts[part_index])
+    {
+        return Err(errors::SplitCommitError::TargetOwnershipNotFound(
+            part.ownership.clone(),
+        ));
+    }
+
+    let mut head = parent_commit;
+    let mut new_commit_oids = vec![];
+    for (part_index, part) in parts.iter().enumerate() {
+        // every part is written onto the parent along with the parts before it, so that the
+        // hunks still apply where the commit had them
+        let tree_oid = if part_index == parts.len() - 1 {
+            commit_to_split.tree_id()
+        } else {
+            let diffs = commit_diffs
+                .iter()
+                .filter_map(|(path, file_diff)| {
+                    let hunks = file_diff
+                        .hunks
+                        .iter()
+                        .enumerate()
+                        .filter(|(hunk_index, _)| {
+                            hunk_parts[&(path.clone(), *hunk_index)] <= part_index
+                        })
+                        .map(|(_, hunk)| hunk.clone())
+                        .collect::<Vec<_>>();
+                    if hunks.is_empty() {
+                        None
+                    } else {
+                        Some((path.clone(), hunks))
+                    }
+                })
+                .collect::<HashMap<_, _>>();
+            write_tree_onto_tree(project_repository, &parent_tree, &diffs)?
+        };
+
+        let new_commit_oid = repo
+            .commit_with_signer(
+                &commit_to_split.author(),
+                &commit_to_split.committer(),
+                &part.message,
+                &repo.find_tree(tree_oid).context("failed to find tree")?,
+                &[&head],
+                signer,
+            )
+            .context("failed to create commit")?;
+        new_commit_oids.push(new_commit_oid);
+        head = repo
+            .find_commit(new_commit_oid)
+            .context("failed to find new commit")?;
+    }
+
+    branch.head = if branch.head == commit_oid {
+        head.id()
+    } else {
+        conflicts_as_none(cherry_rebase(
+            project_repository,
+            head.id(),
+            commit_oid,
+            branch.head,
+            signer,
+        ))?
+        .ok_or(errors::SplitCommitError::RebaseFailed)?
+    };
+    vb_state
+        .set_branch(branch.clone())
+        .context("failed to write branch")?;
+
+    restack_children(project_repository, &vb_state, &branch.id, signer)?;
+    super::integration::update_gitbutler_integration(&vb_state, project_repository)
+        .context("failed to update gitbutler integration")?;
+
+    Ok(new_commit_oids)
+}
+
+// takes a list of file ownership and a commit oid and rewrites that commit to
+// add the file changes. The branch is then rebased onto the new commit
+// and the respective branch head is updated
+pub fn amend(
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    commit_oid: git::Oid,
+    target_ownership: &BranchOwnershipClaims,
+    signer: Option<&git::Signer>,
+) -> Result<git::Oid, errors::VirtualBranchError> {
+    if conflicts::is_conflicting::<&Path>(project_repository, None)? {
+        return Err(errors::VirtualBranchError::Conflict(
+            errors::ProjectConflict {
+                project_id: project_repository.project().id,
+            },
+        ));
+    }
+
+    let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
+
+    let all_branches = vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?;
+
+    if !all_branches.iter().any(|b| b.id == *branch_id) {
+        return Err(errors::VirtualBranchError::BranchNotFound(
+            errors::BranchNotFound {
+                project_id: project_repository.project().id,
+                branch_id: *branch_id,
+            },
+        ));
+    }
+
+    let applied_branches = all_branches
+        .into_iter()
+        .filter(|b| b.applied)
+        .collect::<Vec<_>>();
+
+    if !applied_branches.iter().any(|b| b.id == *branch_id) {
+        return Err(errors::VirtualBranchError::BranchNotFound(
+            errors::BranchNotFound {
+                project_id: project_repository.project().id,
+                branch_id: *branch_id,
+            },
+        ));
+    }
+
+    let default_target = get_default_target(&vb_state)
+        .context("failed to read default target")?
+        .ok_or_else(|| {
+            errors::VirtualBranchError::DefaultTargetNotSet(errors::DefaultTargetNotSet {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let integration_commit_id =
+        super::integration::get_workspace_head(&vb_state, project_repository)?;
+
+    let (mut applied_statuses, _) = get_applied_status(
+        project_repository,
         &integration_commit_id,
         &default_target.sha,
         applied_branches,
@@ -3057,32 +3694,7 @@ pub fn amend(
         .find_commit(commit_oid)
         .context("failed to find commit")?;
 
-    let diffs_to_amend = target_ownership
-        .claims
-        .iter()
-        .filter_map(|file_ownership| {
-            let hunks = target_status
-                .get(&file_ownership.file_path)
-                .map(|hunks| {
-                    hunks
-                        .iter()
-                        .filter(|hunk| {
-                            file_ownership.hunks.iter().any(|owned_hunk| {
-                                owned_hunk.start == hunk.new_start
-                                    && owned_hunk.end == hunk.new_start + hunk.new_lines
-                            })
-                        })
-                        .cloned()
-                        .collect::<Vec<_>>()
-                })
-                .unwrap_or_default();
-            if hunks.is_empty() {
-                None
-            } else {
-                Some((file_ownership.file_path.clone(), hunks))
-            }
-        })
-        .collect::<HashMap<_, _>>();
+    let diffs_to_amend = owned_diffs(target_status, target_ownership);
 
     if diffs_to_amend.is_empty() {
         return Err(errors::VirtualBranchError::TargetOwnerhshipNotFound(
@@ -3103,13 +3715,13 @@ pub fn amend(
 
     let commit_oid = project_repository
         .git_repository
-        .commit(
-            None,
+        .commit_with_signer(
             &amend_commit.author(),
             &amend_commit.committer(),
             &amend_commit.message().to_str_lossy(),
             &new_tree,
             &parents.iter().collect::<Vec<_>>(),
+            signer,
         )
         .context("failed to create commit")?;
 
@@ -3122,6 +3734,7 @@ pub fn amend(
     if upstream_commits.is_empty() {
         target_branch.head = commit_oid;
         vb_state.set_branch(target_branch.clone())?;
+        restack_children(project_repository, &vb_state, &target_branch.id, signer)?;
         super::integration::update_gitbutler_integration(&vb_state, project_repository)?;
         return Ok(commit_oid);
     }
@@ -3133,11 +3746,13 @@ pub fn amend(
         commit_oid,
         amend_commit.id(),
         last_commit,
+        signer,
     )?;
 
     if let Some(new_head) = new_head {
         target_branch.head = new_head;
         vb_state.set_branch(target_branch.clone())?;
+        restack_children(project_repository, &vb_state, &target_branch.id, signer)?;
         super::integration::update_gitbutler_integration(&vb_state, project_repository)?;
         Ok(commit_oid)
     } else {
@@ -3145,69 +3760,373 @@ pub fn amend(
     }
 }
 
-// move a given commit in a branch up one or down one
-// if the offset is positive, move the commit down one
-// if the offset is negative, move the commit up one
-// rewrites the branch head to the new head commit
-pub fn reorder_commit(
+/// Where an uncommitted hunk went when absorbing the changes of a branch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AbsorbedHunk {
+    pub file_path: PathBuf,
+    /// The id of the hunk, like [`VirtualBranchHunk::id`].
+    pub hunk_id: String,
+    /// The commit the hunk was amended into, as it is after absorbing, or `None` if it was left
+    /// alone because it doesn't belong to a single commit of the branch that may be rewritten.
+    pub commit_id: Option<git::Oid>,
+}
+
+/// Amends every uncommitted hunk of the branch into the commit of the branch that introduced
+/// all of the lines it changes or removes. Hunks that only add lines, or whose lines come from
+/// several commits, commits of other branches or pushed commits that can't be force pushed,
+/// are left alone.
+///
+/// The branch is only rewritten once every hunk was absorbed without conflicts.
+pub fn absorb(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
-    commit_oid: git::Oid,
-    offset: i32,
-) -> Result<(), errors::VirtualBranchError> {
+    signer: Option<&git::Signer>,
+) -> Result<Vec<AbsorbedHunk>, errors::AbsorbError> {
+    if conflicts::is_conflicting::<&Path>(project_repository, None)? {
+        return Err(errors::AbsorbError::Conflict(errors::ProjectConflict {
+            project_id: project_repository.project().id,
+        }));
+    }
+
     let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
 
     let default_target = get_default_target(&vb_state)
         .context("failed to read default target")?
         .ok_or_else(|| {
-            errors::VirtualBranchError::DefaultTargetNotSet(errors::DefaultTargetNotSet {
+            errors::AbsorbError::DefaultTargetNotSet(errors::DefaultTargetNotSet {
                 project_id: project_repository.project().id,
             })
         })?;
 
-    let mut branch = match vb_state.get_branch(branch_id) {
-        Ok(branch) => Ok(branch),
-        Err(reader::Error::NotFound) => Err(errors::VirtualBranchError::BranchNotFound(
-            errors::BranchNotFound {
-                branch_id: *branch_id,
+    let applied_branches = vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .filter(|branch| branch.applied)
+        .collect::<Vec<_>>();
+
+    let integration_commit_id = get_workspace_head(&vb_state, project_repository)?;
+    let (applied_statuses, _) = get_applied_status(
+        project_repository,
+        &integration_commit_id,
+        &default_target.sha,
+        applied_branches,
+    )?;
+
+    let (mut branch, branch_status) = applied_statuses
+        .into_iter()
+        .find(|(branch, _)| branch.id == *branch_id)
+        .ok_or_else(|| {
+            errors::AbsorbError::BranchNotFound(errors::BranchNotFound {
                 project_id: project_repository.project().id,
-            },
-        )),
-        Err(error) => Err(errors::VirtualBranchError::Other(error.into())),
-    }?;
+                branch_id: *branch_id,
+            })
+        })?;
 
-    // find the commit to offset from
-    let commit = project_repository
-        .git_repository
-        .find_commit(commit_oid)
-        .context("failed to find commit")?;
+    let branch_base = branch.base(default_target.sha);
+    let branch_commit_oids = project_repository.l(
+        branch.head,
+        project_repository::LogUntil::Commit(branch_base),
+    )?;
 
-    let parent = commit.parent(0).context("failed to find parent")?;
-    let parent_oid = parent.id();
+    let pushed_commit_oids = if *project_repository.project().ok_with_force_push {
+        vec![]
+    } else {
+        branch.upstream_head.map_or_else(
+            || Ok(vec![]),
+            |upstream_head| {
+                project_repository.l(
+                    upstream_head,
+                    project_repository::LogUntil::Commit(default_target.sha),
+                )
+            },
+        )?
+    };
 
-    if offset < 0 {
-        // move commit up
-        if branch.head == commit_oid {
-            // can't move the head commit up
-            return Ok(());
+    let mut absorbed_hunks = vec![];
+    let mut hunks_by_commit = HashMap::<git::Oid, BranchStatus>::new();
+    let mut branch_status = branch_status.into_iter().collect::<Vec<_>>();
+    branch_status.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (path, hunks) in branch_status {
+        for hunk in hunks {
+            let commit_id = blamed_commit(
+                project_repository,
+                &path,
+                &hunk,
+                &default_target.sha,
+                &integration_commit_id,
+            )
+            .filter(|commit_id| {
+                branch_commit_oids.contains(commit_id) && !pushed_commit_oids.contains(commit_id)
+            });
+            absorbed_hunks.push(AbsorbedHunk {
+                file_path: path.clone(),
+                hunk_id: VirtualBranchHunk::gen_id(hunk.new_start, hunk.new_lines),
+                commit_id,
+            });
+            if let Some(commit_id) = commit_id {
+                hunks_by_commit
+                    .entry(commit_id)
+                    .or_default()
+                    .entry(path.clone())
+                    .or_default()
+                    .push(hunk);
+            }
         }
+    }
 
-        // get a list of the commits to rebase
-        let mut ids_to_rebase = project_repository.l(
-            branch.head,
-            project_repository::LogUntil::Commit(commit.id()),
-        )?;
-        let last_oid = ids_to_rebase.pop().context("failed to pop last oid")?;
-        ids_to_rebase.push(commit_oid);
-        ids_to_rebase.push(last_oid);
+    if hunks_by_commit.is_empty() {
+        return Ok(absorbed_hunks);
+    }
 
-        match cherry_rebase_group(project_repository, parent_oid, &mut ids_to_rebase) {
-            Ok(Some(new_head)) => {
-                branch.head = new_head;
-                vb_state
+    let repo = &project_repository.git_repository;
+    let head_commit = repo
+        .find_commit(branch.head)
+        .context("failed to find head")?;
+
+    // the hunks of every commit are committed on top of the branch first, and then cherry-picked
+    // into that commit, so that they still apply if the lines moved since
+    let fixup_commits = hunks_by_commit
+        .iter()
+        .map(|(commit_id, hunks)| {
+            let tree_oid = write_tree_onto_commit(project_repository, branch.head, hunks)?;
+            let fixup_commit_oid = repo
+                .commit_with_signer(
+                    &head_commit.author(),
+                    &head_commit.committer(),
+                    &format!("fixup! {}", commit_id),
+                    &repo.find_tree(tree_oid).context("failed to find tree")?,
+                    &[&head_commit],
+                    None,
+                )
+                .context("failed to create fixup commit")?;
+            Ok((*commit_id, repo.find_commit(fixup_commit_oid)?))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    let cherry_pick_tree = |head: &git::Commit, commit: &git::Commit| {
+        let mut cherrypick_index = repo
+            .cherry_pick(head, commit)
+            .context("failed to cherry pick")?;
+        if cherrypick_index.has_conflicts() {
+            return Err(errors::AbsorbError::RebaseFailed);
+        }
+        let tree_oid = cherrypick_index
+            .write_tree_to(repo)
+            .context("failed to write merge tree")?;
+        Ok(repo.find_tree(tree_oid).context("failed to find tree")?)
+    };
+
+    let mut new_head = repo
+        .find_commit(branch_base)
+        .context("failed to find branch base")?;
+    let mut new_commit_oids = HashMap::new();
+    for commit_oid in branch_commit_oids.iter().rev() {
+        let commit = repo
+            .find_commit(*commit_oid)
+            .context("failed to find commit")?;
+        let fixup_commit = fixup_commits.get(commit_oid);
+        if fixup_commit.is_none() && is_child_of(&commit, &new_head) {
+            new_head = commit;
+            continue;
+        }
+
+        let rebased_commit = if is_child_of(&commit, &new_head) {
+            commit
+        } else {
+            let rebased_commit_oid = repo
+                .commit_with_signer(
+                    &commit.author(),
+                    &commit.committer(),
+                    &commit.message().to_str_lossy(),
+                    &cherry_pick_tree(&new_head, &commit)?,
+                    &[&new_head],
+                    signer,
+                )
+                .context("failed to create commit")?;
+            repo.find_commit(rebased_commit_oid)
+                .context("failed to find commit")?
+        };
+
+        new_head = match fixup_commit {
+            Some(fixup_commit) => {
+                let new_commit_oid = repo
+                    .commit_with_signer(
+                        &rebased_commit.author(),
+                        &rebased_commit.committer(),
+                        &rebased_commit.message().to_str_lossy(),
+                        &cherry_pick_tree(&rebased_commit, fixup_commit)?,
+                        &[&new_head],
+                        signer,
+                    )
+                    .context("failed to create commit")?;
+                new_commit_oids.insert(*commit_oid, new_commit_oid);
+                repo.find_commit(new_commit_oid)
+                    .context("failed to find commit")?
+            }
+            None => rebased_commit,
+        };
+    }
+
+    for absorbed_hunk in &mut absorbed_hunks {
+        absorbed_hunk.commit_id = absorbed_hunk
+            .commit_id
+            .and_then(|commit_id| new_commit_oids.get(&commit_id).copied());
+    }
+
+    branch.head = new_head.id();
+    vb_state
+        .set_branch(branch.clone())
+        .context("failed to write branch")?;
+
+    restack_children(project_repository, &vb_state, &branch.id, signer)?;
+    super::integration::update_gitbutler_integration(&vb_state, project_repository)
+        .context("failed to update gitbutler integration")?;
+
+    Ok(absorbed_hunks)
+}
+
+/// The commit between `oldest_commit` and `newest_commit` that introduced every line `hunk`
+/// changes or removes, if there is exactly one.
+fn blamed_commit(
+    project_repository: &project_repository::Repository,
+    path: &Path,
+    hunk: &diff::GitHunk,
+    oldest_commit: &git::Oid,
+    newest_commit: &git::Oid,
+) -> Option<git::Oid> {
+    if hunk.binary {
+        return None;
+    }
+
+    let mut removed_lines = vec![];
+    let mut old_line = hunk.old_start;
+    for line in hunk
+        .diff_lines
+        .lines()
+        .filter(|line| !line.starts_with(b"@@"))
+    {
+        match line.first() {
+            Some(b'-') => {
+                removed_lines.push(old_line);
+                old_line += 1;
+            }
+            Some(b' ') => old_line += 1,
+            _ => {}
+        }
+    }
+
+    let blame = project_repository
+        .git_repository
+        .blame(
+            path,
+            *removed_lines.first()?,
+            *removed_lines.last()?,
+            oldest_commit,
+            newest_commit,
+        )
+        .ok()?;
+    let mut commit_ids = removed_lines.iter().map(|line| {
+        blame
+            .get_line(*line as usize)
+            .map(|blame_hunk| git::Oid::from(blame_hunk.orig_commit_id()))
+    });
+    let commit_id = commit_ids.next()??;
+    commit_ids
+        .all(|other_commit_id| other_commit_id == Some(commit_id))
+        .then_some(commit_id)
+}
+
+/// The parts of the uncommitted changes in `status` that `ownership` claims.
+fn owned_diffs(status: &BranchStatus, ownership: &BranchOwnershipClaims) -> BranchStatus {
+    ownership
+        .claims
+        .iter()
+        .filter_map(|file_ownership| {
+            let hunks = status
+                .get(&file_ownership.file_path)
+                .map(|hunks| {
+                    hunks
+                        .iter()
+                        .filter_map(|hunk| owned_part_of_hunk(hunk, &file_ownership.hunks))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            if hunks.is_empty() {
+                None
+            } else {
+                Some((file_ownership.file_path.clone(), hunks))
+            }
+        })
+        .collect()
+}
+
+// move a given commit in a branch up one or down one
+// if the offset is positive, move the commit down one
+// if the offset is negative, move the commit up one
+// rewrites the branch head to the new head commit
+pub fn reorder_commit(
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    commit_oid: git::Oid,
+    offset: i32,
+    signer: Option<&git::Signer>,
+) -> Result<(), errors::VirtualBranchError> {
+    let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
+
+    let default_target = get_default_target(&vb_state)
+        .context("failed to read default target")?
+        .ok_or_else(|| {
+            errors::VirtualBranchError::DefaultTargetNotSet(errors::DefaultTargetNotSet {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let mut branch = match vb_state.get_branch(branch_id) {
+        Ok(branch) => Ok(branch),
+        Err(reader::Error::NotFound) => Err(errors::VirtualBranchError::BranchNotFound(
+            errors::BranchNotFound {
+                branch_id: *branch_id,
+                project_id: project_repository.project().id,
+            },
+        )),
+        Err(error) => Err(errors::VirtualBranchError::Other(error.into())),
+    }?;
+
+    // find the commit to offset from
+    let commit = project_repository
+        .git_repository
+        .find_commit(commit_oid)
+        .context("failed to find commit")?;
+
+    let parent = commit.parent(0).context("failed to find parent")?;
+    let parent_oid = parent.id();
+
+    if offset < 0 {
+        // move commit up
+        if branch.head == commit_oid {
+            // can't move the head commit up
+            return Ok(());
+        }
+
+        // get a list of the commits to rebase
+        let mut ids_to_rebase = project_repository.l(
+            branch.head,
+            project_repository::LogUntil::Commit(commit.id()),
+        )?;
+        let last_oid = ids_to_rebase.pop().context("failed to pop last oid")?;
+        ids_to_rebase.push(commit_oid);
+        ids_to_rebase.push(last_oid);
+
+        match cherry_rebase_group(project_repository, parent_oid, &mut ids_to_rebase, signer) {
+            Ok(Some(new_head)) => {
+                branch.head = new_head;
+                vb_state
                     .set_branch(branch.clone())
                     .context("failed to write branch")?;
 
+                restack_children(project_repository, &vb_state, &branch.id, signer)?;
                 super::integration::update_gitbutler_integration(&vb_state, project_repository)
                     .context("failed to update gitbutler integration")?;
             }
@@ -3233,13 +4152,14 @@ pub fn reorder_commit(
         ids_to_rebase.push(parent_oid);
         ids_to_rebase.push(commit_oid);
 
-        match cherry_rebase_group(project_repository, target_oid, &mut ids_to_rebase) {
+        match cherry_rebase_group(project_repository, target_oid, &mut ids_to_rebase, signer) {
             Ok(Some(new_head)) => {
                 branch.head = new_head;
                 vb_state
                     .set_branch(branch.clone())
                     .context("failed to write branch")?;
 
+                restack_children(project_repository, &vb_state, &branch.id, signer)?;
                 super::integration::update_gitbutler_integration(&vb_state, project_repository)
                     .context("failed to update gitbutler integration")?;
             }
@@ -3261,6 +4181,7 @@ pub fn insert_blank_commit(
     commit_oid: git::Oid,
     user: Option<&users::User>,
     offset: i32,
+    signer: Option<&git::Signer>,
 ) -> Result<(), errors::VirtualBranchError> {
     let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
 
@@ -3294,6 +4215,7 @@ pub fn insert_blank_commit(
         vb_state
             .set_branch(branch.clone())
             .context("failed to write branch")?;
+        restack_children(project_repository, &vb_state, &branch.id, signer)?;
         super::integration::update_gitbutler_integration(&vb_state, project_repository)
             .context("failed to update gitbutler integration")?;
     } else {
@@ -3303,6 +4225,7 @@ pub fn insert_blank_commit(
             blank_commit_oid,
             commit.id(),
             branch.head,
+            signer,
         ) {
             Ok(Some(new_head)) => {
                 branch.head = new_head;
@@ -3310,6 +4233,7 @@ pub fn insert_blank_commit(
                     .set_branch(branch.clone())
                     .context("failed to write branch")?;
 
+                restack_children(project_repository, &vb_state, &branch.id, signer)?;
                 super::integration::update_gitbutler_integration(&vb_state, project_repository)
                     .context("failed to update gitbutler integration")?;
             }
@@ -3328,6 +4252,7 @@ pub fn undo_commit(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
     commit_oid: git::Oid,
+    signer: Option<&git::Signer>,
 ) -> Result<(), errors::VirtualBranchError> {
     let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
 
@@ -3361,6 +4286,7 @@ pub fn undo_commit(
             parent_commit_oid,
             commit_oid,
             branch.head,
+            signer,
         ) {
             Ok(Some(new_head)) => {
                 new_commit_oid = new_head;
@@ -3377,6 +4303,7 @@ pub fn undo_commit(
             .set_branch(branch.clone())
             .context("failed to write branch")?;
 
+        restack_children(project_repository, &vb_state, &branch.id, signer)?;
         super::integration::update_gitbutler_integration(&vb_state, project_repository)
             .context("failed to update gitbutler integration")?;
     }
@@ -3392,6 +4319,7 @@ pub fn cherry_rebase(
     target_commit_oid: git::Oid,
     start_commit_oid: git::Oid,
     end_commit_oid: git::Oid,
+    signer: Option<&git::Signer>,
 ) -> Result<Option<git::Oid>, anyhow::Error> {
     // get a list of the commits to rebase
     let mut ids_to_rebase = project_repository.l(
@@ -3403,12 +4331,164 @@ pub fn cherry_rebase(
         return Ok(None);
     }
 
-    let new_head_id =
-        cherry_rebase_group(project_repository, target_commit_oid, &mut ids_to_rebase)?;
+    let new_head_id = cherry_rebase_group(
+        project_repository,
+        target_commit_oid,
+        &mut ids_to_rebase,
+        signer,
+    )?;
 
     Ok(new_head_id)
 }
 
+/// Rebase the applied branches stacked on `parent_id`, directly or on top of each other, onto the
+/// current head of their parent, bottom of the stack first so changes propagate all the way up.
+///
+/// This is for operations that move the head of `parent_id`. Branches that can't be rebased cleanly,
+/// or that were pushed while force-pushing isn't allowed, are left where they are, and so is
+/// everything stacked on them.
+pub(crate) fn restack_children(
+    project_repository: &project_repository::Repository,
+    vb_state: &VirtualBranchesHandle,
+    parent_id: &BranchId,
+    signer: Option<&git::Signer>,
+) -> Result<()> {
+    let mut branches = vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .map(|branch| (branch.id, branch))
+        .collect::<HashMap<_, _>>();
+    let mut restacked = HashSet::from([*parent_id]);
+
+    for branch_id in stack_order(branches.values()) {
+        let mut branch = branches[&branch_id].clone();
+        let Some(parent_head) = branch
+            .parent
+            .filter(|parent_id| restacked.contains(parent_id))
+            .and_then(|parent_id| branches.get(&parent_id))
+            .map(|parent| parent.head)
+        else {
+            continue;
+        };
+        if !branch.applied {
+            continue;
+        }
+        if branch.parent_head == Some(parent_head) {
+            continue;
+        }
+        if branch.upstream.is_some() && !project_repository.project().ok_with_force_push {
+            tracing::warn!(
+                project_id = %project_repository.project().id,
+                branch_id = %branch.id,
+                "not restacking pushed branch as force pushing is not allowed"
+            );
+            continue;
+        }
+
+        if restack_branch(project_repository, &mut branch, parent_head, signer)? {
+            vb_state
+                .set_branch(branch.clone())
+                .context("failed to write branch")?;
+            restacked.insert(branch.id);
+            branches.insert(branch.id, branch);
+        } else {
+            tracing::warn!(
+                project_id = %project_repository.project().id,
+                branch_id = %branch.id,
+                "failed to restack branch onto its parent"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// The ids of `branches`, ordered so that every stacked branch comes after its parent.
+pub(crate) fn stack_order<'a>(branches: impl IntoIterator<Item = &'a Branch>) -> Vec<BranchId> {
+    let parents = branches
+        .into_iter()
+        .map(|branch| (branch.id, branch.parent))
+        .collect::<HashMap<_, _>>();
+    let depth = |mut id: BranchId| {
+        let mut depth = 0;
+        // bounded, so that a cycle can't hang us
+        while let Some(Some(parent_id)) = parents.get(&id) {
+            if depth >= parents.len() {
+                break;
+            }
+            depth += 1;
+            id = *parent_id;
+        }
+        depth
+    };
+    let mut ids = parents.keys().copied().collect::<Vec<_>>();
+    ids.sort_by_key(|id| (depth(*id), *id));
+    ids
+}
+
+/// Rebase the commits `branch` owns onto `parent_head`, and move its uncommitted changes along.
+///
+/// Returns `false` if the commits can't be rebased without conflicts, leaving `branch` untouched.
+pub(crate) fn restack_branch(
+    project_repository: &project_repository::Repository,
+    branch: &mut Branch,
+    parent_head: git::Oid,
+    signer: Option<&git::Signer>,
+) -> Result<bool> {
+    if branch.parent_head == Some(parent_head) {
+        return Ok(true);
+    }
+
+    let repo = &project_repository.git_repository;
+    let base = branch.parent_head.unwrap_or(branch.head);
+
+    let new_head = if branch.head == base {
+        parent_head
+    } else {
+        let Some(new_head) = conflicts_as_none(cherry_rebase(
+            project_repository,
+            parent_head,
+            base,
+            branch.head,
+            signer,
+        ))?
+        else {
+            return Ok(false);
+        };
+        new_head
+    };
+
+    let old_head_tree = repo.find_commit(branch.head)?.tree()?;
+    let new_head_tree = repo.find_commit(new_head)?.tree()?;
+    let branch_tree = repo.find_tree(branch.tree)?;
+    let mut merge_index = repo.merge_trees(&old_head_tree, &branch_tree, &new_head_tree)?;
+    // the uncommitted changes are recomputed from the worktree anyway, so on conflict
+    // it's fine to start over from the new head
+    branch.tree = if merge_index.has_conflicts() {
+        new_head_tree.id()
+    } else {
+        merge_index.write_tree_to(repo)?
+    };
+    branch.head = new_head;
+    branch.parent_head = Some(parent_head);
+
+    Ok(true)
+}
+
+/// The error of [`cherry_rebase_group`] when a commit doesn't apply cleanly.
+#[derive(Debug, thiserror::Error)]
+#[error("failed to rebase")]
+struct RebaseConflict;
+
+/// The `result` of a rebase with conflicts turned into `Ok(None)`, so that only real errors remain.
+fn conflicts_as_none(result: Result<Option<git::Oid>>) -> Result<Option<git::Oid>> {
+    match result {
+        Err(error) if error.downcast_ref::<RebaseConflict>().is_some() => Ok(None),
+        result => result,
+    }
+}
+
 // takes a vector of commit oids and rebases them onto a target commit and returns the
 // new head commit oid if it's successful
 // the difference between this and a libgit2 based rebase is that this will successfully
@@ -3417,6 +4497,7 @@ fn cherry_rebase_group(
     project_repository: &project_repository::Repository,
     target_commit_oid: git::Oid,
     ids_to_rebase: &mut [git::Oid],
+    signer: Option<&git::Signer>,
 ) -> Result<Option<git::Oid>, anyhow::Error> {
     ids_to_rebase.reverse();
     // now, rebase unchanged commits onto the new commit
@@ -3442,7 +4523,7 @@ fn cherry_rebase_group(
                     .context("failed to cherry pick")?;
 
                 if cherrypick_index.has_conflicts() {
-                    bail!("failed to rebase");
+                    return Err(RebaseConflict.into());
                 }
 
                 let merge_tree_oid = cherrypick_index
@@ -3456,13 +4537,13 @@ fn cherry_rebase_group(
 
                 let commit_oid = project_repository
                     .git_repository
-                    .commit(
-                        None,
+                    .commit_with_signer(
                         &to_rebase.author(),
                         &to_rebase.committer(),
                         &to_rebase.message().to_str_lossy(),
                         &merge_tree,
                         &[&head],
+                        signer,
                     )
                     .context("failed to create commit")?;
 
@@ -3535,6 +4616,7 @@ pub fn cherry_pick(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
     target_commit_oid: git::Oid,
+    signer: Option<&git::Signer>,
 ) -> Result<Option<git::Oid>, errors::CherryPickError> {
     if conflicts::is_conflicting::<&Path>(project_repository, None)? {
         return Err(errors::CherryPickError::Conflict(errors::ProjectConflict {
@@ -3606,13 +4688,13 @@ pub fn cherry_pick(
             .context("failed to make gb signature")?;
         let oid = project_repository
             .git_repository
-            .commit(
-                None,
+            .commit_with_signer(
                 &signature,
                 &signature,
                 "wip cherry picking commit",
                 &wip_tree,
                 &[&branch_head_commit],
+                signer,
             )
             .context("failed to commit wip work")?;
         project_repository
@@ -3626,13 +4708,17 @@ pub fn cherry_pick(
         .cherry_pick(&wip_commit, &target_commit)
         .context("failed to cherry pick")?;
 
-    // unapply other branches
-    for other_branch in applied_statuses
-        .iter()
-        .filter(|(b, _)| b.id != branch.id)
-        .map(|(b, _)| b)
+    // unapply other branches, stacked ones before their parents
+    for other_branch_id in stack_order(
+        applied_statuses
+            .iter()
+            .filter(|(b, _)| b.id != branch.id)
+            .map(|(b, _)| b),
+    )
+    .into_iter()
+    .rev()
     {
-        unapply_branch(project_repository, &other_branch.id).context("failed to unapply branch")?;
+        unapply_branch(project_repository, &other_branch_id).context("failed to unapply branch")?;
     }
 
     let commit_oid = if cherrypick_index.has_conflicts() {
@@ -3647,19 +4733,8 @@ pub fn cherry_pick(
             .context("failed to checkout conflicts")?;
 
         // mark conflicts
-        let conflicts = cherrypick_index
-            .conflicts()
-            .context("failed to get conflicts")?;
-        let mut merge_conflicts = Vec::new();
-        for path in conflicts.flatten() {
-            if let Some(ours) = path.our {
-                let path = std::str::from_utf8(&ours.path)
-                    .context("failed to convert path")?
-                    .to_string();
-                merge_conflicts.push(path);
-            }
-        }
-        conflicts::mark(project_repository, &merge_conflicts, Some(branch.head))?;
+        let merge_conflicts = conflicts::from_index(&cherrypick_index, Some(branch.id))?;
+        conflicts::mark(project_repository, merge_conflicts, Some(branch.head))?;
 
         None
     } else {
@@ -3678,13 +4753,13 @@ pub fn cherry_pick(
 
         let commit_oid = project_repository
             .git_repository
-            .commit(
-                None,
+            .commit_with_signer(
                 &target_commit.author(),
                 &target_commit.committer(),
                 &target_commit.message().to_str_lossy(),
                 &merge_tree,
                 &[&branch_head_commit],
+                signer,
             )
             .context("failed to create commit")?;
 
@@ -3706,6 +4781,7 @@ pub fn cherry_pick(
         Some(commit_oid)
     };
 
+    restack_children(project_repository, &vb_state, &branch.id, signer)?;
     super::integration::update_gitbutler_integration(&vb_state, project_repository)
         .context("failed to update gitbutler integration")?;
 
@@ -3717,6 +4793,7 @@ pub fn squash(
     project_repository: &project_repository::Repository,
     branch_id: &BranchId,
     commit_oid: git::Oid,
+    signer: Option<&git::Signer>,
 ) -> Result<(), errors::SquashError> {
     if conflicts::is_conflicting::<&Path>(project_repository, None)? {
         return Err(errors::SquashError::Conflict(errors::ProjectConflict {
@@ -3748,7 +4825,7 @@ pub fn squash(
 
     let branch_commit_oids = project_repository.l(
         branch.head,
-        project_repository::LogUntil::Commit(default_target.sha),
+        project_repository::LogUntil::Commit(branch.base(default_target.sha)),
     )?;
 
     if !branch_commit_oids.contains(&commit_oid) {
@@ -3799,8 +4876,7 @@ pub fn squash(
 
     let new_commit_oid = project_repository
         .git_repository
-        .commit(
-            None,
+        .commit_with_signer(
             &commit_to_squash.author(),
             &commit_to_squash.committer(),
             &format!(
@@ -3810,6 +4886,7 @@ pub fn squash(
             ),
             &commit_to_squash.tree().context("failed to find tree")?,
             &parents.iter().collect::<Vec<_>>(),
+            signer,
         )
         .context("failed to commit")?;
 
@@ -3822,7 +4899,12 @@ pub fn squash(
     .ok_or(errors::SquashError::CommitNotFound(commit_oid))?;
     let mut ids_to_rebase = ids_to_rebase.to_vec();
 
-    match cherry_rebase_group(project_repository, new_commit_oid, &mut ids_to_rebase) {
+    match cherry_rebase_group(
+        project_repository,
+        new_commit_oid,
+        &mut ids_to_rebase,
+        signer,
+    ) {
         Ok(Some(new_head_id)) => {
             // save new branch head
             branch.head = new_head_id;
@@ -3830,6 +4912,7 @@ pub fn squash(
                 .set_branch(branch.clone())
                 .context("failed to write branch")?;
 
+            restack_children(project_repository, &vb_state, &branch.id, signer)?;
             super::integration::update_gitbutler_integration(&vb_state, project_repository)
                 .context("failed to update gitbutler integration")?;
             Ok(())
@@ -3844,6 +4927,7 @@ pub fn update_commit_message(
     branch_id: &BranchId,
     commit_oid: git::Oid,
     message: &str,
+    signer: Option<&git::Signer>,
 ) -> Result<(), errors::UpdateCommitMessageError> {
     if message.is_empty() {
         return Err(errors::UpdateCommitMessageError::EmptyMessage);
@@ -3881,7 +4965,7 @@ pub fn update_commit_message(
 
     let branch_commit_oids = project_repository.l(
         branch.head,
-        project_repository::LogUntil::Commit(default_target.sha),
+        project_repository::LogUntil::Commit(branch.base(default_target.sha)),
     )?;
 
     if !branch_commit_oids.contains(&commit_oid) {
@@ -3919,53 +5003,1120 @@ pub fn update_commit_message(
 
     let new_commit_oid = project_repository
         .git_repository
-        .commit(
-            None,
+        .commit_with_signer(
             &target_commit.author(),
             &target_commit.committer(),
             message,
             &target_commit.tree().context("failed to find tree")?,
             &parents.iter().collect::<Vec<_>>(),
+            signer,
         )
         .context("failed to commit")?;
 
-    let ids_to_rebase = {
-        let ids = branch_commit_oids
-            .split(|oid| oid.eq(&commit_oid))
-            .collect::<Vec<_>>();
-        ids.first().copied()
-    }
-    .ok_or(errors::UpdateCommitMessageError::CommitNotFound(commit_oid))?;
-    let mut ids_to_rebase = ids_to_rebase.to_vec();
+    let ids_to_rebase = {
+        let ids = branch_commit_oids
+            .split(|oid| oid.eq(&commit_oid))
+            .collect::<Vec<_>>();
+        ids.first().copied()
+    }
+    .ok_or(errors::UpdateCommitMessageError::CommitNotFound(commit_oid))?;
+    let mut ids_to_rebase = ids_to_rebase.to_vec();
+
+    match cherry_rebase_group(
+        project_repository,
+        new_commit_oid,
+        &mut ids_to_rebase,
+        signer,
+    ) {
+        Ok(Some(new_head_id)) => {
+            // save new branch head
+            branch.head = new_head_id;
+            vb_state
+                .set_branch(branch.clone())
+                .context("failed to write branch")?;
+
+            restack_children(project_repository, &vb_state, &branch.id, signer)?;
+            super::integration::update_gitbutler_integration(&vb_state, project_repository)
+                .context("failed to update gitbutler integration")?;
+            Ok(())
+        }
+        _ => Err(errors::UpdateCommitMessageError::Other(anyhow!(
+            "rebase error"
+        ))),
+    }
+}
+
+/// What to do with a commit of a branch when editing its history, like the commands of
+/// `git rebase --interactive`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "action",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum HistoryStep {
+    /// Keep the commit as it is.
+    Pick { commit_id: git::Oid },
+    /// Keep the commit with another message.
+    Reword {
+        commit_id: git::Oid,
+        message: String,
+    },
+    /// Meld the commit into the one before it, with `message`, or with both of their messages.
+    Squash {
+        commit_id: git::Oid,
+        message: Option<String>,
+    },
+    /// Meld the commit into the one before it, keeping the message of that one.
+    Fixup { commit_id: git::Oid },
+    /// Leave the commit out.
+    Drop { commit_id: git::Oid },
+    /// Keep the commit, amended with the uncommitted changes of the branch that `ownership` claims.
+    Edit {
+        commit_id: git::Oid,
+        ownership: BranchOwnershipClaims,
+    },
+}
+
+impl HistoryStep {
+    pub fn commit_id(&self) -> git::Oid {
+        match self {
+            HistoryStep::Pick { commit_id }
+            | HistoryStep::Reword { commit_id, .. }
+            | HistoryStep::Squash { commit_id, .. }
+            | HistoryStep::Fixup { commit_id }
+            | HistoryStep::Drop { commit_id }
+            | HistoryStep::Edit { commit_id, .. } => *commit_id,
+        }
+    }
+}
+
+/// Rewrites the commits of the branch according to `plan`, which lists every one of them
+/// oldest first, in the order they should end up in.
+///
+/// The new commits are created before the branch is touched, so if any step conflicts the
+/// branch is left as it was. Returns the new head of the branch.
+pub fn edit_history(
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    plan: &[HistoryStep],
+    signer: Option<&git::Signer>,
+) -> Result<git::Oid, errors::EditHistoryError> {
+    if conflicts::is_conflicting::<&Path>(project_repository, None)? {
+        return Err(errors::EditHistoryError::Conflict(
+            errors::ProjectConflict {
+                project_id: project_repository.project().id,
+            },
+        ));
+    }
+
+    let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
+
+    let default_target = get_default_target(&vb_state)
+        .context("failed to read default target")?
+        .ok_or_else(|| {
+            errors::EditHistoryError::DefaultTargetNotSet(errors::DefaultTargetNotSet {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let mut branch = vb_state
+        .get_branch(branch_id)
+        .map_err(|error| match error {
+            reader::Error::NotFound => {
+                errors::EditHistoryError::BranchNotFound(errors::BranchNotFound {
+                    project_id: project_repository.project().id,
+                    branch_id: *branch_id,
+                })
+            }
+            error => errors::EditHistoryError::Other(error.into()),
+        })?;
+
+    let branch_base = branch.base(default_target.sha);
+    let branch_commit_oids = project_repository.l(
+        branch.head,
+        project_repository::LogUntil::Commit(branch_base),
+    )?;
+
+    let mut planned_commit_oids = HashSet::new();
+    for step in plan {
+        let commit_id = step.commit_id();
+        if !branch_commit_oids.contains(&commit_id) {
+            return Err(errors::EditHistoryError::CommitNotFound(commit_id));
+        }
+        if !planned_commit_oids.insert(commit_id) {
+            return Err(errors::EditHistoryError::DuplicateCommit(commit_id));
+        }
+        if let HistoryStep::Reword { message, .. }
+        | HistoryStep::Squash {
+            message: Some(message),
+            ..
+        } = step
+        {
+            if message.is_empty() {
+                return Err(errors::EditHistoryError::EmptyMessage);
+            }
+        }
+    }
+    if let Some(missing_commit_oid) = branch_commit_oids
+        .iter()
+        .rev()
+        .find(|oid| !planned_commit_oids.contains(oid))
+    {
+        return Err(errors::EditHistoryError::MissingCommit(*missing_commit_oid));
+    }
+
+    let branch_status = if plan
+        .iter()
+        .any(|step| matches!(step, HistoryStep::Edit { .. }))
+    {
+        let applied_branches = vb_state
+            .list_branches()
+            .context("failed to read virtual branches")?
+            .into_iter()
+            .filter(|branch| branch.applied)
+            .collect::<Vec<_>>();
+        let integration_commit_id = get_workspace_head(&vb_state, project_repository)?;
+        let (applied_statuses, _) = get_applied_status(
+            project_repository,
+            &integration_commit_id,
+            &default_target.sha,
+            applied_branches,
+        )?;
+        applied_statuses
+            .into_iter()
+            .find(|(branch, _)| branch.id == *branch_id)
+            .map(|(_, status)| status)
+            .unwrap_or_default()
+    } else {
+        BranchStatus::default()
+    };
+
+    let repo = &project_repository.git_repository;
+
+    // the tree of `commit` applied on top of `head`
+    let pick_tree = |head: &git::Commit, commit: &git::Commit| {
+        if is_child_of(commit, head) {
+            return Ok(commit.tree_id());
+        }
+        let mut cherrypick_index = repo
+            .cherry_pick(head, commit)
+            .context("failed to cherry pick")?;
+        if cherrypick_index.has_conflicts() {
+            return Err(errors::EditHistoryError::StepConflict(commit.id()));
+        }
+        Ok(cherrypick_index
+            .write_tree_to(repo)
+            .context("failed to write merge tree")?)
+    };
+
+    let mut head = repo
+        .find_commit(branch_base)
+        .context("failed to find branch base")?;
+    let mut new_commit_oids = vec![];
+    for step in plan {
+        let commit = repo
+            .find_commit(step.commit_id())
+            .context("failed to find commit")?;
+
+        let new_commit_oid = match step {
+            HistoryStep::Drop { .. } => continue,
+            HistoryStep::Pick { .. } if is_child_of(&commit, &head) => {
+                // nothing changed below it, so it can stay as it is
+                commit.id()
+            }
+            HistoryStep::Pick { .. } | HistoryStep::Reword { .. } | HistoryStep::Edit { .. } => {
+                let mut tree_oid = pick_tree(&head, &commit)?;
+                if let HistoryStep::Edit { ownership, .. } = step {
+                    let diffs_to_amend = owned_diffs(&branch_status, ownership);
+                    if diffs_to_amend.is_empty() {
+                        return Err(errors::EditHistoryError::TargetOwnershipNotFound(
+                            ownership.clone(),
+                        ));
+                    }
+                    let tree = repo.find_tree(tree_oid).context("failed to find tree")?;
+                    tree_oid = write_tree_onto_tree(project_repository, &tree, &diffs_to_amend)?;
+                }
+                let message = match step {
+                    HistoryStep::Reword { message, .. } => message.clone(),
+                    _ => commit.message().to_str_lossy().into_owned(),
+                };
+                repo.commit_with_signer(
+                    &commit.author(),
+                    &commit.committer(),
+                    &message,
+                    &repo.find_tree(tree_oid).context("failed to find tree")?,
+                    &[&head],
+                    signer,
+                )
+                .context("failed to create commit")?
+            }
+            HistoryStep::Squash { .. } | HistoryStep::Fixup { .. } => {
+                if new_commit_oids.pop().is_none() {
+                    return Err(errors::EditHistoryError::NothingToSquashInto(commit.id()));
+                }
+                let tree_oid = pick_tree(&head, &commit)?;
+                let message = match step {
+                    HistoryStep::Squash {
+                        message: Some(message),
+                        ..
+                    } => message.clone(),
+                    HistoryStep::Squash { message: None, .. } => {
+                        format!("{}\n{}", head.message(), commit.message())
+                    }
+                    _ => head.message().to_str_lossy().into_owned(),
+                };
+                let parents = head.parents().context("failed to find parents")?;
+                repo.commit_with_signer(
+                    &head.author(),
+                    &head.committer(),
+                    &message,
+                    &repo.find_tree(tree_oid).context("failed to find tree")?,
+                    &parents.iter().collect::<Vec<_>>(),
+                    signer,
+                )
+                .context("failed to create commit")?
+            }
+        };
+
+        new_commit_oids.push(new_commit_oid);
+        head = repo
+            .find_commit(new_commit_oid)
+            .context("failed to find new commit")?;
+    }
+
+    let pushed_commit_oids = branch.upstream_head.map_or_else(
+        || Ok(vec![]),
+        |upstream_head| {
+            project_repository.l(
+                upstream_head,
+                project_repository::LogUntil::Commit(default_target.sha),
+            )
+        },
+    )?;
+    if !project_repository.project().ok_with_force_push
+        && branch_commit_oids
+            .iter()
+            .any(|oid| pushed_commit_oids.contains(oid) && !new_commit_oids.contains(oid))
+    {
+        // rewriting or dropping a pushed commit will cause a force push that is not allowed
+        return Err(errors::EditHistoryError::ForcePushNotAllowed(
+            errors::ForcePushNotAllowed {
+                project_id: project_repository.project().id,
+            },
+        ));
+    }
+
+    branch.head = head.id();
+    vb_state
+        .set_branch(branch.clone())
+        .context("failed to write branch")?;
+
+    restack_children(project_repository, &vb_state, &branch.id, signer)?;
+    super::integration::update_gitbutler_integration(&vb_state, project_repository)
+        .context("failed to update gitbutler integration")?;
+
+    Ok(branch.head)
+}
+
+fn is_child_of(commit: &git::Commit, parent: &git::Commit) -> bool {
+    commit.parent_count() == 1
+        && commit
+            .parent(0)
+            .map_or(false, |commit_parent| commit_parent.id() == parent.id())
+}
+
+/// moves commit from the branch it's in to the top of the target branch
+pub fn move_commit(
+    project_repository: &project_repository::Repository,
+    target_branch_id: &BranchId,
+    commit_oid: git::Oid,
+    user: Option<&users::User>,
+    signer: Option<&git::Signer>,
+) -> Result<(), errors::MoveCommitError> {
+    if project_repository.is_resolving() {
+        return Err(errors::MoveCommitError::Conflicted(
+            errors::ProjectConflict {
+                project_id: project_repository.project().id,
+            },
+        ));
+    }
+
+    let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
+
+    let applied_branches = vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .filter(|b| b.applied)
+        .collect::<Vec<_>>();
+
+    if !applied_branches.iter().any(|b| b.id == *target_branch_id) {
+        return Err(errors::MoveCommitError::BranchNotFound(
+            errors::BranchNotFound {
+                project_id: project_repository.project().id,
+                branch_id: *target_branch_id,
+            },
+        ));
+    }
+
+    let default_target = get_default_target(&vb_state)
+        .context("failed to get default target")?
+        .ok_or_else(|| {
+            errors::MoveCommitError::DefaultTargetNotSet(errors::DefaultTargetNotSet {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let integration_commit_id =
+        super::integration::get_workspace_head(&vb_state, project_repository)?;
+
+    let (mut applied_statuses, _) = get_applied_status(
+        project_repository,
+        &integration_commit_id,
+        &default_target.sha,
+        applied_branches,
+    )?;
+
+    let (ref mut source_branch, source_status) = applied_statuses
+        .iter_mut()
+        .find(|(b, _)| b.head == commit_oid)
+        .ok_or_else(|| errors::MoveCommitError::CommitNotFound(commit_oid))?;
+
+    let source_branch_non_comitted_files = source_status;
+
+    let source_branch_head = project_repository
+        .git_repository
+        .find_commit(commit_oid)
+        .context("failed to find commit")?;
+    let source_branch_head_parent = source_branch_head
+        .parent(0)
+        .context("failed to get parent commit")?;
+    let source_branch_head_tree = source_branch_head
+        .tree()
+        .context("failed to get commit tree")?;
+    let source_branch_head_parent_tree = source_branch_head_parent
+        .tree()
+        .context("failed to get parent tree")?;
+    let branch_head_diff = diff::trees(
+        &project_repository.git_repository,
+        &source_branch_head_parent_tree,
+        &source_branch_head_tree,
+    )?;
+
+    let branch_head_diff: HashMap<_, _> = diff::diff_files_into_hunks(branch_head_diff).collect();
+    let is_source_locked = source_branch_non_comitted_files
+        .iter()
+        .any(|(path, hunks)| {
+            branch_head_diff.get(path).map_or(false, |head_diff_hunks| {
+                hunks.iter().any(|hunk| {
+                    head_diff_hunks.iter().any(|head_hunk| {
+                        joined(
+                            head_hunk.new_start,
+                            head_hunk.new_start + head_hunk.new_lines,
+                            hunk.new_start,
+                            hunk.new_start + hunk.new_lines,
+                        )
+                    })
+                })
+            })
+        });
+
+    if is_source_locked {
+        return Err(errors::MoveCommitError::SourceLocked);
+    }
+
+    // move files ownerships from source branch to the destination branch
+
+    let ownerships_to_transfer = branch_head_diff
+        .iter()
+        .map(|(file_path, hunks)| {
+            (
+                file_path.clone(),
+                hunks.iter().map(Into::into).collect::<Vec<_>>(),
+            )
+        })
+        .map(|(file_path, hunks)| OwnershipClaim { file_path, hunks })
+        .flat_map(|file_ownership| source_branch.ownership.take(&file_ownership))
+        .collect::<Vec<_>>();
+
+    // reset the source branch to the parent commit
+    {
+        source_branch.head = source_branch_head_parent.id();
+        vb_state.set_branch(source_branch.clone())?;
+    }
+
+    // move the commit to destination branch target branch
+    {
+        let mut destination_branch =
+            vb_state
+                .get_branch(target_branch_id)
+                .map_err(|error| match error {
+                    reader::Error::NotFound => {
+                        errors::MoveCommitError::BranchNotFound(errors::BranchNotFound {
+                            project_id: project_repository.project().id,
+                            branch_id: *target_branch_id,
+                        })
+                    }
+                    error => errors::MoveCommitError::Other(error.into()),
+                })?;
+
+        for ownership in ownerships_to_transfer {
+            destination_branch.ownership.put(ownership);
+        }
+
+        let new_destination_tree_oid = write_tree_onto_commit(
+            project_repository,
+            destination_branch.head,
+            branch_head_diff,
+        )
+        .context("failed to write tree onto commit")?;
+        let new_destination_tree = project_repository
+            .git_repository
+            .find_tree(new_destination_tree_oid)
+            .context("failed to find tree")?;
+
+        let new_destination_head_oid = project_repository
+            .commit(
+                user,
+                &source_branch_head.message().to_str_lossy(),
+                &new_destination_tree,
+                &[&project_repository
+                    .git_repository
+                    .find_commit(destination_branch.head)
+                    .context("failed to get dst branch head commit")?],
+                signer,
+            )
+            .context("failed to commit")?;
+
+        destination_branch.head = new_destination_head_oid;
+        vb_state.set_branch(destination_branch.clone())?;
+    }
+
+    restack_children(project_repository, &vb_state, &source_branch.id, signer)?;
+    restack_children(project_repository, &vb_state, target_branch_id, signer)?;
+    super::integration::update_gitbutler_integration(&vb_state, project_repository)
+        .context("failed to update gitbutler integration")?;
+
+    Ok(())
+}
+
+/// Moves the commits and the uncommitted changes of `source_branch_id` to `target_branch_id`,
+/// and deletes it. The commits are rebased onto the head of the target branch, and branches
+/// stacked on the source branch are stacked on the target branch instead.
+pub fn merge_branches(
+    project_repository: &project_repository::Repository,
+    source_branch_id: &BranchId,
+    target_branch_id: &BranchId,
+    signer: Option<&git::Signer>,
+) -> Result<(), errors::MergeBranchesError> {
+    if conflicts::is_conflicting::<&Path>(project_repository, None)? {
+        return Err(errors::MergeBranchesError::Conflict(
+            errors::ProjectConflict {
+                project_id: project_repository.project().id,
+            },
+        ));
+    }
+
+    if source_branch_id == target_branch_id {
+        return Err(errors::MergeBranchesError::SameBranch);
+    }
+
+    let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
+
+    let default_target = get_default_target(&vb_state)
+        .context("failed to read default target")?
+        .ok_or_else(|| {
+            errors::MergeBranchesError::DefaultTargetNotSet(errors::DefaultTargetNotSet {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let all_branches = vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?;
+    let applied_branch = |branch_id: &BranchId| {
+        all_branches
+            .iter()
+            .find(|branch| branch.id == *branch_id && branch.applied)
+            .cloned()
+            .ok_or_else(|| {
+                errors::MergeBranchesError::BranchNotFound(errors::BranchNotFound {
+                    project_id: project_repository.project().id,
+                    branch_id: *branch_id,
+                })
+            })
+    };
+    let source_branch = applied_branch(source_branch_id)?;
+    let mut target_branch = applied_branch(target_branch_id)?;
+
+    let is_stacked_on = |branch: &Branch, ancestor_id: &BranchId| {
+        let mut parent_id = branch.parent;
+        // bounded, so that a cycle can't hang us
+        for _ in 0..all_branches.len() {
+            match parent_id {
+                Some(id) if id == *ancestor_id => return true,
+                Some(id) => {
+                    parent_id = all_branches
+                        .iter()
+                        .find(|branch| branch.id == id)
+                        .and_then(|branch| branch.parent);
+                }
+                None => return false,
+            }
+        }
+        false
+    };
+    if is_stacked_on(&source_branch, target_branch_id)
+        || is_stacked_on(&target_branch, source_branch_id)
+    {
+        return Err(errors::MergeBranchesError::Stacked);
+    }
+
+    let source_base = source_branch.base(default_target.sha);
+    if source_branch.head != source_base {
+        target_branch.head = conflicts_as_none(cherry_rebase(
+            project_repository,
+            target_branch.head,
+            source_base,
+            source_branch.head,
+            signer,
+        ))?
+        .ok_or(errors::MergeBranchesError::RebaseFailed)?;
+    }
+
+    for claim in &source_branch.ownership.claims {
+        target_branch.ownership.put(claim.clone());
+    }
+    if source_branch.selected_for_changes.is_some() {
+        target_branch.selected_for_changes = source_branch.selected_for_changes;
+    }
+    vb_state
+        .set_branch(target_branch.clone())
+        .context("failed to write branch")?;
+
+    // the stacked branches still have the commits of the source branch below their own, so
+    // restacking them moves only their own commits
+    for mut child in all_branches
+        .iter()
+        .filter(|branch| branch.parent == Some(source_branch.id))
+        .cloned()
+    {
+        child.parent = Some(target_branch.id);
+        vb_state
+            .set_branch(child)
+            .context("failed to write branch")?;
+    }
+    restack_children(project_repository, &vb_state, &target_branch.id, signer)?;
+
+    let rules = vb_state
+        .list_routing_rules()
+        .context("failed to read routing rules")?;
+    if rules.iter().any(|rule| rule.branch_id == source_branch.id) {
+        vb_state
+            .set_routing_rules(
+                rules
+                    .into_iter()
+                    .map(|mut rule| {
+                        if rule.branch_id == source_branch.id {
+                            rule.branch_id = target_branch.id;
+                        }
+                        rule
+                    })
+                    .collect(),
+            )
+            .context("failed to write routing rules")?;
+    }
+
+    vb_state
+        .remove_branch(source_branch.id)
+        .context("failed to remove branch")?;
+    project_repository.delete_branch_reference(&source_branch)?;
+
+    super::integration::update_gitbutler_integration(&vb_state, project_repository)
+        .context("failed to update gitbutler integration")?;
+
+    Ok(())
+}
+
+/// Moves the uncommitted changes of the branch that `ownership` claims, and the commits
+/// `commit_oids`, to a new branch named `name` next to it. The commits keep their order, and the
+/// ones that stay are rebased without them.
+///
+/// Returns the id of the new branch.
+pub fn split_branch(
+    project_repository: &project_repository::Repository,
+    branch_id: &BranchId,
+    name: Option<String>,
+    ownership: &BranchOwnershipClaims,
+    commit_oids: &[git::Oid],
+    signer: Option<&git::Signer>,
+) -> Result<BranchId, errors::SplitBranchError> {
+    if conflicts::is_conflicting::<&Path>(project_repository, None)? {
+        return Err(errors::SplitBranchError::Conflict(
+            errors::ProjectConflict {
+                project_id: project_repository.project().id,
+            },
+        ));
+    }
+
+    if ownership.claims.is_empty() && commit_oids.is_empty() {
+        return Err(errors::SplitBranchError::NothingToSplit);
+    }
+
+    let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
+
+    let default_target = get_default_target(&vb_state)
+        .context("failed to read default target")?
+        .ok_or_else(|| {
+            errors::SplitBranchError::DefaultTargetNotSet(errors::DefaultTargetNotSet {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let applied_branches = vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .filter(|b| b.applied)
+        .collect::<Vec<_>>();
+
+    // the claims of the uncommitted changes are only up to date after computing the status
+    let integration_commit_id = get_workspace_head(&vb_state, project_repository)?;
+    let (applied_statuses, _) = get_applied_status(
+        project_repository,
+        &integration_commit_id,
+        &default_target.sha,
+        applied_branches,
+    )?;
+
+    let mut branch = applied_statuses
+        .into_iter()
+        .map(|(branch, _)| branch)
+        .find(|b| b.id == *branch_id)
+        .ok_or_else(|| {
+            errors::SplitBranchError::BranchNotFound(errors::BranchNotFound {
+                project_id: project_repository.project().id,
+                branch_id: *branch_id,
+            })
+        })?;
+
+    let branch_base = branch.base(default_target.sha);
+    let branch_commit_oids = project_repository.l(
+        branch.head,
+        project_repository::LogUntil::Commit(branch_base),
+    )?;
+
+    if let Some(commit_oid) = commit_oids
+        .iter()
+        .find(|commit_oid| !branch_commit_oids.contains(commit_oid))
+    {
+        return Err(errors::SplitBranchError::CommitNotFound(*commit_oid));
+    }
+
+    // the commits below the oldest one that moves stay as they are, the rest is rewritten
+    let oldest_moved_index = branch_commit_oids
+        .iter()
+        .rposition(|oid| commit_oids.contains(oid));
+
+    if let Some(oldest_moved_index) = oldest_moved_index {
+        let pushed_commit_oids = branch.upstream_head.map_or_else(
+            || Ok(vec![]),
+            |upstream_head| {
+                project_repository.l(
+                    upstream_head,
+                    project_repository::LogUntil::Commit(default_target.sha),
+                )
+            },
+        )?;
+        if branch_commit_oids[..=oldest_moved_index]
+            .iter()
+            .any(|oid| pushed_commit_oids.contains(oid))
+            && !project_repository.project().ok_with_force_push
+        {
+            // moving pushed commits away will cause a force push that is not allowed
+            return Err(errors::SplitBranchError::ForcePushNotAllowed(
+                errors::ForcePushNotAllowed {
+                    project_id: project_repository.project().id,
+                },
+            ));
+        }
+    }
+
+    let mut taken_claims = vec![];
+    for claim in &ownership.claims {
+        let taken = branch.ownership.take(claim);
+        if taken.is_empty() {
+            return Err(errors::SplitBranchError::TargetOwnershipNotFound(
+                ownership.clone(),
+            ));
+        }
+        taken_claims.extend(taken);
+    }
+
+    // the new branch starts out like `create_virtual_branch` would create it
+    let new_branch_base = match branch.parent {
+        Some(parent_id) => {
+            vb_state
+                .get_branch(&parent_id)
+                .context("failed to read parent branch")?
+                .head
+        }
+        None => default_target.sha,
+    };
+    let mut moved_commit_oids = branch_commit_oids
+        .iter()
+        .filter(|oid| commit_oids.contains(oid))
+        .copied()
+        .collect::<Vec<_>>();
+    let new_branch_head = if moved_commit_oids.is_empty() {
+        new_branch_base
+    } else {
+        conflicts_as_none(cherry_rebase_group(
+            project_repository,
+            new_branch_base,
+            &mut moved_commit_oids,
+            signer,
+        ))?
+        .ok_or(errors::SplitBranchError::RebaseFailed)?
+    };
+
+    if oldest_moved_index.is_some() {
+        branch.head = rebase_without_commits(
+            project_repository,
+            &branch_commit_oids,
+            branch_base,
+            commit_oids,
+            signer,
+        )?
+        .ok_or(errors::SplitBranchError::RebaseFailed)?;
+    }
+
+    let mut new_branch = create_virtual_branch(
+        project_repository,
+        &BranchCreateRequest {
+            name,
+            order: Some(branch.order + 1),
+            selected_for_changes: Some(false),
+            parent: branch.parent,
+            ..Default::default()
+        },
+    )
+    .context("failed to create branch")?;
+    new_branch.head = new_branch_head;
+    for claim in taken_claims {
+        new_branch.ownership.put(claim);
+    }
+    vb_state
+        .set_branch(new_branch.clone())
+        .context("failed to write branch")?;
+
+    // creating the new branch changed the order of the branches after it
+    branch.order = vb_state
+        .get_branch(branch_id)
+        .context("failed to read branch")?
+        .order;
+    vb_state
+        .set_branch(branch.clone())
+        .context("failed to write branch")?;
+
+    restack_children(project_repository, &vb_state, &branch.id, signer)?;
+    super::integration::update_gitbutler_integration(&vb_state, project_repository)
+        .context("failed to update gitbutler integration")?;
+
+    Ok(new_branch.id)
+}
+
+/// Rebases the commits of a branch, `branch_commit_oids` from newest to oldest, without the ones
+/// in `removed_commit_oids`. The commits below the oldest removed one stay as they are.
+///
+/// Returns the new head, or `None` if the commits that stay don't apply without the others.
+fn rebase_without_commits(
+    project_repository: &project_repository::Repository,
+    branch_commit_oids: &[git::Oid],
+    branch_base: git::Oid,
+    removed_commit_oids: &[git::Oid],
+    signer: Option<&git::Signer>,
+) -> Result<Option<git::Oid>> {
+    let Some(oldest_removed_index) = branch_commit_oids
+        .iter()
+        .rposition(|oid| removed_commit_oids.contains(oid))
+    else {
+        return Ok(Some(
+            branch_commit_oids.first().copied().unwrap_or(branch_base),
+        ));
+    };
+    let kept_base = branch_commit_oids
+        .get(oldest_removed_index + 1)
+        .copied()
+        .unwrap_or(branch_base);
+    let mut kept_commit_oids = branch_commit_oids[..oldest_removed_index]
+        .iter()
+        .filter(|oid| !removed_commit_oids.contains(oid))
+        .copied()
+        .collect::<Vec<_>>();
+    if kept_commit_oids.is_empty() {
+        return Ok(Some(kept_base));
+    }
+    conflicts_as_none(cherry_rebase_group(
+        project_repository,
+        kept_base,
+        &mut kept_commit_oids,
+        signer,
+    ))
+}
+
+/// The uncommitted hunks of the workspace that `ownership` claims, or `None` if it claims a hunk
+/// that isn't there.
+fn claimed_hunks<'a>(
+    applied_statuses: &'a AppliedStatuses,
+    ownership: &BranchOwnershipClaims,
+) -> Option<Vec<(&'a Path, &'a diff::GitHunk)>> {
+    ownership
+        .claims
+        .iter()
+        .flat_map(|claim| {
+            claim.hunks.iter().map(|claimed_hunk| {
+                applied_statuses.iter().find_map(|(_, status)| {
+                    let (path, hunks) = status.get_key_value(&claim.file_path)?;
+                    let hunk = hunks.iter().find(|hunk| claimed_hunk.shallow_eq(hunk))?;
+                    Some((path.as_path(), hunk))
+                })
+            })
+        })
+        .collect()
+}
+
+/// The commits of a branch, `branch_commit_oids`, that the commits `commit_oids` depend on because
+/// they change lines that were introduced by them, including the ones those depend on in turn and
+/// `commit_oids` themselves. The commits are returned from newest to oldest.
+fn commit_dependencies(
+    project_repository: &project_repository::Repository,
+    commit_oids: &[git::Oid],
+    branch_commit_oids: &[git::Oid],
+    branch_base: git::Oid,
+) -> Result<Vec<git::Oid>> {
+    let mut dependencies = commit_oids.iter().copied().collect::<HashSet<_>>();
+    let mut pending = commit_oids.to_vec();
+    while let Some(commit_oid) = pending.pop() {
+        for blamed_oid in changed_lines_origins(project_repository, commit_oid, branch_base)? {
+            if branch_commit_oids.contains(&blamed_oid) && dependencies.insert(blamed_oid) {
+                pending.push(blamed_oid);
+            }
+        }
+    }
+    Ok(branch_commit_oids
+        .iter()
+        .filter(|oid| dependencies.contains(oid))
+        .copied()
+        .collect())
+}
+
+/// The first of the commits of a branch, `branch_commit_oids`, that isn't one of `moved_commit_oids`
+/// but changes lines introduced by one of them, so it can't stay behind when they are moved.
+fn dependent_commit(
+    project_repository: &project_repository::Repository,
+    moved_commit_oids: &[git::Oid],
+    branch_commit_oids: &[git::Oid],
+    branch_base: git::Oid,
+) -> Result<Option<git::Oid>> {
+    for commit_oid in branch_commit_oids
+        .iter()
+        .filter(|oid| !moved_commit_oids.contains(oid))
+    {
+        if changed_lines_origins(project_repository, *commit_oid, branch_base)?
+            .iter()
+            .any(|oid| moved_commit_oids.contains(oid))
+        {
+            return Ok(Some(*commit_oid));
+        }
+    }
+    Ok(None)
+}
+
+/// The commits since `branch_base` that introduced the lines a commit changes.
+fn changed_lines_origins(
+    project_repository: &project_repository::Repository,
+    commit_oid: git::Oid,
+    branch_base: git::Oid,
+) -> Result<Vec<git::Oid>> {
+    let repo = &project_repository.git_repository;
+    let commit = repo
+        .find_commit(commit_oid)
+        .context("failed to find commit")?;
+    let parent = commit.parent(0).context("failed to find parent")?;
+    let diffs =
+        diff::trees(repo, &parent.tree()?, &commit.tree()?).context("failed to diff trees")?;
+    let mut origins = vec![];
+    for (path, file_diff) in diffs {
+        for hunk in file_diff.hunks.iter().filter(|hunk| hunk.old_lines > 0) {
+            // the same lines lock uncommitted hunks to the commits of a branch
+            let Ok(blame) = repo.blame(
+                &path,
+                hunk.old_start,
+                hunk.old_start + hunk.old_lines - 1,
+                &branch_base,
+                &parent.id(),
+            ) else {
+                continue;
+            };
+            origins.extend(
+                blame
+                    .iter()
+                    .map(|blame_hunk| git::Oid::from(blame_hunk.orig_commit_id())),
+            );
+        }
+    }
+    Ok(origins)
+}
+
+/// The lines of an uncommitted hunk that a commit of an applied branch introduced, which lock the
+/// hunk to that branch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HunkLockCause {
+    pub branch_id: BranchId,
+    pub branch_name: String,
+    pub commit_id: git::Oid,
+    pub description: String,
+    /// The first of the lines, as it is numbered in the file before the change.
+    pub start: u32,
+    /// The line after the last of the lines.
+    pub end: u32,
+    /// Whether the commit was pushed already.
+    pub pushed: bool,
+}
+
+/// Why an uncommitted hunk is locked to the branches it is in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HunkLockExplanation {
+    pub file_path: PathBuf,
+    /// The id of the hunk, like [`VirtualBranchHunk::id`].
+    pub hunk_id: String,
+    /// Empty if the hunk isn't locked.
+    pub causes: Vec<HunkLockCause>,
+}
+
+/// Explains the locks of every uncommitted hunk that `ownership` claims, by the lines it shares
+/// with the commits it is locked to.
+pub fn explain_hunk_locks(
+    project_repository: &project_repository::Repository,
+    ownership: &BranchOwnershipClaims,
+) -> Result<Vec<HunkLockExplanation>, errors::ExplainHunkLocksError> {
+    let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
+
+    let default_target = get_default_target(&vb_state)
+        .context("failed to read default target")?
+        .ok_or_else(|| {
+            errors::ExplainHunkLocksError::DefaultTargetNotSet(errors::DefaultTargetNotSet {
+                project_id: project_repository.project().id,
+            })
+        })?;
+
+    let applied_branches = vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .filter(|branch| branch.applied)
+        .collect::<Vec<_>>();
 
-    match cherry_rebase_group(project_repository, new_commit_oid, &mut ids_to_rebase) {
-        Ok(Some(new_head_id)) => {
-            // save new branch head
-            branch.head = new_head_id;
-            vb_state
-                .set_branch(branch.clone())
-                .context("failed to write branch")?;
+    let integration_commit_id = get_workspace_head(&vb_state, project_repository)?;
+    let (applied_statuses, _) = get_applied_status(
+        project_repository,
+        &integration_commit_id,
+        &default_target.sha,
+        applied_branches,
+    )?;
 
-            super::integration::update_gitbutler_integration(&vb_state, project_repository)
-                .context("failed to update gitbutler integration")?;
-            Ok(())
+    let hunks = claimed_hunks(&applied_statuses, ownership)
+        .ok_or_else(|| errors::ExplainHunkLocksError::TargetOwnershipNotFound(ownership.clone()))?;
+
+    let pushed_commit_oids = applied_statuses
+        .iter()
+        .map(|(branch, _)| {
+            let oids = branch.upstream_head.map_or_else(
+                || Ok(vec![]),
+                |upstream_head| {
+                    project_repository.l(
+                        upstream_head,
+                        project_repository::LogUntil::Commit(default_target.sha),
+                    )
+                },
+            )?;
+            Ok((branch.id, oids))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+
+    let mut explanations = vec![];
+    for (path, hunk) in hunks {
+        let mut causes = vec![];
+        if !hunk.locked_to.is_empty() {
+            let blame = project_repository
+                .git_repository
+                .blame(
+                    path,
+                    hunk.old_start,
+                    (hunk.old_start + hunk.old_lines).saturating_sub(1),
+                    &default_target.sha,
+                    &integration_commit_id,
+                )
+                .context("failed to blame hunk")?;
+            for blame_hunk in blame.iter() {
+                let commit_id = git::Oid::from(blame_hunk.orig_commit_id());
+                let Some(lock) = hunk
+                    .locked_to
+                    .iter()
+                    .find(|lock| lock.commit_id == commit_id)
+                else {
+                    continue;
+                };
+                let Some((branch, _)) = applied_statuses
+                    .iter()
+                    .find(|(branch, _)| branch.id == Id::<Branch>::from(lock.branch_id))
+                else {
+                    continue;
+                };
+                let commit = project_repository
+                    .git_repository
+                    .find_commit(commit_id)
+                    .context("failed to find commit")?;
+                let start = blame_hunk.final_start_line() as u32;
+                causes.push(HunkLockCause {
+                    branch_id: branch.id,
+                    branch_name: branch.name.clone(),
+                    commit_id,
+                    description: commit.message().to_str_lossy().into_owned(),
+                    start: start.max(hunk.old_start),
+                    end: (start + blame_hunk.lines_in_hunk() as u32)
+                        .min(hunk.old_start + hunk.old_lines),
+                    pushed: pushed_commit_oids[&branch.id].contains(&commit_id),
+                });
+            }
         }
-        _ => Err(errors::UpdateCommitMessageError::Other(anyhow!(
-            "rebase error"
-        ))),
+        explanations.push(HunkLockExplanation {
+            file_path: path.to_path_buf(),
+            hunk_id: VirtualBranchHunk::gen_id(hunk.new_start, hunk.new_lines),
+            causes,
+        });
     }
+    Ok(explanations)
 }
 
-/// moves commit from the branch it's in to the top of the target branch
-pub fn move_commit(
+/// Moves the uncommitted hunks that `ownership` claims to the branch `target_branch_id`, along with
+/// the commits they are locked to and the commits those depend on. The commits that stay behind
+/// are rebased without them.
+///
+/// Commits that were pushed are never moved, as that would take them away from the remote branch
+/// they are in.
+pub fn move_hunks_with_dependencies(
     project_repository: &project_repository::Repository,
     target_branch_id: &BranchId,
-    commit_oid: git::Oid,
-    user: Option<&users::User>,
-    signing_key: Option<&keys::PrivateKey>,
-) -> Result<(), errors::MoveCommitError> {
-    if project_repository.is_resolving() {
-        return Err(errors::MoveCommitError::Conflicted(
+    ownership: &BranchOwnershipClaims,
+    signer: Option<&git::Signer>,
+) -> Result<(), errors::MoveHunksWithDependenciesError> {
+    if conflicts::is_conflicting::<&Path>(project_repository, None)? {
+        return Err(errors::MoveHunksWithDependenciesError::Conflict(
             errors::ProjectConflict {
                 project_id: project_repository.project().id,
             },
@@ -3974,15 +6125,28 @@ pub fn move_commit(
 
     let vb_state = VirtualBranchesHandle::new(&project_repository.project().gb_dir());
 
+    let default_target = get_default_target(&vb_state)
+        .context("failed to read default target")?
+        .ok_or_else(|| {
+            errors::MoveHunksWithDependenciesError::DefaultTargetNotSet(
+                errors::DefaultTargetNotSet {
+                    project_id: project_repository.project().id,
+                },
+            )
+        })?;
+
     let applied_branches = vb_state
         .list_branches()
         .context("failed to read virtual branches")?
         .into_iter()
-        .filter(|b| b.applied)
+        .filter(|branch| branch.applied)
         .collect::<Vec<_>>();
 
-    if !applied_branches.iter().any(|b| b.id == *target_branch_id) {
-        return Err(errors::MoveCommitError::BranchNotFound(
+    if !applied_branches
+        .iter()
+        .any(|branch| branch.id == *target_branch_id)
+    {
+        return Err(errors::MoveHunksWithDependenciesError::BranchNotFound(
             errors::BranchNotFound {
                 project_id: project_repository.project().id,
                 branch_id: *target_branch_id,
@@ -3990,137 +6154,118 @@ pub fn move_commit(
         ));
     }
 
-    let default_target = get_default_target(&vb_state)
-        .context("failed to get default target")?
-        .ok_or_else(|| {
-            errors::MoveCommitError::DefaultTargetNotSet(errors::DefaultTargetNotSet {
-                project_id: project_repository.project().id,
-            })
-        })?;
-
-    let integration_commit_id =
-        super::integration::get_workspace_head(&vb_state, project_repository)?;
-
-    let (mut applied_statuses, _) = get_applied_status(
+    let integration_commit_id = get_workspace_head(&vb_state, project_repository)?;
+    let (applied_statuses, _) = get_applied_status(
         project_repository,
         &integration_commit_id,
         &default_target.sha,
         applied_branches,
     )?;
 
-    let (ref mut source_branch, source_status) = applied_statuses
-        .iter_mut()
-        .find(|(b, _)| b.head == commit_oid)
-        .ok_or_else(|| errors::MoveCommitError::CommitNotFound(commit_oid))?;
-
-    let source_branch_non_comitted_files = source_status;
-
-    let source_branch_head = project_repository
-        .git_repository
-        .find_commit(commit_oid)
-        .context("failed to find commit")?;
-    let source_branch_head_parent = source_branch_head
-        .parent(0)
-        .context("failed to get parent commit")?;
-    let source_branch_head_tree = source_branch_head
-        .tree()
-        .context("failed to get commit tree")?;
-    let source_branch_head_parent_tree = source_branch_head_parent
-        .tree()
-        .context("failed to get parent tree")?;
-    let branch_head_diff = diff::trees(
-        &project_repository.git_repository,
-        &source_branch_head_parent_tree,
-        &source_branch_head_tree,
-    )?;
-
-    let branch_head_diff: HashMap<_, _> = diff::diff_files_into_hunks(branch_head_diff).collect();
-    let is_source_locked = source_branch_non_comitted_files
-        .iter()
-        .any(|(path, hunks)| {
-            branch_head_diff.get(path).map_or(false, |head_diff_hunks| {
-                hunks.iter().any(|hunk| {
-                    head_diff_hunks.iter().any(|head_hunk| {
-                        joined(
-                            head_hunk.new_start,
-                            head_hunk.new_start + head_hunk.new_lines,
-                            hunk.new_start,
-                            hunk.new_start + hunk.new_lines,
-                        )
-                    })
-                })
-            })
-        });
+    let hunks = claimed_hunks(&applied_statuses, ownership).ok_or_else(|| {
+        errors::MoveHunksWithDependenciesError::TargetOwnershipNotFound(ownership.clone())
+    })?;
 
-    if is_source_locked {
-        return Err(errors::MoveCommitError::SourceLocked);
+    let mut locking_commit_oids = HashMap::<BranchId, Vec<git::Oid>>::new();
+    for lock in hunks.iter().flat_map(|(_, hunk)| hunk.locked_to.iter()) {
+        let branch_id = Id::<Branch>::from(lock.branch_id);
+        if branch_id != *target_branch_id {
+            locking_commit_oids
+                .entry(branch_id)
+                .or_default()
+                .push(lock.commit_id);
+        }
     }
 
-    // move files ownerships from source branch to the destination branch
+    let mut branches = applied_statuses
+        .into_iter()
+        .map(|(branch, _)| branch)
+        .collect::<Vec<_>>();
+    branches.sort_by_key(|branch| branch.order);
 
-    let ownerships_to_transfer = branch_head_diff
+    let mut target_head = branches
         .iter()
-        .map(|(file_path, hunks)| {
-            (
-                file_path.clone(),
-                hunks.iter().map(Into::into).collect::<Vec<_>>(),
-            )
-        })
-        .map(|(file_path, hunks)| OwnershipClaim { file_path, hunks })
-        .flat_map(|file_ownership| source_branch.ownership.take(&file_ownership))
-        .collect::<Vec<_>>();
+        .find(|branch| branch.id == *target_branch_id)
+        .map(|branch| branch.head)
+        .context("target branch not found")?;
 
-    // reset the source branch to the parent commit
-    {
-        source_branch.head = source_branch_head_parent.id();
-        vb_state.set_branch(source_branch.clone())?;
-    }
+    for branch in &mut branches {
+        let Some(commit_oids) = locking_commit_oids.get(&branch.id) else {
+            continue;
+        };
 
-    // move the commit to destination branch target branch
-    {
-        let mut destination_branch =
-            vb_state
-                .get_branch(target_branch_id)
-                .map_err(|error| match error {
-                    reader::Error::NotFound => {
-                        errors::MoveCommitError::BranchNotFound(errors::BranchNotFound {
-                            project_id: project_repository.project().id,
-                            branch_id: *target_branch_id,
-                        })
-                    }
-                    error => errors::MoveCommitError::Other(error.into()),
-                })?;
+        let branch_base = branch.base(default_target.sha);
+        let branch_commit_oids = project_repository.l(
+            branch.head,
+            project_repository::LogUntil::Commit(branch_base),
+        )?;
+        let mut moved_commit_oids = commit_dependencies(
+            project_repository,
+            commit_oids,
+            &branch_commit_oids,
+            branch_base,
+        )?;
 
-        for ownership in ownerships_to_transfer {
-            destination_branch.ownership.put(ownership);
+        let pushed_commit_oids = branch.upstream_head.map_or_else(
+            || Ok(vec![]),
+            |upstream_head| {
+                project_repository.l(
+                    upstream_head,
+                    project_repository::LogUntil::Commit(default_target.sha),
+                )
+            },
+        )?;
+        if let Some(commit_oid) = moved_commit_oids
+            .iter()
+            .find(|oid| pushed_commit_oids.contains(oid))
+        {
+            return Err(errors::MoveHunksWithDependenciesError::DependsOnPushedCommit(*commit_oid));
         }
-
-        let new_destination_tree_oid = write_tree_onto_commit(
+        if let Some(commit_oid) = dependent_commit(
             project_repository,
-            destination_branch.head,
-            branch_head_diff,
-        )
-        .context("failed to write tree onto commit")?;
-        let new_destination_tree = project_repository
-            .git_repository
-            .find_tree(new_destination_tree_oid)
-            .context("failed to find tree")?;
+            &moved_commit_oids,
+            &branch_commit_oids,
+            branch_base,
+        )? {
+            return Err(errors::MoveHunksWithDependenciesError::DependentCommit(
+                commit_oid,
+            ));
+        }
 
-        let new_destination_head_oid = project_repository
-            .commit(
-                user,
-                &source_branch_head.message().to_str_lossy(),
-                &new_destination_tree,
-                &[&project_repository
-                    .git_repository
-                    .find_commit(destination_branch.head)
-                    .context("failed to get dst branch head commit")?],
-                signing_key,
-            )
-            .context("failed to commit")?;
+        let new_branch_head = rebase_without_commits(
+            project_repository,
+            &branch_commit_oids,
+            branch_base,
+            &moved_commit_oids,
+            signer,
+        )?
+        .ok_or(errors::MoveHunksWithDependenciesError::RebaseFailed)?;
+        target_head = conflicts_as_none(cherry_rebase_group(
+            project_repository,
+            target_head,
+            &mut moved_commit_oids,
+            signer,
+        ))?
+        .ok_or(errors::MoveHunksWithDependenciesError::RebaseFailed)?;
+        branch.head = new_branch_head;
+    }
 
-        destination_branch.head = new_destination_head_oid;
-        vb_state.set_branch(destination_branch.clone())?;
+    for branch in &mut branches {
+        for claim in &ownership.claims {
+            branch.ownership.take(claim);
+        }
+        if branch.id == *target_branch_id {
+            branch.head = target_head;
+            for claim in &ownership.claims {
+                branch.ownership.put(claim.clone());
+            }
+        }
+        vb_state
+            .set_branch(branch.clone())
+            .context("failed to write branch")?;
+    }
+    for branch in &branches {
+        restack_children(project_repository, &vb_state, &branch.id, signer)?;
     }
 
     super::integration::update_gitbutler_integration(&vb_state, project_repository)
@@ -4132,7 +6277,7 @@ pub fn move_commit(
 pub fn create_virtual_branch_from_branch(
     project_repository: &project_repository::Repository,
     upstream: &git::Refname,
-    signing_key: Option<&keys::PrivateKey>,
+    signer: Option<&git::Signer>,
     user: Option<&users::User>,
 ) -> Result<BranchId, errors::CreateVirtualBranchFromBranchError> {
     if !matches!(upstream, git::Refname::Local(_) | git::Refname::Remote(_)) {
@@ -4263,6 +6408,8 @@ pub fn create_virtual_branch_from_branch(
         ownership,
         order,
         selected_for_changes,
+        parent: None,
+        parent_head: None,
     };
 
     vb_state
@@ -4271,7 +6418,7 @@ pub fn create_virtual_branch_from_branch(
 
     project_repository.add_branch_reference(&branch)?;
 
-    match apply_branch(project_repository, &branch.id, signing_key, user) {
+    match apply_branch(project_repository, &branch.id, signer, user) {
         Ok(()) => Ok(branch.id),
         Err(errors::ApplyBranchError::BranchConflicts(_)) => {
             // if branch conflicts with the workspace, it's ok. keep it unapplied
@@ -4375,6 +6522,115 @@ fn update_conflict_markers(
     Ok(())
 }
 
+// this struct holds the three versions of a conflicting file, so that the conflict can be
+// resolved without an external merge tool. a version is `None` if the file doesn't exist
+// on that side of the merge.
+//
+// it is not persisted, it is only used for presentation purposes through the ipc
+//
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictVersions {
+    pub path: PathBuf,
+    pub branch_id: Option<BranchId>,
+    #[serde(serialize_with = "crate::serde::as_opt_string_lossy")]
+    pub ancestor: Option<BString>,
+    #[serde(serialize_with = "crate::serde::as_opt_string_lossy")]
+    pub ours: Option<BString>,
+    #[serde(serialize_with = "crate::serde::as_opt_string_lossy")]
+    pub theirs: Option<BString>,
+}
+
+pub fn list_conflicts(
+    project_repository: &project_repository::Repository,
+) -> Result<Vec<conflicts::Conflict>, errors::ConflictError> {
+    Ok(conflicts::list(project_repository)?)
+}
+
+pub fn get_conflict(
+    project_repository: &project_repository::Repository,
+    path: &Path,
+) -> Result<ConflictVersions, errors::ConflictError> {
+    let conflict = find_conflict(project_repository, path)?;
+    let read = |side| -> Result<Option<BString>> {
+        conflict
+            .side(side)
+            .map(|id| {
+                let blob = project_repository
+                    .git_repository
+                    .find_blob(id)
+                    .context(format!("failed to find blob {}", id))?;
+                Ok(blob.content().into())
+            })
+            .transpose()
+    };
+    Ok(ConflictVersions {
+        path: conflict.path.clone(),
+        branch_id: conflict.branch_id,
+        ancestor: read(conflicts::ConflictSide::Ancestor)?,
+        ours: read(conflicts::ConflictSide::Ours)?,
+        theirs: read(conflicts::ConflictSide::Theirs)?,
+    })
+}
+
+/// Resolve the conflict of the file at `path` by replacing it with `content`.
+pub fn resolve_conflict(
+    project_repository: &project_repository::Repository,
+    path: &Path,
+    content: &[u8],
+) -> Result<(), errors::ConflictError> {
+    let conflict = find_conflict(project_repository, path)?;
+    let full_path = project_repository.path().join(&conflict.path);
+    if let Some(parent) = full_path.parent() {
+        std::fs::create_dir_all(parent).context("failed to create parent directory")?;
+    }
+    std::fs::write(&full_path, content)
+        .context(format!("failed to write {}", full_path.display()))?;
+    conflicts::resolve(project_repository, &conflict.path)?;
+    Ok(())
+}
+
+/// Resolve the conflict of the file at `path` by taking the version on `side` of the merge,
+/// which removes the file if it doesn't exist there.
+pub fn resolve_conflict_with_side(
+    project_repository: &project_repository::Repository,
+    path: &Path,
+    side: conflicts::ConflictSide,
+) -> Result<(), errors::ConflictError> {
+    let conflict = find_conflict(project_repository, path)?;
+    match conflict.side(side) {
+        Some(id) => {
+            let blob = project_repository
+                .git_repository
+                .find_blob(id)
+                .context(format!("failed to find blob {}", id))?;
+            resolve_conflict(project_repository, path, blob.content())
+        }
+        None => {
+            let full_path = project_repository.path().join(&conflict.path);
+            if full_path.exists() {
+                std::fs::remove_file(&full_path)
+                    .context(format!("failed to remove {}", full_path.display()))?;
+            }
+            conflicts::resolve(project_repository, &conflict.path)?;
+            Ok(())
+        }
+    }
+}
+
+fn find_conflict(
+    project_repository: &project_repository::Repository,
+    path: &Path,
+) -> Result<conflicts::Conflict, errors::ConflictError> {
+    let conflict = conflicts::get(project_repository, path)?
+        .ok_or_else(|| errors::ConflictError::NotConflicting(path.to_owned()))?;
+    // conflicts marked by older versions don't know which versions went into the merge
+    if conflict.ancestor.is_none() && conflict.ours.is_none() && conflict.theirs.is_none() {
+        return Err(errors::ConflictError::VersionsUnknown(path.to_owned()));
+    }
+    Ok(conflict)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
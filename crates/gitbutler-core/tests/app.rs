@@ -13,6 +13,7 @@ mod keys;
 mod lock;
 mod reader;
 mod sessions;
+mod ssh;
 mod types;
 pub mod virtual_branches;
 mod zip;
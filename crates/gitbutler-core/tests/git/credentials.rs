@@ -1,4 +1,7 @@
-use std::path::PathBuf;
+use std::{
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+};
 
 use gitbutler_core::{
     git::credentials::{Credential, Helper, HttpsCredential, SshCredential},
@@ -7,12 +10,16 @@ use gitbutler_core::{
 
 use gitbutler_testsupport::{temp_dir, test_repository};
 
+/// `SSH_AUTH_SOCK` is shared by the whole process, so test cases take turns setting it.
+static SSH_AUTH_SOCK: Mutex<()> = Mutex::new(());
+
 #[derive(Default)]
 struct TestCase<'a> {
     remote_url: &'a str,
     github_access_token: Option<&'a str>,
     preferred_key: projects::AuthKey,
     home_dir: Option<PathBuf>,
+    ssh_auth_sock: Option<PathBuf>,
 }
 
 impl TestCase<'_> {
@@ -42,6 +49,11 @@ impl TestCase<'_> {
         };
         let project_repository = project_repository::Repository::open(&project).unwrap();
 
+        let _ssh_auth_sock = SSH_AUTH_SOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        match &self.ssh_auth_sock {
+            Some(ssh_auth_sock) => std::env::set_var("SSH_AUTH_SOCK", ssh_auth_sock),
+            None => std::env::remove_var("SSH_AUTH_SOCK"),
+        }
         let flow = helper.help(&project_repository, "origin").unwrap();
         flow.into_iter()
             .map(|(remote, credentials)| {
@@ -313,3 +325,276 @@ mod github {
         }
     }
 }
+
+#[cfg(unix)]
+mod ssh_agent {
+    use std::{
+        io::{Read, Write},
+        os::unix::net::UnixListener,
+        path::Path,
+        process::{Child, Command, Stdio},
+        time::Duration,
+    };
+
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+    use gitbutler_core::git::credentials::AgentIdentity;
+
+    use super::*;
+
+    fn ssh_string(bytes: &[u8]) -> Vec<u8> {
+        let mut string = (bytes.len() as u32).to_be_bytes().to_vec();
+        string.extend_from_slice(bytes);
+        string
+    }
+
+    fn identity(key_type: &str, key: u8, comment: &str) -> AgentIdentity {
+        let mut public_key = ssh_string(key_type.as_bytes());
+        public_key.extend(ssh_string(&[key; 32]));
+        AgentIdentity {
+            key_type: key_type.to_string(),
+            public_key,
+            comment: comment.to_string(),
+        }
+    }
+
+    /// A stand-in for ssh-agent at `socket` that answers a single request with `message`.
+    fn agent(socket: &Path, message: Vec<u8>) -> std::thread::JoinHandle<()> {
+        let listener = UnixListener::bind(socket).unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 5];
+            stream.read_exact(&mut request).unwrap();
+            assert_eq!(request, [0, 0, 0, 1, 11], "request identities");
+            stream.write_all(&message).unwrap();
+        })
+    }
+
+    fn message(answer: Vec<u8>) -> Vec<u8> {
+        let mut message = (answer.len() as u32).to_be_bytes().to_vec();
+        message.extend(answer);
+        message
+    }
+
+    fn identities_answer(identities: &[AgentIdentity]) -> Vec<u8> {
+        let mut answer = vec![12];
+        answer.extend((identities.len() as u32).to_be_bytes());
+        for identity in identities {
+            answer.extend(ssh_string(&identity.public_key));
+            answer.extend(ssh_string(identity.comment.as_bytes()));
+        }
+        answer
+    }
+
+    fn run_with_agent(remote_url: &str, message: Vec<u8>) -> Vec<(String, Vec<Credential>)> {
+        let dir = temp_dir();
+        let socket = dir.path().join("agent.sock");
+        let agent = agent(&socket, message);
+        let flow = TestCase {
+            remote_url,
+            ssh_auth_sock: Some(socket),
+            ..Default::default()
+        }
+        .run();
+        agent.join().unwrap();
+        flow
+    }
+
+    #[test]
+    fn agent_identities_are_tried_first_in_order() {
+        let identities = vec![
+            identity("sk-ssh-ed25519@openssh.com", 1, "yubikey"),
+            identity("ssh-ed25519", 2, "/home/test/.ssh/id_ed25519"),
+        ];
+        let flow = run_with_agent(
+            "git@gitlab.com:test-gitbutler/test.git",
+            message(identities_answer(&identities)),
+        );
+
+        assert_eq!(flow.len(), 1);
+        assert_eq!(flow[0].0, "git@gitlab.com:test-gitbutler/test.git");
+        assert_eq!(flow[0].1.len(), 2);
+        assert_eq!(
+            flow[0].1[0],
+            Credential::Ssh(SshCredential::Agent(identities))
+        );
+        assert!(matches!(
+            flow[0].1[1],
+            Credential::Ssh(SshCredential::GitButlerKey(_))
+        ));
+    }
+
+    /// A running ssh-agent, which is stopped when dropped.
+    struct SshAgent(Child);
+
+    impl SshAgent {
+        /// Start an ssh-agent at `socket` that holds the key at `key_path`.
+        fn start(socket: &Path, key_path: &Path) -> Self {
+            let agent = Self(
+                Command::new("ssh-agent")
+                    .arg("-D")
+                    .arg("-a")
+                    .arg(socket)
+                    .stdout(Stdio::null())
+                    .spawn()
+                    .expect("ssh-agent is installed"),
+            );
+            for _ in 0..100 {
+                if socket.exists() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+
+            let status = Command::new("ssh-add")
+                .arg("-q")
+                .arg(key_path)
+                .env("SSH_AUTH_SOCK", socket)
+                .stderr(Stdio::null())
+                .status()
+                .unwrap();
+            assert!(status.success(), "ssh-add failed");
+            agent
+        }
+    }
+
+    impl Drop for SshAgent {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    #[test]
+    fn keys_of_a_running_ssh_agent() {
+        let dir = temp_dir();
+        let key_path = dir.path().join("id_ed25519");
+        let status = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", "agent key", "-f"])
+            .arg(&key_path)
+            .status()
+            .unwrap();
+        assert!(status.success(), "ssh-keygen failed");
+        let public_key = std::fs::read_to_string(key_path.with_extension("pub")).unwrap();
+        let public_key = BASE64
+            .decode(public_key.split_whitespace().nth(1).unwrap())
+            .unwrap();
+
+        let socket = dir.path().join("agent.sock");
+        let _agent = SshAgent::start(&socket, &key_path);
+        let flow = TestCase {
+            remote_url: "git@gitlab.com:test-gitbutler/test.git",
+            ssh_auth_sock: Some(socket),
+            ..Default::default()
+        }
+        .run();
+
+        assert_eq!(
+            flow[0].1[0],
+            Credential::Ssh(SshCredential::Agent(vec![AgentIdentity {
+                key_type: "ssh-ed25519".to_string(),
+                public_key,
+                comment: "agent key".to_string(),
+            }]))
+        );
+    }
+
+    #[test]
+    fn agent_is_tried_for_https_remotes_over_ssh() {
+        let identities = vec![identity("ssh-rsa", 1, "rsa key")];
+        let flow = run_with_agent(
+            "https://gitlab.com/test-gitbutler/test.git",
+            message(identities_answer(&identities)),
+        );
+
+        assert_eq!(flow.len(), 1);
+        assert_eq!(flow[0].0, "git@gitlab.com:test-gitbutler/test.git");
+        assert_eq!(
+            flow[0].1[0],
+            Credential::Ssh(SshCredential::Agent(identities))
+        );
+    }
+
+    #[test]
+    fn agent_without_identities_is_skipped() {
+        let flow = run_with_agent(
+            "git@gitlab.com:test-gitbutler/test.git",
+            message(identities_answer(&[])),
+        );
+
+        assert_eq!(flow.len(), 1);
+        assert_eq!(flow[0].1.len(), 1);
+        assert!(matches!(
+            flow[0].1[0],
+            Credential::Ssh(SshCredential::GitButlerKey(_))
+        ));
+    }
+
+    #[test]
+    fn failing_agent_is_skipped() {
+        let flow = run_with_agent("git@gitlab.com:test-gitbutler/test.git", message(vec![5]));
+
+        assert_eq!(flow.len(), 1);
+        assert_eq!(flow[0].1.len(), 1);
+        assert!(matches!(
+            flow[0].1[0],
+            Credential::Ssh(SshCredential::GitButlerKey(_))
+        ));
+    }
+
+    #[test]
+    fn oversized_answer_is_skipped() {
+        let flow = run_with_agent(
+            "git@gitlab.com:test-gitbutler/test.git",
+            u32::MAX.to_be_bytes().to_vec(),
+        );
+
+        assert_eq!(flow.len(), 1);
+        assert_eq!(flow[0].1.len(), 1);
+        assert!(matches!(
+            flow[0].1[0],
+            Credential::Ssh(SshCredential::GitButlerKey(_))
+        ));
+    }
+
+    #[test]
+    fn missing_agent_is_skipped() {
+        let dir = temp_dir();
+        let flow = TestCase {
+            remote_url: "git@gitlab.com:test-gitbutler/test.git",
+            ssh_auth_sock: Some(dir.path().join("no-agent.sock")),
+            ..Default::default()
+        }
+        .run();
+
+        assert_eq!(flow.len(), 1);
+        assert_eq!(flow[0].1.len(), 1);
+        assert!(matches!(
+            flow[0].1[0],
+            Credential::Ssh(SshCredential::GitButlerKey(_))
+        ));
+    }
+
+    #[test]
+    fn preferred_key_ignores_the_agent() {
+        let dir = temp_dir();
+        let socket = dir.path().join("agent.sock");
+        let _listener = UnixListener::bind(&socket).unwrap();
+        let flow = TestCase {
+            remote_url: "git@gitlab.com:test-gitbutler/test.git",
+            preferred_key: projects::AuthKey::Local {
+                private_key_path: PathBuf::from("/tmp/id_rsa"),
+            },
+            ssh_auth_sock: Some(socket),
+            ..Default::default()
+        }
+        .run();
+
+        assert_eq!(
+            flow[0].1,
+            vec![Credential::Ssh(SshCredential::Keyfile {
+                key_path: PathBuf::from("/tmp/id_rsa"),
+                passphrase: None,
+            })]
+        );
+    }
+}
@@ -0,0 +1,385 @@
+use std::{fs, process::Command};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use gitbutler_core::ssh::{
+    self, fingerprint, verify_host_key, Check, HostPattern, KnownHosts, Marker,
+};
+use gitbutler_testsupport::temp_dir;
+use hmac::{Hmac, Mac};
+
+fn ssh_string(bytes: &[u8]) -> Vec<u8> {
+    let mut string = (bytes.len() as u32).to_be_bytes().to_vec();
+    string.extend_from_slice(bytes);
+    string
+}
+
+/// A public key of `key_type` in the ssh wire format, made of `byte`s.
+fn key(key_type: &str, byte: u8) -> Vec<u8> {
+    let mut key = ssh_string(key_type.as_bytes());
+    key.extend(ssh_string(&[byte; 32]));
+    key
+}
+
+fn line(hosts: &str, key: &[u8]) -> String {
+    let key_type = ssh::key_type(key).unwrap();
+    format!("{hosts} {key_type} {}", BASE64.encode(key))
+}
+
+fn hashed(name: &str) -> String {
+    let salt = [7u8; 20];
+    let mut mac = Hmac::<sha1::Sha1>::new_from_slice(&salt).unwrap();
+    mac.update(name.as_bytes());
+    format!(
+        "|1|{}|{}",
+        BASE64.encode(salt),
+        BASE64.encode(mac.finalize().into_bytes())
+    )
+}
+
+mod parse {
+    use super::*;
+
+    #[test]
+    fn all_kinds_of_entries() {
+        let ed25519 = key("ssh-ed25519", 1);
+        let input = format!(
+            "# a comment\n\
+             \n\
+             {} some comment\n\
+             @cert-authority {}\n\
+             @revoked {}\n\
+             not a valid line\n\
+             @unknown-marker {}\n",
+            line(
+                "github.com,[gitlab.example.com]:2222,!bad.example.com",
+                &ed25519
+            ),
+            line("*.example.com", &ed25519),
+            line("*", &ed25519),
+            line("github.com", &ed25519),
+        );
+
+        let known_hosts = KnownHosts::parse(&input);
+
+        assert_eq!(known_hosts.entries.len(), 3);
+        let entry = &known_hosts.entries[0];
+        assert_eq!(entry.marker, None);
+        assert_eq!(entry.key_type, "ssh-ed25519");
+        assert_eq!(entry.key, ed25519);
+        assert_eq!(
+            entry.hosts,
+            vec![
+                HostPattern::Plain {
+                    pattern: "github.com".into(),
+                    negated: false
+                },
+                HostPattern::Plain {
+                    pattern: "[gitlab.example.com]:2222".into(),
+                    negated: false
+                },
+                HostPattern::Plain {
+                    pattern: "bad.example.com".into(),
+                    negated: true
+                },
+            ]
+        );
+        assert_eq!(known_hosts.entries[1].marker, Some(Marker::CertAuthority));
+        assert_eq!(known_hosts.entries[2].marker, Some(Marker::Revoked));
+    }
+
+    #[test]
+    fn hashed_hostnames() {
+        let known_hosts = KnownHosts::parse(&line(&hashed("github.com"), &key("ssh-ed25519", 1)));
+
+        assert_eq!(known_hosts.entries.len(), 1);
+        assert!(matches!(
+            &known_hosts.entries[0].hosts[..],
+            [HostPattern::Hashed { salt, hash }] if salt.len() == 20 && hash.len() == 20
+        ));
+    }
+
+    #[test]
+    fn entries_are_written_back_as_they_were_read() {
+        let input = format!(
+            "{}\n{}\n",
+            line(
+                &format!("{},!x.example.com", hashed("github.com")),
+                &key("ssh-rsa", 2)
+            ),
+            line("@revoked *", &key("ssh-ed25519", 3)),
+        );
+
+        let known_hosts = KnownHosts::parse(&input);
+
+        let output = known_hosts
+            .entries
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(output, input.lines().collect::<Vec<_>>());
+    }
+}
+
+mod check {
+    use super::*;
+
+    fn check(known_hosts: &[String], host: &str, port: u16, key: &[u8]) -> Check {
+        KnownHosts::parse(&known_hosts.join("\n")).check(host, port, key)
+    }
+
+    #[test]
+    fn plain_hostname() {
+        let known = key("ssh-ed25519", 1);
+        let known_hosts = [line("github.com", &known)];
+
+        assert_eq!(check(&known_hosts, "github.com", 22, &known), Check::Match);
+        assert_eq!(check(&known_hosts, "GitHub.com", 22, &known), Check::Match);
+        assert_eq!(
+            check(&known_hosts, "gitlab.com", 22, &known),
+            Check::NotFound
+        );
+        // the port is part of the name unless it's the default
+        assert_eq!(
+            check(&known_hosts, "github.com", 2222, &known),
+            Check::NotFound
+        );
+    }
+
+    #[test]
+    fn non_standard_port() {
+        let known = key("ssh-ed25519", 1);
+        let known_hosts = [line("[git.example.com]:2222", &known)];
+
+        assert_eq!(
+            check(&known_hosts, "git.example.com", 2222, &known),
+            Check::Match
+        );
+        assert_eq!(
+            check(&known_hosts, "git.example.com", 22, &known),
+            Check::NotFound
+        );
+    }
+
+    #[test]
+    fn hashed_hostname() {
+        let known = key("ssh-ed25519", 1);
+        let known_hosts = [
+            line(&hashed("github.com"), &known),
+            line(&hashed("[git.example.com]:2222"), &known),
+        ];
+
+        assert_eq!(check(&known_hosts, "github.com", 22, &known), Check::Match);
+        assert_eq!(
+            check(&known_hosts, "git.example.com", 2222, &known),
+            Check::Match
+        );
+        assert_eq!(
+            check(&known_hosts, "gitlab.com", 22, &known),
+            Check::NotFound
+        );
+    }
+
+    #[test]
+    fn wildcards_and_negation() {
+        let known = key("ssh-ed25519", 1);
+        let known_hosts = [line("*.example.com,!bad.example.com,git?.test", &known)];
+
+        assert_eq!(
+            check(&known_hosts, "git.example.com", 22, &known),
+            Check::Match
+        );
+        assert_eq!(check(&known_hosts, "git1.test", 22, &known), Check::Match);
+        assert_eq!(
+            check(&known_hosts, "bad.example.com", 22, &known),
+            Check::NotFound
+        );
+        assert_eq!(
+            check(&known_hosts, "example.com", 22, &known),
+            Check::NotFound
+        );
+    }
+
+    #[test]
+    fn changed_key() {
+        let known_hosts = [line("github.com", &key("ssh-ed25519", 1))];
+
+        assert_eq!(
+            check(&known_hosts, "github.com", 22, &key("ssh-ed25519", 2)),
+            Check::Mismatch
+        );
+        // keys of other types are new keys, as far as ssh is concerned
+        assert_eq!(
+            check(&known_hosts, "github.com", 22, &key("ssh-rsa", 2)),
+            Check::NotFound
+        );
+    }
+
+    #[test]
+    fn revoked_key() {
+        let revoked = key("ssh-ed25519", 1);
+        let known_hosts = [
+            line("github.com", &revoked),
+            line("@revoked other.example.com", &revoked),
+        ];
+
+        assert_eq!(
+            check(&known_hosts, "github.com", 22, &revoked),
+            Check::Revoked
+        );
+    }
+
+    #[test]
+    fn cert_authority_is_not_a_host_key() {
+        let ca = key("ssh-ed25519", 1);
+        let known_hosts = [line("@cert-authority *.example.com", &ca)];
+
+        assert_eq!(
+            check(&known_hosts, "git.example.com", 22, &ca),
+            Check::NotFound
+        );
+    }
+}
+
+mod verify {
+    use super::*;
+
+    #[test]
+    fn fingerprint_matches_ssh_keygen() {
+        let dir = temp_dir();
+        let key_path = dir.path().join("id_ed25519");
+        let status = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+            .arg(&key_path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let output = Command::new("ssh-keygen")
+            .args(["-l", "-E", "sha256", "-f"])
+            .arg(key_path.with_extension("pub"))
+            .output()
+            .unwrap();
+        let expected = String::from_utf8(output.stdout).unwrap();
+
+        let public_key = fs::read_to_string(key_path.with_extension("pub")).unwrap();
+        let key = BASE64
+            .decode(public_key.split_whitespace().nth(1).unwrap())
+            .unwrap();
+
+        assert_eq!(
+            fingerprint(&key),
+            expected.split_whitespace().nth(1).unwrap()
+        );
+    }
+
+    #[test]
+    fn accepted_key_is_added() {
+        let dir = temp_dir();
+        let known_hosts = dir.path().join(".ssh").join("known_hosts");
+        let key = key("ssh-ed25519", 1);
+
+        let mut prompts = vec![];
+        verify_host_key(&known_hosts, "git.example.com", 2222, &key, |prompt| {
+            prompts.push(prompt);
+            true
+        })
+        .unwrap();
+
+        assert_eq!(
+            prompts,
+            vec![format!(
+                "The authenticity of host '[git.example.com]:2222' can't be established.\n\
+                 ED25519 key fingerprint is {}.\n\
+                 Are you sure you want to continue connecting (yes/no)?",
+                fingerprint(&key)
+            )]
+        );
+        assert_eq!(
+            fs::read_to_string(&known_hosts).unwrap(),
+            format!("{}\n", line("[git.example.com]:2222", &key))
+        );
+
+        // known from now on
+        verify_host_key(&known_hosts, "git.example.com", 2222, &key, |_| {
+            panic!("no prompt for known keys")
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn accepted_key_is_appended_on_a_line_of_its_own() {
+        let dir = temp_dir();
+        let known_hosts = dir.path().join("known_hosts");
+        let existing = line("github.com", &key("ssh-ed25519", 1));
+        fs::write(&known_hosts, &existing).unwrap();
+
+        verify_host_key(&known_hosts, "gitlab.com", 22, &key("ssh-rsa", 2), |_| true).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&known_hosts).unwrap(),
+            format!("{existing}\n{}\n", line("gitlab.com", &key("ssh-rsa", 2)))
+        );
+    }
+
+    #[test]
+    fn rejected_key_is_not_added() {
+        let dir = temp_dir();
+        let known_hosts = dir.path().join("known_hosts");
+        let key = key("ssh-ed25519", 1);
+
+        let error = verify_host_key(&known_hosts, "github.com", 22, &key, |_| false).unwrap_err();
+
+        assert!(
+            matches!(
+                &error,
+                ssh::Error::UnknownHostKey { host, fingerprint: shown, .. }
+                    if host == "github.com" && *shown == fingerprint(&key)
+            ),
+            "{error:?}"
+        );
+        assert!(!known_hosts.exists());
+    }
+
+    #[test]
+    fn rejected_key_explains_how_to_trust_it() {
+        let dir = temp_dir();
+        let known_hosts = dir.path().join("known_hosts");
+
+        let error = verify_host_key(
+            &known_hosts,
+            "git.example.com",
+            2222,
+            &key("ssh-ed25519", 1),
+            |_| false,
+        )
+        .unwrap_err();
+
+        assert!(
+            error.to_string().ends_with(&format!(
+                "to trust it run `ssh-keyscan -p 2222 git.example.com >> {}`",
+                known_hosts.display()
+            )),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn changed_key_is_refused_without_prompting() {
+        let dir = temp_dir();
+        let known_hosts = dir.path().join("known_hosts");
+        fs::write(&known_hosts, line("github.com", &key("ssh-ed25519", 1))).unwrap();
+
+        let error = verify_host_key(
+            &known_hosts,
+            "github.com",
+            22,
+            &key("ssh-ed25519", 2),
+            |_| panic!("no prompt for changed keys"),
+        )
+        .unwrap_err();
+
+        assert!(
+            matches!(error, ssh::Error::MismatchedHostKey { .. }),
+            "{error:?}"
+        );
+    }
+}
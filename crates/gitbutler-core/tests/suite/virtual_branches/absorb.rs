@@ -0,0 +1,205 @@
+use super::*;
+
+fn numbered_lines(count: usize) -> Vec<String> {
+    (1..=count).map(|i| format!("line {i}\n")).collect()
+}
+
+#[tokio::test]
+async fn into_the_commits_that_introduced_the_lines() {
+    let test = Test::default();
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+    let branch_id = setup_branch(&test).await;
+
+    let mut a = numbered_lines(10);
+    fs::write(repository.path().join("a.txt"), a.concat()).unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "add a", None, false)
+        .await
+        .unwrap();
+
+    let mut b = numbered_lines(10);
+    fs::write(repository.path().join("b.txt"), b.concat()).unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "add b", None, false)
+        .await
+        .unwrap();
+
+    a[2] = "line three\n".to_string();
+    fs::write(repository.path().join("a.txt"), a.concat()).unwrap();
+    b[7] = "line eight\n".to_string();
+    fs::write(repository.path().join("b.txt"), b.concat()).unwrap();
+    fs::write(repository.path().join("c.txt"), "new\n").unwrap();
+
+    let absorbed_hunks = controller.absorb(project_id, &branch_id).await.unwrap();
+
+    let branch = branch(&test, &branch_id).await;
+    assert_eq!(branch.commits.len(), 2);
+    assert_eq!(branch.commits[0].description, "add b");
+    assert_eq!(branch.commits[1].description, "add a");
+    assert!(branch.commits[0].files[0].hunks[0]
+        .diff
+        .to_string()
+        .contains("+line eight"));
+    assert!(branch.commits[1].files[0].hunks[0]
+        .diff
+        .to_string()
+        .contains("+line three"));
+    assert_eq!(branch.files.len(), 1);
+    assert_eq!(branch.files[0].path, path::Path::new("c.txt"));
+
+    let mapping = absorbed_hunks
+        .iter()
+        .map(|hunk| (hunk.file_path.display().to_string(), hunk.commit_id))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        mapping,
+        vec![
+            ("a.txt".to_string(), Some(branch.commits[1].id)),
+            ("b.txt".to_string(), Some(branch.commits[0].id)),
+            ("c.txt".to_string(), None),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn lines_that_moved_since() {
+    let test = Test::default();
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+    let branch_id = setup_branch(&test).await;
+
+    let mut lines = numbered_lines(20);
+    fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "add file", None, false)
+        .await
+        .unwrap();
+
+    let header = (1..=5).map(|i| format!("header {i}\n")).collect::<Vec<_>>();
+    lines.splice(0..0, header);
+    fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "add header", None, false)
+        .await
+        .unwrap();
+
+    lines[19] = "line fifteen\n".to_string();
+    fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+
+    let absorbed_hunks = controller.absorb(project_id, &branch_id).await.unwrap();
+
+    let branch = branch(&test, &branch_id).await;
+    assert_eq!(branch.files.len(), 0);
+    assert_eq!(branch.commits.len(), 2);
+    assert_eq!(branch.commits[1].description, "add file");
+    assert!(branch.commits[1].files[0].hunks[0]
+        .diff
+        .to_string()
+        .contains("+line fifteen"));
+    assert_eq!(absorbed_hunks.len(), 1);
+    assert_eq!(absorbed_hunks[0].commit_id, Some(branch.commits[1].id));
+}
+
+#[tokio::test]
+async fn leaves_ambiguous_hunks_alone() {
+    let test = Test::default();
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+    let branch_id = setup_branch(&test).await;
+
+    let mut lines = numbered_lines(10);
+    fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "add file", None, false)
+        .await
+        .unwrap();
+
+    lines[4] = "line five\n".to_string();
+    fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "change line five", None, false)
+        .await
+        .unwrap();
+
+    let commit_ids = branch(&test, &branch_id)
+        .await
+        .commits
+        .iter()
+        .map(|c| c.id)
+        .collect::<Vec<_>>();
+
+    // lines four and six come from the first commit, five from the second
+    lines[3] = "LINE 4\n".to_string();
+    lines[4] = "LINE 5\n".to_string();
+    lines[5] = "LINE 6\n".to_string();
+    fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+
+    let absorbed_hunks = controller.absorb(project_id, &branch_id).await.unwrap();
+    assert_eq!(absorbed_hunks.len(), 1);
+    assert_eq!(absorbed_hunks[0].commit_id, None);
+
+    let branch = branch(&test, &branch_id).await;
+    assert_eq!(
+        branch.commits.iter().map(|c| c.id).collect::<Vec<_>>(),
+        commit_ids
+    );
+    assert_eq!(branch.files.len(), 1);
+}
+
+#[tokio::test]
+async fn leaves_pushed_commits_alone() {
+    let test = Test::default();
+    let Test {
+        repository,
+        project_id,
+        controller,
+        projects,
+        ..
+    } = &test;
+    let branch_id = setup_branch(&test).await;
+
+    let mut lines = numbered_lines(10);
+    fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+    let commit_oid = controller
+        .create_commit(project_id, &branch_id, "add file", None, false)
+        .await
+        .unwrap();
+
+    controller
+        .push_virtual_branch(project_id, &branch_id, false, None)
+        .await
+        .unwrap();
+
+    projects
+        .update(&projects::UpdateRequest {
+            id: *project_id,
+            ok_with_force_push: Some(false),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    lines[2] = "line three\n".to_string();
+    fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+
+    let absorbed_hunks = controller.absorb(project_id, &branch_id).await.unwrap();
+    assert_eq!(absorbed_hunks.len(), 1);
+    assert_eq!(absorbed_hunks[0].commit_id, None);
+
+    let branch = branch(&test, &branch_id).await;
+    assert_eq!(branch.commits[0].id, commit_oid);
+    assert_eq!(branch.files.len(), 1);
+}
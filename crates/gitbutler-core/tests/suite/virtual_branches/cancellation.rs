@@ -0,0 +1,163 @@
+use std::{
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use gitbutler_core::{
+    project_repository::{self, RemoteError},
+    projects::AuthKey,
+};
+
+use super::*;
+
+/// Makes the remote of `repository` one that is reached with an SSH command that never
+/// connects, and returns the file that this command writes its process ID to.
+fn hang_on_remote(repository: &TestProject, scripts: &Path) -> path::PathBuf {
+    let pid_file = scripts.join("ssh.pid");
+    let ssh = scripts.join("hanging-ssh");
+    fs::write(
+        &ssh,
+        format!(
+            "#!/bin/sh\necho $$ > '{}'\nexec sleep 60\n",
+            pid_file.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let repo = git2::Repository::open(repository.path()).unwrap();
+    let mut config = repo.config().unwrap();
+    config
+        .set_str("core.sshCommand", ssh.to_str().unwrap())
+        .unwrap();
+    repo.remote_set_url("origin", "ssh://git@example.invalid/repo.git")
+        .unwrap();
+    pid_file
+}
+
+/// Waits for the process in `pid_file` to be gone, and panics if it doesn't go away.
+fn assert_killed(pid_file: &Path) {
+    let pid = fs::read_to_string(pid_file).unwrap();
+    let start = Instant::now();
+    while Command::new("kill")
+        .args(["-0", pid.trim()])
+        .stderr(Stdio::null())
+        .status()
+        .unwrap()
+        .success()
+    {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "ssh is still running"
+        );
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+async fn use_git_executable(
+    Test {
+        project_id,
+        projects,
+        ..
+    }: &Test,
+) {
+    projects
+        .update(&projects::UpdateRequest {
+            id: *project_id,
+            preferred_key: Some(AuthKey::SystemExecutable),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn fetch_times_out() {
+    let test = Test::default();
+    use_git_executable(&test).await;
+    let scripts = tempfile::tempdir().unwrap();
+    let pid_file = hang_on_remote(&test.repository, scripts.path());
+    git2::Repository::open(test.repository.path())
+        .unwrap()
+        .config()
+        .unwrap()
+        .set_str("gitbutler.remoteTimeout", "1")
+        .unwrap();
+
+    let project = test.projects.get(&test.project_id).unwrap();
+    let project_repository = project_repository::Repository::open(&project).unwrap();
+    let helper = git::credentials::Helper::from_path(test.data_dir.as_ref().unwrap().path());
+    let start = Instant::now();
+    let error = project_repository
+        .fetch("origin", &helper, None, None, None)
+        .unwrap_err();
+
+    assert!(
+        matches!(error, RemoteError::TimedOut(timeout) if timeout == Duration::from_secs(1)),
+        "{error:?}"
+    );
+    assert!(start.elapsed() < Duration::from_secs(30));
+    assert_killed(&pid_file);
+}
+
+#[tokio::test]
+async fn push_can_be_cancelled() {
+    let test = Test::default();
+    use_git_executable(&test).await;
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file.txt"), "content").unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "commit", None, false)
+        .await
+        .unwrap();
+
+    let scripts = tempfile::tempdir().unwrap();
+    let pid_file = hang_on_remote(repository, scripts.path());
+
+    let push = tokio::spawn({
+        let controller = controller.clone();
+        let project_id = *project_id;
+        async move {
+            controller
+                .push_virtual_branch(&project_id, &branch_id, false, None)
+                .await
+        }
+    });
+    // only cancel once ssh is running, so that it's clear that it's killed as well
+    let start = Instant::now();
+    while !pid_file.exists() {
+        assert!(start.elapsed() < Duration::from_secs(10), "ssh never ran");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    controller.cancel_remote_operations(project_id).await;
+
+    let error = push.await.unwrap().unwrap_err();
+    assert!(
+        matches!(
+            error.downcast_ref(),
+            Some(errors::PushError::Remote(RemoteError::Cancelled))
+        ),
+        "{error:?}"
+    );
+    assert_killed(&pid_file);
+
+    // later operations aren't cancelled
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert!(branches[0].upstream.is_none());
+}
@@ -0,0 +1,230 @@
+use gitbutler_core::project_repository::conflicts::ConflictSide;
+
+use super::*;
+
+/// Apply a branch that conflicts with an upstream change of `file.txt`.
+async fn conflicted_branch(
+    repository: &TestProject,
+    project_id: &ProjectId,
+    controller: &Controller,
+) -> branch::BranchId {
+    // make sure we have an undiscovered commit in the remote branch
+    {
+        fs::write(repository.path().join("file.txt"), "first").unwrap();
+        let first_commit_oid = repository.commit_all("first");
+        fs::write(repository.path().join("file.txt"), "second").unwrap();
+        repository.commit_all("second");
+        repository.push();
+        repository.reset_hard(Some(first_commit_oid));
+    }
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file.txt"), "conflict").unwrap();
+
+    controller.update_base_branch(project_id).await.unwrap();
+    controller
+        .apply_virtual_branch(project_id, &branch_id)
+        .await
+        .unwrap();
+
+    branch_id
+}
+
+#[tokio::test]
+async fn list() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let branch_id = conflicted_branch(repository, project_id, controller).await;
+
+    let conflicts = controller.list_conflicts(project_id).await.unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].path, path::PathBuf::from("file.txt"));
+    assert_eq!(conflicts[0].branch_id, Some(branch_id));
+    assert!(conflicts[0].ancestor.is_some());
+    assert!(conflicts[0].ours.is_some());
+    assert!(conflicts[0].theirs.is_some());
+}
+
+#[tokio::test]
+async fn update_base_branch_defers_conflicts_to_apply() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    {
+        fs::write(repository.path().join("file.txt"), "first").unwrap();
+        let first_commit_oid = repository.commit_all("first");
+        fs::write(repository.path().join("file.txt"), "second").unwrap();
+        repository.commit_all("second");
+        repository.push();
+        repository.reset_hard(Some(first_commit_oid));
+    }
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file.txt"), "conflict").unwrap();
+
+    // the conflicting branch is unapplied, so there is nothing to resolve yet
+    controller.update_base_branch(project_id).await.unwrap();
+    assert!(controller
+        .list_conflicts(project_id)
+        .await
+        .unwrap()
+        .is_empty());
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert!(!branches.iter().find(|b| b.id == branch_id).unwrap().active);
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt")).unwrap(),
+        "second"
+    );
+
+    // applying it again brings up the conflict with all three sides
+    controller
+        .apply_virtual_branch(project_id, &branch_id)
+        .await
+        .unwrap();
+    let conflicts = controller.list_conflicts(project_id).await.unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].branch_id, Some(branch_id));
+    assert!(conflicts[0].ancestor.is_some());
+    assert!(conflicts[0].ours.is_some());
+    assert!(conflicts[0].theirs.is_some());
+}
+
+#[tokio::test]
+async fn get() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let branch_id = conflicted_branch(repository, project_id, controller).await;
+
+    let conflict = controller
+        .get_conflict(project_id, path::Path::new("file.txt"))
+        .await
+        .unwrap();
+    assert_eq!(conflict.branch_id, Some(branch_id));
+    assert_eq!(conflict.ancestor.unwrap(), "first");
+    assert_eq!(conflict.ours.unwrap(), "conflict");
+    assert_eq!(conflict.theirs.unwrap(), "second");
+}
+
+#[tokio::test]
+async fn get_not_conflicting() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    conflicted_branch(repository, project_id, controller).await;
+
+    assert!(matches!(
+        controller
+            .get_conflict(project_id, path::Path::new("other.txt"))
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::ConflictError::NotConflicting(_))
+    ));
+}
+
+#[tokio::test]
+async fn resolve_with_content() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    let branch_id = conflicted_branch(repository, project_id, controller).await;
+
+    controller
+        .resolve_conflict(project_id, path::Path::new("file.txt"), b"resolved")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt")).unwrap(),
+        "resolved"
+    );
+    assert!(controller
+        .list_conflicts(project_id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert_eq!(branches[0].id, branch_id);
+    assert_eq!(branches[0].files.len(), 1);
+    assert!(!branches[0].files[0].conflicted);
+
+    // the resolution can be committed as a merge commit
+    let commit_oid = controller
+        .create_commit(project_id, &branch_id, "resolution", None, false)
+        .await
+        .unwrap();
+    assert_eq!(
+        repository.find_commit(commit_oid).unwrap().parent_count(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn resolve_with_side() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    conflicted_branch(repository, project_id, controller).await;
+
+    controller
+        .resolve_conflict_with_side(
+            project_id,
+            path::Path::new("file.txt"),
+            ConflictSide::Theirs,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt")).unwrap(),
+        "second"
+    );
+    assert!(controller
+        .list_conflicts(project_id)
+        .await
+        .unwrap()
+        .is_empty());
+}
@@ -0,0 +1,375 @@
+use gitbutler_core::virtual_branches::HistoryStep;
+
+use super::*;
+
+async fn commit_files(
+    Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    }: &Test,
+    branch_id: &branch::BranchId,
+    files: &[(&str, &str, &str)],
+) -> Vec<git::Oid> {
+    let mut commit_oids = vec![];
+    for (path, content, message) in files {
+        fs::write(repository.path().join(path), content).unwrap();
+        commit_oids.push(
+            controller
+                .create_commit(project_id, branch_id, message, None, false)
+                .await
+                .unwrap(),
+        );
+    }
+    commit_oids
+}
+
+async fn descriptions(test: &Test, branch_id: &branch::BranchId) -> Vec<String> {
+    branch(test, branch_id)
+        .await
+        .commits
+        .iter()
+        .map(|c| c.description.to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn reorder_reword_fixup_and_drop() {
+    let test = Test::default();
+    let branch_id = setup_branch(&test).await;
+    let [one, two, three, four] = commit_files(
+        &test,
+        &branch_id,
+        &[
+            ("file one.txt", "", "commit one"),
+            ("file two.txt", "", "commit two"),
+            ("file three.txt", "", "commit three"),
+            ("file four.txt", "", "commit four"),
+        ],
+    )
+    .await[..] else {
+        unreachable!()
+    };
+
+    test.controller
+        .edit_history(
+            &test.project_id,
+            &branch_id,
+            &[
+                HistoryStep::Pick { commit_id: one },
+                HistoryStep::Reword {
+                    commit_id: three,
+                    message: "commit 3".to_string(),
+                },
+                HistoryStep::Fixup { commit_id: four },
+                HistoryStep::Drop { commit_id: two },
+            ],
+        )
+        .await
+        .unwrap();
+
+    let branch = test
+        .controller
+        .list_virtual_branches(&test.project_id)
+        .await
+        .unwrap()
+        .0
+        .into_iter()
+        .find(|b| b.id == branch_id)
+        .unwrap();
+    assert_eq!(branch.commits.len(), 2);
+    assert_eq!(branch.commits[0].description, "commit 3");
+    assert_eq!(branch.commits[0].files.len(), 2);
+    // the untouched commit at the bottom is kept as it is
+    assert_eq!(branch.commits[1].id, one);
+}
+
+#[tokio::test]
+async fn squash_messages() {
+    let test = Test::default();
+    let branch_id = setup_branch(&test).await;
+    let [one, two, three, four] = commit_files(
+        &test,
+        &branch_id,
+        &[
+            ("file one.txt", "", "commit one"),
+            ("file two.txt", "", "commit two"),
+            ("file three.txt", "", "commit three"),
+            ("file four.txt", "", "commit four"),
+        ],
+    )
+    .await[..] else {
+        unreachable!()
+    };
+
+    test.controller
+        .edit_history(
+            &test.project_id,
+            &branch_id,
+            &[
+                HistoryStep::Pick { commit_id: one },
+                HistoryStep::Squash {
+                    commit_id: two,
+                    message: None,
+                },
+                HistoryStep::Pick { commit_id: three },
+                HistoryStep::Squash {
+                    commit_id: four,
+                    message: Some("three and four".to_string()),
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        descriptions(&test, &branch_id).await,
+        vec!["three and four", "commit one\ncommit two"]
+    );
+}
+
+#[tokio::test]
+async fn edit_amends_uncommitted_changes() {
+    let test = Test::default();
+    let branch_id = setup_branch(&test).await;
+    let [one, two] = commit_files(
+        &test,
+        &branch_id,
+        &[
+            ("file one.txt", "", "commit one"),
+            ("file two.txt", "", "commit two"),
+        ],
+    )
+    .await[..] else {
+        unreachable!()
+    };
+
+    fs::write(test.repository.path().join("file three.txt"), "three").unwrap();
+    test.controller
+        .edit_history(
+            &test.project_id,
+            &branch_id,
+            &[
+                HistoryStep::Edit {
+                    commit_id: one,
+                    ownership: "file three.txt:1-2".parse().unwrap(),
+                },
+                HistoryStep::Pick { commit_id: two },
+            ],
+        )
+        .await
+        .unwrap();
+
+    let branch = test
+        .controller
+        .list_virtual_branches(&test.project_id)
+        .await
+        .unwrap()
+        .0
+        .into_iter()
+        .find(|b| b.id == branch_id)
+        .unwrap();
+    assert_eq!(branch.files.len(), 0);
+    assert_eq!(branch.commits.len(), 2);
+    assert_eq!(branch.commits[1].description, "commit one");
+    assert_eq!(branch.commits[1].files.len(), 2);
+}
+
+#[tokio::test]
+async fn conflict_leaves_branch_untouched() {
+    let test = Test::default();
+    let branch_id = setup_branch(&test).await;
+    let [one, two, three] = commit_files(
+        &test,
+        &branch_id,
+        &[
+            ("file.txt", "one\n", "commit one"),
+            ("file.txt", "two\n", "commit two"),
+            ("other.txt", "", "commit three"),
+        ],
+    )
+    .await[..] else {
+        unreachable!()
+    };
+
+    assert!(matches!(
+        test.controller
+            .edit_history(
+                &test.project_id,
+                &branch_id,
+                &[
+                    HistoryStep::Reword {
+                        commit_id: three,
+                        message: "commit 3".to_string(),
+                    },
+                    HistoryStep::Pick { commit_id: two },
+                    HistoryStep::Pick { commit_id: one },
+                ],
+            )
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::EditHistoryError::StepConflict(oid)) if *oid == two
+    ));
+
+    assert_eq!(
+        descriptions(&test, &branch_id).await,
+        vec!["commit three", "commit two", "commit one"]
+    );
+}
+
+#[tokio::test]
+async fn every_commit_must_be_planned_once() {
+    let test = Test::default();
+    let branch_id = setup_branch(&test).await;
+    let [one, two] = commit_files(
+        &test,
+        &branch_id,
+        &[
+            ("file one.txt", "", "commit one"),
+            ("file two.txt", "", "commit two"),
+        ],
+    )
+    .await[..] else {
+        unreachable!()
+    };
+
+    assert!(matches!(
+        test.controller
+            .edit_history(
+                &test.project_id,
+                &branch_id,
+                &[HistoryStep::Pick { commit_id: two }],
+            )
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::EditHistoryError::MissingCommit(oid)) if *oid == one
+    ));
+
+    assert!(matches!(
+        test.controller
+            .edit_history(
+                &test.project_id,
+                &branch_id,
+                &[
+                    HistoryStep::Pick { commit_id: one },
+                    HistoryStep::Pick { commit_id: two },
+                    HistoryStep::Drop { commit_id: two },
+                ],
+            )
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::EditHistoryError::DuplicateCommit(oid)) if *oid == two
+    ));
+}
+
+#[tokio::test]
+async fn nothing_to_squash_into() {
+    let test = Test::default();
+    let branch_id = setup_branch(&test).await;
+    let [one, two] = commit_files(
+        &test,
+        &branch_id,
+        &[
+            ("file one.txt", "", "commit one"),
+            ("file two.txt", "", "commit two"),
+        ],
+    )
+    .await[..] else {
+        unreachable!()
+    };
+
+    assert!(matches!(
+        test.controller
+            .edit_history(
+                &test.project_id,
+                &branch_id,
+                &[
+                    HistoryStep::Drop { commit_id: one },
+                    HistoryStep::Fixup { commit_id: two },
+                ],
+            )
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::EditHistoryError::NothingToSquashInto(oid)) if *oid == two
+    ));
+}
+
+#[tokio::test]
+async fn forcepush_forbidden() {
+    let test = Test::default();
+    let branch_id = setup_branch(&test).await;
+    let [one] = commit_files(&test, &branch_id, &[("file one.txt", "", "commit one")]).await[..]
+    else {
+        unreachable!()
+    };
+
+    test.controller
+        .push_virtual_branch(&test.project_id, &branch_id, false, None)
+        .await
+        .unwrap();
+
+    test.projects
+        .update(&projects::UpdateRequest {
+            id: test.project_id,
+            ok_with_force_push: Some(false),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let [two, three] = commit_files(
+        &test,
+        &branch_id,
+        &[
+            ("file two.txt", "", "commit two"),
+            ("file three.txt", "", "commit three"),
+        ],
+    )
+    .await[..] else {
+        unreachable!()
+    };
+
+    assert!(matches!(
+        test.controller
+            .edit_history(
+                &test.project_id,
+                &branch_id,
+                &[
+                    HistoryStep::Reword {
+                        commit_id: one,
+                        message: "commit 1".to_string(),
+                    },
+                    HistoryStep::Pick { commit_id: two },
+                    HistoryStep::Pick { commit_id: three },
+                ],
+            )
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::EditHistoryError::ForcePushNotAllowed(_))
+    ));
+
+    // the unpushed commits can still be edited
+    test.controller
+        .edit_history(
+            &test.project_id,
+            &branch_id,
+            &[
+                HistoryStep::Pick { commit_id: one },
+                HistoryStep::Pick { commit_id: three },
+                HistoryStep::Pick { commit_id: two },
+            ],
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        descriptions(&test, &branch_id).await,
+        vec!["commit two", "commit three", "commit one"]
+    );
+}
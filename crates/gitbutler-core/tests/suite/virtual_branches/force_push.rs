@@ -0,0 +1,153 @@
+use gitbutler_core::projects::AuthKey;
+
+use super::*;
+
+/// Creates a branch named `name` with a single commit and pushes it, using `preferred_key` to
+/// choose between pushing with the git executable or with libgit2.
+async fn pushed_branch(
+    Test {
+        repository,
+        project_id,
+        projects,
+        controller,
+        ..
+    }: &Test,
+    preferred_key: AuthKey,
+) -> (branch::BranchId, git::Oid) {
+    projects
+        .update(&projects::UpdateRequest {
+            id: *project_id,
+            preferred_key: Some(preferred_key),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(
+            project_id,
+            &branch::BranchCreateRequest {
+                name: Some("name".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file.txt"), "content").unwrap();
+    let commit_id = controller
+        .create_commit(project_id, &branch_id, "test", None, false)
+        .await
+        .unwrap();
+    controller
+        .push_virtual_branch(project_id, &branch_id, false, None)
+        .await
+        .unwrap();
+
+    (branch_id, commit_id)
+}
+
+fn remote_head(repository: &TestProject) -> git::Oid {
+    repository.fetch();
+    git::Repository::open(repository.path())
+        .unwrap()
+        .refname_to_id("refs/remotes/origin/name")
+        .unwrap()
+}
+
+async fn rewritten_branch_is_force_pushed(preferred_key: AuthKey) {
+    let test = Test::default();
+    let (branch_id, commit_id) = pushed_branch(&test, preferred_key).await;
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+
+    controller
+        .update_commit_message(project_id, &branch_id, commit_id, "reworded")
+        .await
+        .unwrap();
+    controller
+        .push_virtual_branch(project_id, &branch_id, true, None)
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert_ne!(branches[0].head, commit_id);
+    assert_eq!(remote_head(repository), branches[0].head);
+}
+
+async fn force_push_does_not_overwrite_changes_of_others(preferred_key: AuthKey) {
+    let test = Test::default();
+    let (branch_id, commit_id) = pushed_branch(&test, preferred_key).await;
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+
+    // someone else changes the remote branch in the meantime
+    let others_head = repository
+        .find_commit(commit_id)
+        .unwrap()
+        .parent(0)
+        .unwrap()
+        .id();
+    git::Repository::open(repository.path())
+        .unwrap()
+        .find_remote("origin")
+        .unwrap()
+        .push(&[&format!("+{others_head}:refs/heads/name")], None)
+        .unwrap();
+
+    controller
+        .update_commit_message(project_id, &branch_id, commit_id, "reworded")
+        .await
+        .unwrap();
+    assert!(matches!(
+        controller
+            .push_virtual_branch(project_id, &branch_id, true, None)
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::PushError::LeaseViolated(_))
+    ));
+
+    assert_eq!(remote_head(repository), others_head);
+}
+
+mod git_executable {
+    use super::*;
+
+    #[tokio::test]
+    async fn rewritten_branch_is_force_pushed() {
+        super::rewritten_branch_is_force_pushed(AuthKey::SystemExecutable).await;
+    }
+
+    #[tokio::test]
+    async fn force_push_does_not_overwrite_changes_of_others() {
+        super::force_push_does_not_overwrite_changes_of_others(AuthKey::SystemExecutable).await;
+    }
+}
+
+mod libgit2 {
+    use super::*;
+
+    #[tokio::test]
+    async fn rewritten_branch_is_force_pushed() {
+        super::rewritten_branch_is_force_pushed(AuthKey::Default).await;
+    }
+
+    #[tokio::test]
+    async fn force_push_does_not_overwrite_changes_of_others() {
+        super::force_push_does_not_overwrite_changes_of_others(AuthKey::Default).await;
+    }
+}
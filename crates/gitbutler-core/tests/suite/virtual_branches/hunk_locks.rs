@@ -0,0 +1,199 @@
+use super::*;
+
+/// A branch with a file, a change to one of its lines and an unrelated commit on top, and
+/// another, empty branch.
+async fn setup(test: &Test) -> (branch::BranchId, branch::BranchId, Vec<git::Oid>) {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = test;
+
+    let branch_id = setup_branch(test).await;
+    let other_branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    let mut lines = (1..=10).map(|i| format!("line {i}\n")).collect::<Vec<_>>();
+    fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+    let add_file = controller
+        .create_commit(project_id, &branch_id, "add file", None, false)
+        .await
+        .unwrap();
+
+    lines[2] = "line three\n".to_string();
+    fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+    let change_line = controller
+        .create_commit(project_id, &branch_id, "change line three", None, false)
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("other.txt"), "other\n").unwrap();
+    let add_other = controller
+        .create_commit(project_id, &branch_id, "add other", None, false)
+        .await
+        .unwrap();
+
+    lines[2] = "LINE 3\n".to_string();
+    fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+
+    (
+        branch_id,
+        other_branch_id,
+        vec![add_file, change_line, add_other],
+    )
+}
+
+async fn locked_hunk(test: &Test, branch_id: &branch::BranchId) -> branch::BranchOwnershipClaims {
+    let branch = branch(test, branch_id).await;
+    assert_eq!(branch.files.len(), 1);
+    assert!(branch.files[0].hunks[0].locked);
+    format!("file.txt:{}", branch.files[0].hunks[0].id)
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn explain_locks() {
+    let test = Test::default();
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+    let (branch_id, _, commit_oids) = setup(&test).await;
+
+    let ownership = locked_hunk(&test, &branch_id).await;
+    let explanations = controller
+        .explain_hunk_locks(project_id, &ownership)
+        .await
+        .unwrap();
+    assert_eq!(explanations.len(), 1);
+    assert_eq!(explanations[0].file_path, path::Path::new("file.txt"));
+
+    let causes = &explanations[0].causes;
+    assert!(causes.iter().all(|cause| cause.branch_id == branch_id));
+    assert!(causes.iter().all(|cause| !cause.pushed));
+    let changed_line = causes
+        .iter()
+        .find(|cause| cause.commit_id == commit_oids[1])
+        .unwrap();
+    assert_eq!((changed_line.start, changed_line.end), (3, 4));
+    assert_eq!(changed_line.description, "change line three");
+    assert!(causes.iter().any(|cause| cause.commit_id == commit_oids[0]));
+
+    fs::write(repository.path().join("new.txt"), "new\n").unwrap();
+    let explanations = controller
+        .explain_hunk_locks(project_id, &"new.txt:1-2".parse().unwrap())
+        .await
+        .unwrap();
+    assert!(explanations[0].causes.is_empty());
+}
+
+#[tokio::test]
+async fn move_with_dependencies() {
+    let test = Test::default();
+    let Test {
+        project_id,
+        controller,
+        ..
+    } = &test;
+    let (branch_id, other_branch_id, _) = setup(&test).await;
+
+    let ownership = locked_hunk(&test, &branch_id).await;
+    controller
+        .move_hunks_with_dependencies(project_id, &other_branch_id, &ownership)
+        .await
+        .unwrap();
+
+    let source = branch(&test, &branch_id).await;
+    assert_eq!(source.files.len(), 0);
+    let descriptions = source
+        .commits
+        .iter()
+        .map(|c| c.description.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(descriptions, vec!["add other"]);
+
+    let target = branch(&test, &other_branch_id).await;
+    let descriptions = target
+        .commits
+        .iter()
+        .map(|c| c.description.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(descriptions, vec!["change line three", "add file"]);
+    assert_eq!(target.files.len(), 1);
+    assert_eq!(target.files[0].path, path::Path::new("file.txt"));
+    assert!(target.files[0].hunks[0].locked);
+}
+
+#[tokio::test]
+async fn move_with_pushed_dependencies() {
+    let test = Test::default();
+    let Test {
+        project_id,
+        controller,
+        ..
+    } = &test;
+    let (branch_id, other_branch_id, commit_oids) = setup(&test).await;
+
+    controller
+        .push_virtual_branch(project_id, &branch_id, false, None)
+        .await
+        .unwrap();
+
+    let ownership = locked_hunk(&test, &branch_id).await;
+    assert!(matches!(
+        controller
+            .move_hunks_with_dependencies(project_id, &other_branch_id, &ownership)
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::MoveHunksWithDependenciesError::DependsOnPushedCommit(oid)) if commit_oids.contains(oid)
+    ));
+
+    let source = branch(&test, &branch_id).await;
+    assert_eq!(source.commits.len(), 3);
+    assert_eq!(source.files.len(), 1);
+}
+
+#[tokio::test]
+async fn move_with_newer_dependent_commit() {
+    let test = Test::default();
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+    let (branch_id, other_branch_id, commit_oids) = setup(&test).await;
+
+    // a later commit changes a line of "add file", which has to move along with "change line three"
+    let mut lines = (1..=10).map(|i| format!("line {i}\n")).collect::<Vec<_>>();
+    lines[2] = "line three\n".to_string();
+    lines[6] = "line seven\n".to_string();
+    fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+    let change_other_line = controller
+        .create_commit(project_id, &branch_id, "change line seven", None, false)
+        .await
+        .unwrap();
+    lines[2] = "LINE 3\n".to_string();
+    fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+
+    let ownership = locked_hunk(&test, &branch_id).await;
+    assert!(matches!(
+        controller
+            .move_hunks_with_dependencies(project_id, &other_branch_id, &ownership)
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::MoveHunksWithDependenciesError::DependentCommit(oid)) if *oid == change_other_line
+    ));
+
+    let source = branch(&test, &branch_id).await;
+    assert_eq!(source.commits.len(), commit_oids.len() + 1);
+    assert_eq!(source.files.len(), 1);
+}
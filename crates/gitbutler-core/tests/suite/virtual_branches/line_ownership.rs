@@ -0,0 +1,194 @@
+use std::fs;
+
+use gitbutler_core::virtual_branches::branch;
+
+use super::Test;
+
+async fn setup(test: &Test) -> (branch::BranchId, branch::BranchId) {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = test;
+
+    fs::write(
+        repository.path().join("file.txt"),
+        "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n",
+    )
+    .unwrap();
+    repository.commit_all("initial");
+    repository.push();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch1_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    // changes on line 2 and line 9 end up in the same hunk
+    fs::write(
+        repository.path().join("file.txt"),
+        "1\ntwo\n3\n4\n5\n6\n7\n8\nnine\n10\n11\n12\n",
+    )
+    .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert_eq!(branches.len(), 1);
+    assert_eq!(branches[0].files.len(), 1);
+    assert_eq!(branches[0].files[0].hunks.len(), 1);
+    let hunk = &branches[0].files[0].hunks[0];
+    assert!(hunk.lines.is_empty());
+
+    // the lines of the hunk are: " 1", "-2", "+two", " 3", ..., " 8", "-9", "+nine", ...
+    let branch2_id = controller
+        .create_virtual_branch(
+            project_id,
+            &branch::BranchCreateRequest {
+                ownership: Some(
+                    format!("file.txt:{}-{}-{:x}--9.10", hunk.start, hunk.end, hunk.hash)
+                        .parse()
+                        .unwrap(),
+                ),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    (branch1_id, branch2_id)
+}
+
+#[tokio::test]
+async fn claimed_lines_are_split_off_into_their_branch() {
+    let test = Test::default();
+    let (branch1_id, branch2_id) = setup(&test).await;
+
+    let (branches, _) = test
+        .controller
+        .list_virtual_branches(&test.project_id)
+        .await
+        .unwrap();
+    assert_eq!(branches.len(), 2);
+
+    let branch1 = branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert_eq!(branch1.files.len(), 1);
+    assert_eq!(branch1.files[0].hunks.len(), 1);
+    let diff = branch1.files[0].hunks[0].diff.to_string();
+    assert!(diff.contains("+two"));
+    assert!(!diff.contains("+nine"));
+    assert_eq!(branch1.files[0].hunks[0].lines, vec![1, 2]);
+
+    let branch2 = branches.iter().find(|b| b.id == branch2_id).unwrap();
+    assert_eq!(branch2.files.len(), 1);
+    assert_eq!(branch2.files[0].hunks.len(), 1);
+    let diff = branch2.files[0].hunks[0].diff.to_string();
+    assert!(!diff.contains("+two"));
+    assert!(diff.contains("+nine"));
+    assert_eq!(branch2.files[0].hunks[0].lines, vec![9, 10]);
+
+    // the workspace is left alone
+    assert_eq!(
+        fs::read_to_string(test.repository.path().join("file.txt")).unwrap(),
+        "1\ntwo\n3\n4\n5\n6\n7\n8\nnine\n10\n11\n12\n"
+    );
+}
+
+#[tokio::test]
+async fn commit_only_contains_claimed_lines() {
+    let test = Test::default();
+    let (branch1_id, branch2_id) = setup(&test).await;
+
+    test.controller
+        .create_commit(&test.project_id, &branch2_id, "nine", None, false)
+        .await
+        .unwrap();
+
+    let (branches, _) = test
+        .controller
+        .list_virtual_branches(&test.project_id)
+        .await
+        .unwrap();
+
+    let branch2 = branches.iter().find(|b| b.id == branch2_id).unwrap();
+    assert!(branch2.files.is_empty());
+    assert_eq!(branch2.commits.len(), 1);
+    let diff = branch2.commits[0].files[0].hunks[0].diff.to_string();
+    assert!(diff.contains("+nine"));
+    assert!(!diff.contains("+two"));
+
+    let branch1 = branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert_eq!(branch1.files.len(), 1);
+    let diff = branch1.files[0].hunks[0].diff.to_string();
+    assert!(diff.contains("+two"));
+    assert!(!diff.contains("+nine"));
+}
+
+#[tokio::test]
+async fn discarding_claimed_lines_keeps_the_others() {
+    let test = Test::default();
+    let (branch1_id, branch2_id) = setup(&test).await;
+
+    let (branches, _) = test
+        .controller
+        .list_virtual_branches(&test.project_id)
+        .await
+        .unwrap();
+    let hunk = &branches.iter().find(|b| b.id == branch2_id).unwrap().files[0].hunks[0];
+
+    test.controller
+        .unapply_ownership(
+            &test.project_id,
+            &format!("file.txt:{}-{}-{:x}--9.10", hunk.start, hunk.end, hunk.hash)
+                .parse()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(test.repository.path().join("file.txt")).unwrap(),
+        "1\ntwo\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n"
+    );
+
+    let (branches, _) = test
+        .controller
+        .list_virtual_branches(&test.project_id)
+        .await
+        .unwrap();
+    let branch1 = branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert_eq!(branch1.files.len(), 1);
+    let branch2 = branches.iter().find(|b| b.id == branch2_id).unwrap();
+    assert!(branch2.files.is_empty());
+}
+
+#[tokio::test]
+async fn resetting_a_file_keeps_the_lines_of_other_branches() {
+    let test = Test::default();
+    let (branch1_id, branch2_id) = setup(&test).await;
+
+    test.controller
+        .reset_files(&test.project_id, &branch1_id, &["file.txt".to_string()])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(test.repository.path().join("file.txt")).unwrap(),
+        "1\n2\n3\n4\n5\n6\n7\n8\nnine\n10\n11\n12\n"
+    );
+
+    let (branches, _) = test
+        .controller
+        .list_virtual_branches(&test.project_id)
+        .await
+        .unwrap();
+    let branch1 = branches.iter().find(|b| b.id == branch1_id).unwrap();
+    assert!(branch1.files.is_empty());
+    let branch2 = branches.iter().find(|b| b.id == branch2_id).unwrap();
+    assert_eq!(branch2.files.len(), 1);
+    assert!(branch2.files[0].hunks[0].diff.to_string().contains("+nine"));
+}
@@ -0,0 +1,215 @@
+use gitbutler_core::virtual_branches::RoutingRule;
+
+use super::*;
+
+#[tokio::test]
+async fn moves_commits_and_changes() {
+    let test = Test::default();
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+
+    let source_id = setup_branch(&test).await;
+    let target_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("a.txt"), "a\n").unwrap();
+    controller
+        .create_commit(project_id, &source_id, "add a", None, false)
+        .await
+        .unwrap();
+
+    controller
+        .update_virtual_branch(
+            project_id,
+            branch::BranchUpdateRequest {
+                id: target_id,
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    fs::write(repository.path().join("b.txt"), "b\n").unwrap();
+    controller
+        .create_commit(project_id, &target_id, "add b", None, false)
+        .await
+        .unwrap();
+
+    controller
+        .update_virtual_branch(
+            project_id,
+            branch::BranchUpdateRequest {
+                id: source_id,
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    fs::write(repository.path().join("c.txt"), "c\n").unwrap();
+
+    controller
+        .set_routing_rules(
+            project_id,
+            vec![RoutingRule {
+                pattern: "*.md".to_string(),
+                branch_id: source_id,
+            }],
+        )
+        .await
+        .unwrap();
+
+    controller
+        .merge_branches(project_id, &source_id, &target_id)
+        .await
+        .unwrap();
+
+    let branches = controller
+        .list_virtual_branches(project_id)
+        .await
+        .unwrap()
+        .0;
+    assert_eq!(branches.len(), 1);
+    let branch = &branches[0];
+    assert_eq!(branch.id, target_id);
+    assert!(branch.selected_for_changes);
+    let descriptions = branch
+        .commits
+        .iter()
+        .map(|c| c.description.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(descriptions, vec!["add a", "add b"]);
+    assert_eq!(branch.files.len(), 1);
+    assert_eq!(branch.files[0].path, path::Path::new("c.txt"));
+
+    assert_eq!(
+        controller.list_routing_rules(project_id).await.unwrap(),
+        vec![RoutingRule {
+            pattern: "*.md".to_string(),
+            branch_id: target_id,
+        }]
+    );
+}
+
+#[tokio::test]
+async fn restacks_stacked_branches() {
+    let test = Test::default();
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+
+    let source_id = setup_branch(&test).await;
+    let target_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("a.txt"), "a\n").unwrap();
+    controller
+        .create_commit(project_id, &source_id, "add a", None, false)
+        .await
+        .unwrap();
+
+    let child_id = controller
+        .create_virtual_branch(
+            project_id,
+            &branch::BranchCreateRequest {
+                parent: Some(source_id),
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    fs::write(repository.path().join("child.txt"), "child\n").unwrap();
+    controller
+        .create_commit(project_id, &child_id, "add child", None, false)
+        .await
+        .unwrap();
+
+    controller
+        .update_virtual_branch(
+            project_id,
+            branch::BranchUpdateRequest {
+                id: target_id,
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    fs::write(repository.path().join("b.txt"), "b\n").unwrap();
+    controller
+        .create_commit(project_id, &target_id, "add b", None, false)
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        controller
+            .merge_branches(project_id, &child_id, &source_id)
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::MergeBranchesError::Stacked)
+    ));
+
+    controller
+        .merge_branches(project_id, &source_id, &target_id)
+        .await
+        .unwrap();
+
+    let branches = controller
+        .list_virtual_branches(project_id)
+        .await
+        .unwrap()
+        .0;
+    assert_eq!(branches.len(), 2);
+    let target = branches.iter().find(|b| b.id == target_id).unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+    assert_eq!(child.parent, Some(target_id));
+    assert_eq!(child.commits.len(), 1);
+    assert_eq!(child.commits[0].description, "add child");
+    assert_eq!(
+        child.commits[0].parent_ids,
+        vec![target.head],
+        "the child is stacked on the new head of the target"
+    );
+}
+
+#[tokio::test]
+async fn into_itself() {
+    let test = Test::default();
+    let Test {
+        project_id,
+        controller,
+        ..
+    } = &test;
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        controller
+            .merge_branches(project_id, &branch_id, &branch_id)
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::MergeBranchesError::SameBranch)
+    ));
+}
@@ -49,23 +49,77 @@ impl Default for Test {
     }
 }
 
+/// Sets the base branch of the test project and creates an empty virtual branch on top of it.
+async fn setup_branch(
+    Test {
+        project_id,
+        controller,
+        ..
+    }: &Test,
+) -> branch::BranchId {
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap()
+}
+
+async fn branch(
+    Test {
+        project_id,
+        controller,
+        ..
+    }: &Test,
+    branch_id: &branch::BranchId,
+) -> gitbutler_core::virtual_branches::VirtualBranch {
+    controller
+        .list_virtual_branches(project_id)
+        .await
+        .unwrap()
+        .0
+        .into_iter()
+        .find(|b| b.id == *branch_id)
+        .unwrap()
+}
+
+mod absorb;
 mod amend;
 mod apply_virtual_branch;
+#[cfg(unix)]
+mod cancellation;
 mod cherry_pick;
+mod conflicts;
 mod create_commit;
 mod create_virtual_branch_from_branch;
 mod delete_virtual_branch;
+mod edit_history;
 mod fetch_from_target;
+mod force_push;
+mod hunk_locks;
 mod init;
 mod insert_blank_commit;
+mod line_ownership;
+mod merge_branches;
 mod move_commit_file;
 mod move_commit_to_vbranch;
+mod progress;
+mod push_hooks;
 mod references;
 mod reorder_commit;
 mod reset_virtual_branch;
+mod routing_rules;
 mod selected_for_changes;
 mod set_base_branch;
+#[cfg(unix)]
+mod signing;
+mod split_branch;
+mod split_commit;
 mod squash;
+mod stacked;
 mod unapply;
 mod unapply_ownership;
 mod undo_commit;
@@ -0,0 +1,220 @@
+use std::sync::{Arc, Mutex};
+
+use gitbutler_core::{
+    project_repository::{RemoteOperation, RemoteProgress, Stage},
+    projects::{AuthKey, Project},
+};
+
+use super::*;
+
+/// Watchers that remember all the progress they are told about.
+#[derive(Clone, Default)]
+struct RecordingWatchers {
+    progress: Arc<Mutex<Vec<(ProjectId, RemoteProgress)>>>,
+}
+
+#[async_trait::async_trait]
+impl projects::Watchers for RecordingWatchers {
+    fn watch(&self, _project: &Project) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn stop(&self, _id: ProjectId) {}
+
+    async fn fetch_gb_data(&self, _id: ProjectId) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn push_gb_data(&self, _id: ProjectId) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn report_progress(&self, id: ProjectId, progress: RemoteProgress) {
+        self.progress.lock().unwrap().push((id, progress));
+    }
+}
+
+impl RecordingWatchers {
+    fn take(&self, operation: RemoteOperation) -> Vec<RemoteProgress> {
+        self.progress
+            .lock()
+            .unwrap()
+            .drain(..)
+            .map(|(_, progress)| progress)
+            .filter(|progress| progress.operation == operation)
+            .collect()
+    }
+}
+
+/// Like [`Test`], but with watchers that record progress.
+struct ProgressTest {
+    repository: TestProject,
+    project_id: ProjectId,
+    controller: Controller,
+    watchers: RecordingWatchers,
+    _data_dir: TempDir,
+}
+
+async fn progress_test(preferred_key: AuthKey) -> ProgressTest {
+    let data_dir = paths::data_dir();
+    let watchers = RecordingWatchers::default();
+    let users = users::Controller::from_path(data_dir.path());
+    let projects = projects::Controller::new(
+        data_dir.path().into(),
+        projects::storage::Storage::from_path(data_dir.path()),
+        users.clone(),
+        Some(watchers.clone()),
+    );
+    let keys = keys::Controller::from_path(data_dir.path());
+    let helper = git::credentials::Helper::from_path(data_dir.path());
+
+    let repository = TestProject::default();
+    let project = projects
+        .add(repository.path())
+        .expect("failed to add project");
+    projects
+        .update(&projects::UpdateRequest {
+            id: project.id,
+            preferred_key: Some(preferred_key),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    ProgressTest {
+        repository,
+        project_id: project.id,
+        controller: Controller::new(projects, users, keys, helper),
+        watchers,
+        _data_dir: data_dir,
+    }
+}
+
+/// Adds a commit to the remote's `master` that the project doesn't have yet.
+fn commit_on_remote(repository: &TestProject) {
+    let local = git2::Repository::open(repository.path()).unwrap();
+    let url = local
+        .find_remote("origin")
+        .unwrap()
+        .url()
+        .unwrap()
+        .to_owned();
+    let clone_dir = tempfile::tempdir().unwrap();
+    let clone = git2::Repository::clone(&url, clone_dir.path()).unwrap();
+
+    fs::write(clone_dir.path().join("remote.txt"), "from the remote").unwrap();
+    let mut index = clone.index().unwrap();
+    index.add_path(path::Path::new("remote.txt")).unwrap();
+    let tree = clone.find_tree(index.write_tree().unwrap()).unwrap();
+    let parent = clone.head().unwrap().peel_to_commit().unwrap();
+    let signature = git2::Signature::now("test", "test@email.com").unwrap();
+    clone
+        .commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "remote commit",
+            &tree,
+            &[&parent],
+        )
+        .unwrap();
+    clone
+        .find_remote("origin")
+        .unwrap()
+        .push(&["refs/heads/master:refs/heads/master"], None)
+        .unwrap();
+}
+
+async fn push_reports_progress(preferred_key: AuthKey) {
+    let ProgressTest {
+        repository,
+        project_id,
+        controller,
+        watchers,
+        ..
+    } = &progress_test(preferred_key).await;
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file.txt"), "content").unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "commit", None, false)
+        .await
+        .unwrap();
+
+    controller
+        .push_virtual_branch(project_id, &branch_id, false, None)
+        .await
+        .unwrap();
+
+    let progress = watchers.take(RemoteOperation::Push);
+    let last_write = progress
+        .iter()
+        .filter(|progress| progress.progress.stage == Stage::Writing)
+        .last()
+        .expect("the push reports writing objects");
+    assert_eq!(last_write.remote, "origin");
+    assert_eq!(Some(last_write.progress.current), last_write.progress.total);
+    assert!(last_write.progress.current > 0);
+}
+
+async fn fetch_reports_progress(preferred_key: AuthKey) {
+    let ProgressTest {
+        repository,
+        project_id,
+        controller,
+        watchers,
+        ..
+    } = &progress_test(preferred_key).await;
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+    commit_on_remote(repository);
+
+    controller
+        .fetch_from_target(project_id, None)
+        .await
+        .unwrap();
+
+    let progress = watchers.take(RemoteOperation::Fetch);
+    assert!(!progress.is_empty(), "the fetch reports progress");
+    assert!(progress.iter().all(|progress| progress.remote == "origin"));
+    let last = progress.last().unwrap();
+    assert_eq!(Some(last.progress.current), last.progress.total);
+}
+
+mod git_executable {
+    use super::*;
+
+    #[tokio::test]
+    async fn push_reports_progress() {
+        super::push_reports_progress(AuthKey::SystemExecutable).await;
+    }
+
+    #[tokio::test]
+    async fn fetch_reports_progress() {
+        super::fetch_reports_progress(AuthKey::SystemExecutable).await;
+    }
+}
+
+mod libgit2 {
+    use super::*;
+
+    #[tokio::test]
+    async fn push_reports_progress() {
+        super::push_reports_progress(AuthKey::Default).await;
+    }
+
+    #[tokio::test]
+    async fn fetch_reports_progress() {
+        super::fetch_reports_progress(AuthKey::Default).await;
+    }
+}
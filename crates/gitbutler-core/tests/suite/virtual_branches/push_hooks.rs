@@ -0,0 +1,172 @@
+use gitbutler_core::projects::AuthKey;
+
+use super::*;
+
+/// Creates a branch named `name` with a single commit, using `preferred_key` to choose between
+/// pushing with the git executable or with libgit2.
+async fn committed_branch(
+    Test {
+        repository,
+        project_id,
+        projects,
+        controller,
+        ..
+    }: &Test,
+    preferred_key: AuthKey,
+) -> (branch::BranchId, git::Oid) {
+    projects
+        .update(&projects::UpdateRequest {
+            id: *project_id,
+            preferred_key: Some(preferred_key),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(
+            project_id,
+            &branch::BranchCreateRequest {
+                name: Some("name".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file.txt"), "content").unwrap();
+    let commit_id = controller
+        .create_commit(project_id, &branch_id, "test", None, false)
+        .await
+        .unwrap();
+
+    (branch_id, commit_id)
+}
+
+fn create_hook(repository: &TestProject, name: &str, script: &str) {
+    git2_hooks::create_hook(
+        &git2::Repository::open(repository.path()).unwrap(),
+        name,
+        script.as_bytes(),
+    );
+}
+
+fn remote_head(repository: &TestProject) -> Option<git::Oid> {
+    repository.fetch();
+    git::Repository::open(repository.path())
+        .unwrap()
+        .refname_to_id("refs/remotes/origin/name")
+        .ok()
+}
+
+async fn hooks_receive_the_pushed_refs(preferred_key: AuthKey) {
+    let test = Test::default();
+    let (branch_id, commit_id) = committed_branch(&test, preferred_key).await;
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+
+    create_hook(
+        repository,
+        git::HOOK_PRE_PUSH,
+        "#!/bin/sh\necho \"$1\" >> .git/pre-push-input\ncat >> .git/pre-push-input\n",
+    );
+    create_hook(
+        repository,
+        git::HOOK_POST_PUSH,
+        "#!/bin/sh\necho \"$1\" >> .git/post-push-input\ncat >> .git/post-push-input\n",
+    );
+
+    controller
+        .push_virtual_branch(project_id, &branch_id, false, None)
+        .await
+        .unwrap();
+    assert_eq!(remote_head(repository), Some(commit_id));
+
+    // each hook runs exactly once, even if the git executable pushes
+    let expected = format!(
+        "origin\nrefs/gitbutler/name {commit_id} refs/heads/name {}\n",
+        "0".repeat(40)
+    );
+    let git_dir = repository.path().join(".git");
+    assert_eq!(
+        fs::read_to_string(git_dir.join("pre-push-input")).unwrap(),
+        expected
+    );
+    assert_eq!(
+        fs::read_to_string(git_dir.join("post-push-input")).unwrap(),
+        expected
+    );
+}
+
+async fn pre_push_hook_rejects_push(preferred_key: AuthKey) {
+    let test = Test::default();
+    let (branch_id, _) = committed_branch(&test, preferred_key).await;
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+
+    create_hook(
+        repository,
+        git::HOOK_PRE_PUSH,
+        "#!/bin/sh\necho 'secret found in file.txt'\nexit 1\n",
+    );
+    create_hook(
+        repository,
+        git::HOOK_POST_PUSH,
+        "#!/bin/sh\ntouch .git/post-push-ran\n",
+    );
+
+    let error = controller
+        .push_virtual_branch(project_id, &branch_id, false, None)
+        .await
+        .unwrap_err();
+    let Some(errors::PushError::PrePushHookRejected(output)) = error.downcast_ref() else {
+        panic!("unexpected error: {error:?}")
+    };
+    assert!(output.starts_with("secret found in file.txt\n"), "{output}");
+
+    assert_eq!(remote_head(repository), None);
+    assert!(!repository.path().join(".git/post-push-ran").exists());
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert!(branches[0].upstream.is_none());
+}
+
+mod git_executable {
+    use super::*;
+
+    #[tokio::test]
+    async fn hooks_receive_the_pushed_refs() {
+        super::hooks_receive_the_pushed_refs(AuthKey::SystemExecutable).await;
+    }
+
+    #[tokio::test]
+    async fn pre_push_hook_rejects_push() {
+        super::pre_push_hook_rejects_push(AuthKey::SystemExecutable).await;
+    }
+}
+
+mod libgit2 {
+    use super::*;
+
+    #[tokio::test]
+    async fn hooks_receive_the_pushed_refs() {
+        super::hooks_receive_the_pushed_refs(AuthKey::Default).await;
+    }
+
+    #[tokio::test]
+    async fn pre_push_hook_rejects_push() {
+        super::pre_push_hook_rejects_push(AuthKey::Default).await;
+    }
+}
@@ -0,0 +1,229 @@
+use gitbutler_core::virtual_branches::RoutingRule;
+
+use super::*;
+
+#[tokio::test]
+async fn new_hunks_follow_matching_rules() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let default_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    let docs_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    let deps_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    controller
+        .set_routing_rules(
+            project_id,
+            vec![
+                RoutingRule {
+                    pattern: "docs/".to_string(),
+                    branch_id: docs_id,
+                },
+                RoutingRule {
+                    pattern: "*.lock".to_string(),
+                    branch_id: deps_id,
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+    fs::create_dir_all(repository.path().join("docs/guide")).unwrap();
+    fs::write(repository.path().join("docs/guide/intro.md"), "intro").unwrap();
+    fs::create_dir_all(repository.path().join("app")).unwrap();
+    fs::write(repository.path().join("app/Cargo.lock"), "lock").unwrap();
+    fs::write(repository.path().join("main.rs"), "main").unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let files_of = |id| {
+        branches
+            .iter()
+            .find(|b| b.id == id)
+            .unwrap()
+            .files
+            .iter()
+            .map(|f| f.path.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        files_of(docs_id),
+        vec![path::PathBuf::from("docs/guide/intro.md")]
+    );
+    assert_eq!(
+        files_of(deps_id),
+        vec![path::PathBuf::from("app/Cargo.lock")]
+    );
+    assert_eq!(files_of(default_id), vec![path::PathBuf::from("main.rs")]);
+}
+
+#[tokio::test]
+async fn rules_for_unapplied_branches_are_skipped() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let default_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    let docs_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    controller
+        .set_routing_rules(
+            project_id,
+            vec![RoutingRule {
+                pattern: "docs/".to_string(),
+                branch_id: docs_id,
+            }],
+        )
+        .await
+        .unwrap();
+    controller
+        .unapply_virtual_branch(project_id, &docs_id)
+        .await
+        .unwrap();
+
+    fs::create_dir_all(repository.path().join("docs")).unwrap();
+    fs::write(repository.path().join("docs/intro.md"), "intro").unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let default_branch = branches.iter().find(|b| b.id == default_id).unwrap();
+    assert_eq!(default_branch.files.len(), 1);
+}
+
+#[tokio::test]
+async fn invalid_rules_are_rejected() {
+    let Test {
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    for pattern in ["", "/etc/passwd", "../outside"] {
+        assert!(matches!(
+            controller
+                .set_routing_rules(
+                    project_id,
+                    vec![RoutingRule {
+                        pattern: pattern.to_string(),
+                        branch_id,
+                    }],
+                )
+                .await
+                .unwrap_err()
+                .downcast_ref(),
+            Some(errors::RoutingRuleError::InvalidPattern(_))
+        ));
+    }
+
+    assert!(matches!(
+        controller
+            .set_routing_rules(
+                project_id,
+                vec![RoutingRule {
+                    pattern: "docs/".to_string(),
+                    branch_id: branch::BranchId::generate(),
+                }],
+            )
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::RoutingRuleError::BranchNotFound(_))
+    ));
+
+    assert!(controller
+        .list_routing_rules(project_id)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn deleting_branch_removes_its_rules() {
+    let Test {
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let default_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    let docs_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    let default_rule = RoutingRule {
+        pattern: "src/".to_string(),
+        branch_id: default_id,
+    };
+    controller
+        .set_routing_rules(
+            project_id,
+            vec![
+                RoutingRule {
+                    pattern: "docs/".to_string(),
+                    branch_id: docs_id,
+                },
+                default_rule.clone(),
+            ],
+        )
+        .await
+        .unwrap();
+
+    controller
+        .delete_virtual_branch(project_id, &docs_id)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        controller.list_routing_rules(project_id).await.unwrap(),
+        vec![default_rule]
+    );
+}
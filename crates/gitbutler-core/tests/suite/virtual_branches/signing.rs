@@ -0,0 +1,336 @@
+use std::{os::unix::fs::PermissionsExt, path::Path, process::Command};
+
+use gitbutler_core::error::AnyhowContextExt;
+
+use super::*;
+
+fn set_config(repository: &TestProject, values: &[(&str, &str)]) {
+    let repo = git2::Repository::open(repository.path()).unwrap();
+    let mut config = repo.config().unwrap();
+    for (key, value) in values {
+        config.set_str(key, value).unwrap();
+    }
+}
+
+/// A stand-in for gpg that signs anything with a fixed signature, or fails like gpg does
+/// when the key is missing if `fail` is set.
+fn fake_gpg(dir: &Path, fail: bool) -> String {
+    let path = dir.join("fake-gpg");
+    let script = if fail {
+        "#!/bin/sh\ncat > /dev/null\necho \"gpg: signing failed: No secret key\" >&2\nexit 2\n"
+    } else {
+        "#!/bin/sh\ncat > /dev/null\necho \"[GNUPG:] SIG_CREATED D 1 8 00 0 ABCDEF\" >&2\n\
+         printf -- '-----BEGIN PGP SIGNATURE-----\\n\\nfake-signature-for-%s\\n-----END PGP SIGNATURE-----\\n' \"$3\"\n"
+    };
+    fs::write(&path, script).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+/// The message the user gets to see for `err`.
+fn user_message(err: gitbutler_core::error::Error) -> String {
+    anyhow::Error::from(err)
+        .custom_context_or_root_cause()
+        .to_string()
+}
+
+async fn commits_on_branch(
+    controller: &Controller,
+    project_id: &ProjectId,
+    branch_id: &branch::BranchId,
+) -> Vec<git::Oid> {
+    controller
+        .list_virtual_branches(project_id)
+        .await
+        .unwrap()
+        .0
+        .into_iter()
+        .find(|b| b.id == *branch_id)
+        .unwrap()
+        .commits
+        .iter()
+        .map(|c| c.id)
+        .collect()
+}
+
+#[tokio::test]
+async fn commits_are_signed_with_the_configured_gpg_program() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+    let scripts = tempfile::tempdir().unwrap();
+    set_config(
+        repository,
+        &[
+            ("commit.gpgSign", "true"),
+            ("user.signingkey", "ABCDEF"),
+            ("gpg.program", &fake_gpg(scripts.path(), false)),
+        ],
+    );
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file one.txt"), "").unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "commit one", None, false)
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file two.txt"), "").unwrap();
+    let commit_two_oid = controller
+        .create_commit(project_id, &branch_id, "commit two", None, false)
+        .await
+        .unwrap();
+
+    let commit = repository.find_commit(commit_two_oid).unwrap();
+    let header = commit.raw_header().unwrap();
+    assert!(header.contains("gpgsig -----BEGIN PGP SIGNATURE-----"));
+    assert!(header.contains("fake-signature-for-ABCDEF"));
+
+    controller
+        .squash(project_id, &branch_id, commit_two_oid)
+        .await
+        .unwrap();
+    let commits = commits_on_branch(controller, project_id, &branch_id).await;
+    assert_eq!(commits.len(), 1);
+    let commit = repository.find_commit(commits[0]).unwrap();
+    assert!(commit
+        .raw_header()
+        .unwrap()
+        .contains("fake-signature-for-ABCDEF"));
+}
+
+#[tokio::test]
+async fn gpg_picks_the_key_by_committer_without_a_signing_key() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+    let scripts = tempfile::tempdir().unwrap();
+    set_config(
+        repository,
+        &[
+            ("commit.gpgSign", "true"),
+            ("user.name", "Jane Doe"),
+            ("user.email", "jane@example.com"),
+            ("gpg.program", &fake_gpg(scripts.path(), false)),
+        ],
+    );
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file.txt"), "content").unwrap();
+    let commit_oid = controller
+        .create_commit(project_id, &branch_id, "commit", None, false)
+        .await
+        .unwrap();
+
+    // not signed with GitButler's own key
+    let commit = repository.find_commit(commit_oid).unwrap();
+    assert!(commit
+        .raw_header()
+        .unwrap()
+        .contains("fake-signature-for-Jane Doe <jane@example.com>"));
+}
+
+#[tokio::test]
+async fn missing_ssh_signing_key_is_reported() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+    set_config(
+        repository,
+        &[("commit.gpgSign", "true"), ("gpg.format", "ssh")],
+    );
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file.txt"), "content").unwrap();
+    let err = controller
+        .create_commit(project_id, &branch_id, "commit", None, false)
+        .await
+        .unwrap_err();
+    let message = user_message(err);
+    assert!(message.contains("user.signingkey"), "{message}");
+
+    let commits = commits_on_branch(controller, project_id, &branch_id).await;
+    assert!(commits.is_empty());
+}
+
+#[tokio::test]
+async fn rewritten_commits_are_signed_with_an_ssh_key() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+    let keys = tempfile::tempdir().unwrap();
+    let key_path = keys.path().join("id_ed25519");
+    let status = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+        .arg(&key_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    // both commits are unsigned at first
+    fs::write(repository.path().join("file one.txt"), "").unwrap();
+    let commit_one_oid = controller
+        .create_commit(project_id, &branch_id, "commit one", None, false)
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file two.txt"), "").unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "commit two", None, false)
+        .await
+        .unwrap();
+
+    set_config(
+        repository,
+        &[
+            ("commit.gpgSign", "true"),
+            ("gpg.format", "ssh"),
+            ("user.signingkey", key_path.to_str().unwrap()),
+        ],
+    );
+
+    // rewording the first commit also rebases the second one, which both get signed
+    controller
+        .update_commit_message(project_id, &branch_id, commit_one_oid, "commit one updated")
+        .await
+        .unwrap();
+
+    let commits = commits_on_branch(controller, project_id, &branch_id).await;
+    assert_eq!(commits.len(), 2);
+    for commit_oid in commits {
+        let commit = repository.find_commit(commit_oid).unwrap();
+        assert!(commit
+            .raw_header()
+            .unwrap()
+            .contains("gpgsig -----BEGIN SSH SIGNATURE-----"));
+    }
+}
+
+#[tokio::test]
+async fn signer_failure_is_reported() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+    let scripts = tempfile::tempdir().unwrap();
+    set_config(
+        repository,
+        &[
+            ("commit.gpgSign", "true"),
+            ("user.signingkey", "ABCDEF"),
+            ("gpg.program", &fake_gpg(scripts.path(), true)),
+        ],
+    );
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file.txt"), "content").unwrap();
+    let err = controller
+        .create_commit(project_id, &branch_id, "commit", None, false)
+        .await
+        .unwrap_err();
+    let message = user_message(err);
+    assert!(
+        message.contains("failed to sign the commit: gpg: signing failed: No secret key"),
+        "{message}"
+    );
+
+    let commits = commits_on_branch(controller, project_id, &branch_id).await;
+    assert!(commits.is_empty());
+}
+
+#[tokio::test]
+async fn unknown_signing_format_is_reported() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+    set_config(
+        repository,
+        &[
+            ("commit.gpgSign", "true"),
+            ("gpg.format", "pgp"),
+            ("user.signingkey", "ABCDEF"),
+        ],
+    );
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file.txt"), "content").unwrap();
+    let err = controller
+        .create_commit(project_id, &branch_id, "commit", None, false)
+        .await
+        .unwrap_err();
+    let message = user_message(err);
+    assert!(
+        message.contains("unsupported gpg.format `pgp`"),
+        "{message}"
+    );
+}
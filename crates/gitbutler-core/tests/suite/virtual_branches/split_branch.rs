@@ -0,0 +1,168 @@
+use super::*;
+
+async fn setup(test: &Test) -> (branch::BranchId, Vec<git::Oid>) {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = test;
+
+    let branch_id = setup_branch(test).await;
+
+    let mut commit_oids = vec![];
+    for name in ["one", "two", "three"] {
+        fs::write(repository.path().join(format!("file {name}.txt")), "").unwrap();
+        commit_oids.push(
+            controller
+                .create_commit(
+                    project_id,
+                    &branch_id,
+                    &format!("commit {name}"),
+                    None,
+                    false,
+                )
+                .await
+                .unwrap(),
+        );
+    }
+    (branch_id, commit_oids)
+}
+
+#[tokio::test]
+async fn moves_commits_and_changes() {
+    let test = Test::default();
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+    let (branch_id, commit_oids) = setup(&test).await;
+
+    fs::write(repository.path().join("file four.txt"), "four\n").unwrap();
+    fs::write(repository.path().join("file five.txt"), "five\n").unwrap();
+
+    let new_branch_id = controller
+        .split_branch(
+            project_id,
+            &branch_id,
+            Some("split".to_string()),
+            &"file four.txt:1-2".parse().unwrap(),
+            &[commit_oids[1]],
+        )
+        .await
+        .unwrap();
+
+    let source = branch(&test, &branch_id).await;
+    let descriptions = source
+        .commits
+        .iter()
+        .map(|c| c.description.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(descriptions, vec!["commit three", "commit one"]);
+    // the commit below the one that moved is kept as it is
+    assert_eq!(source.commits[1].id, commit_oids[0]);
+    assert_eq!(source.files.len(), 1);
+    assert_eq!(source.files[0].path, path::Path::new("file five.txt"));
+
+    let new_branch = branch(&test, &new_branch_id).await;
+    assert_eq!(new_branch.name, "split");
+    assert_eq!(new_branch.order, source.order + 1);
+    assert_eq!(new_branch.commits.len(), 1);
+    assert_eq!(new_branch.commits[0].description, "commit two");
+    assert_eq!(new_branch.files.len(), 1);
+    assert_eq!(new_branch.files[0].path, path::Path::new("file four.txt"));
+}
+
+#[tokio::test]
+async fn nothing_to_split() {
+    let test = Test::default();
+    let Test {
+        project_id,
+        controller,
+        ..
+    } = &test;
+    let (branch_id, _) = setup(&test).await;
+
+    assert!(matches!(
+        controller
+            .split_branch(
+                project_id,
+                &branch_id,
+                None,
+                &branch::BranchOwnershipClaims::default(),
+                &[],
+            )
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::SplitBranchError::NothingToSplit)
+    ));
+
+    assert!(matches!(
+        controller
+            .split_branch(
+                project_id,
+                &branch_id,
+                None,
+                &"file six.txt:1-2".parse().unwrap(),
+                &[],
+            )
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::SplitBranchError::TargetOwnershipNotFound(_))
+    ));
+
+    assert_eq!(
+        controller
+            .list_virtual_branches(project_id)
+            .await
+            .unwrap()
+            .0
+            .len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn forcepush_forbidden() {
+    let test = Test::default();
+    let Test {
+        project_id,
+        controller,
+        projects,
+        ..
+    } = &test;
+    let (branch_id, commit_oids) = setup(&test).await;
+
+    controller
+        .push_virtual_branch(project_id, &branch_id, false, None)
+        .await
+        .unwrap();
+
+    projects
+        .update(&projects::UpdateRequest {
+            id: *project_id,
+            ok_with_force_push: Some(false),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        controller
+            .split_branch(
+                project_id,
+                &branch_id,
+                None,
+                &branch::BranchOwnershipClaims::default(),
+                &[commit_oids[1]],
+            )
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::SplitBranchError::ForcePushNotAllowed(_))
+    ));
+}
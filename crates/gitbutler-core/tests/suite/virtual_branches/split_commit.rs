@@ -0,0 +1,299 @@
+use gitbutler_core::virtual_branches::CommitPart;
+
+use super::*;
+
+fn part(ownership: &str, message: &str) -> CommitPart {
+    CommitPart {
+        ownership: ownership.parse().unwrap(),
+        message: message.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn head_by_file() {
+    let test = Test::default();
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file one.txt"), "one\n").unwrap();
+    fs::write(repository.path().join("file two.txt"), "two\n").unwrap();
+    let commit_oid = controller
+        .create_commit(project_id, &branch_id, "commit", None, false)
+        .await
+        .unwrap();
+
+    let new_commit_oids = controller
+        .split_commit(
+            project_id,
+            &branch_id,
+            commit_oid,
+            &[
+                part("file two.txt:1-2", "add file two"),
+                part("file one.txt:1-2", "add file one"),
+            ],
+        )
+        .await
+        .unwrap();
+
+    let branch = branch(&test, &branch_id).await;
+    assert_eq!(branch.files.len(), 0);
+    assert_eq!(
+        branch.commits.iter().map(|c| c.id).collect::<Vec<_>>(),
+        new_commit_oids.into_iter().rev().collect::<Vec<_>>()
+    );
+    assert_eq!(branch.commits[0].description, "add file one");
+    assert_eq!(branch.commits[0].files.len(), 1);
+    assert_eq!(
+        branch.commits[0].files[0].path.display().to_string(),
+        "file one.txt"
+    );
+    assert_eq!(branch.commits[1].description, "add file two");
+    assert_eq!(branch.commits[1].files.len(), 1);
+    assert_eq!(
+        branch.commits[1].files[0].path.display().to_string(),
+        "file two.txt"
+    );
+}
+
+#[tokio::test]
+async fn middle_by_hunk() {
+    let test = Test::default();
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    let mut lines = (1..=20).map(|i| format!("line {i}\n")).collect::<Vec<_>>();
+    fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "commit one", None, false)
+        .await
+        .unwrap();
+
+    lines[1] = "line two\n".to_string();
+    lines[17] = "line eighteen\n".to_string();
+    fs::write(repository.path().join("file.txt"), lines.concat()).unwrap();
+    let commit_two_oid = controller
+        .create_commit(project_id, &branch_id, "commit two", None, false)
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("other.txt"), "").unwrap();
+    controller
+        .create_commit(project_id, &branch_id, "commit three", None, false)
+        .await
+        .unwrap();
+
+    let hunk_ids = branch(&test, &branch_id).await.commits[1].files[0]
+        .hunks
+        .iter()
+        .map(|hunk| hunk.id.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(hunk_ids.len(), 2);
+
+    controller
+        .split_commit(
+            project_id,
+            &branch_id,
+            commit_two_oid,
+            &[
+                part(&format!("file.txt:{}", hunk_ids[1]), "change line eighteen"),
+                part(&format!("file.txt:{}", hunk_ids[0]), "change line two"),
+            ],
+        )
+        .await
+        .unwrap();
+
+    let branch = branch(&test, &branch_id).await;
+    assert_eq!(branch.files.len(), 0);
+    let descriptions = branch
+        .commits
+        .iter()
+        .map(|c| c.description.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        descriptions,
+        vec![
+            "commit three",
+            "change line two",
+            "change line eighteen",
+            "commit one"
+        ]
+    );
+    assert!(branch.commits[2].files[0].hunks[0]
+        .diff
+        .to_string()
+        .contains("+line eighteen"));
+    assert!(branch.commits[1].files[0].hunks[0]
+        .diff
+        .to_string()
+        .contains("+line two"));
+}
+
+#[tokio::test]
+async fn every_hunk_must_be_in_one_part() {
+    let test = Test::default();
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &test;
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file one.txt"), "one\n").unwrap();
+    fs::write(repository.path().join("file two.txt"), "two\n").unwrap();
+    fs::write(repository.path().join("file three.txt"), "three\n").unwrap();
+    let commit_oid = controller
+        .create_commit(project_id, &branch_id, "commit", None, false)
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        controller
+            .split_commit(
+                project_id,
+                &branch_id,
+                commit_oid,
+                &[
+                    part("file one.txt:1-2", "one"),
+                    part("file two.txt:1-2", "two"),
+                ],
+            )
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::SplitCommitError::HunkNotClaimed(path)) if path == path::Path::new("file three.txt")
+    ));
+
+    assert!(matches!(
+        controller
+            .split_commit(
+                project_id,
+                &branch_id,
+                commit_oid,
+                &[
+                    part("file one.txt:1-2\nfile three.txt:1-2", "one"),
+                    part("file two.txt:1-2\nfile three.txt:1-2", "two"),
+                ],
+            )
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::SplitCommitError::HunkClaimedTwice(path)) if path == path::Path::new("file three.txt")
+    ));
+
+    assert!(matches!(
+        controller
+            .split_commit(
+                project_id,
+                &branch_id,
+                commit_oid,
+                &[part(
+                    "file one.txt:1-2\nfile two.txt:1-2\nfile three.txt:1-2",
+                    "all"
+                )],
+            )
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::SplitCommitError::TooFewParts)
+    ));
+
+    assert_eq!(branch(&test, &branch_id).await.commits[0].id, commit_oid);
+}
+
+#[tokio::test]
+async fn forcepush_forbidden() {
+    let test = Test::default();
+    let Test {
+        repository,
+        project_id,
+        controller,
+        projects,
+        ..
+    } = &test;
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let branch_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    fs::write(repository.path().join("file one.txt"), "one\n").unwrap();
+    fs::write(repository.path().join("file two.txt"), "two\n").unwrap();
+    let commit_oid = controller
+        .create_commit(project_id, &branch_id, "commit", None, false)
+        .await
+        .unwrap();
+
+    controller
+        .push_virtual_branch(project_id, &branch_id, false, None)
+        .await
+        .unwrap();
+
+    projects
+        .update(&projects::UpdateRequest {
+            id: *project_id,
+            ok_with_force_push: Some(false),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        controller
+            .split_commit(
+                project_id,
+                &branch_id,
+                commit_oid,
+                &[
+                    part("file one.txt:1-2", "one"),
+                    part("file two.txt:1-2", "two"),
+                ],
+            )
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::SplitCommitError::ForcePushNotAllowed(_))
+    ));
+}
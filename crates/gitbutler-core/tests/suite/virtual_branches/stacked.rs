@@ -0,0 +1,794 @@
+use gitbutler_core::virtual_branches::{CommitPart, HistoryStep};
+
+use super::*;
+
+/// Create a branch with a commit of `file.txt`, and a branch stacked on top of it
+/// with a commit of `file2.txt`, which is selected for changes.
+async fn stack(
+    repository: &TestProject,
+    project_id: &ProjectId,
+    controller: &Controller,
+) -> (branch::BranchId, branch::BranchId) {
+    let parent_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file.txt"), "content").unwrap();
+    controller
+        .create_commit(project_id, &parent_id, "parent", None, false)
+        .await
+        .unwrap();
+
+    let child_id = controller
+        .create_virtual_branch(
+            project_id,
+            &branch::BranchCreateRequest {
+                parent: Some(parent_id),
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file2.txt"), "content2").unwrap();
+    controller
+        .create_commit(project_id, &child_id, "child", None, false)
+        .await
+        .unwrap();
+
+    (parent_id, child_id)
+}
+
+#[tokio::test]
+async fn create_on_parent() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = stack(repository, project_id, controller).await;
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert_eq!(branches.len(), 2);
+    let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+
+    assert_eq!(parent.parent, None);
+    assert_eq!(parent.commits.len(), 1);
+    assert_eq!(parent.commits[0].description, "parent");
+
+    // only the commits on top of the parent belong to the child
+    assert_eq!(child.parent, Some(parent_id));
+    assert_eq!(child.commits.len(), 1);
+    assert_eq!(child.commits[0].description, "child");
+    assert_eq!(child.commits[0].parent_ids, vec![parent.head]);
+}
+
+#[tokio::test]
+async fn create_on_missing_parent() {
+    let Test {
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        controller
+            .create_virtual_branch(
+                project_id,
+                &branch::BranchCreateRequest {
+                    parent: Some(branch::BranchId::generate()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::CreateVirtualBranchError::ParentNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn restack_after_parent_commit() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = stack(repository, project_id, controller).await;
+
+    controller
+        .update_virtual_branch(
+            project_id,
+            branch::BranchUpdateRequest {
+                id: parent_id,
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file3.txt"), "content3").unwrap();
+    let parent_head = controller
+        .create_commit(project_id, &parent_id, "parent 2", None, false)
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+
+    assert_eq!(parent.commits.len(), 2);
+    assert_eq!(parent.head, parent_head);
+    assert_eq!(child.commits.len(), 1);
+    assert_eq!(child.commits[0].description, "child");
+    assert_eq!(child.commits[0].parent_ids, vec![parent_head]);
+    assert!(child.files.is_empty());
+}
+
+#[tokio::test]
+async fn no_restack_of_pushed_child_without_force_push() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        projects,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = stack(repository, project_id, controller).await;
+
+    controller
+        .push_virtual_branch(project_id, &child_id, false, None)
+        .await
+        .unwrap();
+    projects
+        .update(&projects::UpdateRequest {
+            id: *project_id,
+            ok_with_force_push: Some(false),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    controller
+        .update_virtual_branch(
+            project_id,
+            branch::BranchUpdateRequest {
+                id: parent_id,
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file3.txt"), "content3").unwrap();
+    let parent_head = controller
+        .create_commit(project_id, &parent_id, "parent 2", None, false)
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+    assert_eq!(child.commits.len(), 1);
+    assert_ne!(child.commits[0].parent_ids, vec![parent_head]);
+    assert!(!child.requires_force);
+}
+
+#[tokio::test]
+async fn restack_after_parent_amend() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = stack(repository, project_id, controller).await;
+
+    let parent_commit = controller
+        .list_virtual_branches(project_id)
+        .await
+        .unwrap()
+        .0
+        .into_iter()
+        .find(|b| b.id == parent_id)
+        .unwrap()
+        .head;
+
+    controller
+        .update_virtual_branch(
+            project_id,
+            branch::BranchUpdateRequest {
+                id: parent_id,
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file3.txt"), "content3").unwrap();
+    let to_amend: branch::BranchOwnershipClaims = "file3.txt:1-2".parse().unwrap();
+    controller
+        .amend(project_id, &parent_id, parent_commit, &to_amend)
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+
+    assert_eq!(parent.commits.len(), 1);
+    assert_ne!(parent.head, parent_commit);
+    assert_eq!(parent.commits[0].files.len(), 2);
+    assert_eq!(child.commits.len(), 1);
+    assert_eq!(child.commits[0].parent_ids, vec![parent.head]);
+}
+
+#[tokio::test]
+async fn restack_after_parent_history_edit() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = stack(repository, project_id, controller).await;
+
+    let parent_commit = controller
+        .list_virtual_branches(project_id)
+        .await
+        .unwrap()
+        .0
+        .into_iter()
+        .find(|b| b.id == parent_id)
+        .unwrap()
+        .head;
+
+    controller
+        .edit_history(
+            project_id,
+            &parent_id,
+            &[HistoryStep::Reword {
+                commit_id: parent_commit,
+                message: "parent reworded".to_string(),
+            }],
+        )
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+
+    assert_eq!(parent.commits.len(), 1);
+    assert_eq!(parent.commits[0].description, "parent reworded");
+    assert_eq!(child.commits.len(), 1);
+    assert_eq!(child.commits[0].description, "child");
+    assert_eq!(child.commits[0].parent_ids, vec![parent.head]);
+}
+
+#[tokio::test]
+async fn restack_after_parent_commit_split() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = stack(repository, project_id, controller).await;
+
+    controller
+        .update_virtual_branch(
+            project_id,
+            branch::BranchUpdateRequest {
+                id: parent_id,
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file3.txt"), "content3\n").unwrap();
+    fs::write(repository.path().join("file4.txt"), "content4\n").unwrap();
+    let parent_commit = controller
+        .create_commit(project_id, &parent_id, "parent 2", None, false)
+        .await
+        .unwrap();
+
+    controller
+        .split_commit(
+            project_id,
+            &parent_id,
+            parent_commit,
+            &[
+                CommitPart {
+                    ownership: "file3.txt:1-2".parse().unwrap(),
+                    message: "parent 2".to_string(),
+                },
+                CommitPart {
+                    ownership: "file4.txt:1-2".parse().unwrap(),
+                    message: "parent 3".to_string(),
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+
+    assert_eq!(parent.commits.len(), 3);
+    assert_eq!(child.commits.len(), 1);
+    assert_eq!(child.commits[0].description, "child");
+    assert_eq!(child.commits[0].parent_ids, vec![parent.head]);
+}
+
+#[tokio::test]
+async fn restack_after_parent_absorb() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = stack(repository, project_id, controller).await;
+
+    controller
+        .update_virtual_branch(
+            project_id,
+            branch::BranchUpdateRequest {
+                id: parent_id,
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    let mut lines = (1..=10).map(|i| format!("line {i}\n")).collect::<Vec<_>>();
+    fs::write(repository.path().join("file3.txt"), lines.concat()).unwrap();
+    controller
+        .create_commit(project_id, &parent_id, "parent 2", None, false)
+        .await
+        .unwrap();
+
+    lines[2] = "line three\n".to_string();
+    fs::write(repository.path().join("file3.txt"), lines.concat()).unwrap();
+    controller.absorb(project_id, &parent_id).await.unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+
+    assert_eq!(parent.commits.len(), 2);
+    assert!(parent.files.is_empty());
+    assert_eq!(child.commits.len(), 1);
+    assert_eq!(child.commits[0].description, "child");
+    assert_eq!(child.commits[0].parent_ids, vec![parent.head]);
+}
+
+#[tokio::test]
+async fn restack_after_merge_into_parent() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = stack(repository, project_id, controller).await;
+
+    let source_id = controller
+        .create_virtual_branch(
+            project_id,
+            &branch::BranchCreateRequest {
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file3.txt"), "content3").unwrap();
+    controller
+        .create_commit(project_id, &source_id, "source", None, false)
+        .await
+        .unwrap();
+
+    controller
+        .merge_branches(project_id, &source_id, &parent_id)
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+
+    assert_eq!(parent.commits.len(), 2);
+    assert_eq!(child.commits.len(), 1);
+    assert_eq!(child.commits[0].description, "child");
+    assert_eq!(child.commits[0].parent_ids, vec![parent.head]);
+}
+
+#[tokio::test]
+async fn restack_after_parent_split() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = stack(repository, project_id, controller).await;
+
+    controller
+        .update_virtual_branch(
+            project_id,
+            branch::BranchUpdateRequest {
+                id: parent_id,
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    fs::write(repository.path().join("file3.txt"), "content3").unwrap();
+    let parent_commit = controller
+        .create_commit(project_id, &parent_id, "parent 2", None, false)
+        .await
+        .unwrap();
+
+    let new_branch_id = controller
+        .split_branch(
+            project_id,
+            &parent_id,
+            None,
+            &branch::BranchOwnershipClaims::default(),
+            &[parent_commit],
+        )
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+    let new_branch = branches.iter().find(|b| b.id == new_branch_id).unwrap();
+
+    assert_eq!(parent.commits.len(), 1);
+    assert_eq!(new_branch.commits.len(), 1);
+    assert_eq!(child.commits.len(), 1);
+    assert_eq!(child.commits[0].description, "child");
+    assert_eq!(child.commits[0].parent_ids, vec![parent.head]);
+}
+
+#[tokio::test]
+async fn restack_after_moving_hunks_out_of_parent() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = stack(repository, project_id, controller).await;
+    let other_id = controller
+        .create_virtual_branch(project_id, &branch::BranchCreateRequest::default())
+        .await
+        .unwrap();
+
+    controller
+        .update_virtual_branch(
+            project_id,
+            branch::BranchUpdateRequest {
+                id: parent_id,
+                selected_for_changes: Some(true),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    let mut lines = (1..=10).map(|i| format!("line {i}\n")).collect::<Vec<_>>();
+    fs::write(repository.path().join("file3.txt"), lines.concat()).unwrap();
+    controller
+        .create_commit(project_id, &parent_id, "parent 2", None, false)
+        .await
+        .unwrap();
+    lines[2] = "line three\n".to_string();
+    fs::write(repository.path().join("file3.txt"), lines.concat()).unwrap();
+
+    let parent = controller
+        .list_virtual_branches(project_id)
+        .await
+        .unwrap()
+        .0
+        .into_iter()
+        .find(|b| b.id == parent_id)
+        .unwrap();
+    let ownership = format!("file3.txt:{}", parent.files[0].hunks[0].id)
+        .parse()
+        .unwrap();
+    controller
+        .move_hunks_with_dependencies(project_id, &other_id, &ownership)
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+
+    assert_eq!(parent.commits.len(), 1);
+    assert_eq!(parent.commits[0].description, "parent");
+    assert_eq!(child.commits.len(), 1);
+    assert_eq!(child.commits[0].description, "child");
+    assert_eq!(child.commits[0].parent_ids, vec![parent.head]);
+}
+
+#[tokio::test]
+async fn restack_after_base_update() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    // make sure we have an undiscovered commit in the remote branch
+    {
+        fs::write(repository.path().join("upstream.txt"), "first").unwrap();
+        let first_commit_oid = repository.commit_all("first");
+        fs::write(repository.path().join("upstream.txt"), "second").unwrap();
+        repository.commit_all("second");
+        repository.push();
+        repository.reset_hard(Some(first_commit_oid));
+    }
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = stack(repository, project_id, controller).await;
+
+    controller.update_base_branch(project_id).await.unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+
+    assert!(parent.active);
+    assert!(child.active);
+    assert_eq!(parent.commits.len(), 1);
+    assert_eq!(child.commits.len(), 1);
+    assert_eq!(child.commits[0].description, "child");
+    assert_eq!(child.commits[0].parent_ids, vec![parent.head]);
+
+    assert_eq!(
+        fs::read_to_string(repository.path().join("upstream.txt")).unwrap(),
+        "second"
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file.txt")).unwrap(),
+        "content"
+    );
+    assert_eq!(
+        fs::read_to_string(repository.path().join("file2.txt")).unwrap(),
+        "content2"
+    );
+}
+
+#[tokio::test]
+async fn restack_after_parent_integrated() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = stack(repository, project_id, controller).await;
+
+    controller
+        .push_virtual_branch(project_id, &parent_id, false, None)
+        .await
+        .unwrap();
+
+    let parent = controller
+        .list_virtual_branches(project_id)
+        .await
+        .unwrap()
+        .0
+        .into_iter()
+        .find(|b| b.id == parent_id)
+        .unwrap();
+
+    repository.rebase_and_merge(&parent.upstream.unwrap().name);
+
+    controller
+        .fetch_from_target(project_id, None)
+        .await
+        .unwrap();
+    controller.update_base_branch(project_id).await.unwrap();
+
+    // the parent is gone, so the child sits on the target now
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert_eq!(branches.len(), 1);
+    let child = &branches[0];
+    assert_eq!(child.id, child_id);
+    assert!(child.active);
+    assert_eq!(child.parent, None);
+    assert_eq!(child.commits.len(), 1);
+    assert_eq!(child.commits[0].description, "child");
+}
+
+#[tokio::test]
+async fn push_pushes_parent() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = stack(repository, project_id, controller).await;
+
+    controller
+        .push_virtual_branch(project_id, &child_id, false, None)
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    let parent = branches.iter().find(|b| b.id == parent_id).unwrap();
+    let child = branches.iter().find(|b| b.id == child_id).unwrap();
+
+    let parent_upstream = parent.upstream.as_ref().unwrap();
+    let child_upstream = child.upstream.as_ref().unwrap();
+    assert_ne!(parent_upstream.name, child_upstream.name);
+    assert_eq!(parent_upstream.sha, parent.head);
+    assert_eq!(child_upstream.sha, child.head);
+    assert!(parent.commits.iter().all(|c| c.is_remote));
+    assert!(child.commits.iter().all(|c| c.is_remote));
+}
+
+#[tokio::test]
+async fn unapply_parent() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = stack(repository, project_id, controller).await;
+
+    assert!(matches!(
+        controller
+            .unapply_virtual_branch(project_id, &parent_id)
+            .await
+            .unwrap_err()
+            .downcast_ref(),
+        Some(errors::UnapplyBranchError::Stacked)
+    ));
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert!(branches.iter().all(|b| b.active));
+    assert!(repository.path().join("file.txt").exists());
+
+    controller
+        .unapply_virtual_branch(project_id, &child_id)
+        .await
+        .unwrap();
+    controller
+        .unapply_virtual_branch(project_id, &parent_id)
+        .await
+        .unwrap();
+
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert!(branches.iter().all(|b| !b.active));
+    assert!(!repository.path().join("file.txt").exists());
+    assert!(!repository.path().join("file2.txt").exists());
+}
+
+#[tokio::test]
+async fn delete_parent() {
+    let Test {
+        repository,
+        project_id,
+        controller,
+        ..
+    } = &Test::default();
+
+    controller
+        .set_base_branch(project_id, &"refs/remotes/origin/master".parse().unwrap())
+        .await
+        .unwrap();
+
+    let (parent_id, child_id) = stack(repository, project_id, controller).await;
+
+    controller
+        .delete_virtual_branch(project_id, &parent_id)
+        .await
+        .unwrap();
+
+    // the child takes over the commits of its parent
+    let (branches, _) = controller.list_virtual_branches(project_id).await.unwrap();
+    assert_eq!(branches.len(), 1);
+    assert_eq!(branches[0].id, child_id);
+    assert_eq!(branches[0].parent, None);
+    assert_eq!(branches[0].commits.len(), 2);
+}
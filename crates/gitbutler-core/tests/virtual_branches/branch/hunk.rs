@@ -41,6 +41,37 @@ fn to_string_no_hash() {
     );
 }
 
+#[test]
+fn parse_with_lines() {
+    let hunk = "2-3--123-3.1.1".parse::<Hunk>().unwrap();
+    assert_eq!(
+        hunk,
+        Hunk::new(2, 3, None, Some(123))
+            .unwrap()
+            .with_lines(vec![1, 3])
+    );
+    assert_eq!("2-3--123-1.3", hunk.to_string());
+}
+
+#[test]
+fn parse_with_lines_no_timestamp() {
+    let hunk = "2-3---4".parse::<Hunk>().unwrap();
+    assert_eq!(hunk.timestamp_ms, None);
+    assert_eq!(hunk.lines, vec![4]);
+    assert_eq!("2-3---4", hunk.to_string());
+}
+
+#[test]
+fn eq_with_lines() {
+    let hunk = "1-2".parse::<Hunk>().unwrap();
+    assert_ne!(hunk, hunk.clone().with_lines(vec![1]));
+    assert_ne!(
+        hunk.clone().with_lines(vec![1]),
+        hunk.clone().with_lines(vec![2])
+    );
+    assert!(hunk.same_hunk(&hunk.clone().with_lines(vec![1])));
+}
+
 #[test]
 fn hash_diff_no_diff_header_is_normal_hash() {
     let actual = Hunk::hash_diff("a".as_ref());
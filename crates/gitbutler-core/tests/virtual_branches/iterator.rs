@@ -42,6 +42,8 @@ fn new_test_branch() -> virtual_branches::branch::Branch {
         ownership: virtual_branches::branch::BranchOwnershipClaims::default(),
         order: TEST_INDEX.load(Ordering::Relaxed),
         selected_for_changes: Some(1),
+        parent: None,
+        parent_head: None,
     }
 }
 
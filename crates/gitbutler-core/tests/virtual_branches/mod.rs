@@ -123,7 +123,7 @@ fn signed_commit() -> Result<()> {
         &branch1_id,
         "test commit",
         None,
-        Some(suite.keys.get_or_create()?).as_ref(),
+        Some(git::Signer::Key(Box::new(suite.keys.get_or_create()?))).as_ref(),
         None,
         false,
     )?;
@@ -813,7 +813,7 @@ fn merge_vbranch_upstream_clean_rebase() -> Result<()> {
     merge_virtual_branch_upstream(
         project_repository,
         &branch1.id,
-        Some(suite.keys.get_or_create()?).as_ref(),
+        Some(git::Signer::Key(Box::new(suite.keys.get_or_create()?))).as_ref(),
         None,
     )?;
 
@@ -2107,7 +2107,7 @@ fn pre_commit_hook_rejection() -> Result<()> {
         &branch1_id,
         "test commit",
         None,
-        Some(suite.keys.get_or_create()?).as_ref(),
+        Some(git::Signer::Key(Box::new(suite.keys.get_or_create()?))).as_ref(),
         None,
         true,
     );
@@ -2172,7 +2172,7 @@ fn post_commit_hook() -> Result<()> {
         &branch1_id,
         "test commit",
         None,
-        Some(suite.keys.get_or_create()?).as_ref(),
+        Some(git::Signer::Key(Box::new(suite.keys.get_or_create()?))).as_ref(),
         None,
         true,
     )?;
@@ -2221,7 +2221,7 @@ fn commit_msg_hook_rejection() -> Result<()> {
         &branch1_id,
         "test commit",
         None,
-        Some(suite.keys.get_or_create()?).as_ref(),
+        Some(git::Signer::Key(Box::new(suite.keys.get_or_create()?))).as_ref(),
         None,
         true,
     );
@@ -2238,3 +2238,230 @@ fn commit_msg_hook_rejection() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn pre_commit_hook_rejection_output() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case();
+
+    set_test_target(project_repository)?;
+
+    let branch1_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())
+        .expect("failed to create virtual branch")
+        .id;
+
+    std::fs::write(Path::new(&project.path).join("test.txt"), "content\n")?;
+
+    let hook = b"#!/bin/sh
+    echo 'lint failed'
+    echo 'test.txt: bad formatting' >&2
+    exit 1
+            ";
+
+    git2_hooks::create_hook(
+        (&project_repository.git_repository).into(),
+        git2_hooks::HOOK_PRE_COMMIT,
+        hook,
+    );
+
+    let error = commit(
+        project_repository,
+        &branch1_id,
+        "test commit",
+        None,
+        None,
+        None,
+        true,
+    )
+    .unwrap_err();
+
+    let CommitError::CommitHookRejected(output) = error else {
+        panic!("unexpected error: {error:?}")
+    };
+    assert_eq!(&output, "lint failed\ntest.txt: bad formatting\n");
+
+    Ok(())
+}
+
+#[test]
+fn pre_commit_hook_without_shebang() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case();
+
+    set_test_target(project_repository)?;
+
+    let branch1_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())
+        .expect("failed to create virtual branch")
+        .id;
+
+    std::fs::write(Path::new(&project.path).join("test.txt"), "content\n")?;
+
+    // can't be executed directly, so it's run by `sh`
+    let hook = b"echo 'rejected'
+    exit 1
+            ";
+
+    git2_hooks::create_hook(
+        (&project_repository.git_repository).into(),
+        git2_hooks::HOOK_PRE_COMMIT,
+        hook,
+    );
+
+    let error = commit(
+        project_repository,
+        &branch1_id,
+        "test commit",
+        None,
+        None,
+        None,
+        true,
+    )
+    .unwrap_err();
+
+    let CommitError::CommitHookRejected(output) = error else {
+        panic!("unexpected error: {error:?}")
+    };
+    assert_eq!(&output, "rejected\n");
+
+    Ok(())
+}
+
+#[test]
+fn commit_hooks_only_see_the_committed_branch() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case();
+
+    set_test_target(project_repository)?;
+
+    let branch1_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())
+        .expect("failed to create virtual branch")
+        .id;
+    std::fs::write(Path::new(&project.path).join("one.txt"), "one\n")?;
+    virtual_branches::get_status_by_branch(project_repository, None)?;
+
+    create_virtual_branch(
+        project_repository,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+    )
+    .expect("failed to create virtual branch");
+    std::fs::write(Path::new(&project.path).join("two.txt"), "two\n")?;
+    virtual_branches::get_status_by_branch(project_repository, None)?;
+
+    // record what the hooks see in the git directory, which is shared with the real worktree
+    for hook_name in [git2_hooks::HOOK_PRE_COMMIT, git2_hooks::HOOK_COMMIT_MSG] {
+        let hook = format!(
+            "#!/bin/sh
+    ls > \"$GIT_COMMON_DIR/{hook_name}-worktree\"
+    git ls-files > \"$GIT_COMMON_DIR/{hook_name}-index\"
+            "
+        );
+        git2_hooks::create_hook(
+            (&project_repository.git_repository).into(),
+            hook_name,
+            hook.as_bytes(),
+        );
+    }
+
+    commit(
+        project_repository,
+        &branch1_id,
+        "test commit",
+        None,
+        None,
+        None,
+        true,
+    )?;
+
+    let git_dir = project_repository.git_repository.path();
+    for hook_name in [git2_hooks::HOOK_PRE_COMMIT, git2_hooks::HOOK_COMMIT_MSG] {
+        for seen in ["worktree", "index"] {
+            let files = std::fs::read_to_string(git_dir.join(format!("{hook_name}-{seen}")))?;
+            let files = files.lines().collect::<Vec<_>>();
+            assert!(files.contains(&"one.txt"), "{hook_name} {seen}: {files:?}");
+            assert!(!files.contains(&"two.txt"), "{hook_name} {seen}: {files:?}");
+        }
+    }
+
+    // the real worktree is left alone
+    assert!(Path::new(&project.path).join("two.txt").exists());
+
+    Ok(())
+}
+
+#[test]
+fn pre_commit_hook_diffs_against_the_branch_head() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case();
+
+    set_test_target(project_repository)?;
+
+    let branch1_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())
+        .expect("failed to create virtual branch")
+        .id;
+    std::fs::write(Path::new(&project.path).join("one.txt"), "one\n")?;
+    virtual_branches::get_status_by_branch(project_repository, None)?;
+
+    let branch2_id = create_virtual_branch(
+        project_repository,
+        &BranchCreateRequest {
+            selected_for_changes: Some(true),
+            ..Default::default()
+        },
+    )
+    .expect("failed to create virtual branch")
+    .id;
+    std::fs::write(Path::new(&project.path).join("two.txt"), "two\n")?;
+    commit(
+        project_repository,
+        &branch2_id,
+        "commit two",
+        None,
+        None,
+        None,
+        false,
+    )?;
+
+    // the integration commit contains `two.txt`, which must not show up as staged
+    let hook = b"#!/bin/sh
+    git diff --cached --name-only > \"$GIT_COMMON_DIR/staged\"
+            ";
+    git2_hooks::create_hook(
+        (&project_repository.git_repository).into(),
+        git2_hooks::HOOK_PRE_COMMIT,
+        hook,
+    );
+
+    commit(
+        project_repository,
+        &branch1_id,
+        "commit one",
+        None,
+        None,
+        None,
+        true,
+    )?;
+
+    let staged = std::fs::read_to_string(project_repository.git_repository.path().join("staged"))?;
+    assert_eq!(staged, "one.txt\n");
+
+    Ok(())
+}
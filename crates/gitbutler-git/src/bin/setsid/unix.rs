@@ -1,8 +1,29 @@
 use nix::{
     libc::{c_int, wait, EXIT_FAILURE, WEXITSTATUS, WIFEXITED, WIFSIGNALED, WTERMSIG},
-    unistd::{fork, setsid, ForkResult},
+    sys::signal::{killpg, sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal},
+    unistd::{fork, setsid, ForkResult, Pid},
 };
-use std::{os::unix::process::CommandExt, process};
+use std::{
+    os::unix::process::CommandExt,
+    process,
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+/// The child, which leads the session (and process group) it creates.
+static CHILD: AtomicI32 = AtomicI32::new(0);
+
+/// Passes signals that are meant to terminate us on to the child's session,
+/// which doesn't get them otherwise, as it's no longer in our process group.
+extern "C" fn forward_signal(signal: c_int) {
+    let child = CHILD.load(Ordering::SeqCst);
+    if child > 0 {
+        if let Ok(signal) = Signal::try_from(signal) {
+            // before the child called setsid() there is no such group,
+            // but then the child received the signal along with us.
+            killpg(Pid::from_raw(child), signal).ok();
+        }
+    }
+}
 
 pub fn main() {
     let has_pipe_var = std::env::var("GITBUTLER_ASKPASS_PIPE")
@@ -16,6 +37,16 @@ pub fn main() {
 
     match unsafe { fork() }.unwrap() {
         ForkResult::Parent { child, .. } => {
+            CHILD.store(child.as_raw(), Ordering::SeqCst);
+            let forward = SigAction::new(
+                SigHandler::Handler(forward_signal),
+                SaFlags::SA_RESTART,
+                SigSet::empty(),
+            );
+            for signal in [Signal::SIGTERM, Signal::SIGINT, Signal::SIGHUP] {
+                unsafe { sigaction(signal, &forward) }.unwrap();
+            }
+
             let mut status: c_int = 0;
 
             let waited_pid = unsafe { wait(&mut status as *mut _) };
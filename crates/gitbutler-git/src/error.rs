@@ -11,6 +11,10 @@ pub enum Error<BE: std::error::Error + core::fmt::Debug + Send + Sync + 'static>
     /// Usually returned by a push or fetch operation.
     #[error("a ref-spec was not found: {0}")]
     RefNotFound(String),
+    /// A forced push was rejected because the remote ref was no longer
+    /// at the object id it was leased at, i.e. someone else updated it.
+    #[error("the remote ref changed since it was leased: {0}")]
+    StaleLease(String),
     /// An authorized operation was attempted, but the authorization
     /// credentials were rejected by the remote (or further credentials
     /// were required).
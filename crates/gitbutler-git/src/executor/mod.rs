@@ -1,5 +1,7 @@
 use std::{collections::HashMap, path::Path, time::Duration};
 
+#[cfg(test)]
+pub mod scripted;
 #[cfg(any(test, feature = "tokio"))]
 pub mod tokio;
 
@@ -52,7 +54,9 @@ pub unsafe trait GitExecutor {
     /// Returns a tuple of `(exit_code, stdout, stderr)`.
     ///
     /// To the best of their abilities, child processes should
-    /// be killed if the future is dropped.
+    /// be killed if the future is dropped. This includes the
+    /// processes they spawned in turn, like `ssh`, which is
+    /// how operations are cancelled.
     ///
     /// `Err` is returned if the command could not be executed,
     /// **not** if the command returned a non-zero exit code.
@@ -63,6 +67,31 @@ pub unsafe trait GitExecutor {
         envs: Option<HashMap<String, String>>,
     ) -> Result<(usize, String, String), Self::Error>;
 
+    /// Like [`GitExecutor::execute_raw`], but also passes each line
+    /// the command writes to stderr to `on_stderr` as soon as it is
+    /// complete, which is how Git's progress is reported.
+    ///
+    /// Lines end in either `\n` or `\r`, as Git uses the latter to
+    /// update progress in place. The line terminators are not passed
+    /// to `on_stderr`, and neither are empty lines.
+    ///
+    /// The default implementation only calls `on_stderr` once
+    /// the command exited.
+    async fn execute_raw_streaming<P: AsRef<Path>, F: FnMut(&str)>(
+        &self,
+        args: &[&str],
+        cwd: P,
+        envs: Option<HashMap<String, String>>,
+        mut on_stderr: F,
+    ) -> Result<(usize, String, String), Self::Error> {
+        let (status, stdout, stderr) = self.execute_raw(args, cwd, envs).await?;
+        stderr
+            .split(['\r', '\n'])
+            .filter(|line| !line.is_empty())
+            .for_each(&mut on_stderr);
+        Ok((status, stdout, stderr))
+    }
+
     /// Executes the given Git command with sane defaults.
     /// `git` is never passed as the first argument (arg 0).
     ///
@@ -73,6 +102,18 @@ pub unsafe trait GitExecutor {
         args: &[&str],
         cwd: P,
         envs: Option<HashMap<String, String>>,
+    ) -> Result<(usize, String, String), Self::Error> {
+        self.execute_streaming(args, cwd, envs, |_| {}).await
+    }
+
+    /// Like [`GitExecutor::execute`], but streams stderr to `on_stderr`
+    /// as described in [`GitExecutor::execute_raw_streaming`].
+    async fn execute_streaming<P: AsRef<Path>, F: FnMut(&str)>(
+        &self,
+        args: &[&str],
+        cwd: P,
+        envs: Option<HashMap<String, String>>,
+        on_stderr: F,
     ) -> Result<(usize, String, String), Self::Error> {
         let mut args = args.as_ref().to_vec();
 
@@ -88,7 +129,8 @@ pub unsafe trait GitExecutor {
         envs.insert("GIT_TERMINAL_PROMPT".into(), "0".into());
         envs.insert("LC_ALL".into(), "C".into()); // Force English. We need this for parsing output.
 
-        self.execute_raw(&args, cwd, Some(envs)).await
+        self.execute_raw_streaming(&args, cwd, Some(envs), on_stderr)
+            .await
     }
 
     /// Creates a named pipe server that is compatible with
@@ -143,6 +185,12 @@ pub unsafe trait GitExecutor {
     ///
     /// **Do not follow symbolic links.**
     async fn stat<P: AsRef<Path>>(&self, path: P) -> Result<FileStat, Self::Error>;
+
+    /// Waits for `duration` to pass.
+    ///
+    /// This is used to time out operations without tying
+    /// this library to a particular async runtime.
+    async fn sleep(&self, duration: Duration);
 }
 
 /// Stats for a file on the filesystem.
@@ -0,0 +1,316 @@
+//! An in-process [`GitExecutor`] that replays scripted Git invocations,
+//! including the askpass connections Git would make while prompting for
+//! credentials, so that the authorization harness can be tested without
+//! Git or a network.
+
+use super::{AskpassServer, FileStat, GitExecutor, Pid, Socket};
+use futures::{channel::mpsc, lock::Mutex as AsyncMutex, StreamExt};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// A credentials prompt, as the askpass utility would relay it.
+#[derive(Debug, Clone)]
+pub struct ScriptedPrompt {
+    /// The prompt, e.g. `Password for 'https://example.com': `.
+    pub prompt: String,
+    /// The secret the askpass utility presents, or `None` for the one Git was given.
+    pub secret: Option<String>,
+}
+
+/// How a scripted Git invocation behaves.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedCommand {
+    subcommand: String,
+    prompts: Vec<ScriptedPrompt>,
+    status: usize,
+    stdout: String,
+    stderr: String,
+    hang: bool,
+}
+
+impl ScriptedCommand {
+    /// A command that answers the first invocation of `subcommand`, e.g. `fetch`,
+    /// and exits successfully without any output.
+    pub fn new(subcommand: &str) -> Self {
+        Self {
+            subcommand: subcommand.to_owned(),
+            ..Self::default()
+        }
+    }
+
+    /// Prompts for credentials, in order, before exiting.
+    pub fn prompt(self, prompt: &str) -> Self {
+        self.prompt_with_secret(prompt, None)
+    }
+
+    /// Prompts for credentials like [`Self::prompt`], but presents `secret`
+    /// to the askpass server instead of the one Git was given.
+    pub fn prompt_with_secret(mut self, prompt: &str, secret: Option<&str>) -> Self {
+        self.prompts.push(ScriptedPrompt {
+            prompt: prompt.to_owned(),
+            secret: secret.map(ToOwned::to_owned),
+        });
+        self
+    }
+
+    /// Exits with `status`, having written `stdout` and `stderr`.
+    pub fn exit(self, status: usize, stdout: &str, stderr: &str) -> Self {
+        Self {
+            status,
+            stdout: stdout.to_owned(),
+            stderr: stderr.to_owned(),
+            ..self
+        }
+    }
+
+    /// Never exits once all prompts were answered.
+    pub fn hang(mut self) -> Self {
+        self.hang = true;
+        self
+    }
+}
+
+/// A Git invocation as the executor saw it.
+#[derive(Debug, Clone)]
+pub struct Invocation {
+    /// The arguments, including the ones added by [`GitExecutor::execute`].
+    pub args: Vec<String>,
+    /// The environment variables Git was given.
+    pub envs: HashMap<String, String>,
+    /// The answers to the scripted prompts, in order.
+    pub responses: Vec<String>,
+}
+
+/// A [`GitExecutor`] that answers invocations from a script.
+///
+/// Invocations that aren't scripted exit with status `1` and no output,
+/// which is what `git config --get` does for unset keys, unless the
+/// executor was created with [`ScriptedExecutor::passthrough`].
+#[derive(Clone)]
+pub struct ScriptedExecutor {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    commands: Mutex<VecDeque<ScriptedCommand>>,
+    invocations: Mutex<Vec<Invocation>>,
+    connections: mpsc::UnboundedSender<ScriptedSocket>,
+    incoming: Arc<AsyncMutex<mpsc::UnboundedReceiver<ScriptedSocket>>>,
+    passthrough: bool,
+    untrusted_askpass: bool,
+}
+
+impl ScriptedExecutor {
+    fn with_options(passthrough: bool, untrusted_askpass: bool) -> Self {
+        let (connections, incoming) = mpsc::unbounded();
+        Self {
+            inner: Arc::new(Inner {
+                commands: Mutex::default(),
+                invocations: Mutex::default(),
+                connections,
+                incoming: Arc::new(AsyncMutex::new(incoming)),
+                passthrough,
+                untrusted_askpass,
+            }),
+        }
+    }
+
+    /// An executor that only knows the scripted commands.
+    pub fn new() -> Self {
+        Self::with_options(false, false)
+    }
+
+    /// An executor that runs the real `git` for invocations that aren't
+    /// scripted, which is how transports are tested against local remotes.
+    pub fn passthrough() -> Self {
+        Self::with_options(true, false)
+    }
+
+    /// An executor whose askpass connections come from an executable other
+    /// than the askpass utility.
+    pub fn untrusted_askpass() -> Self {
+        Self::with_options(false, true)
+    }
+
+    /// Adds `command` to the script.
+    pub fn script(self, command: ScriptedCommand) -> Self {
+        self.inner.commands.lock().unwrap().push_back(command);
+        self
+    }
+
+    /// All invocations so far, in order.
+    pub fn invocations(&self) -> Vec<Invocation> {
+        self.inner.invocations.lock().unwrap().clone()
+    }
+
+    /// The first invocation of `subcommand`.
+    pub fn invocation(&self, subcommand: &str) -> Option<Invocation> {
+        self.invocations()
+            .into_iter()
+            .find(|invocation| invocation.args.iter().any(|arg| arg == subcommand))
+    }
+}
+
+#[allow(unsafe_code)]
+unsafe impl GitExecutor for ScriptedExecutor {
+    type Error = std::io::Error;
+    type ServerHandle = ScriptedAskpassServer;
+
+    async fn execute_raw<P: AsRef<Path>>(
+        &self,
+        args: &[&str],
+        cwd: P,
+        envs: Option<HashMap<String, String>>,
+    ) -> Result<(usize, String, String), Self::Error> {
+        let envs = envs.unwrap_or_default();
+        let index = {
+            let mut invocations = self.inner.invocations.lock().unwrap();
+            invocations.push(Invocation {
+                args: args.iter().map(ToString::to_string).collect(),
+                envs: envs.clone(),
+                responses: Vec::new(),
+            });
+            invocations.len() - 1
+        };
+
+        let command = {
+            let mut commands = self.inner.commands.lock().unwrap();
+            commands
+                .iter()
+                .position(|command| args.contains(&command.subcommand.as_str()))
+                .and_then(|position| commands.remove(position))
+        };
+        let Some(command) = command else {
+            return if self.inner.passthrough {
+                super::tokio::TokioExecutor
+                    .execute_raw(args, cwd, Some(envs))
+                    .await
+            } else {
+                Ok((1, String::new(), String::new()))
+            };
+        };
+
+        for prompt in command.prompts {
+            let secret = prompt.secret.unwrap_or_else(|| {
+                envs.get("GITBUTLER_ASKPASS_SECRET")
+                    .cloned()
+                    .unwrap_or_default()
+            });
+            let (responses, mut response) = mpsc::unbounded();
+            self.inner
+                .connections
+                .unbounded_send(ScriptedSocket {
+                    lines: VecDeque::from([secret, prompt.prompt]),
+                    responses,
+                })
+                .expect("the receiver lives as long as the executor");
+
+            match response.next().await {
+                Some(response) => self.inner.invocations.lock().unwrap()[index]
+                    .responses
+                    .push(response),
+                // that's what Git does if askpass exits without an answer
+                None => {
+                    return Ok((
+                        128,
+                        String::new(),
+                        "fatal: could not read Password: terminal prompts disabled\n".into(),
+                    ))
+                }
+            }
+        }
+
+        if command.hang {
+            futures::future::pending::<()>().await;
+        }
+
+        Ok((command.status, command.stdout, command.stderr))
+    }
+
+    async unsafe fn create_askpass_server(&self) -> Result<Self::ServerHandle, Self::Error> {
+        Ok(ScriptedAskpassServer {
+            incoming: Arc::clone(&self.inner.incoming),
+        })
+    }
+
+    async fn stat<P: AsRef<Path>>(&self, path: P) -> Result<FileStat, Self::Error> {
+        // the askpass peer is this very process, which passes for the askpass
+        // utility unless the executor is told otherwise
+        let is_askpass_utility = path.as_ref().file_name().map_or(false, |name| {
+            name.to_string_lossy().starts_with("gitbutler-git-")
+        });
+        Ok(FileStat {
+            dev: 0,
+            ino: if is_askpass_utility || !self.inner.untrusted_askpass {
+                1
+            } else {
+                2
+            },
+            is_regular_file: true,
+        })
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        ::tokio::time::sleep(duration).await;
+    }
+}
+
+/// The askpass server of a [`ScriptedExecutor`], which accepts the connections
+/// of its scripted prompts.
+pub struct ScriptedAskpassServer {
+    incoming: Arc<AsyncMutex<mpsc::UnboundedReceiver<ScriptedSocket>>>,
+}
+
+impl core::fmt::Display for ScriptedAskpassServer {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("scripted-askpass")
+    }
+}
+
+impl AskpassServer for ScriptedAskpassServer {
+    type Error = std::io::Error;
+    type SocketHandle = ScriptedSocket;
+
+    async fn accept(&self, _timeout: Option<Duration>) -> Result<Self::SocketHandle, Self::Error> {
+        match self.incoming.lock().await.next().await {
+            Some(socket) => Ok(socket),
+            None => futures::future::pending().await,
+        }
+    }
+}
+
+/// A connection of a scripted prompt, which comes from this very process.
+pub struct ScriptedSocket {
+    lines: VecDeque<String>,
+    responses: mpsc::UnboundedSender<String>,
+}
+
+impl Socket for ScriptedSocket {
+    type Error = std::io::Error;
+
+    #[allow(clippy::unnecessary_cast)]
+    fn pid(&self) -> Result<Pid, Self::Error> {
+        Ok(std::process::id() as Pid)
+    }
+
+    #[cfg(unix)]
+    fn uid(&self) -> Result<super::Uid, Self::Error> {
+        Ok(nix::unistd::getuid().as_raw())
+    }
+
+    async fn read_line(&mut self) -> Result<String, Self::Error> {
+        self.lines
+            .pop_front()
+            .ok_or_else(|| std::io::ErrorKind::UnexpectedEof.into())
+    }
+
+    async fn write_line(&mut self, line: &str) -> Result<(), Self::Error> {
+        self.responses
+            .unbounded_send(line.to_owned())
+            .map_err(|_| std::io::ErrorKind::BrokenPipe.into())
+    }
+}
@@ -5,8 +5,8 @@ mod unix;
 #[cfg(windows)]
 mod windows;
 
-use std::{collections::HashMap, path::Path};
-use tokio::process::Command;
+use std::{collections::HashMap, path::Path, process::Stdio, time::Duration};
+use tokio::{io::AsyncReadExt, process::Command};
 
 #[cfg(unix)]
 pub use self::unix::TokioAskpassServer;
@@ -27,6 +27,16 @@ unsafe impl super::GitExecutor for TokioExecutor {
         args: &[&str],
         cwd: P,
         envs: Option<HashMap<String, String>>,
+    ) -> Result<(usize, String, String), Self::Error> {
+        self.execute_raw_streaming(args, cwd, envs, |_| {}).await
+    }
+
+    async fn execute_raw_streaming<P: AsRef<Path>, F: FnMut(&str)>(
+        &self,
+        args: &[&str],
+        cwd: P,
+        envs: Option<HashMap<String, String>>,
+        mut on_stderr: F,
     ) -> Result<(usize, String, String), Self::Error> {
         let git_exe = {
             #[cfg(unix)]
@@ -62,6 +72,17 @@ unsafe impl super::GitExecutor for TokioExecutor {
         cmd.kill_on_drop(true);
         cmd.current_dir(cwd);
 
+        // Git gets a process group of its own, so that everything it spawns
+        // can be terminated along with it.
+        #[cfg(unix)]
+        #[allow(unsafe_code)]
+        unsafe {
+            cmd.pre_exec(|| {
+                let own_pid = nix::unistd::Pid::from_raw(0);
+                nix::unistd::setpgid(own_pid, own_pid).map_err(std::io::Error::from)
+            });
+        }
+
         #[cfg(not(windows))]
         cmd.args(args);
 
@@ -97,22 +118,61 @@ unsafe impl super::GitExecutor for TokioExecutor {
             }
         }
 
-        let output = cmd.output().await?;
+        cmd.stdin(Stdio::null());
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+
+        let mut child = cmd.spawn()?;
+        #[cfg(unix)]
+        let mut process_group = self::unix::ProcessGroup::new(child.id());
+        let mut stdout_pipe = child.stdout.take().expect("stdout is piped");
+        let mut stderr_pipe = child.stderr.take().expect("stderr is piped");
+
+        let read_stdout = async {
+            let mut stdout = Vec::new();
+            stdout_pipe.read_to_end(&mut stdout).await?;
+            Ok::<_, Self::Error>(stdout)
+        };
+        let read_stderr = async {
+            let mut stderr = Vec::new();
+            let mut line_start = 0;
+            let mut chunk = [0; 4096];
+            loop {
+                let len = stderr_pipe.read(&mut chunk).await?;
+                if len == 0 {
+                    break;
+                }
+                stderr.extend_from_slice(&chunk[..len]);
+                while let Some(line_len) = stderr[line_start..]
+                    .iter()
+                    .position(|b| *b == b'\r' || *b == b'\n')
+                {
+                    emit_line(&stderr[line_start..line_start + line_len], &mut on_stderr);
+                    line_start += line_len + 1;
+                }
+            }
+            emit_line(&stderr[line_start..], &mut on_stderr);
+            Ok(stderr)
+        };
+        let (stdout, stderr) = futures::try_join!(read_stdout, read_stderr)?;
+        let status = child.wait().await?;
+        #[cfg(unix)]
+        process_group.exited();
 
         #[cfg(any(test, debug_assertions))]
         {
             eprintln!(
                 "\n\n GIT STDOUT:\n\n{}\n\nGIT STDERR:\n\n{}\n\nGIT EXIT CODE: {}\n",
-                String::from_utf8_lossy(&output.stdout),
-                String::from_utf8_lossy(&output.stderr),
-                output.status.code().unwrap_or(127) as usize
+                String::from_utf8_lossy(&stdout),
+                String::from_utf8_lossy(&stderr),
+                status.code().unwrap_or(127) as usize
             );
         }
 
         Ok((
-            output.status.code().unwrap_or(127) as usize,
-            String::from_utf8_lossy(&output.stdout).trim().into(),
-            String::from_utf8_lossy(&output.stderr).trim().into(),
+            status.code().unwrap_or(127) as usize,
+            String::from_utf8_lossy(&stdout).trim().into(),
+            String::from_utf8_lossy(&stderr).trim().into(),
         ))
     }
 
@@ -127,6 +187,10 @@ unsafe impl super::GitExecutor for TokioExecutor {
         }
     }
 
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+
     async fn stat<P: AsRef<Path>>(&self, path: P) -> Result<super::FileStat, Self::Error> {
         #[cfg(unix)]
         {
@@ -138,3 +202,9 @@ unsafe impl super::GitExecutor for TokioExecutor {
         }
     }
 }
+
+fn emit_line<F: FnMut(&str)>(line: &[u8], on_stderr: &mut F) {
+    if !line.is_empty() {
+        on_stderr(&String::from_utf8_lossy(line));
+    }
+}
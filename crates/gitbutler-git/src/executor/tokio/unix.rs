@@ -104,3 +104,29 @@ pub async fn stat<P: AsRef<Path>>(path: P) -> Result<FileStat, std::io::Error> {
         is_regular_file: metadata.is_file(),
     })
 }
+
+/// The process group of a running Git command. Unless the command
+/// exited by then, the whole group is terminated when this is dropped.
+pub(super) struct ProcessGroup(Option<nix::unistd::Pid>);
+
+impl ProcessGroup {
+    /// The group that `leader`, the process ID of Git, leads.
+    pub(super) fn new(leader: Option<u32>) -> Self {
+        Self(leader.map(|pid| nix::unistd::Pid::from_raw(pid as i32)))
+    }
+
+    /// Remembers that Git exited on its own.
+    pub(super) fn exited(&mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        if let Some(pgid) = self.0.take() {
+            // SIGTERM rather than SIGKILL, so that `gitbutler-git-setsid`
+            // gets to pass it on to the session it created for SSH.
+            nix::sys::signal::killpg(pgid, nix::sys::signal::Signal::SIGTERM).ok();
+        }
+    }
+}
@@ -0,0 +1,117 @@
+/// The phase of a fetch or push that a [`Progress`] refers to.
+///
+/// These correspond to the titles Git prints on stderr when `--progress`
+/// is passed, with or without the `remote: ` prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub enum Stage {
+    /// `Enumerating objects`
+    Enumerating,
+    /// `Counting objects`
+    Counting,
+    /// `Compressing objects`
+    Compressing,
+    /// `Receiving objects`
+    Receiving,
+    /// `Unpacking objects`
+    Unpacking,
+    /// `Writing objects`
+    Writing,
+    /// `Resolving deltas`
+    ResolvingDeltas,
+}
+
+impl Stage {
+    fn from_title(title: &str) -> Option<Self> {
+        Some(match title {
+            "Enumerating objects" => Stage::Enumerating,
+            "Counting objects" => Stage::Counting,
+            "Compressing objects" => Stage::Compressing,
+            "Receiving objects" => Stage::Receiving,
+            "Unpacking objects" => Stage::Unpacking,
+            "Writing objects" => Stage::Writing,
+            "Resolving deltas" => Stage::ResolvingDeltas,
+            _ => return None,
+        })
+    }
+}
+
+/// A snapshot of how far along a fetch or push is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct Progress {
+    /// What is being done.
+    pub stage: Stage,
+    /// The number of objects (or deltas) processed so far.
+    pub current: u64,
+    /// The number of objects (or deltas) to process, if known.
+    pub total: Option<u64>,
+    /// The number of bytes transferred so far, if reported.
+    pub bytes: Option<u64>,
+}
+
+impl Progress {
+    /// Parses a single progress line as Git writes it to stderr, for example
+    /// `Receiving objects:  45% (450/1000), 1.20 MiB | 1.00 MiB/s`.
+    ///
+    /// Returns `None` if the line isn't a progress line.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let line = line.strip_prefix("remote:").unwrap_or(line).trim_start();
+        let (title, rest) = line.split_once(':')?;
+        let stage = Stage::from_title(title)?;
+        let rest = rest.trim_start();
+
+        // `45% (450/1000), …` if the total is known, `5, done.` otherwise
+        let (current, total, rest) = match rest.split_once('(') {
+            Some((_, counts)) => {
+                let (counts, rest) = counts.split_once(')')?;
+                let (current, total) = counts.split_once('/')?;
+                (current.parse().ok()?, Some(total.parse().ok()?), rest)
+            }
+            None => {
+                let (current, rest) = rest.split_once(',').unwrap_or((rest, ""));
+                (current.trim().parse().ok()?, None, rest)
+            }
+        };
+
+        let bytes = rest
+            .trim_start_matches(',')
+            .split(['|', ','])
+            .next()
+            .and_then(parse_size);
+
+        Some(Progress {
+            stage,
+            current,
+            total,
+            bytes,
+        })
+    }
+}
+
+/// Parses sizes like `250 bytes` or `1.20 MiB`.
+fn parse_size(size: &str) -> Option<u64> {
+    let (amount, unit) = size.trim().split_once(' ')?;
+    let amount = amount.parse::<f64>().ok()?;
+    let multiplier = match unit {
+        "bytes" | "byte" => 1u64,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        "TiB" => 1 << 40,
+        _ => return None,
+    };
+    Some((amount * multiplier as f64) as u64)
+}
+
+/// `stderr` without the progress lines, which are only noise in error messages.
+pub(crate) fn without_progress(stderr: &str) -> String {
+    stderr
+        .split(['\r', '\n'])
+        .filter(|line| !line.is_empty() && Progress::parse(line).is_none())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -1,8 +1,9 @@
 use super::executor::{AskpassServer, GitExecutor, Pid, Socket};
-use crate::RefSpec;
+use crate::{progress::without_progress, Progress, RefSpec};
 use futures::{select, FutureExt};
 use rand::Rng;
 use std::{collections::HashMap, path::Path, time::Duration};
+use tokio_util::sync::CancellationToken;
 
 /// The number of characters in the secret used for checking
 /// askpass invocations by ssh/git when connecting to our process.
@@ -17,36 +18,58 @@ pub enum RepositoryError<
     Easkpass: std::error::Error + core::fmt::Debug + Send + Sync + 'static,
     Esocket: std::error::Error + core::fmt::Debug + Send + Sync + 'static,
 > {
+    /// The executor failed to run Git.
     #[error("failed to execute git command: {0}")]
     Exec(Eexec),
+    /// The askpass server failed.
     #[error("failed to create askpass server: {0}")]
     AskpassServer(Easkpass),
+    /// Talking to the askpass utility failed.
     #[error("i/o error communicating with askpass utility: {0}")]
     AskpassIo(Esocket),
+    /// Git exited with a non-zero exit code.
     #[error("git command exited with non-zero exit code {status}: {args:?}\n\nSTDOUT:\n{stdout}\n\nSTDERR:\n{stderr}")]
     Failed {
+        /// The exit code.
         status: usize,
+        /// The arguments Git was run with.
         args: Vec<String>,
+        /// What Git wrote to stdout.
         stdout: String,
+        /// What Git wrote to stderr, without progress.
         stderr: String,
     },
+    /// The path to the askpass utility couldn't be determined.
     #[error("failed to determine path to this executable: {0}")]
     NoSelfExe(std::io::Error),
+    /// The askpass utility didn't know the secret.
     #[error("askpass secret mismatch")]
     AskpassSecretMismatch,
+    /// Git prompted for credentials, but the prompt wasn't answered.
     #[error("git requires authorization credentials but none were provided: prompt was {0:?}")]
     NeedsAuthorization(String),
+    /// The process connecting to the askpass server couldn't be determined.
     #[error("unable to determine PID of askpass peer: {0}")]
     NoPid(Esocket),
+    /// The user of the process connecting to the askpass server couldn't be determined.
     #[cfg(unix)]
     #[error("unable to determine UID of askpass peer: {0}")]
     NoUid(Esocket),
+    /// The process connecting to the askpass server doesn't exist.
     #[error("failed to perform askpass security check; no such PID: {0}")]
     NoSuchPid(Pid),
+    /// The process connecting to the askpass server runs from another device.
     #[error("failed to perform askpass security check; device mismatch")]
     AskpassDeviceMismatch,
+    /// The process connecting to the askpass server isn't the askpass utility.
     #[error("failed to perform askpass security check; executable mismatch")]
     AskpassExecutableMismatch,
+    /// The operation was cancelled before Git exited.
+    #[error("git command was cancelled")]
+    Cancelled,
+    /// Git didn't exit within the given time.
+    #[error("git command timed out after {0:?}")]
+    TimedOut(Duration),
 }
 
 /// Higher level errors that can occur when interacting with the CLI.
@@ -56,18 +79,63 @@ pub type Error<E> = RepositoryError<
     <<<E as GitExecutor>::ServerHandle as AskpassServer>::SocketHandle as Socket>::Error,
 >;
 
-#[cold]
-async fn execute_with_auth_harness<P, F, Fut, E, Extra>(
+/// Runs [`run_auth_harness`] until Git exits, unless `cancellation` is cancelled
+/// or `timeout` passes first. Git and everything it spawned is killed then.
+#[allow(clippy::too_many_arguments)]
+async fn execute_with_auth_harness<P, F, Fut, E, Extra, G>(
     repo_path: P,
     executor: E,
     args: &[&str],
     envs: Option<HashMap<String, String>>,
+    on_stderr: G,
+    on_prompt: F,
+    extra: Extra,
+    cancellation: &CancellationToken,
+    timeout: Option<Duration>,
+) -> Result<(usize, String, String), Error<E>>
+where
+    P: AsRef<Path>,
+    E: GitExecutor,
+    G: FnMut(&str),
+    F: FnMut(String, Extra) -> Fut,
+    Fut: std::future::Future<Output = Option<String>>,
+    Extra: Send + Clone,
+{
+    let mut harness = core::pin::pin! {
+        run_auth_harness(repo_path, &executor, args, envs, on_stderr, on_prompt, extra).fuse()
+    };
+    let mut cancelled = core::pin::pin! { cancellation.cancelled().fuse() };
+    let mut timed_out = core::pin::pin! {
+        async {
+            match timeout {
+                Some(timeout) => executor.sleep(timeout).await,
+                None => futures::future::pending().await,
+            }
+        }
+        .fuse()
+    };
+
+    select! {
+        res = harness => res,
+        () = cancelled => Err(Error::<E>::Cancelled),
+        () = timed_out => Err(Error::<E>::TimedOut(timeout.unwrap_or_default())),
+    }
+}
+
+#[cold]
+async fn run_auth_harness<P, F, Fut, E, Extra, G>(
+    repo_path: P,
+    executor: &E,
+    args: &[&str],
+    envs: Option<HashMap<String, String>>,
+    on_stderr: G,
     mut on_prompt: F,
     extra: Extra,
 ) -> Result<(usize, String, String), Error<E>>
 where
     P: AsRef<Path>,
     E: GitExecutor,
+    G: FnMut(&str),
     F: FnMut(String, Extra) -> Fut,
     Fut: std::future::Future<Output = Option<String>>,
     Extra: Send + Clone,
@@ -155,7 +223,7 @@ where
         .or_else(|| std::env::var("GIT_SSH").ok())
     {
         Some(v) => v,
-        None => get_core_sshcommand(&executor, &repo_path)
+        None => get_core_sshcommand(executor, &repo_path)
             .await
             .unwrap_or_else(|| "ssh".into()),
     };
@@ -192,7 +260,7 @@ where
     let mut child_process = core::pin::pin! {
         async {
             executor
-                .execute(args, repo_path, Some(envs))
+                .execute_streaming(args, repo_path, Some(envs), on_stderr)
                 .await
                 .map_err(Error::<E>::Exec)
         }.fuse()
@@ -203,7 +271,8 @@ where
             res = child_process => {
                 return res;
             },
-            res = sock_server.accept(Some(Duration::from_secs(120))).fuse() => {
+            // no timeout, as the caller decides how long the whole operation may take
+            res = sock_server.accept(None).fuse() => {
                 let mut sock = res.map_err(Error::<E>::AskpassServer)?;
 
                 // get the PID of the peer
@@ -277,30 +346,55 @@ where
 /// callback `on_prompt` which should return the user's response or `None` if the
 /// operation should be aborted, in which case an `Err` value is returned from this
 /// function.
-pub async fn fetch<P, F, Fut, E, Extra>(
+///
+/// The progress of the fetch is passed to `on_progress` while it runs.
+///
+/// The fetch is aborted with [`RepositoryError::Cancelled`] once `cancellation`
+/// is cancelled, or with [`RepositoryError::TimedOut`] if it takes longer than `timeout`.
+#[allow(clippy::too_many_arguments)]
+pub async fn fetch<P, F, Fut, E, Extra, G>(
     repo_path: P,
     executor: E,
     remote: &str,
     refspec: RefSpec,
+    mut on_progress: G,
     on_prompt: F,
     extra: Extra,
+    cancellation: &CancellationToken,
+    timeout: Option<Duration>,
 ) -> Result<(), crate::Error<Error<E>>>
 where
     P: AsRef<Path>,
     E: GitExecutor,
+    G: FnMut(Progress),
     F: FnMut(String, Extra) -> Fut,
     Fut: std::future::Future<Output = Option<String>>,
     Extra: Send + Clone,
 {
-    let mut args = vec!["fetch", "--quiet", "--prune"];
+    let mut args = vec!["fetch", "--quiet", "--progress", "--prune"];
 
     let refspec = refspec.to_string();
 
     args.push(remote);
     args.push(&refspec);
 
-    let (status, stdout, stderr) =
-        execute_with_auth_harness(repo_path, executor, &args, None, on_prompt, extra).await?;
+    let (status, stdout, stderr) = execute_with_auth_harness(
+        repo_path,
+        executor,
+        &args,
+        None,
+        |line| {
+            if let Some(progress) = Progress::parse(line) {
+                on_progress(progress);
+            }
+        },
+        on_prompt,
+        extra,
+        cancellation,
+        timeout,
+    )
+    .await?;
+    let stderr = without_progress(&stderr);
 
     if status == 0 {
         Ok(())
@@ -312,57 +406,103 @@ where
             .map(|line| line.split_whitespace().last().unwrap_or_default())
         {
             Err(crate::Error::RefNotFound(refname.to_owned()))?
-        } else if stderr.to_lowercase().contains("permission denied") {
-            Err(crate::Error::AuthorizationFailed(Error::<E>::Failed {
-                status,
-                args: args.into_iter().map(Into::into).collect(),
-                stdout,
-                stderr,
-            }))?
         } else {
-            Err(Error::<E>::Failed {
-                status,
-                args: args.into_iter().map(Into::into).collect(),
-                stdout,
-                stderr,
-            })?
+            Err(failed::<E>(status, &args, stdout, stderr))
         }
     }
 }
 
-/// Pushes a refspec to the given remote in the repository at the given path.
+/// Pushes refspecs to the given remote in the repository at the given path.
 /// Any prompts for the user are passed to the asynchronous callback `on_prompt`,
 /// which should return the user's response or `None` if the operation should be
 /// aborted, in which case an `Err` value is returned from this function.
-pub async fn push<P, F, Fut, E, Extra>(
+///
+/// Each refspec comes with the object id its destination is leased at, if any.
+/// If `force` is set, the destinations of leased refspecs are only overwritten
+/// if the remote still has them at their lease, and [`crate::Error::StaleLease`]
+/// is returned for the first one that it doesn't.
+///
+/// Several refspecs are pushed atomically: either all of them are updated on
+/// the remote, or none is.
+///
+/// The progress of the push is passed to `on_progress` while it runs.
+///
+/// The push is aborted with [`RepositoryError::Cancelled`] once `cancellation`
+/// is cancelled, or with [`RepositoryError::TimedOut`] if it takes longer than `timeout`.
+#[allow(clippy::too_many_arguments)]
+pub async fn push<P, F, Fut, E, Extra, G>(
     repo_path: P,
     executor: E,
     remote: &str,
-    refspec: RefSpec,
+    refspecs: &[(RefSpec, Option<&str>)],
     force: bool,
+    mut on_progress: G,
     on_prompt: F,
     extra: Extra,
+    cancellation: &CancellationToken,
+    timeout: Option<Duration>,
 ) -> Result<(), crate::Error<Error<E>>>
 where
     P: AsRef<Path>,
     E: GitExecutor,
+    G: FnMut(Progress),
     F: FnMut(String, Extra) -> Fut,
     Fut: std::future::Future<Output = Option<String>>,
     Extra: Send + Clone,
 {
-    let mut args = vec!["push", "--quiet"];
-
-    let refspec = refspec.to_string();
-
-    args.push(remote);
-    args.push(&refspec);
+    // push hooks are run by the caller, for all push backends alike
+    let mut args = vec!["push", "--quiet", "--progress", "--no-verify"];
 
-    if force {
-        args.push("--force");
+    if refspecs.len() > 1 {
+        args.push("--atomic");
     }
 
-    let (status, stdout, stderr) =
-        execute_with_auth_harness(repo_path, executor, &args, None, on_prompt, extra).await?;
+    let mut leases = Vec::new();
+    let refspecs = refspecs
+        .iter()
+        .map(|(refspec, lease)| match lease.filter(|_| force) {
+            Some(lease) => {
+                leases.push(format!(
+                    "--force-with-lease={}:{}",
+                    refspec.destination.as_deref().unwrap_or_default(),
+                    lease
+                ));
+                // a `+` in the refspec would force the update regardless of the lease
+                refspec
+                    .clone()
+                    .with_update_non_fastforward(false)
+                    .to_string()
+            }
+            // not `--force`, which would also override the leases of the other refspecs
+            None if force => refspec
+                .clone()
+                .with_update_non_fastforward(true)
+                .to_string(),
+            None => refspec.to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    args.push(remote);
+    args.extend(refspecs.iter().map(String::as_str));
+    args.extend(leases.iter().map(String::as_str));
+
+    let (status, stdout, stderr) = execute_with_auth_harness(
+        repo_path,
+        executor,
+        &args,
+        None,
+        |line| {
+            if let Some(progress) = Progress::parse(line) {
+                on_progress(progress);
+            }
+        },
+        on_prompt,
+        extra,
+        cancellation,
+        timeout,
+    )
+    .await?;
+    let stderr = without_progress(&stderr);
 
     if status == 0 {
         Ok(())
@@ -371,25 +511,206 @@ where
         if let Some(refname) = stderr
             .lines()
             .find(|line| line.to_lowercase().contains("does not match any"))
-            .map(|line| line.split_whitespace().last().unwrap_or_default())
+            // `error: src refspec <refname> does not match any`
+            .map(|line| {
+                line.split_whitespace()
+                    .skip_while(|word| *word != "refspec")
+                    .nth(1)
+                    .unwrap_or_default()
+            })
         {
             Err(crate::Error::RefNotFound(refname.to_owned()))?
-        } else if stderr.to_lowercase().contains("permission denied") {
-            Err(crate::Error::AuthorizationFailed(Error::<E>::Failed {
-                status,
-                args: args.into_iter().map(Into::into).collect(),
-                stdout,
-                stderr,
-            }))?
+        } else if let Some(line) = stderr.lines().find(|line| line.contains("(stale info)")) {
+            // ` ! [rejected]        <source> -> <destination> (stale info)`
+            let destination = line
+                .split_whitespace()
+                .skip_while(|word| *word != "->")
+                .nth(1)
+                .unwrap_or_default();
+            let refspec = refspecs
+                .iter()
+                .find(|refspec| {
+                    refspec.ends_with(&format!(":{destination}"))
+                        || refspec.ends_with(&format!("/{destination}"))
+                })
+                .unwrap_or(&refspecs[0]);
+            Err(crate::Error::StaleLease(refspec.clone()))?
         } else {
-            Err(Error::<E>::Failed {
+            Err(failed::<E>(status, &args, stdout, stderr))
+        }
+    }
+}
+
+/// A reference on a remote, as listed by [`ls_remote`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+pub struct RemoteRef {
+    /// The full name of the reference, e.g. `refs/heads/master` or `HEAD`.
+    pub name: String,
+    /// The object id the reference points to.
+    pub oid: String,
+    /// The object id an annotated tag points to.
+    pub peeled: Option<String>,
+    /// The reference a symbolic reference like `HEAD` points to.
+    pub target: Option<String>,
+}
+
+/// Lists the references of `remote`, which is either the name of a remote of the
+/// repository at the given path, or a URL. With `patterns`, only references whose
+/// names end in one of them are listed, as with `git ls-remote`.
+///
+/// Prompts, cancellation and timeouts are handled like they are by [`fetch`].
+#[allow(clippy::too_many_arguments)]
+pub async fn ls_remote<P, F, Fut, E, Extra>(
+    repo_path: P,
+    executor: E,
+    remote: &str,
+    patterns: &[&str],
+    on_prompt: F,
+    extra: Extra,
+    cancellation: &CancellationToken,
+    timeout: Option<Duration>,
+) -> Result<Vec<RemoteRef>, crate::Error<Error<E>>>
+where
+    P: AsRef<Path>,
+    E: GitExecutor,
+    F: FnMut(String, Extra) -> Fut,
+    Fut: std::future::Future<Output = Option<String>>,
+    Extra: Send + Clone,
+{
+    let mut args = vec!["ls-remote", "--symref", remote];
+    args.extend(patterns);
+
+    let (status, stdout, stderr) = execute_with_auth_harness(
+        repo_path,
+        executor,
+        &args,
+        None,
+        |_| {},
+        on_prompt,
+        extra,
+        cancellation,
+        timeout,
+    )
+    .await?;
+
+    if status == 0 {
+        Ok(parse_ls_remote(&stdout))
+    } else if stderr.contains("does not appear to be a git repository") {
+        Err(crate::Error::NoSuchRemote(
+            remote.to_owned(),
+            Error::<E>::Failed {
                 status,
                 args: args.into_iter().map(Into::into).collect(),
                 stdout,
                 stderr,
-            })?
+            },
+        ))
+    } else {
+        Err(failed::<E>(status, &args, stdout, stderr))
+    }
+}
+
+/// Parses the output of `git ls-remote --symref`, which lists a reference per line
+/// as `<oid>\t<name>`, preceded by `ref: <target>\t<name>` for symbolic references
+/// and followed by `<oid>\t<name>^{}` for annotated tags.
+pub(crate) fn parse_ls_remote(stdout: &str) -> Vec<RemoteRef> {
+    let mut refs = Vec::<RemoteRef>::new();
+    let mut targets = HashMap::new();
+    for (value, name) in stdout.lines().filter_map(|line| line.split_once('\t')) {
+        if let Some(target) = value.strip_prefix("ref: ") {
+            targets.insert(name, target);
+        } else if let Some(tag) = name.strip_suffix("^{}") {
+            if let Some(tag) = refs.iter_mut().rev().find(|r| r.name == tag) {
+                tag.peeled = Some(value.to_owned());
+            }
+        } else {
+            refs.push(RemoteRef {
+                name: name.to_owned(),
+                oid: value.to_owned(),
+                peeled: None,
+                target: targets.get(name).map(|target| (*target).to_owned()),
+            });
         }
     }
+    refs
+}
+
+/// Clones the repository at `url` into `target`, which must not exist or be an
+/// empty directory, and whose parent directory must exist. Relative paths are
+/// relative to the current working directory.
+///
+/// Prompts, progress, cancellation and timeouts are handled like they are by [`fetch`].
+#[allow(clippy::too_many_arguments)]
+pub async fn clone<P, F, Fut, E, Extra, G>(
+    executor: E,
+    url: &str,
+    target: P,
+    mut on_progress: G,
+    on_prompt: F,
+    extra: Extra,
+    cancellation: &CancellationToken,
+    timeout: Option<Duration>,
+) -> Result<(), crate::Error<Error<E>>>
+where
+    P: AsRef<Path>,
+    E: GitExecutor,
+    G: FnMut(Progress),
+    F: FnMut(String, Extra) -> Fut,
+    Fut: std::future::Future<Output = Option<String>>,
+    Extra: Send + Clone,
+{
+    // git runs in the caller's working directory, so that relative targets and local urls
+    // resolve the same as they would for the caller
+    let target = target.as_ref().to_string_lossy();
+    let args = vec!["clone", "--quiet", "--progress", "--", url, &target];
+
+    let (status, stdout, stderr) = execute_with_auth_harness(
+        ".",
+        executor,
+        &args,
+        None,
+        |line| {
+            if let Some(progress) = Progress::parse(line) {
+                on_progress(progress);
+            }
+        },
+        on_prompt,
+        extra,
+        cancellation,
+        timeout,
+    )
+    .await?;
+    let stderr = without_progress(&stderr);
+
+    if status == 0 {
+        Ok(())
+    } else {
+        Err(failed::<E>(status, &args, stdout, stderr))
+    }
+}
+
+/// The error for Git exiting with a non-zero `status`, which is an authorization
+/// failure if the remote denied access.
+fn failed<E: GitExecutor>(
+    status: usize,
+    args: &[&str],
+    stdout: String,
+    stderr: String,
+) -> crate::Error<Error<E>> {
+    let denied = stderr.to_lowercase().contains("permission denied");
+    let error = Error::<E>::Failed {
+        status,
+        args: args.iter().map(ToString::to_string).collect(),
+        stdout,
+        stderr,
+    };
+    if denied {
+        crate::Error::AuthorizationFailed(error)
+    } else {
+        crate::Error::Backend(error)
+    }
 }
 
 async fn get_core_sshcommand<E: GitExecutor, P: AsRef<Path>>(
@@ -408,3 +729,6 @@ async fn get_core_sshcommand<E: GitExecutor, P: AsRef<Path>>(
         })
         .unwrap_or(None)
 }
+
+#[cfg(test)]
+mod tests;
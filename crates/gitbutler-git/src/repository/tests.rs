@@ -0,0 +1,734 @@
+use super::*;
+use crate::executor::scripted::{ScriptedCommand, ScriptedExecutor};
+use std::{path::PathBuf, process::Command};
+
+type TestError = crate::Error<Error<ScriptedExecutor>>;
+
+fn refspec() -> RefSpec {
+    RefSpec::parse("refs/heads/master:refs/remotes/origin/master").unwrap()
+}
+
+async fn fetch_with(executor: &ScriptedExecutor, answer: Option<&str>) -> Result<(), TestError> {
+    let answer = answer.map(ToOwned::to_owned);
+    fetch(
+        ".",
+        executor.clone(),
+        "origin",
+        refspec(),
+        |_| {},
+        |_, ()| {
+            let answer = answer.clone();
+            async move { answer }
+        },
+        (),
+        &CancellationToken::new(),
+        None,
+    )
+    .await
+}
+
+async fn push_with(
+    executor: &ScriptedExecutor,
+    repo_path: &Path,
+    refspecs: &[(RefSpec, Option<&str>)],
+    force: bool,
+    progress: &mut Vec<Progress>,
+) -> Result<(), TestError> {
+    push(
+        repo_path,
+        executor.clone(),
+        "origin",
+        refspecs,
+        force,
+        |p| progress.push(p),
+        |_, ()| async { None },
+        (),
+        &CancellationToken::new(),
+        None,
+    )
+    .await
+}
+
+#[tokio::test]
+async fn prompts_are_answered() {
+    let executor = ScriptedExecutor::new().script(
+        ScriptedCommand::new("fetch")
+            .prompt("Username for 'https://example.com': ")
+            .prompt("Password for 'https://user@example.com': "),
+    );
+
+    fetch_with(&executor, Some("answer")).await.unwrap();
+
+    let invocation = executor.invocation("fetch").unwrap();
+    assert_eq!(invocation.responses, ["answer", "answer"]);
+    assert!(invocation.args.iter().any(|arg| arg == "--progress"));
+    assert_eq!(invocation.envs["GIT_TERMINAL_PROMPT"], "0");
+    assert_eq!(
+        invocation.envs["GITBUTLER_ASKPASS_SECRET"].len(),
+        ASKPASS_SECRET_LENGTH
+    );
+}
+
+#[tokio::test]
+async fn unanswered_prompt_needs_authorization() {
+    let executor = ScriptedExecutor::new()
+        .script(ScriptedCommand::new("fetch").prompt("Password for 'https://user@example.com': "));
+
+    let error = fetch_with(&executor, None).await.unwrap_err();
+
+    assert!(
+        matches!(
+            &error,
+            crate::Error::Backend(RepositoryError::NeedsAuthorization(prompt))
+                if prompt == "Password for 'https://user@example.com': "
+        ),
+        "{error:?}"
+    );
+}
+
+#[tokio::test]
+async fn askpass_with_the_wrong_secret_is_rejected() {
+    let executor = ScriptedExecutor::new().script(
+        ScriptedCommand::new("fetch").prompt_with_secret("Password: ", Some("not-the-secret")),
+    );
+
+    let error = fetch_with(&executor, Some("password")).await.unwrap_err();
+
+    assert!(
+        matches!(
+            error,
+            crate::Error::Backend(RepositoryError::AskpassSecretMismatch)
+        ),
+        "{error:?}"
+    );
+    assert!(executor.invocation("fetch").unwrap().responses.is_empty());
+}
+
+#[tokio::test]
+async fn askpass_from_another_executable_is_rejected() {
+    let executor = ScriptedExecutor::untrusted_askpass()
+        .script(ScriptedCommand::new("fetch").prompt("Password: "));
+
+    let error = fetch_with(&executor, Some("password")).await.unwrap_err();
+
+    assert!(
+        matches!(
+            error,
+            crate::Error::Backend(RepositoryError::AskpassExecutableMismatch)
+        ),
+        "{error:?}"
+    );
+}
+
+#[tokio::test]
+async fn failure_is_reported_without_progress() {
+    let executor = ScriptedExecutor::new().script(ScriptedCommand::new("push").exit(
+        1,
+        "",
+        "Writing objects: 100% (3/3), 250 bytes | 250.00 KiB/s, done.\r\n\
+         error: failed to push some refs to 'https://example.com/repo.git'\n",
+    ));
+    let mut progress = Vec::new();
+
+    let error = push_with(
+        &executor,
+        Path::new("."),
+        &[(refspec(), None)],
+        false,
+        &mut progress,
+    )
+    .await
+    .unwrap_err();
+
+    let crate::Error::Backend(RepositoryError::Failed {
+        status,
+        args,
+        stderr,
+        ..
+    }) = error
+    else {
+        panic!("unexpected error: {error:?}");
+    };
+    assert_eq!(status, 1);
+    assert!(args.iter().any(|arg| arg == "push"));
+    assert_eq!(
+        stderr,
+        "error: failed to push some refs to 'https://example.com/repo.git'"
+    );
+    assert_eq!(progress.len(), 1);
+    assert_eq!(progress[0].stage, crate::Stage::Writing);
+}
+
+#[tokio::test]
+async fn permission_denied_is_an_authorization_failure() {
+    let executor = ScriptedExecutor::new().script(ScriptedCommand::new("fetch").exit(
+        128,
+        "",
+        "git@example.com: Permission denied (publickey).\n\
+         fatal: Could not read from remote repository.\n",
+    ));
+
+    let error = fetch_with(&executor, None).await.unwrap_err();
+
+    assert!(
+        matches!(
+            error,
+            crate::Error::AuthorizationFailed(RepositoryError::Failed { status: 128, .. })
+        ),
+        "{error:?}"
+    );
+}
+
+#[tokio::test]
+async fn missing_remote_ref_is_reported() {
+    let executor = ScriptedExecutor::new().script(ScriptedCommand::new("fetch").exit(
+        128,
+        "",
+        "fatal: couldn't find remote ref refs/heads/master\n",
+    ));
+
+    let error = fetch_with(&executor, None).await.unwrap_err();
+
+    assert!(
+        matches!(&error, crate::Error::RefNotFound(refname) if refname == "refs/heads/master"),
+        "{error:?}"
+    );
+}
+
+#[tokio::test]
+async fn hanging_fetch_times_out() {
+    let executor = ScriptedExecutor::new().script(ScriptedCommand::new("fetch").hang());
+
+    let error = fetch(
+        ".",
+        executor,
+        "origin",
+        refspec(),
+        |_| {},
+        |_, ()| async { None },
+        (),
+        &CancellationToken::new(),
+        Some(Duration::from_millis(10)),
+    )
+    .await
+    .unwrap_err();
+
+    assert!(
+        matches!(
+            error,
+            crate::Error::Backend(RepositoryError::TimedOut(timeout))
+                if timeout == Duration::from_millis(10)
+        ),
+        "{error:?}"
+    );
+}
+
+#[tokio::test]
+async fn cancelled_push_is_aborted_while_prompting() {
+    let executor =
+        ScriptedExecutor::new().script(ScriptedCommand::new("push").prompt("Password: "));
+    let cancellation = CancellationToken::new();
+
+    let error = push(
+        ".",
+        executor.clone(),
+        "origin",
+        &[(refspec(), None)],
+        false,
+        |_| {},
+        |_, cancellation: CancellationToken| async move {
+            // the user closes the credentials dialog by cancelling the push
+            cancellation.cancel();
+            futures::future::pending().await
+        },
+        cancellation.clone(),
+        &cancellation,
+        None,
+    )
+    .await
+    .unwrap_err();
+
+    assert!(
+        matches!(error, crate::Error::Backend(RepositoryError::Cancelled)),
+        "{error:?}"
+    );
+    assert!(executor.invocation("push").unwrap().responses.is_empty());
+}
+
+/// A repository with a commit on `master`, and an empty bare repository
+/// that is its `origin`.
+struct LocalRemote {
+    dir: tempfile::TempDir,
+}
+
+impl LocalRemote {
+    fn new() -> Self {
+        let local_remote = Self {
+            dir: tempfile::tempdir().unwrap(),
+        };
+        git(
+            local_remote.dir.path(),
+            &["init", "--bare", "--initial-branch=master", "remote.git"],
+        );
+        git(
+            local_remote.dir.path(),
+            &["init", "--initial-branch=master", "local"],
+        );
+        let local = local_remote.local();
+        std::fs::write(local.join("file.txt"), "content").unwrap();
+        git(&local, &["add", "file.txt"]);
+        git(&local, &["commit", "-m", "initial"]);
+        git(
+            &local,
+            &[
+                "remote",
+                "add",
+                "origin",
+                local_remote.remote().to_str().unwrap(),
+            ],
+        );
+        local_remote
+    }
+
+    fn local(&self) -> PathBuf {
+        self.dir.path().join("local")
+    }
+
+    fn remote(&self) -> PathBuf {
+        self.dir.path().join("remote.git")
+    }
+}
+
+fn git(cwd: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args([
+            "-c",
+            "user.name=Test",
+            "-c",
+            "user.email=test@example.com",
+            "-c",
+            "commit.gpgSign=false",
+        ])
+        .args(args)
+        .current_dir(cwd)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "git {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap().trim().to_owned()
+}
+
+#[tokio::test]
+async fn push_and_fetch_with_a_local_remote() {
+    let local_remote = LocalRemote::new();
+    let local = local_remote.local();
+    let head = git(&local, &["rev-parse", "HEAD"]);
+    let executor = ScriptedExecutor::passthrough();
+
+    let mut progress = Vec::new();
+    push_with(
+        &executor,
+        &local,
+        &[(
+            RefSpec::parse("refs/heads/master:refs/heads/master").unwrap(),
+            None,
+        )],
+        false,
+        &mut progress,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        git(&local_remote.remote(), &["rev-parse", "refs/heads/master"]),
+        head
+    );
+
+    fetch(
+        &local,
+        executor.clone(),
+        "origin",
+        refspec(),
+        |_| {},
+        |_, ()| async { None },
+        (),
+        &CancellationToken::new(),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        git(&local, &["rev-parse", "refs/remotes/origin/master"]),
+        head
+    );
+    assert!(executor
+        .invocations()
+        .iter()
+        .all(|invocation| invocation.responses.is_empty()));
+}
+
+#[tokio::test]
+async fn pushing_a_missing_ref_to_a_local_remote_fails() {
+    let local_remote = LocalRemote::new();
+
+    let error = push_with(
+        &ScriptedExecutor::passthrough(),
+        &local_remote.local(),
+        &[(
+            RefSpec::parse("refs/heads/missing:refs/heads/missing").unwrap(),
+            None,
+        )],
+        false,
+        &mut Vec::new(),
+    )
+    .await
+    .unwrap_err();
+
+    assert!(
+        matches!(&error, crate::Error::RefNotFound(refname) if refname == "refs/heads/missing"),
+        "{error:?}"
+    );
+}
+
+fn branch(source: &str, destination: &str) -> RefSpec {
+    RefSpec::parse(format!("refs/heads/{source}:refs/heads/{destination}")).unwrap()
+}
+
+async fn ls_remote_with(
+    executor: ScriptedExecutor,
+    repo_path: &Path,
+    remote: &str,
+) -> Result<Vec<RemoteRef>, TestError> {
+    ls_remote(
+        repo_path,
+        executor,
+        remote,
+        &[],
+        |_, ()| async { None },
+        (),
+        &CancellationToken::new(),
+        None,
+    )
+    .await
+}
+
+#[test]
+fn parse_ls_remote_output() {
+    let refs = parse_ls_remote(
+        "ref: refs/heads/main\tHEAD\n\
+         1111111111111111111111111111111111111111\tHEAD\n\
+         1111111111111111111111111111111111111111\trefs/heads/main\n\
+         2222222222222222222222222222222222222222\trefs/tags/v1.0\n\
+         1111111111111111111111111111111111111111\trefs/tags/v1.0^{}\n",
+    );
+
+    assert_eq!(
+        refs,
+        [
+            RemoteRef {
+                name: "HEAD".into(),
+                oid: "1".repeat(40),
+                peeled: None,
+                target: Some("refs/heads/main".into()),
+            },
+            RemoteRef {
+                name: "refs/heads/main".into(),
+                oid: "1".repeat(40),
+                peeled: None,
+                target: None,
+            },
+            RemoteRef {
+                name: "refs/tags/v1.0".into(),
+                oid: "2".repeat(40),
+                peeled: Some("1".repeat(40)),
+                target: None,
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn ls_remote_of_a_local_remote() {
+    let local_remote = LocalRemote::new();
+    let local = local_remote.local();
+    let head = git(&local, &["rev-parse", "HEAD"]);
+    git(&local, &["push", "origin", "master"]);
+
+    let refs = ls_remote_with(ScriptedExecutor::passthrough(), &local, "origin")
+        .await
+        .unwrap();
+
+    assert_eq!(
+        refs,
+        [
+            RemoteRef {
+                name: "HEAD".into(),
+                oid: head.clone(),
+                peeled: None,
+                target: Some("refs/heads/master".into()),
+            },
+            RemoteRef {
+                name: "refs/heads/master".into(),
+                oid: head,
+                peeled: None,
+                target: None,
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn ls_remote_of_a_missing_remote() {
+    let local_remote = LocalRemote::new();
+
+    let error = ls_remote_with(
+        ScriptedExecutor::passthrough(),
+        &local_remote.local(),
+        "nope",
+    )
+    .await
+    .unwrap_err();
+
+    assert!(
+        matches!(&error, crate::Error::NoSuchRemote(remote, _) if remote == "nope"),
+        "{error:?}"
+    );
+}
+
+#[tokio::test]
+async fn ls_remote_prompts_like_fetch() {
+    let executor = ScriptedExecutor::new().script(
+        ScriptedCommand::new("ls-remote").prompt("Password: ").exit(
+            0,
+            "1111111111111111111111111111111111111111\trefs/heads/main\n",
+            "",
+        ),
+    );
+
+    let refs = ls_remote(
+        ".",
+        executor.clone(),
+        "https://example.com/repo.git",
+        &["refs/heads/main"],
+        |_, ()| async { Some("password".to_owned()) },
+        (),
+        &CancellationToken::new(),
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(refs.len(), 1);
+    let invocation = executor.invocation("ls-remote").unwrap();
+    assert_eq!(invocation.responses, ["password"]);
+    assert!(invocation.args.ends_with(&[
+        "https://example.com/repo.git".to_owned(),
+        "refs/heads/main".to_owned()
+    ]));
+}
+
+#[tokio::test]
+async fn clone_a_local_remote() {
+    let local_remote = LocalRemote::new();
+    git(&local_remote.local(), &["push", "origin", "master"]);
+    let target = local_remote.dir.path().join("clone");
+
+    clone(
+        ScriptedExecutor::passthrough(),
+        local_remote.remote().to_str().unwrap(),
+        &target,
+        |_| {},
+        |_, ()| async { None },
+        (),
+        &CancellationToken::new(),
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(target.join("file.txt")).unwrap(),
+        "content"
+    );
+    assert_eq!(
+        git(&target, &["rev-parse", "refs/remotes/origin/master"]),
+        git(&local_remote.local(), &["rev-parse", "HEAD"])
+    );
+}
+
+#[tokio::test]
+async fn clone_into_a_relative_target() {
+    let local_remote = LocalRemote::new();
+    git(&local_remote.local(), &["push", "origin", "master"]);
+    // relative to the working directory, which is the crate directory under `cargo test`
+    let dir = tempfile::tempdir_in(".").unwrap();
+    let parent = Path::new(dir.path().file_name().unwrap()).join("nested");
+    std::fs::create_dir(&parent).unwrap();
+
+    for (url, target) in [
+        (local_remote.remote(), parent.join("clone")),
+        (parent.join("clone"), parent.join("clone of clone")),
+    ] {
+        clone(
+            ScriptedExecutor::passthrough(),
+            url.to_str().unwrap(),
+            &target,
+            |_| {},
+            |_, ()| async { None },
+            (),
+            &CancellationToken::new(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(target.join("file.txt")).unwrap(),
+            "content"
+        );
+    }
+}
+
+#[tokio::test]
+async fn clone_into_a_non_empty_directory_fails() {
+    let local_remote = LocalRemote::new();
+
+    let error = clone(
+        ScriptedExecutor::passthrough(),
+        local_remote.remote().to_str().unwrap(),
+        local_remote.local(),
+        |_| {},
+        |_, ()| async { None },
+        (),
+        &CancellationToken::new(),
+        None,
+    )
+    .await
+    .unwrap_err();
+
+    assert!(
+        matches!(
+            &error,
+            crate::Error::Backend(RepositoryError::Failed { stderr, .. })
+                if stderr.contains("already exists and is not an empty directory")
+        ),
+        "{error:?}"
+    );
+}
+
+/// A remote with `master` at the first commit and `other` at the second commit of
+/// the local `master`, which has a branch `first` at the first commit.
+fn diverged_local_remote() -> (LocalRemote, String, String) {
+    let local_remote = LocalRemote::new();
+    let local = local_remote.local();
+    let first = git(&local, &["rev-parse", "HEAD"]);
+    git(&local, &["branch", "first"]);
+    git(&local, &["push", "origin", "master", "master:other"]);
+    std::fs::write(local.join("file.txt"), "more content").unwrap();
+    git(&local, &["commit", "-am", "second"]);
+    let second = git(&local, &["rev-parse", "HEAD"]);
+    git(&local, &["push", "origin", "master:other"]);
+    (local_remote, first, second)
+}
+
+#[tokio::test]
+async fn atomic_push_updates_all_refs() {
+    let (local_remote, _, second) = diverged_local_remote();
+
+    push_with(
+        &ScriptedExecutor::passthrough(),
+        &local_remote.local(),
+        &[
+            (branch("master", "master"), None),
+            (branch("master", "new"), None),
+        ],
+        false,
+        &mut Vec::new(),
+    )
+    .await
+    .unwrap();
+
+    let remote = local_remote.remote();
+    assert_eq!(git(&remote, &["rev-parse", "refs/heads/master"]), second);
+    assert_eq!(git(&remote, &["rev-parse", "refs/heads/new"]), second);
+}
+
+#[tokio::test]
+async fn atomic_push_updates_nothing_if_one_ref_is_rejected() {
+    let (local_remote, first, second) = diverged_local_remote();
+    let executor = ScriptedExecutor::passthrough();
+
+    let error = push_with(
+        &executor,
+        &local_remote.local(),
+        &[
+            (branch("master", "master"), None),
+            (branch("first", "other"), None),
+        ],
+        false,
+        &mut Vec::new(),
+    )
+    .await
+    .unwrap_err();
+
+    assert!(
+        matches!(error, crate::Error::Backend(RepositoryError::Failed { .. })),
+        "{error:?}"
+    );
+    assert!(executor
+        .invocation("push")
+        .unwrap()
+        .args
+        .contains(&"--atomic".to_owned()));
+    let remote = local_remote.remote();
+    assert_eq!(git(&remote, &["rev-parse", "refs/heads/master"]), first);
+    assert_eq!(git(&remote, &["rev-parse", "refs/heads/other"]), second);
+}
+
+#[tokio::test]
+async fn stale_lease_names_its_refspec() {
+    let (local_remote, first, second) = diverged_local_remote();
+
+    let error = push_with(
+        &ScriptedExecutor::passthrough(),
+        &local_remote.local(),
+        &[
+            (branch("master", "master"), Some(&first)),
+            (branch("first", "other"), Some(&first)),
+        ],
+        true,
+        &mut Vec::new(),
+    )
+    .await
+    .unwrap_err();
+
+    assert!(
+        matches!(&error, crate::Error::StaleLease(refspec) if refspec == "refs/heads/first:refs/heads/other"),
+        "{error:?}"
+    );
+    let remote = local_remote.remote();
+    assert_eq!(git(&remote, &["rev-parse", "refs/heads/master"]), first);
+    assert_eq!(git(&remote, &["rev-parse", "refs/heads/other"]), second);
+}
+
+#[tokio::test]
+async fn forced_push_without_lease_overwrites_remote_refs() {
+    let (local_remote, first, _) = diverged_local_remote();
+
+    push_with(
+        &ScriptedExecutor::passthrough(),
+        &local_remote.local(),
+        &[(branch("first", "other"), None)],
+        true,
+        &mut Vec::new(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        git(&local_remote.remote(), &["rev-parse", "refs/heads/other"]),
+        first
+    );
+}
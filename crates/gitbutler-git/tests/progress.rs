@@ -0,0 +1,79 @@
+use gitbutler_git::{Progress, Stage};
+
+#[test]
+fn parse_receiving_objects() {
+    assert_eq!(
+        Progress::parse("Receiving objects:  45% (450/1000), 1.50 MiB | 1.00 MiB/s"),
+        Some(Progress {
+            stage: Stage::Receiving,
+            current: 450,
+            total: Some(1000),
+            bytes: Some(1_572_864),
+        })
+    );
+}
+
+#[test]
+fn parse_writing_objects_done() {
+    assert_eq!(
+        Progress::parse("Writing objects: 100% (3/3), 250 bytes | 250.00 KiB/s, done."),
+        Some(Progress {
+            stage: Stage::Writing,
+            current: 3,
+            total: Some(3),
+            bytes: Some(250),
+        })
+    );
+}
+
+#[test]
+fn parse_remote_counting_objects() {
+    assert_eq!(
+        Progress::parse("remote: Counting objects:  33% (1/3)"),
+        Some(Progress {
+            stage: Stage::Counting,
+            current: 1,
+            total: Some(3),
+            bytes: None,
+        })
+    );
+}
+
+#[test]
+fn parse_without_total() {
+    assert_eq!(
+        Progress::parse("remote: Enumerating objects: 5, done."),
+        Some(Progress {
+            stage: Stage::Enumerating,
+            current: 5,
+            total: None,
+            bytes: None,
+        })
+    );
+}
+
+#[test]
+fn parse_resolving_deltas() {
+    assert_eq!(
+        Progress::parse("Resolving deltas: 100% (2/2), completed with 1 local object."),
+        Some(Progress {
+            stage: Stage::ResolvingDeltas,
+            current: 2,
+            total: Some(2),
+            bytes: None,
+        })
+    );
+}
+
+#[test]
+fn parse_non_progress() {
+    assert_eq!(
+        Progress::parse("remote: Total 3 (delta 0), reused 0 (delta 0), pack-reused 0"),
+        None
+    );
+    assert_eq!(Progress::parse("To ../remote"), None);
+    assert_eq!(
+        Progress::parse("error: failed to push some refs to '../remote'"),
+        None
+    );
+}
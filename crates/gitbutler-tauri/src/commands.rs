@@ -46,19 +46,30 @@ pub async fn git_test_push(
     remote_name: &str,
     branch_name: &str,
 ) -> Result<(), Error> {
-    let app = handle.state::<app::App>();
-    let helper = handle.state::<gitbutler_core::git::credentials::Helper>();
+    let app = handle.state::<app::App>().inner().clone();
+    let helper = handle
+        .state::<gitbutler_core::git::credentials::Helper>()
+        .inner()
+        .clone();
     let askpass_broker = handle
         .state::<gitbutler_core::askpass::AskpassBroker>()
         .inner()
         .clone();
-    Ok(app.git_test_push(
-        &project_id,
-        remote_name,
-        branch_name,
-        &helper,
-        Some((askpass_broker, None)),
-    )?)
+    let remote_name = remote_name.to_string();
+    let branch_name = branch_name.to_string();
+    // pushing blocks, also while waiting for the user to answer prompts
+    tokio::task::spawn_blocking(move || {
+        app.git_test_push(
+            &project_id,
+            &remote_name,
+            &branch_name,
+            &helper,
+            Some((askpass_broker, None)),
+        )
+    })
+    .await
+    .context("failed to push")??;
+    Ok(())
 }
 
 #[tauri::command(async)]
@@ -69,18 +80,28 @@ pub async fn git_test_fetch(
     remote_name: &str,
     action: Option<String>,
 ) -> Result<(), Error> {
-    let app = handle.state::<app::App>();
-    let helper = handle.state::<gitbutler_core::git::credentials::Helper>();
+    let app = handle.state::<app::App>().inner().clone();
+    let helper = handle
+        .state::<gitbutler_core::git::credentials::Helper>()
+        .inner()
+        .clone();
     let askpass_broker = handle
         .state::<gitbutler_core::askpass::AskpassBroker>()
         .inner()
         .clone();
-    Ok(app.git_test_fetch(
-        &project_id,
-        remote_name,
-        &helper,
-        Some((askpass_broker, action.unwrap_or_else(|| "test".to_string()))),
-    )?)
+    let remote_name = remote_name.to_string();
+    // fetching blocks, also while waiting for the user to answer prompts
+    tokio::task::spawn_blocking(move || {
+        app.git_test_fetch(
+            &project_id,
+            &remote_name,
+            &helper,
+            Some((askpass_broker, action.unwrap_or_else(|| "test".to_string()))),
+        )
+    })
+    .await
+    .context("failed to fetch")??;
+    Ok(())
 }
 
 #[tauri::command(async)]
@@ -227,10 +227,30 @@ fn main() {
                     virtual_branches::commands::list_remote_branches,
                     virtual_branches::commands::get_remote_branch_data,
                     virtual_branches::commands::squash_branch_commit,
+                    virtual_branches::commands::edit_branch_history,
+                    virtual_branches::commands::split_commit,
+                    virtual_branches::commands::absorb,
+                    virtual_branches::commands::merge_virtual_branches,
+                    virtual_branches::commands::split_virtual_branch,
+                    virtual_branches::commands::explain_hunk_locks,
+                    virtual_branches::commands::move_hunks_with_dependencies,
                     virtual_branches::commands::fetch_from_target,
+                    virtual_branches::commands::cancel_remote_operations,
                     virtual_branches::commands::move_commit,
+                    virtual_branches::commands::list_conflicts,
+                    virtual_branches::commands::get_conflict,
+                    virtual_branches::commands::resolve_conflict,
+                    virtual_branches::commands::resolve_conflict_with_side,
+                    virtual_branches::commands::list_routing_rules,
+                    virtual_branches::commands::set_routing_rules,
                     snapshots::list_snapshots,
                     snapshots::restore_snapshot,
+                    snapshots::restore_snapshot_paths,
+                    snapshots::restore_snapshot_branch,
+                    snapshots::snapshot_diff,
+                    snapshots::undo_operation,
+                    snapshots::redo_operation,
+                    snapshots::compact_snapshots,
                     menu::menu_item_set_enabled,
                     keys::commands::get_public_key,
                     github::commands::init_device_oauth,
@@ -1,8 +1,13 @@
 use crate::error::Error;
 use anyhow::Context;
 use gitbutler_core::{
-    projects, projects::ProjectId, snapshots::entry::Snapshot, snapshots::snapshot,
+    projects,
+    projects::ProjectId,
+    snapshots::entry::{Snapshot, SnapshotDiff},
+    snapshots::snapshot,
+    virtual_branches::BranchId,
 };
+use std::path::PathBuf;
 use tauri::Manager;
 use tracing::instrument;
 
@@ -35,3 +40,97 @@ pub async fn restore_snapshot(
     snapshot::restore(&project, sha)?;
     Ok(())
 }
+
+#[tauri::command(async)]
+#[instrument(skip(handle), err(Debug))]
+pub async fn restore_snapshot_paths(
+    handle: tauri::AppHandle,
+    project_id: ProjectId,
+    sha: String,
+    paths: Vec<PathBuf>,
+) -> Result<(), Error> {
+    let project = handle
+        .state::<projects::Controller>()
+        .get(&project_id)
+        .context("failed to get project")?;
+    snapshot::restore_paths(&project, sha, &paths)?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle), err(Debug))]
+pub async fn restore_snapshot_branch(
+    handle: tauri::AppHandle,
+    project_id: ProjectId,
+    sha: String,
+    branch_id: BranchId,
+) -> Result<(), Error> {
+    let project = handle
+        .state::<projects::Controller>()
+        .get(&project_id)
+        .context("failed to get project")?;
+    snapshot::restore_branch(&project, sha, branch_id)?;
+    Ok(())
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle), err(Debug))]
+pub async fn snapshot_diff(
+    handle: tauri::AppHandle,
+    project_id: ProjectId,
+    from_sha: String,
+    to_sha: String,
+) -> Result<SnapshotDiff, Error> {
+    let project = handle
+        .state::<projects::Controller>()
+        .get(&project_id)
+        .context("failed to get project")?;
+    let diff = snapshot::diff(&project, from_sha, to_sha)?;
+    Ok(diff)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle), err(Debug))]
+pub async fn undo_operation(
+    handle: tauri::AppHandle,
+    project_id: ProjectId,
+) -> Result<Option<String>, Error> {
+    let project = handle
+        .state::<projects::Controller>()
+        .get(&project_id)
+        .context("failed to get project")?;
+    let sha = snapshot::undo(&project)?;
+    Ok(sha)
+}
+
+#[tauri::command(async)]
+#[instrument(skip(handle), err(Debug))]
+pub async fn redo_operation(
+    handle: tauri::AppHandle,
+    project_id: ProjectId,
+) -> Result<Option<String>, Error> {
+    let project = handle
+        .state::<projects::Controller>()
+        .get(&project_id)
+        .context("failed to get project")?;
+    let sha = snapshot::redo(&project)?;
+    Ok(sha)
+}
+
+/// Compacts the oplog according to the project's snapshot retention policy, returning how many snapshots were dropped.
+#[tauri::command(async)]
+#[instrument(skip(handle), err(Debug))]
+pub async fn compact_snapshots(
+    handle: tauri::AppHandle,
+    project_id: ProjectId,
+) -> Result<usize, Error> {
+    let project = handle
+        .state::<projects::Controller>()
+        .get(&project_id)
+        .context("failed to get project")?;
+    let Some(retention) = &project.snapshot_retention else {
+        return Ok(0);
+    };
+    let dropped = snapshot::compact(&project, retention)?;
+    Ok(dropped)
+}
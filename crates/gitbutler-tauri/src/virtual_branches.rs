@@ -5,14 +5,19 @@ pub mod commands {
         askpass::AskpassBroker,
         assets,
         error::Code,
-        git, projects,
+        git,
+        project_repository::conflicts,
+        projects,
         projects::ProjectId,
         virtual_branches::{
             branch::{self, BranchId, BranchOwnershipClaims},
             controller::Controller,
-            BaseBranch, RemoteBranch, RemoteBranchData, RemoteBranchFile, VirtualBranches,
+            AbsorbedHunk, BaseBranch, CommitPart, ConflictVersions, HistoryStep,
+            HunkLockExplanation, RemoteBranch, RemoteBranchData, RemoteBranchFile, RoutingRule,
+            VirtualBranches,
         },
     };
+    use std::path::PathBuf;
     use tauri::{AppHandle, Manager};
     use tracing::instrument;
 
@@ -233,6 +238,7 @@ pub mod commands {
     pub async fn reset_files(
         handle: AppHandle,
         project_id: ProjectId,
+        branch_id: BranchId,
         files: &str,
     ) -> Result<(), Error> {
         // convert files to Vec<String>
@@ -242,7 +248,7 @@ pub mod commands {
             .collect::<Vec<String>>();
         handle
             .state::<Controller>()
-            .reset_files(&project_id, &files)
+            .reset_files(&project_id, &branch_id, &files)
             .await?;
         emit_vbranches(&handle, &project_id).await;
         Ok(())
@@ -482,6 +488,118 @@ pub mod commands {
         Ok(())
     }
 
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn merge_virtual_branches(
+        handle: AppHandle,
+        project_id: ProjectId,
+        source_branch_id: BranchId,
+        target_branch_id: BranchId,
+    ) -> Result<(), Error> {
+        handle
+            .state::<Controller>()
+            .merge_branches(&project_id, &source_branch_id, &target_branch_id)
+            .await?;
+        emit_vbranches(&handle, &project_id).await;
+        Ok(())
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn split_virtual_branch(
+        handle: AppHandle,
+        project_id: ProjectId,
+        branch_id: BranchId,
+        name: Option<String>,
+        ownership: BranchOwnershipClaims,
+        commit_oids: Vec<git::Oid>,
+    ) -> Result<BranchId, Error> {
+        let new_branch_id = handle
+            .state::<Controller>()
+            .split_branch(&project_id, &branch_id, name, &ownership, &commit_oids)
+            .await?;
+        emit_vbranches(&handle, &project_id).await;
+        Ok(new_branch_id)
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn absorb(
+        handle: AppHandle,
+        project_id: ProjectId,
+        branch_id: BranchId,
+    ) -> Result<Vec<AbsorbedHunk>, Error> {
+        let absorbed_hunks = handle
+            .state::<Controller>()
+            .absorb(&project_id, &branch_id)
+            .await?;
+        emit_vbranches(&handle, &project_id).await;
+        Ok(absorbed_hunks)
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn explain_hunk_locks(
+        handle: AppHandle,
+        project_id: ProjectId,
+        ownership: BranchOwnershipClaims,
+    ) -> Result<Vec<HunkLockExplanation>, Error> {
+        handle
+            .state::<Controller>()
+            .explain_hunk_locks(&project_id, &ownership)
+            .await
+            .map_err(Into::into)
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn move_hunks_with_dependencies(
+        handle: AppHandle,
+        project_id: ProjectId,
+        target_branch_id: BranchId,
+        ownership: BranchOwnershipClaims,
+    ) -> Result<(), Error> {
+        handle
+            .state::<Controller>()
+            .move_hunks_with_dependencies(&project_id, &target_branch_id, &ownership)
+            .await?;
+        emit_vbranches(&handle, &project_id).await;
+        Ok(())
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn split_commit(
+        handle: AppHandle,
+        project_id: ProjectId,
+        branch_id: BranchId,
+        commit_oid: git::Oid,
+        parts: Vec<CommitPart>,
+    ) -> Result<Vec<git::Oid>, Error> {
+        let oids = handle
+            .state::<Controller>()
+            .split_commit(&project_id, &branch_id, commit_oid, &parts)
+            .await?;
+        emit_vbranches(&handle, &project_id).await;
+        Ok(oids)
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn edit_branch_history(
+        handle: AppHandle,
+        project_id: ProjectId,
+        branch_id: BranchId,
+        plan: Vec<HistoryStep>,
+    ) -> Result<git::Oid, Error> {
+        let oid = handle
+            .state::<Controller>()
+            .edit_history(&project_id, &branch_id, &plan)
+            .await?;
+        emit_vbranches(&handle, &project_id).await;
+        Ok(oid)
+    }
+
     #[tauri::command(async)]
     #[instrument(skip(handle), err(Debug))]
     pub async fn fetch_from_target(
@@ -504,6 +622,15 @@ pub mod commands {
         Ok(base_branch)
     }
 
+    #[tauri::command(async)]
+    #[instrument(skip(handle))]
+    pub async fn cancel_remote_operations(handle: tauri::AppHandle, project_id: ProjectId) {
+        handle
+            .state::<Controller>()
+            .cancel_remote_operations(&project_id)
+            .await;
+    }
+
     #[tauri::command(async)]
     #[instrument(skip(handle), err(Debug))]
     pub async fn move_commit(
@@ -537,6 +664,90 @@ pub mod commands {
         Ok(())
     }
 
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn list_conflicts(
+        handle: AppHandle,
+        project_id: ProjectId,
+    ) -> Result<Vec<conflicts::Conflict>, Error> {
+        Ok(handle
+            .state::<Controller>()
+            .list_conflicts(&project_id)
+            .await?)
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn get_conflict(
+        handle: AppHandle,
+        project_id: ProjectId,
+        path: PathBuf,
+    ) -> Result<ConflictVersions, Error> {
+        Ok(handle
+            .state::<Controller>()
+            .get_conflict(&project_id, &path)
+            .await?)
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle, content), err(Debug))]
+    pub async fn resolve_conflict(
+        handle: AppHandle,
+        project_id: ProjectId,
+        path: PathBuf,
+        content: &str,
+    ) -> Result<(), Error> {
+        handle
+            .state::<Controller>()
+            .resolve_conflict(&project_id, &path, content.as_bytes())
+            .await?;
+        emit_vbranches(&handle, &project_id).await;
+        Ok(())
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn resolve_conflict_with_side(
+        handle: AppHandle,
+        project_id: ProjectId,
+        path: PathBuf,
+        side: conflicts::ConflictSide,
+    ) -> Result<(), Error> {
+        handle
+            .state::<Controller>()
+            .resolve_conflict_with_side(&project_id, &path, side)
+            .await?;
+        emit_vbranches(&handle, &project_id).await;
+        Ok(())
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn list_routing_rules(
+        handle: AppHandle,
+        project_id: ProjectId,
+    ) -> Result<Vec<RoutingRule>, Error> {
+        Ok(handle
+            .state::<Controller>()
+            .list_routing_rules(&project_id)
+            .await?)
+    }
+
+    #[tauri::command(async)]
+    #[instrument(skip(handle), err(Debug))]
+    pub async fn set_routing_rules(
+        handle: AppHandle,
+        project_id: ProjectId,
+        rules: Vec<RoutingRule>,
+    ) -> Result<(), Error> {
+        handle
+            .state::<Controller>()
+            .set_routing_rules(&project_id, rules)
+            .await?;
+        emit_vbranches(&handle, &project_id).await;
+        Ok(())
+    }
+
     async fn emit_vbranches(handle: &AppHandle, project_id: &projects::ProjectId) {
         if let Err(error) = handle
             .state::<watcher::Watchers>()
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use anyhow::{Context, Result};
 use futures::executor::block_on;
 use gitbutler_core::projects::{self, Project, ProjectId};
-use gitbutler_core::{assets, deltas, sessions, users, virtual_branches};
+use gitbutler_core::{assets, deltas, project_repository, sessions, users, virtual_branches};
 use tauri::{AppHandle, Manager};
 use tracing::instrument;
 
@@ -44,6 +44,14 @@ mod event {
                     payload: serde_json::json!({}),
                     project_id,
                 },
+                Change::GitProgress {
+                    project_id,
+                    progress,
+                } => ChangeForFrontend {
+                    name: format!("project://{}/git/progress", project_id),
+                    payload: serde_json::to_value(progress).unwrap(),
+                    project_id,
+                },
                 Change::File {
                     project_id,
                     session_id,
@@ -201,4 +209,13 @@ impl gitbutler_core::projects::Watchers for Watchers {
         self.post(gitbutler_watcher::Action::PushGitbutlerData(id))
             .await
     }
+
+    fn report_progress(&self, id: ProjectId, progress: project_repository::RemoteProgress) {
+        // progress is only informational, so not being able to show it isn't an error
+        if let Err(error) =
+            block_on(self.post(gitbutler_watcher::Action::ReportProgress(id, progress)))
+        {
+            tracing::debug!(project_id = %id, ?error, "failed to report progress");
+        }
+    }
 }
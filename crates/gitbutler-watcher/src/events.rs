@@ -1,7 +1,7 @@
 use std::fmt::Display;
 use std::path::PathBuf;
 
-use gitbutler_core::{deltas, reader, sessions::SessionId, virtual_branches};
+use gitbutler_core::{deltas, project_repository, reader, sessions::SessionId, virtual_branches};
 use gitbutler_core::{projects::ProjectId, sessions};
 
 /// An event for internal use, as merge between [super::file_monitor::Event] and [Action].
@@ -12,6 +12,7 @@ pub(super) enum InternalEvent {
     CalculateVirtualBranches(ProjectId),
     FetchGitbutlerData(ProjectId),
     PushGitbutlerData(ProjectId),
+    ReportProgress(ProjectId, project_repository::RemoteProgress),
 
     // From file monitor
     GitFilesChange(ProjectId, Vec<PathBuf>),
@@ -28,6 +29,7 @@ pub enum Action {
     CalculateVirtualBranches(ProjectId),
     FetchGitbutlerData(ProjectId),
     PushGitbutlerData(ProjectId),
+    ReportProgress(ProjectId, project_repository::RemoteProgress),
 }
 
 impl Action {
@@ -37,7 +39,8 @@ impl Action {
             Action::FetchGitbutlerData(project_id)
             | Action::Flush(project_id, _)
             | Action::CalculateVirtualBranches(project_id)
-            | Action::PushGitbutlerData(project_id) => *project_id,
+            | Action::PushGitbutlerData(project_id)
+            | Action::ReportProgress(project_id, _) => *project_id,
         }
     }
 }
@@ -49,6 +52,7 @@ impl From<Action> for InternalEvent {
             Action::CalculateVirtualBranches(v) => InternalEvent::CalculateVirtualBranches(v),
             Action::FetchGitbutlerData(v) => InternalEvent::FetchGitbutlerData(v),
             Action::PushGitbutlerData(v) => InternalEvent::PushGitbutlerData(v),
+            Action::ReportProgress(a, b) => InternalEvent::ReportProgress(a, b),
         }
     }
 }
@@ -80,6 +84,9 @@ impl Display for InternalEvent {
             }
             InternalEvent::CalculateVirtualBranches(pid) => write!(f, "VirtualBranch({})", pid),
             InternalEvent::PushGitbutlerData(pid) => write!(f, "PushGitbutlerData({})", pid),
+            InternalEvent::ReportProgress(pid, progress) => {
+                write!(f, "ReportProgress({}, {:?})", pid, progress.progress.stage)
+            }
         }
     }
 }
@@ -111,6 +118,11 @@ pub enum Change {
         head: String,
     },
     GitActivity(ProjectId),
+    /// A fetch or push made progress.
+    GitProgress {
+        project_id: ProjectId,
+        progress: project_repository::RemoteProgress,
+    },
     File {
         project_id: ProjectId,
         session_id: SessionId,
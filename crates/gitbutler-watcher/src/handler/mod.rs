@@ -101,6 +101,10 @@ impl Handler {
                 .calculate_virtual_branches(project_id)
                 .await
                 .context("failed to handle virtual branch event"),
+
+            events::InternalEvent::ReportProgress(project_id, progress) => {
+                self.report_progress(project_id, progress)
+            }
         }
     }
 }
@@ -110,6 +114,18 @@ impl Handler {
         (self.send_event)(event).context("failed to send event")
     }
 
+    /// Let the receiver of changes know how far along a fetch or push is.
+    pub fn report_progress(
+        &self,
+        project_id: ProjectId,
+        progress: project_repository::RemoteProgress,
+    ) -> Result<()> {
+        self.emit_app_event(Change::GitProgress {
+            project_id,
+            progress,
+        })
+    }
+
     fn emit_session_file(
         &self,
         project_id: ProjectId,
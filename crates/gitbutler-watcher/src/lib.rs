@@ -85,6 +85,12 @@ pub fn watch_in_background(
         cancellation_token: cancellation_token.clone(),
     };
     let handle_event = move |event: InternalEvent| -> Result<()> {
+        // Progress is frequent and cheap to forward, and must arrive in order,
+        // so it doesn't get a thread of its own.
+        if let InternalEvent::ReportProgress(project_id, progress) = event {
+            handler.report_progress(project_id, progress).ok();
+            return Ok(());
+        }
         let handler = handler.clone();
         // NOTE: Traditional parallelization (blocking) is required as `tokio::spawn()` on
         //       the `handler.handle()` future isn't `Send` as it keeps non-Send things
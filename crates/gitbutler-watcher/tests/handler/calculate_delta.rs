@@ -96,6 +96,8 @@ fn new_test_branch() -> branch::Branch {
         ownership: branch::BranchOwnershipClaims::default(),
         order: TEST_INDEX.load(Ordering::Relaxed),
         selected_for_changes: None,
+        parent: None,
+        parent_head: None,
     }
 }
 